@@ -0,0 +1,517 @@
+//! `market-cli`: marketplace operations (config, listings & offers) from a keypair file.
+
+use std::error::Error;
+use std::path::PathBuf;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::AccountDeserialize;
+use anchor_spl::associated_token::spl_associated_token_account::instruction::create_associated_token_account_idempotent;
+use anchor_spl::{token, token_2022};
+use clap::{Parser, Subcommand};
+use fashion_market_client as client;
+use marketplace::{
+    FeeSchedule, Listing, ListingOptions, MarketplaceConfig, Offer, OfferEscrow, Provenance,
+    Storefront,
+};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::signature::{read_keypair_file, Keypair, Signer};
+use solana_sdk::system_instruction;
+use solana_sdk::transaction::Transaction;
+
+type CliResult<T> = Result<T, Box<dyn Error>>;
+
+#[derive(Parser)]
+#[command(name = "market-cli", about = "Marketplace operations")]
+struct Cli {
+    /// RPC URL, or one of `devnet`, `mainnet`, `localnet`.
+    #[arg(long, short = 'u', default_value = "devnet", global = true)]
+    url: String,
+
+    /// Signer keypair file (fee payer & acting wallet).
+    #[arg(
+        long,
+        short = 'k',
+        default_value = "~/.config/solana/id.json",
+        global = true
+    )]
+    keypair: String,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Creates the marketplace config, with the signer as authority.
+    InitConfig {
+        #[arg(long)]
+        treasury: Pubkey,
+        #[arg(long, default_value_t = 0)]
+        primary_fee_bps: u16,
+        #[arg(long, default_value_t = 0)]
+        primary_royalty_bps: u16,
+        #[arg(long, default_value_t = 0)]
+        secondary_fee_bps: u16,
+        #[arg(long, default_value_t = 0)]
+        secondary_royalty_bps: u16,
+    },
+    /// Lists an NFT held by the signer, priced in whole SOL or in USD (6 decimals).
+    List {
+        #[arg(long)]
+        mint: Pubkey,
+        #[arg(
+            long,
+            required_unless_present = "price_usd",
+            conflicts_with = "price_usd"
+        )]
+        price: Option<u64>,
+        #[arg(long)]
+        price_usd: Option<u64>,
+        /// Storefront to list through.
+        #[arg(long)]
+        storefront: Option<Pubkey>,
+        /// Verified collection of the mint, for curated mode & curated storefronts.
+        #[arg(long)]
+        collection: Option<Pubkey>,
+        /// Registry page (with room left) to add the listing to.
+        #[arg(long, default_value_t = 0)]
+        registry_page: u32,
+        /// Confirm a price above the marketplace's listing cap.
+        #[arg(long, conflicts_with = "price_usd")]
+        confirm_high_price: bool,
+    },
+    /// Gives the signer's NFT to another wallet.
+    Gift {
+        #[arg(long)]
+        mint: Pubkey,
+        #[arg(long)]
+        recipient: Pubkey,
+    },
+    /// Removes one of the signer's listings, returning the NFT.
+    Delist {
+        #[arg(long)]
+        listing: Pubkey,
+    },
+    /// Buys a listing.
+    Buy {
+        #[arg(long)]
+        listing: Pubkey,
+        /// Pyth SOL/USD price update account, for USD listings.
+        #[arg(long)]
+        price_update: Option<Pubkey>,
+        /// Membership token account, for the membership discount.
+        #[arg(long)]
+        membership_account: Option<Pubkey>,
+        /// Most to pay, for listings priced on-chain (Dutch auctions, USD).
+        #[arg(long)]
+        max_price: Option<u64>,
+    },
+    /// Offers lamports for a mint, escrowed until accepted or cancelled.
+    MakeOffer {
+        #[arg(long)]
+        mint: Pubkey,
+        /// In lamports.
+        #[arg(long)]
+        amount: u64,
+        /// Escrow as WSOL, wrapping `amount` SOL into the signer's WSOL account first.
+        #[arg(long)]
+        wsol: bool,
+        /// Offer in this SPL token instead (e.g. USDC), `amount` in its base units.
+        #[arg(long, conflicts_with = "wsol")]
+        payment_mint: Option<Pubkey>,
+        /// Unix timestamp the offer expires at.
+        #[arg(long)]
+        expires_at: Option<i64>,
+    },
+    /// Withdraws the signer's offer on a mint.
+    CancelOffer {
+        #[arg(long)]
+        mint: Pubkey,
+    },
+    /// Closes a bidder's expired offer on a mint, for the crank tip.
+    CloseExpiredOffer {
+        #[arg(long)]
+        mint: Pubkey,
+        #[arg(long)]
+        bidder: Pubkey,
+    },
+    /// Sells the signer's NFT to a bidder's offer.
+    AcceptOffer {
+        #[arg(long)]
+        mint: Pubkey,
+        #[arg(long)]
+        bidder: Pubkey,
+    },
+    /// Counters a bidder's lamport offer on the signer's NFT with a higher amount.
+    CounterOffer {
+        #[arg(long)]
+        mint: Pubkey,
+        #[arg(long)]
+        bidder: Pubkey,
+        /// In lamports.
+        #[arg(long)]
+        amount: u64,
+    },
+    /// Takes the seller's counter to the signer's offer on a mint.
+    AcceptCounterOffer {
+        #[arg(long)]
+        mint: Pubkey,
+    },
+}
+
+fn main() {
+    if let Err(err) = run(Cli::parse()) {
+        eprintln!("error: {err}");
+        std::process::exit(1);
+    }
+}
+
+fn run(cli: Cli) -> CliResult<()> {
+    let rpc = RpcClient::new_with_commitment(rpc_url(&cli.url), CommitmentConfig::confirmed());
+    let signer = read_keypair_file(expand_home(&cli.keypair))
+        .map_err(|err| format!("reading keypair {}: {err}", cli.keypair))?;
+    let wallet = signer.pubkey();
+
+    match cli.command {
+        Command::InitConfig {
+            treasury,
+            primary_fee_bps,
+            primary_royalty_bps,
+            secondary_fee_bps,
+            secondary_royalty_bps,
+        } => {
+            let ix = client::initialize_config(
+                wallet,
+                treasury,
+                FeeSchedule {
+                    marketplace_fee_bps: primary_fee_bps,
+                    royalty_bps: primary_royalty_bps,
+                },
+                FeeSchedule {
+                    marketplace_fee_bps: secondary_fee_bps,
+                    royalty_bps: secondary_royalty_bps,
+                },
+            );
+            send(&rpc, &signer, &[], vec![ix])?;
+            println!("config: {}", client::derive_config_pda().0);
+        }
+        Command::List {
+            mint,
+            price,
+            price_usd,
+            storefront,
+            collection,
+            registry_page,
+            confirm_high_price,
+        } => {
+            let listing = Keypair::new();
+            let extra = client::ListAccounts {
+                storefront,
+                collection,
+                registry_page,
+                payer: None,
+            };
+            let ix = match price_usd {
+                Some(price_usd) => {
+                    client::list_nft_usd(listing.pubkey(), wallet, mint, price_usd, extra)
+                }
+                None if confirm_high_price => client::list_nft_v2(
+                    listing.pubkey(),
+                    wallet,
+                    mint,
+                    price.unwrap_or_default(),
+                    ListingOptions {
+                        confirm_high_price,
+                        ..Default::default()
+                    },
+                    extra,
+                ),
+                None => client::list_nft(
+                    listing.pubkey(),
+                    wallet,
+                    mint,
+                    price.unwrap_or_default(),
+                    extra,
+                ),
+            };
+            send(&rpc, &signer, &[&listing], vec![ix])?;
+            println!("listing: {}", listing.pubkey());
+        }
+        Command::Gift { mint, recipient } => {
+            send(
+                &rpc,
+                &signer,
+                &[],
+                vec![client::gift_nft(wallet, recipient, mint)],
+            )?;
+        }
+        Command::Delist { listing } => {
+            let data: Listing = fetch(&rpc, &listing)?;
+            send(
+                &rpc,
+                &signer,
+                &[],
+                vec![client::remove_listed_nft(listing, &data, wallet)],
+            )?;
+        }
+        Command::Buy {
+            listing,
+            price_update,
+            membership_account,
+            max_price,
+        } => {
+            let data: Listing = fetch(&rpc, &listing)?;
+            let config: MarketplaceConfig = fetch(&rpc, &client::derive_config_pda().0)?;
+            let provenance: Provenance = fetch(&rpc, &client::derive_provenance_pda(&data.mint).0)?;
+            let fee_recipient = match data.storefront {
+                Some(storefront) => fetch::<Storefront>(&rpc, &storefront)?.treasury,
+                None => config.treasury,
+            };
+
+            // SPL payment destination accounts must exist; `buy_nft` creates the buyer's NFT account.
+            let mut ixs = vec![];
+            let payment_token_program = match data.payment_mint {
+                Some(payment_mint) => {
+                    let token_program = rpc.get_account(&payment_mint)?.owner;
+                    let payees = data
+                        .payees
+                        .iter()
+                        .flatten()
+                        .chain(data.royalty_splits.iter().flatten())
+                        .map(|payee| payee.payee);
+                    for owner in [data.seller, fee_recipient, provenance.creator]
+                        .into_iter()
+                        .chain(payees)
+                    {
+                        ixs.push(create_associated_token_account_idempotent(
+                            &wallet,
+                            &owner,
+                            &payment_mint,
+                            &token_program,
+                        ));
+                    }
+                    Some(token_program)
+                }
+                None => None,
+            };
+
+            ixs.push(client::buy_nft(
+                listing,
+                &data,
+                wallet,
+                fee_recipient,
+                client::BuyAccounts {
+                    price_update,
+                    creator: Some(provenance.creator),
+                    membership_account,
+                    payment_token_program,
+                    insurance_fund: config.insurance_bps > 0,
+                    ..Default::default()
+                },
+                provenance.sales_count,
+                Vec::new(),
+                None,
+                max_price,
+            ));
+            send(&rpc, &signer, &[], ixs)?;
+        }
+        Command::MakeOffer {
+            mint,
+            amount,
+            wsol,
+            payment_mint,
+            expires_at,
+        } => {
+            let offer_book = has_offer_book(&rpc, &mint);
+            let ixs = if let Some(payment_mint) = payment_mint {
+                let token_program = rpc.get_account(&payment_mint)?.owner;
+                vec![client::make_spl_offer(
+                    wallet,
+                    wallet,
+                    mint,
+                    payment_mint,
+                    token_program,
+                    amount,
+                    expires_at,
+                )]
+            } else if wsol {
+                let wsol_account = client::wsol_token_account(&wallet);
+                vec![
+                    create_associated_token_account_idempotent(
+                        &wallet,
+                        &wallet,
+                        &token::spl_token::native_mint::ID,
+                        &token::ID,
+                    ),
+                    system_instruction::transfer(&wallet, &wsol_account, amount),
+                    token::spl_token::instruction::sync_native(&token::ID, &wsol_account)?,
+                    client::make_wsol_offer(wallet, wallet, mint, amount, expires_at, offer_book),
+                ]
+            } else {
+                vec![client::make_offer(
+                    wallet, wallet, mint, amount, expires_at, offer_book,
+                )]
+            };
+            send(&rpc, &signer, &[], ixs)?;
+            println!("offer: {}", client::derive_offer_pda(&mint, &wallet).0);
+        }
+        Command::CancelOffer { mint } => {
+            let offer: Offer = fetch(&rpc, &client::derive_offer_pda(&mint, &wallet).0)?;
+            send(
+                &rpc,
+                &signer,
+                &[],
+                vec![client::cancel_offer(&offer, has_offer_book(&rpc, &mint))],
+            )?;
+        }
+        Command::CloseExpiredOffer { mint, bidder } => {
+            let offer: Offer = fetch(&rpc, &client::derive_offer_pda(&mint, &bidder).0)?;
+            send(
+                &rpc,
+                &signer,
+                &[],
+                vec![client::close_expired_offer(
+                    &offer,
+                    wallet,
+                    has_offer_book(&rpc, &mint),
+                )],
+            )?;
+        }
+        Command::AcceptOffer { mint, bidder } => {
+            let offer: Offer = fetch(&rpc, &client::derive_offer_pda(&mint, &bidder).0)?;
+            let config: MarketplaceConfig = fetch(&rpc, &client::derive_config_pda().0)?;
+            // No provenance yet: this is the first sale & the seller becomes the creator.
+            let (creator, sale_number) =
+                fetch::<Provenance>(&rpc, &client::derive_provenance_pda(&mint).0)
+                    .map_or((wallet, 0), |provenance| {
+                        (provenance.creator, provenance.sales_count)
+                    });
+
+            let mut ixs = vec![create_associated_token_account_idempotent(
+                &wallet,
+                &bidder,
+                &mint,
+                &token_2022::ID,
+            )];
+            if offer.escrow == OfferEscrow::Wsol && !config.unwrap_wsol_proceeds {
+                ixs.push(create_associated_token_account_idempotent(
+                    &wallet,
+                    &wallet,
+                    &token::spl_token::native_mint::ID,
+                    &token::ID,
+                ));
+            }
+            if let (Some(payment_mint), Some(token_program)) =
+                (offer.payment_mint(), offer.payment_token_program())
+            {
+                for owner in [wallet, config.treasury, bidder, creator] {
+                    ixs.push(create_associated_token_account_idempotent(
+                        &wallet,
+                        &owner,
+                        &payment_mint,
+                        &token_program,
+                    ));
+                }
+            }
+            ixs.push(client::accept_offer(
+                &offer,
+                wallet,
+                config.treasury,
+                Some(creator),
+                sale_number,
+                config.unwrap_wsol_proceeds,
+                has_offer_book(&rpc, &mint),
+                config.insurance_bps > 0,
+                config.withholding_bps > 0,
+            ));
+            send(&rpc, &signer, &[], ixs)?;
+        }
+        Command::CounterOffer {
+            mint,
+            bidder,
+            amount,
+        } => {
+            let offer: Offer = fetch(&rpc, &client::derive_offer_pda(&mint, &bidder).0)?;
+            send(
+                &rpc,
+                &signer,
+                &[],
+                vec![client::counter_offer(&offer, wallet, amount)],
+            )?;
+        }
+        Command::AcceptCounterOffer { mint } => {
+            let offer: Offer = fetch(&rpc, &client::derive_offer_pda(&mint, &wallet).0)?;
+            let counter = offer.counter.ok_or("offer has no counter")?;
+            let config: MarketplaceConfig = fetch(&rpc, &client::derive_config_pda().0)?;
+            // No provenance yet: this is the first sale & the countering seller becomes the creator.
+            let (creator, sale_number) =
+                fetch::<Provenance>(&rpc, &client::derive_provenance_pda(&mint).0)
+                    .map_or((counter.seller, 0), |provenance| {
+                        (provenance.creator, provenance.sales_count)
+                    });
+            let ix = client::accept_counter_offer(
+                &offer,
+                counter.seller,
+                config.treasury,
+                Some(creator),
+                sale_number,
+                has_offer_book(&rpc, &mint),
+                config.insurance_bps > 0,
+                config.withholding_bps > 0,
+            );
+            send(&rpc, &signer, &[], vec![ix])?;
+        }
+    }
+
+    Ok(())
+}
+
+fn rpc_url(url: &str) -> String {
+    match url {
+        "devnet" => "https://api.devnet.solana.com".to_string(),
+        "mainnet" => "https://api.mainnet-beta.solana.com".to_string(),
+        "localnet" => "http://127.0.0.1:8899".to_string(),
+        url => url.to_string(),
+    }
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+fn fetch<T: AccountDeserialize>(rpc: &RpcClient, address: &Pubkey) -> CliResult<T> {
+    let account = rpc.get_account(address)?;
+    Ok(T::try_deserialize(&mut account.data.as_slice())?)
+}
+
+/// Whether `mint` has a bid order book for offer instructions to keep in sync.
+fn has_offer_book(rpc: &RpcClient, mint: &Pubkey) -> bool {
+    rpc.get_account(&client::derive_offer_book_pda(mint).0)
+        .is_ok()
+}
+
+/// Signs with `signer` (fee payer) & `extra_signers`, sends & confirms; prints the signature.
+fn send(
+    rpc: &RpcClient,
+    signer: &Keypair,
+    extra_signers: &[&Keypair],
+    ixs: Vec<Instruction>,
+) -> CliResult<()> {
+    let mut signers = vec![signer];
+    signers.extend_from_slice(extra_signers);
+
+    let tx = Transaction::new_signed_with_payer(
+        &ixs,
+        Some(&signer.pubkey()),
+        &signers,
+        rpc.get_latest_blockhash()?,
+    );
+    let signature = rpc.send_and_confirm_transaction(&tx)?;
+    println!("signature: {signature}");
+    Ok(())
+}
@@ -0,0 +1,3723 @@
+//! Off-chain helpers for the marketplace program: PDA derivation & typed `Instruction` builders.
+//!
+//! Builders derive every PDA & token account they can; the rest (signers, listing / auction keypairs,
+//! fee recipients) are arguments. Listings & auctions are keypair accounts, not PDAs: builders that act on
+//! an existing one take its key & its fetched data.
+//!
+//! Transfer hook extra accounts aren't resolved here: for hooked mints, append them to `Instruction::accounts`.
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::ed25519_program;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::program_error::ProgramError;
+use anchor_lang::solana_program::sysvar;
+use anchor_lang::{system_program, InstructionData, ToAccountMetas};
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+use anchor_spl::{associated_token, token, token_2022};
+use marketplace::constant::{ORDER_NONCES_PER_PAGE, PREFIX};
+use marketplace::{accounts, instruction};
+use marketplace::{
+    AllowedCaller, Auction, BondingCurve, CancelKind, CollectionOffer, CpiPolicy, EditionListing,
+    EmergencyAction, EscrowedSale, FeeSchedule, FeeTier, FlashSale, Fraction, InstallmentPlan,
+    InstallmentTerms, Listing, ListingOptions, ModerationReason, NoSaleAction, Offer, OfferEscrow,
+    Pool, Raffle, Rental, SignedOrder, VaultDeposit,
+};
+
+pub use marketplace::ID as PROGRAM_ID;
+
+// --------------------------------------------------------------------
+// PDAs
+// --------------------------------------------------------------------
+fn find(seeds: &[&[u8]]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(seeds, &PROGRAM_ID)
+}
+
+/// Signer of the self-CPI events emitted with the `event-cpi` feature.
+#[cfg(feature = "event-cpi")]
+pub fn derive_event_authority_pda() -> (Pubkey, u8) {
+    find(&[b"__event_authority"])
+}
+
+pub fn derive_config_pda() -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"config"])
+}
+
+/// NFT escrow of `mint`, shared by its auctions, rentals & raffles (and listings made before per-listing vaults).
+pub fn derive_vault_pda(mint: &Pubkey) -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"vault", mint.as_ref()])
+}
+
+/// Last deposit into `mint`'s shared vault.
+pub fn derive_vault_deposit_pda(mint: &Pubkey) -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"vault_deposit", mint.as_ref()])
+}
+
+/// NFT escrow of a single listing of `mint`, closed once the NFT leaves it.
+pub fn derive_listing_vault_pda(mint: &Pubkey, listing: &Pubkey) -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"vault", mint.as_ref(), listing.as_ref()])
+}
+
+pub fn derive_provenance_pda(mint: &Pubkey) -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"provenance", mint.as_ref()])
+}
+
+pub fn derive_bid_pda(auction: &Pubkey, bidder: &Pubkey) -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"bid", auction.as_ref(), bidder.as_ref()])
+}
+
+pub fn derive_global_stats_pda() -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"global_stats"])
+}
+
+pub fn derive_trader_stats_pda(trader: &Pubkey) -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"trader_stats", trader.as_ref()])
+}
+
+pub fn derive_trader_profile_pda(trader: &Pubkey) -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"trader_profile", trader.as_ref()])
+}
+
+pub fn derive_collection_stats_pda(collection: &Pubkey) -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"collection_stats", collection.as_ref()])
+}
+
+pub fn derive_royalty_cache_pda(mint: &Pubkey) -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"royalty_cache", mint.as_ref()])
+}
+
+pub fn derive_allowed_collection_pda(collection: &Pubkey) -> (Pubkey, u8) {
+    find(&[
+        PREFIX.as_bytes(),
+        b"allowed_collection",
+        collection.as_ref(),
+    ])
+}
+
+pub fn derive_blocked_mint_pda(mint: &Pubkey) -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"blocked_mint", mint.as_ref()])
+}
+
+/// Insurance fund; the sale builders' `insurance_fund` flag passes it, required while `insurance_bps` is set.
+pub fn derive_insurance_fund_pda() -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"insurance_fund"])
+}
+
+/// Loyalty points of `wallet`.
+pub fn derive_loyalty_pda(wallet: &Pubkey) -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"loyalty", wallet.as_ref()])
+}
+
+/// Trade mining emissions; passed by `buy_nft` with `BuyAccounts::reward_mint`.
+pub fn derive_trade_mining_pda() -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"trade_mining"])
+}
+
+/// Lamports set aside for `execute_buyback`; point the treasury at it to route fees into buybacks.
+pub fn derive_buyback_vault_pda() -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"buyback_vault"])
+}
+
+/// Collects marketplace fees for `sweep_fees`; point the treasury at it to batch fee payouts.
+pub fn derive_fee_vault_pda() -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"fee_vault"])
+}
+
+/// Holds the escrows' stake pool tokens, depositing & withdrawing for them.
+pub fn derive_yield_vault_pda() -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"yield_vault"])
+}
+
+/// Stake pool position of an installment plan or auction bid `escrow`.
+pub fn derive_escrow_yield_pda(escrow: &Pubkey) -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"escrow_yield", escrow.as_ref()])
+}
+
+pub fn derive_loan_offer_pda(listing: &Pubkey, lender: &Pubkey) -> (Pubkey, u8) {
+    find(&[
+        PREFIX.as_bytes(),
+        b"loan_offer",
+        listing.as_ref(),
+        lender.as_ref(),
+    ])
+}
+
+pub fn derive_banned_wallet_pda(wallet: &Pubkey) -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"banned_wallet", wallet.as_ref()])
+}
+
+pub fn derive_offer_pda(mint: &Pubkey, bidder: &Pubkey) -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"offer", mint.as_ref(), bidder.as_ref()])
+}
+
+pub fn derive_collection_offer_pda(collection: &Pubkey, bidder: &Pubkey) -> (Pubkey, u8) {
+    find(&[
+        PREFIX.as_bytes(),
+        b"collection_offer",
+        collection.as_ref(),
+        bidder.as_ref(),
+    ])
+}
+
+/// Purchases of `buyer` in `seller`'s drop `drop`, for listings with a purchase limit.
+pub fn derive_wallet_purchases_pda(seller: &Pubkey, drop: &Pubkey, buyer: &Pubkey) -> (Pubkey, u8) {
+    find(&[
+        PREFIX.as_bytes(),
+        b"wallet_purchases",
+        seller.as_ref(),
+        drop.as_ref(),
+        buyer.as_ref(),
+    ])
+}
+
+/// Best offer tracker of `mint`.
+pub fn derive_best_offer_pda(mint: &Pubkey) -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"best_offer", mint.as_ref()])
+}
+
+pub fn derive_offer_book_pda(mint: &Pubkey) -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"offer_book", mint.as_ref()])
+}
+
+/// Vesting of a vested listing's sale proceeds.
+pub fn derive_vesting_pda(listing: &Pubkey) -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"vesting", listing.as_ref()])
+}
+
+/// Escrowed proceeds of a phygital listing's sale.
+pub fn derive_escrowed_sale_pda(listing: &Pubkey) -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"escrowed_sale", listing.as_ref()])
+}
+
+/// Withheld sale proceeds of `seller`.
+pub fn derive_withholding_pda(seller: &Pubkey) -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"withholding", seller.as_ref()])
+}
+
+/// Marketplace freeze authority: mints with the default account state extension that make it their
+/// freeze authority get their frozen vault / buyer accounts thawed on transfer. Append it to the
+/// `remaining_accounts` of NFT transfers of such mints.
+pub fn derive_freeze_authority_pda() -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"freeze_authority"])
+}
+
+/// SPL Memo program, to append to the `remaining_accounts` of NFT transfers to recipients whose
+/// token account requires incoming transfer memos.
+pub use anchor_spl::memo::ID as MEMO_PROGRAM_ID;
+
+/// Immutable record of `mint`'s sale number `sale` (its provenance `sales_count` before the sale).
+pub fn derive_sale_record_pda(mint: &Pubkey, sale: u64) -> (Pubkey, u8) {
+    find(&[
+        PREFIX.as_bytes(),
+        b"sale_record",
+        mint.as_ref(),
+        &sale.to_le_bytes(),
+    ])
+}
+
+/// Receipt of `buyer`'s purchase of `mint`'s sale number `sale`.
+pub fn derive_sale_receipt_pda(buyer: &Pubkey, mint: &Pubkey, sale: u64) -> (Pubkey, u8) {
+    find(&[
+        PREFIX.as_bytes(),
+        b"sale_receipt",
+        buyer.as_ref(),
+        mint.as_ref(),
+        &sale.to_le_bytes(),
+    ])
+}
+
+/// Listing registry page `page`.
+pub fn derive_registry_page_pda(page: u32) -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"registry", &page.to_le_bytes()])
+}
+
+/// Listing rewards vault of `reward_mint`.
+pub fn derive_reward_vault_pda(reward_mint: &Pubkey) -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"reward_vault", reward_mint.as_ref()])
+}
+
+pub fn derive_queued_action_pda(nonce: u64) -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"queued_action", &nonce.to_le_bytes()])
+}
+
+/// Edition listing of `seller`'s units of `mint`.
+pub fn derive_edition_listing_pda(mint: &Pubkey, seller: &Pubkey) -> (Pubkey, u8) {
+    find(&[
+        PREFIX.as_bytes(),
+        b"edition_listing",
+        mint.as_ref(),
+        seller.as_ref(),
+    ])
+}
+
+/// `owner`'s liquidity pool for `collection`.
+pub fn derive_pool_pda(owner: &Pubkey, collection: &Pubkey) -> (Pubkey, u8) {
+    find(&[
+        PREFIX.as_bytes(),
+        b"pool",
+        owner.as_ref(),
+        collection.as_ref(),
+    ])
+}
+
+pub fn derive_raffle_pda(mint: &Pubkey) -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"raffle", mint.as_ref()])
+}
+
+/// Raffle entry starting at ticket `first_ticket`.
+pub fn derive_raffle_entry_pda(raffle: &Pubkey, first_ticket: u64) -> (Pubkey, u8) {
+    find(&[
+        PREFIX.as_bytes(),
+        b"raffle_entry",
+        raffle.as_ref(),
+        &first_ticket.to_le_bytes(),
+    ])
+}
+
+pub fn derive_fraction_pda(mint: &Pubkey) -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"fraction", mint.as_ref()])
+}
+
+/// Share mint of a fractionalized NFT (SPL Token).
+pub fn derive_share_mint_pda(mint: &Pubkey) -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"share_mint", mint.as_ref()])
+}
+
+/// Associated share token account of `owner` for a fractionalized `mint`.
+pub fn share_token_account(owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+    get_associated_token_address_with_program_id(owner, &derive_share_mint_pda(mint).0, &token::ID)
+}
+
+pub fn derive_rental_pda(mint: &Pubkey) -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"rental", mint.as_ref()])
+}
+
+pub fn derive_installment_plan_pda(listing: &Pubkey) -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"installment", listing.as_ref()])
+}
+
+pub fn derive_group_buy_pda(listing: &Pubkey) -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"group_buy", listing.as_ref()])
+}
+
+pub fn derive_group_buy_contribution_pda(group_buy: &Pubkey, contributor: &Pubkey) -> (Pubkey, u8) {
+    find(&[
+        PREFIX.as_bytes(),
+        b"contribution",
+        group_buy.as_ref(),
+        contributor.as_ref(),
+    ])
+}
+
+/// Token account holding a group buy's NFT, owned by the group PDA.
+pub fn derive_group_nft_account_pda(group_buy: &Pubkey) -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"group_vault", group_buy.as_ref()])
+}
+
+/// WSOL escrow token account of a WSOL offer.
+pub fn derive_offer_escrow_pda(offer: &Pubkey) -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"offer_escrow", offer.as_ref()])
+}
+
+/// Transfer delegate sellers approve for signed orders.
+pub fn derive_delegate_pda() -> (Pubkey, u8) {
+    find(&[PREFIX.as_bytes(), b"delegate"])
+}
+
+/// Bitmap page of `seller`'s signed-order nonces holding `nonce`.
+pub fn derive_order_nonce_bitmap_pda(seller: &Pubkey, nonce: u64) -> (Pubkey, u8) {
+    find(&[
+        PREFIX.as_bytes(),
+        b"order_nonces",
+        seller.as_ref(),
+        &(nonce / ORDER_NONCES_PER_PAGE).to_le_bytes(),
+    ])
+}
+
+/// Per-nonce account used before nonce bitmaps.
+pub fn derive_order_nonce_pda(seller: &Pubkey, nonce: u64) -> (Pubkey, u8) {
+    find(&[
+        PREFIX.as_bytes(),
+        b"order",
+        seller.as_ref(),
+        &nonce.to_le_bytes(),
+    ])
+}
+
+/// Metaplex metadata account of `mint`.
+pub fn derive_metadata_pda(mint: &Pubkey) -> Pubkey {
+    mpl_token_metadata::accounts::Metadata::find_pda(mint).0
+}
+
+/// Passes `mint` itself instead of its Metaplex metadata in `ix`, for Token-2022 group members (e.g.
+/// WNS collections): the program then reads the collection from the mint's group member extension.
+pub fn with_group_membership(mut ix: Instruction, mint: &Pubkey) -> Instruction {
+    let metadata = derive_metadata_pda(mint);
+    for account in ix
+        .accounts
+        .iter_mut()
+        .filter(|account| account.pubkey == metadata)
+    {
+        account.pubkey = *mint;
+    }
+    ix
+}
+
+/// Associated WSOL (SPL Token native mint) account of `owner`.
+pub fn wsol_token_account(owner: &Pubkey) -> Pubkey {
+    get_associated_token_address_with_program_id(
+        owner,
+        &token::spl_token::native_mint::ID,
+        &token::ID,
+    )
+}
+
+/// Token-2022 associated token account of `owner` for NFT `mint`.
+pub fn nft_token_account(owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+    get_associated_token_address_with_program_id(owner, mint, &token_2022::ID)
+}
+
+/// Associated account of `owner` for the SPL mint `offer` is escrowed in, `None` for SOL offers.
+pub fn offer_payment_account(owner: &Pubkey, offer: &Offer) -> Option<Pubkey> {
+    offer
+        .payment_mint()
+        .zip(offer.payment_token_program())
+        .map(|(mint, token_program)| {
+            get_associated_token_address_with_program_id(owner, &mint, &token_program)
+        })
+}
+
+/// Merkle tree levels over `keys` (leaves first); an unpaired node moves up as is.
+fn merkle_levels(keys: &[Pubkey]) -> Vec<Vec<[u8; 32]>> {
+    let mut levels = vec![keys
+        .iter()
+        .map(marketplace::merkle_leaf)
+        .collect::<Vec<_>>()];
+    while let Some(level) = levels.last().filter(|level| level.len() > 1) {
+        let parents = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [a, b] => marketplace::merkle_parent(a, b),
+                [a] => *a,
+                _ => unreachable!(),
+            })
+            .collect();
+        levels.push(parents);
+    }
+    levels
+}
+
+/// Merkle root over `keys`: a collection offer's `mint_root`, or a listing's `allowlist_root`.
+pub fn merkle_root(keys: &[Pubkey]) -> [u8; 32] {
+    merkle_levels(keys)
+        .last()
+        .and_then(|level| level.first())
+        .copied()
+        .unwrap_or_default()
+}
+
+/// Proof that `key` is one of `keys`, for `accept_collection_offer` or `buy_nft`.
+pub fn merkle_proof(keys: &[Pubkey], key: &Pubkey) -> Option<Vec<[u8; 32]>> {
+    let mut index = keys.iter().position(|candidate| candidate == key)?;
+    let levels = merkle_levels(keys);
+    let mut proof = Vec::new();
+    for level in &levels[..levels.len() - 1] {
+        if let Some(sibling) = level.get(index ^ 1) {
+            proof.push(*sibling);
+        }
+        index /= 2;
+    }
+    Some(proof)
+}
+
+// --------------------------------------------------------------------
+// Instruction builders
+// --------------------------------------------------------------------
+fn build(accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+fn update_config_accounts(authority: Pubkey) -> accounts::UpdateConfig {
+    accounts::UpdateConfig {
+        config: derive_config_pda().0,
+        authority,
+    }
+}
+
+pub fn initialize_config(
+    authority: Pubkey,
+    treasury: Pubkey,
+    primary_fees: FeeSchedule,
+    secondary_fees: FeeSchedule,
+) -> Instruction {
+    build(
+        accounts::InitializeConfig {
+            config: derive_config_pda().0,
+            authority,
+            system_program: system_program::ID,
+        },
+        instruction::InitializeConfig {
+            treasury,
+            primary_fees,
+            secondary_fees,
+        },
+    )
+}
+
+pub fn nominate_new_authority(authority: Pubkey, new_authority: Pubkey) -> Instruction {
+    build(
+        update_config_accounts(authority),
+        instruction::NominateNewAuthority { new_authority },
+    )
+}
+
+pub fn accept_authority(pending_authority: Pubkey) -> Instruction {
+    build(
+        accounts::AcceptAuthority {
+            config: derive_config_pda().0,
+            pending_authority,
+        },
+        instruction::AcceptAuthority {},
+    )
+}
+
+/// Replaces the given fee schedules only.
+pub fn update_fees(
+    authority: Pubkey,
+    primary_fees: Option<FeeSchedule>,
+    secondary_fees: Option<FeeSchedule>,
+) -> Instruction {
+    build(
+        update_config_accounts(authority),
+        instruction::UpdateFees {
+            primary_fees,
+            secondary_fees,
+        },
+    )
+}
+
+pub fn update_fee_schedules(
+    authority: Pubkey,
+    primary_fees: FeeSchedule,
+    secondary_fees: FeeSchedule,
+) -> Instruction {
+    build(
+        update_config_accounts(authority),
+        instruction::UpdateFeeSchedules {
+            primary_fees,
+            secondary_fees,
+        },
+    )
+}
+
+pub fn set_fee_tiers(authority: Pubkey, fee_tiers: Vec<FeeTier>) -> Instruction {
+    build(
+        update_config_accounts(authority),
+        instruction::SetFeeTiers { fee_tiers },
+    )
+}
+
+pub fn set_royalty_enforcement(authority: Pubkey, enforce_royalties: bool) -> Instruction {
+    build(
+        update_config_accounts(authority),
+        instruction::SetRoyaltyEnforcement { enforce_royalties },
+    )
+}
+
+pub fn set_crank_tip(authority: Pubkey, crank_tip_lamports: u64) -> Instruction {
+    build(
+        update_config_accounts(authority),
+        instruction::SetCrankTip { crank_tip_lamports },
+    )
+}
+
+pub fn set_price_feed(
+    authority: Pubkey,
+    sol_usd_feed_id: [u8; 32],
+    max_price_age_secs: u64,
+    max_price_conf_bps: u16,
+) -> Instruction {
+    build(
+        update_config_accounts(authority),
+        instruction::SetPriceFeed {
+            sol_usd_feed_id,
+            max_price_age_secs,
+            max_price_conf_bps,
+        },
+    )
+}
+
+pub fn set_membership(
+    authority: Pubkey,
+    membership_mint: Pubkey,
+    min_amount: u64,
+    fee_bps: u16,
+) -> Instruction {
+    build(
+        update_config_accounts(authority),
+        instruction::SetMembership {
+            membership_mint,
+            min_amount,
+            fee_bps,
+        },
+    )
+}
+
+pub fn set_wsol_settlement(authority: Pubkey, unwrap_wsol_proceeds: bool) -> Instruction {
+    build(
+        update_config_accounts(authority),
+        instruction::SetWsolSettlement {
+            unwrap_wsol_proceeds,
+        },
+    )
+}
+
+pub fn set_listing_rewards(
+    authority: Pubkey,
+    reward_mint: Pubkey,
+    reward_per_point: u64,
+) -> Instruction {
+    build(
+        update_config_accounts(authority),
+        instruction::SetListingRewards {
+            reward_mint,
+            reward_per_point,
+        },
+    )
+}
+
+pub fn set_trade_mining(
+    authority: Pubkey,
+    mining_rate: u64,
+    mining_epoch_secs: i64,
+    mining_epoch_cap: u64,
+) -> Instruction {
+    build(
+        update_config_accounts(authority),
+        instruction::SetTradeMining {
+            mining_rate,
+            mining_epoch_secs,
+            mining_epoch_cap,
+        },
+    )
+}
+
+pub fn set_loyalty_program(
+    authority: Pubkey,
+    loyalty_points_per_sol: u64,
+    loyalty_point_value: u64,
+) -> Instruction {
+    build(
+        update_config_accounts(authority),
+        instruction::SetLoyaltyProgram {
+            loyalty_points_per_sol,
+            loyalty_point_value,
+        },
+    )
+}
+
+/// Pays to the wallet's associated account of `reward_mint`.
+pub fn redeem_points(
+    wallet: Pubkey,
+    reward_mint: Pubkey,
+    reward_token_program: Pubkey,
+    points: u64,
+) -> Instruction {
+    build(
+        accounts::RedeemPoints {
+            loyalty_account: derive_loyalty_pda(&wallet).0,
+            wallet,
+            config: derive_config_pda().0,
+            reward_mint,
+            reward_vault: derive_reward_vault_pda(&reward_mint).0,
+            wallet_reward_account: get_associated_token_address_with_program_id(
+                &wallet,
+                &reward_mint,
+                &reward_token_program,
+            ),
+            reward_token_program,
+        },
+        instruction::RedeemPoints { points },
+    )
+}
+
+pub fn initialize_trade_mining(payer: Pubkey) -> Instruction {
+    build(
+        accounts::InitializeTradeMining {
+            trade_mining: derive_trade_mining_pda().0,
+            payer,
+            system_program: system_program::ID,
+        },
+        instruction::InitializeTradeMining {},
+    )
+}
+
+/// Funds from the authority's associated account of `reward_mint` (owned by `reward_token_program`).
+/// `payer` funds the vault's rent on first use.
+pub fn fund_listing_rewards(
+    authority: Pubkey,
+    payer: Pubkey,
+    reward_mint: Pubkey,
+    reward_token_program: Pubkey,
+    amount: u64,
+) -> Instruction {
+    build(
+        accounts::FundListingRewards {
+            config: derive_config_pda().0,
+            authority,
+            payer,
+            reward_mint,
+            reward_vault: derive_reward_vault_pda(&reward_mint).0,
+            authority_reward_account: get_associated_token_address_with_program_id(
+                &authority,
+                &reward_mint,
+                &reward_token_program,
+            ),
+            reward_token_program,
+            system_program: system_program::ID,
+        },
+        instruction::FundListingRewards { amount },
+    )
+}
+
+pub fn enroll_listing_rewards(seller: Pubkey, listing: Pubkey) -> Instruction {
+    build(
+        accounts::EnrollListingRewards {
+            listing,
+            seller,
+            config: derive_config_pda().0,
+        },
+        instruction::EnrollListingRewards {},
+    )
+}
+
+/// Pays to the seller's associated account of `reward_mint`.
+pub fn claim_listing_rewards(
+    seller: Pubkey,
+    listing: Pubkey,
+    reward_mint: Pubkey,
+    reward_token_program: Pubkey,
+) -> Instruction {
+    build(
+        accounts::ClaimListingRewards {
+            listing,
+            seller,
+            config: derive_config_pda().0,
+            reward_mint,
+            reward_vault: derive_reward_vault_pda(&reward_mint).0,
+            seller_reward_account: get_associated_token_address_with_program_id(
+                &seller,
+                &reward_mint,
+                &reward_token_program,
+            ),
+            reward_token_program,
+        },
+        instruction::ClaimListingRewards {},
+    )
+}
+
+pub fn set_cpi_policy(
+    authority: Pubkey,
+    cpi_policy: CpiPolicy,
+    allowed_callers: Vec<AllowedCaller>,
+) -> Instruction {
+    build(
+        update_config_accounts(authority),
+        instruction::SetCpiPolicy {
+            cpi_policy,
+            allowed_callers,
+        },
+    )
+}
+
+pub fn set_maker_rebate(authority: Pubkey, maker_rebate_bps: u16) -> Instruction {
+    build(
+        update_config_accounts(authority),
+        instruction::SetMakerRebate { maker_rebate_bps },
+    )
+}
+
+pub fn set_collection_curation(authority: Pubkey, curated_collections: bool) -> Instruction {
+    build(
+        update_config_accounts(authority),
+        instruction::SetCollectionCuration {
+            curated_collections,
+        },
+    )
+}
+
+pub fn set_insurance_bps(authority: Pubkey, insurance_bps: u16) -> Instruction {
+    build(
+        update_config_accounts(authority),
+        instruction::SetInsuranceBps { insurance_bps },
+    )
+}
+
+pub fn initialize_insurance_fund(payer: Pubkey) -> Instruction {
+    build(
+        accounts::InitializeInsuranceFund {
+            insurance_fund: derive_insurance_fund_pda().0,
+            payer,
+            system_program: system_program::ID,
+        },
+        instruction::InitializeInsuranceFund {},
+    )
+}
+
+pub fn pay_insurance_claim(
+    authority: Pubkey,
+    recipient: Pubkey,
+    amount: u64,
+    claim_id: u64,
+) -> Instruction {
+    build(
+        accounts::PayInsuranceClaim {
+            insurance_fund: derive_insurance_fund_pda().0,
+            config: derive_config_pda().0,
+            authority,
+            recipient,
+        },
+        instruction::PayInsuranceClaim { amount, claim_id },
+    )
+}
+
+pub fn set_buyback(
+    authority: Pubkey,
+    buyback_mint: Pubkey,
+    buyback_amm_program: Pubkey,
+    max_buyback_lamports: u64,
+    buyback_interval: i64,
+    min_buyback_rate: u64,
+) -> Instruction {
+    build(
+        update_config_accounts(authority),
+        instruction::SetBuyback {
+            buyback_mint,
+            buyback_amm_program,
+            max_buyback_lamports,
+            buyback_interval,
+            min_buyback_rate,
+        },
+    )
+}
+
+/// Buys back & burns `buyback_mint` (owned by `buyback_token_program`) through `amm_program`.
+/// `swap_accounts` & `swap_data` make up the AMM's swap instruction, swapping the WSOL account of
+/// the buyback vault into its `buyback_mint` account; the vault goes in as a non-signer.
+pub fn execute_buyback(
+    cranker: Pubkey,
+    buyback_mint: Pubkey,
+    buyback_token_program: Pubkey,
+    amm_program: Pubkey,
+    swap_accounts: &[AccountMeta],
+    swap_data: Vec<u8>,
+) -> Instruction {
+    let buyback_vault = derive_buyback_vault_pda().0;
+    let mut ix = build(
+        accounts::ExecuteBuyback {
+            config: derive_config_pda().0,
+            cranker,
+            buyback_vault,
+            vault_wsol_account: wsol_token_account(&buyback_vault),
+            vault_token_account: get_associated_token_address_with_program_id(
+                &buyback_vault,
+                &buyback_mint,
+                &buyback_token_program,
+            ),
+            wsol_mint: token::spl_token::native_mint::ID,
+            buyback_mint,
+            amm_program,
+            token_program: token::ID,
+            buyback_token_program,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::ExecuteBuyback { swap_data },
+    );
+    ix.accounts.extend_from_slice(swap_accounts);
+    ix
+}
+
+pub fn set_fee_sweep(
+    authority: Pubkey,
+    fee_sweep_recipient: Pubkey,
+    fee_sweep_threshold: u64,
+) -> Instruction {
+    build(
+        update_config_accounts(authority),
+        instruction::SetFeeSweep {
+            fee_sweep_recipient,
+            fee_sweep_threshold,
+        },
+    )
+}
+
+/// Sweeps the fee vault to `recipient` (`config.fee_sweep_recipient`), along with its associated
+/// account of `payment` (mint, token program) when given, into the recipient's.
+pub fn sweep_fees(
+    cranker: Pubkey,
+    recipient: Pubkey,
+    payment: Option<(Pubkey, Pubkey)>,
+) -> Instruction {
+    let fee_vault = derive_fee_vault_pda().0;
+    build(
+        accounts::SweepFees {
+            config: derive_config_pda().0,
+            cranker,
+            fee_vault,
+            recipient,
+            payment_mint: payment.map(|(mint, _)| mint),
+            vault_payment_account: payment.map(|(mint, token_program)| {
+                get_associated_token_address_with_program_id(&fee_vault, &mint, &token_program)
+            }),
+            recipient_payment_account: payment.map(|(mint, token_program)| {
+                get_associated_token_address_with_program_id(&recipient, &mint, &token_program)
+            }),
+            payment_token_program: payment.map(|(_, token_program)| token_program),
+            system_program: system_program::ID,
+        },
+        instruction::SweepFees {},
+    )
+}
+
+pub fn set_escrow_yield(
+    authority: Pubkey,
+    yield_stake_pool_program: Pubkey,
+    yield_stake_pool: Pubkey,
+    yield_to_treasury: bool,
+) -> Instruction {
+    build(
+        update_config_accounts(authority),
+        instruction::SetEscrowYield {
+            yield_stake_pool_program,
+            yield_stake_pool,
+            yield_to_treasury,
+        },
+    )
+}
+
+/// Stakes `lamports` of `owner`'s installment plan or bid `escrow` (`auction` being a bid's auction).
+/// `deposit_accounts` are the stake pool's `DepositSol` accounts, the yield vault
+/// (`derive_yield_vault_pda`) being the depositor & its `pool_mint` associated account the pool token
+/// destination.
+pub fn stake_escrow(
+    owner: Pubkey,
+    escrow: Pubkey,
+    auction: Option<Pubkey>,
+    pool_mint: Pubkey,
+    stake_pool_program: Pubkey,
+    deposit_accounts: &[AccountMeta],
+    lamports: u64,
+) -> Instruction {
+    let yield_vault = derive_yield_vault_pda().0;
+    let mut ix = build(
+        accounts::StakeEscrow {
+            config: derive_config_pda().0,
+            owner,
+            escrow,
+            auction,
+            escrow_yield: derive_escrow_yield_pda(&escrow).0,
+            yield_vault,
+            vault_pool_account: get_associated_token_address_with_program_id(
+                &yield_vault,
+                &pool_mint,
+                &token::ID,
+            ),
+            pool_mint,
+            stake_pool_program,
+            token_program: token::ID,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::StakeEscrow { lamports },
+    );
+    ix.accounts.extend_from_slice(deposit_accounts);
+    ix
+}
+
+/// Unstakes `escrow` for its owner `beneficiary`; `withdraw_accounts` are the stake pool's
+/// `WithdrawSol` accounts, the yield vault being the transfer authority & lamports destination.
+pub fn unstake_escrow(
+    cranker: Pubkey,
+    escrow: Pubkey,
+    beneficiary: Pubkey,
+    treasury: Pubkey,
+    pool_mint: Pubkey,
+    stake_pool_program: Pubkey,
+    withdraw_accounts: &[AccountMeta],
+) -> Instruction {
+    let yield_vault = derive_yield_vault_pda().0;
+    let mut ix = build(
+        accounts::UnstakeEscrow {
+            config: derive_config_pda().0,
+            cranker,
+            escrow,
+            escrow_yield: derive_escrow_yield_pda(&escrow).0,
+            beneficiary,
+            treasury,
+            yield_vault,
+            vault_pool_account: get_associated_token_address_with_program_id(
+                &yield_vault,
+                &pool_mint,
+                &token::ID,
+            ),
+            stake_pool_program,
+            system_program: system_program::ID,
+        },
+        instruction::UnstakeEscrow {},
+    );
+    ix.accounts.extend_from_slice(withdraw_accounts);
+    ix
+}
+
+pub fn make_loan_offer(
+    lender: Pubkey,
+    listing: Pubkey,
+    principal: u64,
+    repayment: u64,
+    duration: i64,
+) -> Instruction {
+    build(
+        accounts::MakeLoanOffer {
+            loan_offer: derive_loan_offer_pda(&listing, &lender).0,
+            listing,
+            lender,
+            system_program: system_program::ID,
+        },
+        instruction::MakeLoanOffer {
+            principal,
+            repayment,
+            duration,
+        },
+    )
+}
+
+pub fn cancel_loan_offer(lender: Pubkey, listing: Pubkey) -> Instruction {
+    build(
+        accounts::CancelLoanOffer {
+            loan_offer: derive_loan_offer_pda(&listing, &lender).0,
+            lender,
+        },
+        instruction::CancelLoanOffer {},
+    )
+}
+
+pub fn accept_loan(seller: Pubkey, listing: Pubkey, lender: Pubkey) -> Instruction {
+    build(
+        accounts::AcceptLoan {
+            loan_offer: derive_loan_offer_pda(&listing, &lender).0,
+            listing,
+            seller,
+        },
+        instruction::AcceptLoan {},
+    )
+}
+
+pub fn repay_loan(borrower: Pubkey, listing: Pubkey, lender: Pubkey) -> Instruction {
+    build(
+        accounts::RepayLoan {
+            loan_offer: derive_loan_offer_pda(&listing, &lender).0,
+            listing,
+            borrower,
+            lender,
+            system_program: system_program::ID,
+        },
+        instruction::RepayLoan {},
+    )
+}
+
+/// Forecloses `lender`'s overdue loan against `listing`, the NFT going to the lender's associated account
+/// (which must exist). `hook_accounts` are the mint's transfer hook extra accounts, if any.
+pub fn foreclose_loan(
+    lender: Pubkey,
+    listing_key: Pubkey,
+    listing: &Listing,
+    hook_accounts: &[AccountMeta],
+) -> Instruction {
+    let mut ix = build(
+        accounts::ForecloseLoan {
+            loan_offer: derive_loan_offer_pda(&listing_key, &lender).0,
+            listing: listing_key,
+            lender,
+            rent_payer: listing.rent_recipient(),
+            lender_nft_account: nft_token_account(&lender, &listing.mint),
+            mint: listing.mint,
+            vault: derive_listing_vault_pda(&listing.mint, &listing_key).0,
+            token_program: token_2022::ID,
+        },
+        instruction::ForecloseLoan {},
+    );
+    ix.accounts.extend_from_slice(hook_accounts);
+    ix
+}
+
+pub fn set_max_listing_price(authority: Pubkey, max_listing_price: u64) -> Instruction {
+    build(
+        update_config_accounts(authority),
+        instruction::SetMaxListingPrice { max_listing_price },
+    )
+}
+
+pub fn set_strict_listing(authority: Pubkey, strict_listing: bool) -> Instruction {
+    build(
+        update_config_accounts(authority),
+        instruction::SetStrictListing { strict_listing },
+    )
+}
+
+pub fn queue_action(
+    authority: Pubkey,
+    payer: Pubkey,
+    nonce: u64,
+    action: EmergencyAction,
+) -> Instruction {
+    build(
+        accounts::QueueAction {
+            queued_action: derive_queued_action_pda(nonce).0,
+            config: derive_config_pda().0,
+            authority,
+            payer,
+            system_program: system_program::ID,
+        },
+        instruction::QueueAction { nonce, action },
+    )
+}
+
+fn settle_queued_action_accounts(
+    authority: Pubkey,
+    nonce: u64,
+    reward_vault_sweep: Option<RewardVaultSweepAccounts>,
+) -> accounts::SettleQueuedAction {
+    accounts::SettleQueuedAction {
+        queued_action: derive_queued_action_pda(nonce).0,
+        config: derive_config_pda().0,
+        authority,
+        reward_mint: reward_vault_sweep.map(|sweep| sweep.reward_mint),
+        reward_vault: reward_vault_sweep.map(|sweep| derive_reward_vault_pda(&sweep.reward_mint).0),
+        treasury_reward_account: reward_vault_sweep.map(|sweep| {
+            get_associated_token_address_with_program_id(
+                &sweep.treasury,
+                &sweep.reward_mint,
+                &sweep.reward_token_program,
+            )
+        }),
+        reward_token_program: reward_vault_sweep.map(|sweep| sweep.reward_token_program),
+    }
+}
+
+/// Accounts of a queued `RewardVaultSweep`; the treasury's associated reward account gets the tokens.
+#[derive(Clone, Copy)]
+pub struct RewardVaultSweepAccounts {
+    pub treasury: Pubkey,
+    pub reward_mint: Pubkey,
+    pub reward_token_program: Pubkey,
+}
+
+pub fn cancel_queued_action(authority: Pubkey, nonce: u64) -> Instruction {
+    build(
+        settle_queued_action_accounts(authority, nonce, None),
+        instruction::CancelQueuedAction {},
+    )
+}
+
+/// `reward_vault_sweep` is required for `RewardVaultSweep` actions.
+pub fn execute_queued_action(
+    authority: Pubkey,
+    nonce: u64,
+    reward_vault_sweep: Option<RewardVaultSweepAccounts>,
+) -> Instruction {
+    build(
+        settle_queued_action_accounts(authority, nonce, reward_vault_sweep),
+        instruction::ExecuteQueuedAction {},
+    )
+}
+
+/// Runs queued `ForceDelist` action `nonce` on one of its listings.
+pub fn force_delist(
+    cranker: Pubkey,
+    nonce: u64,
+    listing_key: Pubkey,
+    listing: &Listing,
+) -> Instruction {
+    build(
+        accounts::ForceDelist {
+            #[cfg(feature = "event-cpi")]
+            event_authority: derive_event_authority_pda().0,
+            #[cfg(feature = "event-cpi")]
+            program: PROGRAM_ID,
+            queued_action: derive_queued_action_pda(nonce).0,
+            listing: listing_key,
+            config: derive_config_pda().0,
+            cranker,
+            seller: listing.seller,
+            rent_payer: listing.rent_recipient(),
+            nft_account: nft_token_account(&listing.seller, &listing.mint),
+            mint: listing.mint,
+            vault: derive_listing_vault_pda(&listing.mint, &listing_key).0,
+            token_program: token_2022::ID,
+        },
+        instruction::ForceDelist {},
+    )
+}
+
+pub fn pause(authority: Pubkey) -> Instruction {
+    build(update_config_accounts(authority), instruction::Pause {})
+}
+
+pub fn unpause(authority: Pubkey) -> Instruction {
+    build(update_config_accounts(authority), instruction::Unpause {})
+}
+
+pub fn set_withholding(
+    authority: Pubkey,
+    withholding_bps: u16,
+    withholding_release_ts: i64,
+) -> Instruction {
+    build(
+        update_config_accounts(authority),
+        instruction::SetWithholding {
+            withholding_bps,
+            withholding_release_ts,
+        },
+    )
+}
+
+pub fn set_arbiter(authority: Pubkey, arbiter: Pubkey) -> Instruction {
+    build(
+        update_config_accounts(authority),
+        instruction::SetArbiter { arbiter },
+    )
+}
+
+/// `payer` funds the rent, & may differ from a PDA `authority`.
+pub fn add_allowed_collection(authority: Pubkey, payer: Pubkey, collection: Pubkey) -> Instruction {
+    build(
+        accounts::AddAllowedCollection {
+            allowed_collection: derive_allowed_collection_pda(&collection).0,
+            config: derive_config_pda().0,
+            authority,
+            payer,
+            system_program: system_program::ID,
+        },
+        instruction::AddAllowedCollection { collection },
+    )
+}
+
+pub fn set_collection_min_price(
+    authority: Pubkey,
+    payer: Pubkey,
+    collection: Pubkey,
+    min_price: Option<u64>,
+) -> Instruction {
+    build(
+        accounts::SetCollectionMinPrice {
+            allowed_collection: derive_allowed_collection_pda(&collection).0,
+            config: derive_config_pda().0,
+            authority,
+            payer,
+            system_program: system_program::ID,
+        },
+        instruction::SetCollectionMinPrice { min_price },
+    )
+}
+
+pub fn remove_allowed_collection(authority: Pubkey, collection: Pubkey) -> Instruction {
+    build(
+        accounts::RemoveAllowedCollection {
+            allowed_collection: derive_allowed_collection_pda(&collection).0,
+            config: derive_config_pda().0,
+            authority,
+        },
+        instruction::RemoveAllowedCollection {},
+    )
+}
+
+/// `payer` funds the rent, & may differ from a PDA `authority`.
+pub fn block_mint(authority: Pubkey, payer: Pubkey, mint: Pubkey) -> Instruction {
+    build(
+        accounts::BlockMint {
+            blocked_mint: derive_blocked_mint_pda(&mint).0,
+            config: derive_config_pda().0,
+            authority,
+            payer,
+            system_program: system_program::ID,
+        },
+        instruction::BlockMint { mint },
+    )
+}
+
+pub fn unblock_mint(authority: Pubkey, mint: Pubkey) -> Instruction {
+    build(
+        accounts::UnblockMint {
+            blocked_mint: derive_blocked_mint_pda(&mint).0,
+            config: derive_config_pda().0,
+            authority,
+        },
+        instruction::UnblockMint {},
+    )
+}
+
+pub fn ban_wallet(authority: Pubkey, payer: Pubkey, wallet: Pubkey) -> Instruction {
+    build(
+        accounts::BanWallet {
+            banned_wallet: derive_banned_wallet_pda(&wallet).0,
+            config: derive_config_pda().0,
+            authority,
+            payer,
+            system_program: system_program::ID,
+        },
+        instruction::BanWallet { wallet },
+    )
+}
+
+pub fn unban_wallet(authority: Pubkey, wallet: Pubkey) -> Instruction {
+    build(
+        accounts::UnbanWallet {
+            banned_wallet: derive_banned_wallet_pda(&wallet).0,
+            config: derive_config_pda().0,
+            authority,
+        },
+        instruction::UnbanWallet {},
+    )
+}
+
+pub fn initialize_global_stats(payer: Pubkey) -> Instruction {
+    build(
+        accounts::InitializeGlobalStats {
+            global_stats: derive_global_stats_pda().0,
+            payer,
+            system_program: system_program::ID,
+        },
+        instruction::InitializeGlobalStats {},
+    )
+}
+
+pub fn create_collection_stats(payer: Pubkey, collection: Pubkey) -> Instruction {
+    build(
+        accounts::CreateCollectionStats {
+            collection_stats: derive_collection_stats_pda(&collection).0,
+            payer,
+            system_program: system_program::ID,
+        },
+        instruction::CreateCollectionStats { collection },
+    )
+}
+
+/// Caches (or refreshes) `mint`'s creator shares & verified collection from its metadata.
+pub fn cache_royalties(payer: Pubkey, mint: Pubkey) -> Instruction {
+    build(
+        accounts::CacheRoyalties {
+            royalty_cache: derive_royalty_cache_pda(&mint).0,
+            mint,
+            metadata: derive_metadata_pda(&mint),
+            payer,
+            system_program: system_program::ID,
+        },
+        instruction::CacheRoyalties {},
+    )
+}
+
+pub fn sync_royalty_creator(mint: Pubkey) -> Instruction {
+    build(
+        accounts::SyncRoyaltyCreator {
+            provenance: derive_provenance_pda(&mint).0,
+            mint,
+            metadata: derive_metadata_pda(&mint),
+        },
+        instruction::SyncRoyaltyCreator {},
+    )
+}
+
+pub fn create_trader_profile(trader: Pubkey) -> Instruction {
+    build(
+        accounts::CreateTraderProfile {
+            profile: derive_trader_profile_pda(&trader).0,
+            trader,
+            system_program: system_program::ID,
+        },
+        instruction::CreateTraderProfile {},
+    )
+}
+
+/// `storefront` is a new keypair, signing alongside the config authority.
+pub fn create_storefront(
+    storefront: Pubkey,
+    config_authority: Pubkey,
+    authority: Pubkey,
+    treasury: Pubkey,
+    fee_bps: u16,
+) -> Instruction {
+    build(
+        accounts::CreateStorefront {
+            storefront,
+            config: derive_config_pda().0,
+            authority: config_authority,
+            system_program: system_program::ID,
+        },
+        instruction::CreateStorefront {
+            authority,
+            treasury,
+            fee_bps,
+        },
+    )
+}
+
+pub fn update_storefront(
+    storefront: Pubkey,
+    authority: Pubkey,
+    treasury: Pubkey,
+    fee_bps: u16,
+) -> Instruction {
+    build(
+        accounts::UpdateStorefront {
+            storefront,
+            authority,
+            config: derive_config_pda().0,
+        },
+        instruction::UpdateStorefront { treasury, fee_bps },
+    )
+}
+
+pub fn add_storefront_collection(
+    storefront: Pubkey,
+    authority: Pubkey,
+    collection: Pubkey,
+) -> Instruction {
+    build(
+        accounts::UpdateStorefront {
+            storefront,
+            authority,
+            config: derive_config_pda().0,
+        },
+        instruction::AddStorefrontCollection { collection },
+    )
+}
+
+pub fn remove_storefront_collection(
+    storefront: Pubkey,
+    authority: Pubkey,
+    collection: Pubkey,
+) -> Instruction {
+    build(
+        accounts::UpdateStorefront {
+            storefront,
+            authority,
+            config: derive_config_pda().0,
+        },
+        instruction::RemoveStorefrontCollection { collection },
+    )
+}
+
+/// Where a new listing / auction goes through curation.
+#[derive(Clone, Copy, Default)]
+pub struct ListAccounts {
+    /// Storefront to list through.
+    pub storefront: Option<Pubkey>,
+    /// Verified collection of the mint, needed in curated mode & by curated storefronts.
+    pub collection: Option<Pubkey>,
+    /// Registry page (with room left) new listings are added to.
+    pub registry_page: u32,
+    /// Pays the listing's rent instead of the seller (e.g. a relayer), co-signing.
+    pub payer: Option<Pubkey>,
+}
+
+fn list_nft_accounts(
+    listing: Pubkey,
+    seller: Pubkey,
+    mint: Pubkey,
+    extra: ListAccounts,
+) -> accounts::ListNFT {
+    accounts::ListNFT {
+        #[cfg(feature = "event-cpi")]
+        event_authority: derive_event_authority_pda().0,
+        #[cfg(feature = "event-cpi")]
+        program: PROGRAM_ID,
+        listing,
+        seller,
+        payer: extra.payer.unwrap_or(seller),
+        nft_account: nft_token_account(&seller, &mint),
+        mint,
+        vault: derive_listing_vault_pda(&mint, &listing).0,
+        provenance: derive_provenance_pda(&mint).0,
+        config: derive_config_pda().0,
+        global_stats: derive_global_stats_pda().0,
+        blocked_mint: derive_blocked_mint_pda(&mint).0,
+        seller_ban: derive_banned_wallet_pda(&seller).0,
+        allowed_collection: extra
+            .collection
+            .map(|collection| derive_allowed_collection_pda(&collection).0),
+        storefront: extra.storefront,
+        metadata: extra.collection.map(|_| derive_metadata_pda(&mint)),
+        registry_page: Some(derive_registry_page_pda(extra.registry_page).0),
+        system_program: system_program::ID,
+        token_program: token_2022::ID,
+        rent: sysvar::rent::ID,
+    }
+}
+
+/// `listing` is a new keypair, signing alongside the seller. `price` is in whole SOL.
+pub fn list_nft(
+    listing: Pubkey,
+    seller: Pubkey,
+    mint: Pubkey,
+    price: u64,
+    extra: ListAccounts,
+) -> Instruction {
+    build(
+        list_nft_accounts(listing, seller, mint, extra),
+        instruction::ListNft { price },
+    )
+}
+
+pub fn list_nft_v2(
+    listing: Pubkey,
+    seller: Pubkey,
+    mint: Pubkey,
+    price: u64,
+    options: ListingOptions,
+    extra: ListAccounts,
+) -> Instruction {
+    build(
+        list_nft_accounts(listing, seller, mint, extra),
+        instruction::ListNftV2 { price, options },
+    )
+}
+
+/// `list_nft_v2`, or an update of `listing` if it already exists (e.g. the same transaction retried).
+/// `listing` signs either way.
+pub fn list_or_update_nft(
+    listing: Pubkey,
+    seller: Pubkey,
+    mint: Pubkey,
+    price: u64,
+    options: ListingOptions,
+    extra: ListAccounts,
+) -> Instruction {
+    build(
+        list_nft_accounts(listing, seller, mint, extra),
+        instruction::ListOrUpdateNft { price, options },
+    )
+}
+
+pub fn list_nft_usd(
+    listing: Pubkey,
+    seller: Pubkey,
+    mint: Pubkey,
+    price_usd: u64,
+    extra: ListAccounts,
+) -> Instruction {
+    build(
+        list_nft_accounts(listing, seller, mint, extra),
+        instruction::ListNftUsd { price_usd },
+    )
+}
+
+/// `authority` is the seller or the config authority.
+pub fn reallocate_listing(listing: Pubkey, authority: Pubkey) -> Instruction {
+    build(
+        accounts::ReallocateListing {
+            listing,
+            authority,
+            config: derive_config_pda().0,
+            system_program: system_program::ID,
+        },
+        instruction::ReallocateListing {},
+    )
+}
+
+pub fn migrate_listing(listing: Pubkey, payer: Pubkey) -> Instruction {
+    build(
+        accounts::MigrateListing {
+            listing,
+            payer,
+            system_program: system_program::ID,
+        },
+        instruction::MigrateListing {},
+    )
+}
+
+/// Moves a listing's NFT out of the mint-only vault used before listings had their own.
+pub fn migrate_listing_vault(seller: Pubkey, listing: Pubkey, mint: Pubkey) -> Instruction {
+    build(
+        accounts::MigrateListingVault {
+            listing,
+            seller,
+            mint,
+            legacy_vault: derive_vault_pda(&mint).0,
+            vault: derive_listing_vault_pda(&mint, &listing).0,
+            system_program: system_program::ID,
+            token_program: token_2022::ID,
+        },
+        instruction::MigrateListingVault {},
+    )
+}
+
+/// `migrate_listing_vault` by the config authority, for a listing of `seller`.
+pub fn migrate_vault(
+    authority: Pubkey,
+    listing: Pubkey,
+    seller: Pubkey,
+    mint: Pubkey,
+) -> Instruction {
+    build(
+        accounts::MigrateVault {
+            config: derive_config_pda().0,
+            authority,
+            listing,
+            seller,
+            mint,
+            legacy_vault: derive_vault_pda(&mint).0,
+            vault: derive_listing_vault_pda(&mint, &listing).0,
+            system_program: system_program::ID,
+            token_program: token_2022::ID,
+        },
+        instruction::MigrateVault {},
+    )
+}
+
+pub fn create_registry_page(page: u32, payer: Pubkey) -> Instruction {
+    build(
+        accounts::CreateRegistryPage {
+            registry_page: derive_registry_page_pda(page).0,
+            payer,
+            system_program: system_program::ID,
+        },
+        instruction::CreateRegistryPage { page },
+    )
+}
+
+/// Drops the closed / inactive ones of `listings` from registry page `page`.
+pub fn prune_registry(page: u32, listings: &[Pubkey]) -> Instruction {
+    let mut ix = build(
+        accounts::PruneRegistry {
+            registry_page: derive_registry_page_pda(page).0,
+        },
+        instruction::PruneRegistry {},
+    );
+    ix.accounts.extend(
+        listings
+            .iter()
+            .map(|listing| AccountMeta::new_readonly(*listing, false)),
+    );
+    ix
+}
+
+/// Closes sold `listings` (key & account) & finished `auctions`, given as (auction, seller) pairs; each
+/// listing is passed with its rent recipient, vault & installment plan.
+pub fn sweep_closed_state(
+    cranker: Pubkey,
+    listings: &[(Pubkey, &Listing)],
+    auctions: &[(Pubkey, Pubkey)],
+) -> Instruction {
+    let mut ix = build(
+        accounts::SweepClosedState {
+            cranker,
+            config: derive_config_pda().0,
+        },
+        instruction::SweepClosedState {},
+    );
+    ix.accounts
+        .extend(listings.iter().flat_map(|(listing_key, listing)| {
+            [
+                AccountMeta::new(*listing_key, false),
+                AccountMeta::new(listing.rent_recipient(), false),
+                AccountMeta::new_readonly(
+                    derive_listing_vault_pda(&listing.mint, listing_key).0,
+                    false,
+                ),
+                AccountMeta::new_readonly(derive_installment_plan_pda(listing_key).0, false),
+            ]
+        }));
+    ix.accounts
+        .extend(auctions.iter().flat_map(|(auction, seller)| {
+            [
+                AccountMeta::new(*auction, false),
+                AccountMeta::new(*seller, false),
+            ]
+        }));
+    ix
+}
+
+/// Takes back an NFT left in its mint's shared vault once `deposit.holder` is closed.
+pub fn recover_vault(deposit: &VaultDeposit) -> Instruction {
+    build(
+        accounts::RecoverVault {
+            vault_deposit: derive_vault_deposit_pda(&deposit.mint).0,
+            holder: deposit.holder,
+            depositor: deposit.depositor,
+            depositor_nft_account: nft_token_account(&deposit.depositor, &deposit.mint),
+            mint: deposit.mint,
+            vault: derive_vault_pda(&deposit.mint).0,
+            token_program: token_2022::ID,
+        },
+        instruction::RecoverVault {},
+    )
+}
+
+pub fn close_expired_listing(
+    listing_key: Pubkey,
+    listing: &Listing,
+    cranker: Pubkey,
+) -> Instruction {
+    build(
+        accounts::CloseExpiredListing {
+            #[cfg(feature = "event-cpi")]
+            event_authority: derive_event_authority_pda().0,
+            #[cfg(feature = "event-cpi")]
+            program: PROGRAM_ID,
+            listing: listing_key,
+            cranker,
+            seller: listing.seller,
+            rent_payer: listing.rent_recipient(),
+            nft_account: nft_token_account(&listing.seller, &listing.mint),
+            mint: listing.mint,
+            vault: derive_listing_vault_pda(&listing.mint, &listing_key).0,
+            config: derive_config_pda().0,
+            token_program: token_2022::ID,
+        },
+        instruction::CloseExpiredListing {},
+    )
+}
+
+/// Queues the take-down as action `nonce`; run it with [`force_delist`] once the timelock passed.
+pub fn admin_delist(
+    authority: Pubkey,
+    payer: Pubkey,
+    nonce: u64,
+    listing_key: Pubkey,
+    reason: ModerationReason,
+) -> Instruction {
+    build(
+        accounts::AdminDelist {
+            queued_action: derive_queued_action_pda(nonce).0,
+            listing: listing_key,
+            config: derive_config_pda().0,
+            authority,
+            payer,
+            system_program: system_program::ID,
+        },
+        instruction::AdminDelist { nonce, reason },
+    )
+}
+
+/// `authority` is the seller or the listing's seller authority.
+pub fn remove_listed_nft(listing_key: Pubkey, listing: &Listing, authority: Pubkey) -> Instruction {
+    build(
+        accounts::RemoveListedNFT {
+            seller: listing.seller,
+            rent_payer: listing.rent_recipient(),
+            authority,
+            nft_account: nft_token_account(&listing.seller, &listing.mint),
+            listing: listing_key,
+            mint: listing.mint,
+            vault: derive_listing_vault_pda(&listing.mint, &listing_key).0,
+            registry_page: listing
+                .registry_page
+                .map(|page| derive_registry_page_pda(page).0),
+            system_program: system_program::ID,
+            token_program: token_2022::ID,
+            rent: sysvar::rent::ID,
+        },
+        instruction::RemoveListedNft {},
+    )
+}
+
+/// Delists every one of `seller`'s `listings` in one instruction; see `remove_listed_nft` for mints
+/// with a transfer hook.
+pub fn delist_batch(seller: Pubkey, listings: &[(Pubkey, Listing)]) -> Instruction {
+    let mut ix = build(
+        accounts::DelistBatch {
+            seller,
+            token_program: token_2022::ID,
+        },
+        instruction::DelistBatch {},
+    );
+    ix.accounts
+        .extend(listings.iter().flat_map(|(listing_key, listing)| {
+            let registry_page = listing
+                .registry_page
+                .map_or(*listing_key, |page| derive_registry_page_pda(page).0);
+            [
+                AccountMeta::new(*listing_key, false),
+                AccountMeta::new(
+                    derive_listing_vault_pda(&listing.mint, listing_key).0,
+                    false,
+                ),
+                AccountMeta::new(nft_token_account(&seller, &listing.mint), false),
+                AccountMeta::new_readonly(listing.mint, false),
+                AccountMeta::new(listing.rent_recipient(), false),
+                AccountMeta::new(registry_page, false),
+            ]
+        }));
+    ix
+}
+
+pub fn transfer_listing_authority(
+    seller: Pubkey,
+    listing: Pubkey,
+    new_seller: Pubkey,
+) -> Instruction {
+    build(
+        accounts::TransferListingAuthority { listing, seller },
+        instruction::TransferListingAuthority { new_seller },
+    )
+}
+
+/// `payment_mint` is `None` for SOL.
+/// `collection` is the listing's `collection`, whose curation `min_price` applies.
+pub fn update_payment_terms(
+    seller: Pubkey,
+    listing: Pubkey,
+    collection: Option<Pubkey>,
+    payment_mint: Option<Pubkey>,
+    price: u64,
+) -> Instruction {
+    build(
+        accounts::UpdatePaymentTerms {
+            listing,
+            seller,
+            config: derive_config_pda().0,
+            allowed_collection: derive_allowed_collection_pda(&collection.unwrap_or_default()).0,
+        },
+        instruction::UpdatePaymentTerms {
+            payment_mint,
+            price,
+        },
+    )
+}
+
+/// Optional accounts of `buy_nft` & `quote_buy`.
+#[derive(Clone, Copy, Default)]
+pub struct BuyAccounts {
+    /// Pyth SOL/USD price update, required for USD listings.
+    pub price_update: Option<Pubkey>,
+    /// Royalty recipient (the mint's provenance creator).
+    pub creator: Option<Pubkey>,
+    /// Membership token account, for the membership discount.
+    pub membership_account: Option<Pubkey>,
+    /// Buyer's token account of the listing's gate mint, required for token-gated listings.
+    pub gate_token_account: Option<Pubkey>,
+    /// Buyer's credential account, required for attestation-gated listings.
+    pub attestation: Option<Pubkey>,
+    /// Pass the mint's `RoyaltyCache` (which must exist), read instead of its metadata. Its creators
+    /// replace the listing's royalty splits, which are what gets appended: both must still match.
+    pub royalty_cache: bool,
+    /// Token program of the listing's payment mint, SPL Token when unset.
+    pub payment_token_program: Option<Pubkey>,
+    /// Token program of the listing's voucher mint, SPL Token when unset.
+    pub voucher_token_program: Option<Pubkey>,
+    /// Fee share recipient of the allowlisted program composing the purchase via CPI.
+    pub caller_fee_recipient: Option<Pubkey>,
+    /// Pass the seller's withholding, required for SOL listings while `withholding_bps` is set.
+    pub withholding: bool,
+    /// Have a sale receipt created for the buyer.
+    pub sale_receipt: bool,
+    /// Pass the insurance fund, required for SOL listings while `insurance_bps` is set.
+    pub insurance_fund: bool,
+    /// Credit the buyer's loyalty points (creating their loyalty account).
+    pub loyalty: bool,
+    /// `config.reward_mint`, for trade mining rewards paid to both sides' associated accounts.
+    pub reward_mint: Option<Pubkey>,
+    /// Token program of the reward mint, SPL Token when unset.
+    pub reward_token_program: Option<Pubkey>,
+}
+
+pub fn quote_buy(
+    listing_key: Pubkey,
+    listing: &Listing,
+    buyer: Option<Pubkey>,
+    extra: BuyAccounts,
+) -> Instruction {
+    build(
+        accounts::QuoteBuy {
+            listing: listing_key,
+            config: derive_config_pda().0,
+            provenance: derive_provenance_pda(&listing.mint).0,
+            price_update: extra.price_update,
+            storefront: listing.storefront,
+            trader_stats: buyer.map(|buyer| derive_trader_stats_pda(&buyer).0),
+            membership_account: extra.membership_account,
+        },
+        instruction::QuoteBuy {},
+    )
+}
+
+/// `fee_recipient` is the listing's storefront treasury, or the config treasury.
+/// The listing's payees (their associated payment accounts for SPL-priced listings) are appended first,
+/// then the creators of its royalty splits likewise.
+/// `allowlist_proof` from `merkle_proof` for allowlisted listings, empty otherwise.
+/// `price_leg` indexes the listing's `alt_prices` to pay in, its own price when `None`.
+/// `max_price` bounds the price settled on-chain, for Dutch auction & USD listings.
+/// `sale_number` is the mint's provenance `sales_count`, keying the sale's `SaleRecord` (as in every
+/// other settling builder).
+#[allow(clippy::too_many_arguments)]
+pub fn buy_nft(
+    listing_key: Pubkey,
+    listing: &Listing,
+    buyer: Pubkey,
+    fee_recipient: Pubkey,
+    extra: BuyAccounts,
+    sale_number: u64,
+    allowlist_proof: Vec<[u8; 32]>,
+    price_leg: Option<u8>,
+    max_price: Option<u64>,
+) -> Instruction {
+    let payment_mint = listing.leg_payment_mint(price_leg);
+    let mint = listing.mint;
+    let (vault, vault_bump) = derive_listing_vault_pda(&mint, &listing_key);
+    let payment_token_program = extra.payment_token_program.unwrap_or(token::ID);
+    let voucher_token_program = extra.voucher_token_program.unwrap_or(token::ID);
+    let reward_token_program = extra.reward_token_program.unwrap_or(token::ID);
+    let reward_account = |owner: &Pubkey| {
+        extra.reward_mint.map(|reward_mint| {
+            get_associated_token_address_with_program_id(owner, &reward_mint, &reward_token_program)
+        })
+    };
+    let payment_account = |owner: &Pubkey| {
+        payment_mint.map(|payment_mint| {
+            get_associated_token_address_with_program_id(
+                owner,
+                &payment_mint,
+                &payment_token_program,
+            )
+        })
+    };
+
+    let mut ix = build(
+        accounts::BuyNFT {
+            #[cfg(feature = "event-cpi")]
+            event_authority: derive_event_authority_pda().0,
+            #[cfg(feature = "event-cpi")]
+            program: PROGRAM_ID,
+            listing: listing_key,
+            buyer,
+            vesting: listing
+                .vesting_duration
+                .map(|_| derive_vesting_pda(&listing_key).0),
+            escrowed_sale: listing
+                .receipt_window
+                .map(|_| derive_escrowed_sale_pda(&listing_key).0),
+            seller: listing.seller,
+            rent_payer: listing.rent_recipient(),
+            nft_account: vault,
+            mint,
+            vault,
+            config: derive_config_pda().0,
+            provenance: derive_provenance_pda(&mint).0,
+            sale_record: derive_sale_record_pda(&mint, sale_number).0,
+            storefront: listing.storefront,
+            global_stats: derive_global_stats_pda().0,
+            blocked_mint: derive_blocked_mint_pda(&mint).0,
+            buyer_ban: derive_banned_wallet_pda(&buyer).0,
+            seller_ban: derive_banned_wallet_pda(&listing.seller).0,
+            trader_stats: derive_trader_stats_pda(&buyer).0,
+            loyalty_account: extra.loyalty.then(|| derive_loyalty_pda(&buyer).0),
+            withholding: extra
+                .withholding
+                .then(|| derive_withholding_pda(&listing.seller).0),
+            sale_receipt: extra
+                .sale_receipt
+                .then(|| derive_sale_receipt_pda(&buyer, &mint, sale_number).0),
+            wallet_purchases: listing
+                .purchase_limit
+                .map(|limit| derive_wallet_purchases_pda(&listing.seller, &limit.drop, &buyer).0),
+            buyer_profile: derive_trader_profile_pda(&buyer).0,
+            collection_stats: derive_collection_stats_pda(&listing.collection.unwrap_or_default())
+                .0,
+            royalty_cache: extra
+                .royalty_cache
+                .then(|| derive_royalty_cache_pda(&mint).0),
+            seller_profile: derive_trader_profile_pda(&listing.seller).0,
+            treasury: fee_recipient,
+            insurance_fund: extra.insurance_fund.then(|| derive_insurance_fund_pda().0),
+            creator: extra.creator,
+            price_update: extra.price_update,
+            token_program: token_2022::ID,
+            system_program: system_program::ID,
+            associated_token_program: associated_token::ID,
+            buyer_token_account: nft_token_account(&buyer, &mint),
+            seller_token_account: nft_token_account(&listing.seller, &mint),
+            payment_mint,
+            payment_token_program: payment_mint.map(|_| payment_token_program),
+            buyer_payment_account: payment_account(&buyer),
+            seller_payment_account: payment_account(&listing.seller),
+            treasury_payment_account: payment_account(&fee_recipient),
+            creator_payment_account: extra.creator.and_then(|creator| payment_account(&creator)),
+            charity: listing.charity_split.map(|split| split.charity),
+            charity_payment_account: listing
+                .charity_split
+                .and_then(|split| payment_account(&split.charity)),
+            membership_account: extra.membership_account,
+            gate_token_account: extra.gate_token_account,
+            attestation: extra.attestation,
+            voucher_mint: listing.voucher.map(|voucher| voucher.mint),
+            buyer_voucher_account: listing.voucher.map(|voucher| {
+                get_associated_token_address_with_program_id(
+                    &buyer,
+                    &voucher.mint,
+                    &voucher_token_program,
+                )
+            }),
+            voucher_token_program: listing.voucher.map(|_| voucher_token_program),
+            instructions: Some(sysvar::instructions::ID),
+            caller_fee_recipient: extra.caller_fee_recipient,
+            registry_page: listing
+                .registry_page
+                .map(|page| derive_registry_page_pda(page).0),
+            trade_mining: extra.reward_mint.map(|_| derive_trade_mining_pda().0),
+            reward_mint: extra.reward_mint,
+            reward_vault: extra
+                .reward_mint
+                .map(|reward_mint| derive_reward_vault_pda(&reward_mint).0),
+            buyer_reward_account: reward_account(&buyer),
+            seller_reward_account: reward_account(&listing.seller),
+            reward_token_program: extra.reward_mint.map(|_| reward_token_program),
+        },
+        instruction::BuyNft {
+            vault_bump,
+            allowlist_proof,
+            price_leg,
+            max_price,
+        },
+    );
+    for payee in listing
+        .payees
+        .iter()
+        .flatten()
+        .chain(listing.royalty_splits.iter().flatten())
+    {
+        let account = payment_account(&payee.payee).unwrap_or(payee.payee);
+        ix.accounts.push(AccountMeta::new(account, false));
+    }
+    ix
+}
+
+pub fn withdraw_withholding(seller: Pubkey) -> Instruction {
+    build(
+        accounts::WithdrawWithholding {
+            withholding: derive_withholding_pda(&seller).0,
+            config: derive_config_pda().0,
+            seller,
+        },
+        instruction::WithdrawWithholding {},
+    )
+}
+
+/// `authority` is the seller or the vesting's seller authority.
+pub fn claim_vested(seller: Pubkey, listing: Pubkey, authority: Pubkey) -> Instruction {
+    build(
+        accounts::ClaimVested {
+            vesting: derive_vesting_pda(&listing).0,
+            seller,
+            authority,
+        },
+        instruction::ClaimVested {},
+    )
+}
+
+fn settle_escrowed_sale_accounts(
+    authority: Pubkey,
+    escrowed_sale: &EscrowedSale,
+) -> accounts::SettleEscrowedSale {
+    accounts::SettleEscrowedSale {
+        escrowed_sale: derive_escrowed_sale_pda(&escrowed_sale.listing).0,
+        authority,
+        seller: escrowed_sale.seller,
+        buyer: escrowed_sale.buyer,
+    }
+}
+
+/// Signed by the buyer.
+pub fn confirm_receipt(escrowed_sale: &EscrowedSale) -> Instruction {
+    build(
+        settle_escrowed_sale_accounts(escrowed_sale.buyer, escrowed_sale),
+        instruction::ConfirmReceipt {},
+    )
+}
+
+pub fn release_expired_escrow(cranker: Pubkey, escrowed_sale: &EscrowedSale) -> Instruction {
+    build(
+        settle_escrowed_sale_accounts(cranker, escrowed_sale),
+        instruction::ReleaseExpiredEscrow {},
+    )
+}
+
+/// Signed by the seller.
+pub fn refund_escrowed_sale(escrowed_sale: &EscrowedSale) -> Instruction {
+    build(
+        settle_escrowed_sale_accounts(escrowed_sale.seller, escrowed_sale),
+        instruction::RefundEscrowedSale {},
+    )
+}
+
+/// `authority` is the sale's buyer or seller.
+pub fn open_dispute(authority: Pubkey, listing: Pubkey) -> Instruction {
+    build(
+        accounts::OpenDispute {
+            escrowed_sale: derive_escrowed_sale_pda(&listing).0,
+            config: derive_config_pda().0,
+            authority,
+        },
+        instruction::OpenDispute {},
+    )
+}
+
+/// `release_to` is the sale's buyer or seller.
+pub fn resolve_dispute(
+    arbiter: Pubkey,
+    escrowed_sale: &EscrowedSale,
+    release_to: Pubkey,
+) -> Instruction {
+    build(
+        accounts::ResolveDispute {
+            escrowed_sale: derive_escrowed_sale_pda(&escrowed_sale.listing).0,
+            config: derive_config_pda().0,
+            arbiter,
+            seller: escrowed_sale.seller,
+            buyer: escrowed_sale.buyer,
+        },
+        instruction::ResolveDispute { release_to },
+    )
+}
+
+/// Approves the marketplace delegate on the seller's NFT account, once before signing orders for `mint`.
+pub fn approve_order_delegate(seller: Pubkey, mint: Pubkey) -> Result<Instruction, ProgramError> {
+    token_2022::spl_token_2022::instruction::approve(
+        &token_2022::ID,
+        &nft_token_account(&seller, &mint),
+        &derive_delegate_pda().0,
+        &seller,
+        &[],
+        1,
+    )
+}
+
+/// Ed25519 program instruction verifying the seller's `signature` over `signed_order_message(order)`,
+/// to go right before `fill_signed_order`.
+pub fn verify_signed_order(order: &SignedOrder, signature: &[u8; 64]) -> Instruction {
+    let message = marketplace::signed_order_message(order);
+    // Header (1 signature + padding + 7 u16 offsets), then pubkey, signature & message inline.
+    let (pubkey_offset, signature_offset, message_offset) = (16u16, 48u16, 112u16);
+    let mut data = vec![1u8, 0];
+    for value in [
+        signature_offset,
+        u16::MAX,
+        pubkey_offset,
+        u16::MAX,
+        message_offset,
+        message.len() as u16,
+        u16::MAX,
+    ] {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    data.extend_from_slice(order.seller.as_ref());
+    data.extend_from_slice(signature);
+    data.extend_from_slice(&message);
+
+    Instruction {
+        program_id: ed25519_program::ID,
+        accounts: vec![],
+        data,
+    }
+}
+
+/// `treasury` is the config treasury, `creator` the mint's provenance creator (the seller on a first sale).
+pub fn fill_signed_order(
+    order: SignedOrder,
+    buyer: Pubkey,
+    treasury: Pubkey,
+    creator: Option<Pubkey>,
+    sale_number: u64,
+    insurance_fund: bool,
+    withholding: bool,
+) -> Instruction {
+    build(
+        accounts::FillSignedOrder {
+            #[cfg(feature = "event-cpi")]
+            event_authority: derive_event_authority_pda().0,
+            #[cfg(feature = "event-cpi")]
+            program: PROGRAM_ID,
+            order_nonces: derive_order_nonce_bitmap_pda(&order.seller, order.nonce).0,
+            legacy_order_nonce: derive_order_nonce_pda(&order.seller, order.nonce).0,
+            buyer,
+            seller: order.seller,
+            seller_nft_account: nft_token_account(&order.seller, &order.mint),
+            buyer_nft_account: nft_token_account(&buyer, &order.mint),
+            mint: order.mint,
+            delegate: derive_delegate_pda().0,
+            config: derive_config_pda().0,
+            provenance: derive_provenance_pda(&order.mint).0,
+            sale_record: derive_sale_record_pda(&order.mint, sale_number).0,
+            global_stats: derive_global_stats_pda().0,
+            blocked_mint: derive_blocked_mint_pda(&order.mint).0,
+            seller_ban: derive_banned_wallet_pda(&order.seller).0,
+            buyer_ban: derive_banned_wallet_pda(&buyer).0,
+            treasury,
+            insurance_fund: insurance_fund.then(|| derive_insurance_fund_pda().0),
+            withholding: withholding.then(|| derive_withholding_pda(&order.seller).0),
+            creator,
+            instructions: sysvar::instructions::ID,
+            token_program: token_2022::ID,
+            system_program: system_program::ID,
+        },
+        instruction::FillSignedOrder { order },
+    )
+}
+
+pub fn cancel_signed_order(seller: Pubkey, nonce: u64) -> Instruction {
+    build(
+        accounts::CancelSignedOrder {
+            order_nonces: derive_order_nonce_bitmap_pda(&seller, nonce).0,
+            legacy_order_nonce: derive_order_nonce_pda(&seller, nonce).0,
+            seller,
+            system_program: system_program::ID,
+        },
+        instruction::CancelSignedOrder { nonce },
+    )
+}
+
+/// `offer_book` in the offer builders: whether the mint has an order book (`create_offer_book`) to keep in sync.
+/// `payer` pays the offer's rent, the bidder or a relayer co-signing.
+pub fn make_offer(
+    bidder: Pubkey,
+    payer: Pubkey,
+    mint: Pubkey,
+    amount: u64,
+    expires_at: Option<i64>,
+    offer_book: bool,
+) -> Instruction {
+    build(
+        accounts::MakeOffer {
+            best_offer: derive_best_offer_pda(&mint).0,
+            offer_book: offer_book.then(|| derive_offer_book_pda(&mint).0),
+            offer: derive_offer_pda(&mint, &bidder).0,
+            bidder,
+            bidder_ban: derive_banned_wallet_pda(&bidder).0,
+            payer,
+            mint,
+            system_program: system_program::ID,
+        },
+        instruction::MakeOffer { amount, expires_at },
+    )
+}
+
+/// Escrows `amount` from the bidder's associated WSOL account.
+pub fn make_wsol_offer(
+    bidder: Pubkey,
+    payer: Pubkey,
+    mint: Pubkey,
+    amount: u64,
+    expires_at: Option<i64>,
+    offer_book: bool,
+) -> Instruction {
+    let offer = derive_offer_pda(&mint, &bidder).0;
+    build(
+        accounts::MakeWsolOffer {
+            best_offer: derive_best_offer_pda(&mint).0,
+            offer_book: offer_book.then(|| derive_offer_book_pda(&mint).0),
+            offer,
+            escrow_token_account: derive_offer_escrow_pda(&offer).0,
+            bidder,
+            bidder_ban: derive_banned_wallet_pda(&bidder).0,
+            payer,
+            bidder_wsol_account: wsol_token_account(&bidder),
+            mint,
+            wsol_mint: token::spl_token::native_mint::ID,
+            wsol_token_program: token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::MakeWsolOffer { amount, expires_at },
+    )
+}
+
+/// Escrows `amount` of `payment_mint` (not WSOL, see `make_wsol_offer`) from the bidder's associated account.
+#[allow(clippy::too_many_arguments)]
+pub fn make_spl_offer(
+    bidder: Pubkey,
+    payer: Pubkey,
+    mint: Pubkey,
+    payment_mint: Pubkey,
+    payment_token_program: Pubkey,
+    amount: u64,
+    expires_at: Option<i64>,
+) -> Instruction {
+    let offer = derive_offer_pda(&mint, &bidder).0;
+    build(
+        accounts::MakeSplOffer {
+            best_offer: derive_best_offer_pda(&mint).0,
+            offer,
+            escrow_token_account: derive_offer_escrow_pda(&offer).0,
+            bidder,
+            bidder_ban: derive_banned_wallet_pda(&bidder).0,
+            payer,
+            bidder_payment_account: get_associated_token_address_with_program_id(
+                &bidder,
+                &payment_mint,
+                &payment_token_program,
+            ),
+            mint,
+            payment_mint,
+            payment_token_program,
+            system_program: system_program::ID,
+        },
+        instruction::MakeSplOffer { amount, expires_at },
+    )
+}
+
+pub fn cancel_offer(offer: &Offer, offer_book: bool) -> Instruction {
+    let offer_key = derive_offer_pda(&offer.mint, &offer.bidder).0;
+    let wsol = offer.escrow == OfferEscrow::Wsol;
+    let spl = offer.payment_mint().is_some();
+    build(
+        accounts::CancelOffer {
+            best_offer: derive_best_offer_pda(&offer.mint).0,
+            offer_book: offer_book.then(|| derive_offer_book_pda(&offer.mint).0),
+            offer: offer_key,
+            bidder: offer.bidder,
+            rent_payer: offer.rent_payer,
+            escrow_token_account: (wsol || spl).then(|| derive_offer_escrow_pda(&offer_key).0),
+            bidder_wsol_account: wsol.then(|| wsol_token_account(&offer.bidder)),
+            wsol_mint: wsol.then_some(token::spl_token::native_mint::ID),
+            wsol_token_program: wsol.then_some(token::ID),
+            payment_mint: offer.payment_mint(),
+            payment_token_program: offer.payment_token_program(),
+            bidder_payment_account: offer_payment_account(&offer.bidder, offer),
+        },
+        instruction::CancelOffer {},
+    )
+}
+
+/// Re-points `mint`'s best offer at the best live one of `offers`.
+pub fn refresh_best_offer(mint: &Pubkey, offers: &[Pubkey]) -> Instruction {
+    let mut ix = build(
+        accounts::RefreshBestOffer {
+            best_offer: derive_best_offer_pda(mint).0,
+        },
+        instruction::RefreshBestOffer {},
+    );
+    ix.accounts.extend(
+        offers
+            .iter()
+            .map(|offer| AccountMeta::new_readonly(*offer, false)),
+    );
+    ix
+}
+
+/// Creates `mint`'s bid order book.
+pub fn create_offer_book(mint: Pubkey, payer: Pubkey) -> Instruction {
+    build(
+        accounts::CreateOfferBook {
+            offer_book: derive_offer_book_pda(&mint).0,
+            mint,
+            payer,
+            system_program: system_program::ID,
+        },
+        instruction::CreateOfferBook {},
+    )
+}
+
+/// Books the live ones of `offers` in `mint`'s order book & drops the closed or expired ones.
+pub fn refresh_offer_book(mint: &Pubkey, offers: &[Pubkey]) -> Instruction {
+    let mut ix = build(
+        accounts::RefreshOfferBook {
+            offer_book: derive_offer_book_pda(mint).0,
+        },
+        instruction::RefreshOfferBook {},
+    );
+    ix.accounts.extend(
+        offers
+            .iter()
+            .map(|offer| AccountMeta::new_readonly(*offer, false)),
+    );
+    ix
+}
+
+pub fn close_expired_offer(offer: &Offer, cranker: Pubkey, offer_book: bool) -> Instruction {
+    let offer_key = derive_offer_pda(&offer.mint, &offer.bidder).0;
+    let wsol = offer.escrow == OfferEscrow::Wsol;
+    let spl = offer.payment_mint().is_some();
+    build(
+        accounts::CloseExpiredOffer {
+            best_offer: derive_best_offer_pda(&offer.mint).0,
+            offer_book: offer_book.then(|| derive_offer_book_pda(&offer.mint).0),
+            offer: offer_key,
+            cranker,
+            bidder: offer.bidder,
+            rent_payer: offer.rent_payer,
+            config: derive_config_pda().0,
+            escrow_token_account: (wsol || spl).then(|| derive_offer_escrow_pda(&offer_key).0),
+            bidder_wsol_account: wsol.then(|| wsol_token_account(&offer.bidder)),
+            wsol_mint: wsol.then_some(token::spl_token::native_mint::ID),
+            wsol_token_program: wsol.then_some(token::ID),
+            payment_mint: offer.payment_mint(),
+            payment_token_program: offer.payment_token_program(),
+            bidder_payment_account: offer_payment_account(&offer.bidder, offer),
+        },
+        instruction::CloseExpiredOffer {},
+    )
+}
+
+/// Auction House-style `execute_sale` of `listing` against `offer` (a lamport offer on its mint), at the
+/// offer's amount. `fee_recipient` is the listing's storefront treasury, or the config treasury.
+#[allow(clippy::too_many_arguments)]
+pub fn execute_sale(
+    cranker: Pubkey,
+    listing_key: Pubkey,
+    listing: &Listing,
+    offer: &Offer,
+    fee_recipient: Pubkey,
+    creator: Option<Pubkey>,
+    sale_number: u64,
+    offer_book: bool,
+    insurance_fund: bool,
+    withholding: bool,
+) -> Instruction {
+    let mint = listing.mint;
+    build(
+        accounts::ExecuteSale {
+            #[cfg(feature = "event-cpi")]
+            event_authority: derive_event_authority_pda().0,
+            #[cfg(feature = "event-cpi")]
+            program: PROGRAM_ID,
+            listing: listing_key,
+            offer: derive_offer_pda(&mint, &offer.bidder).0,
+            best_offer: derive_best_offer_pda(&mint).0,
+            offer_book: offer_book.then(|| derive_offer_book_pda(&mint).0),
+            cranker,
+            seller: listing.seller,
+            bidder: offer.bidder,
+            rent_payer: offer.rent_payer,
+            listing_rent_payer: listing.rent_recipient(),
+            mint,
+            vault: derive_listing_vault_pda(&mint, &listing_key).0,
+            bidder_nft_account: nft_token_account(&offer.bidder, &mint),
+            config: derive_config_pda().0,
+            provenance: derive_provenance_pda(&mint).0,
+            sale_record: derive_sale_record_pda(&mint, sale_number).0,
+            storefront: listing.storefront,
+            global_stats: derive_global_stats_pda().0,
+            blocked_mint: derive_blocked_mint_pda(&mint).0,
+            seller_ban: derive_banned_wallet_pda(&listing.seller).0,
+            bidder_ban: derive_banned_wallet_pda(&offer.bidder).0,
+            treasury: fee_recipient,
+            insurance_fund: insurance_fund.then(|| derive_insurance_fund_pda().0),
+            withholding: withholding.then(|| derive_withholding_pda(&listing.seller).0),
+            creator,
+            registry_page: listing
+                .registry_page
+                .map(|page| derive_registry_page_pda(page).0),
+            token_program: token_2022::ID,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::ExecuteSale {
+            buyer_price: offer.amount,
+            token_size: 1,
+        },
+    )
+}
+
+/// `treasury` is the config treasury, `creator` the mint's provenance creator (the seller on a first sale).
+/// WSOL offers pay the seller's associated WSOL account unless the config unwraps proceeds, SPL offers each
+/// party's associated account of the payment mint.
+#[allow(clippy::too_many_arguments)]
+pub fn accept_offer(
+    offer: &Offer,
+    seller: Pubkey,
+    treasury: Pubkey,
+    creator: Option<Pubkey>,
+    sale_number: u64,
+    offer_book: bool,
+    unwrap_wsol_proceeds: bool,
+    insurance_fund: bool,
+    withholding: bool,
+) -> Instruction {
+    let offer_key = derive_offer_pda(&offer.mint, &offer.bidder).0;
+    let wsol = offer.escrow == OfferEscrow::Wsol;
+    let spl = offer.payment_mint().is_some();
+    build(
+        accounts::AcceptOffer {
+            best_offer: derive_best_offer_pda(&offer.mint).0,
+            offer_book: offer_book.then(|| derive_offer_book_pda(&offer.mint).0),
+            #[cfg(feature = "event-cpi")]
+            event_authority: derive_event_authority_pda().0,
+            #[cfg(feature = "event-cpi")]
+            program: PROGRAM_ID,
+            offer: offer_key,
+            seller,
+            bidder: offer.bidder,
+            rent_payer: offer.rent_payer,
+            seller_nft_account: nft_token_account(&seller, &offer.mint),
+            bidder_nft_account: nft_token_account(&offer.bidder, &offer.mint),
+            mint: offer.mint,
+            config: derive_config_pda().0,
+            provenance: derive_provenance_pda(&offer.mint).0,
+            sale_record: derive_sale_record_pda(&offer.mint, sale_number).0,
+            global_stats: derive_global_stats_pda().0,
+            blocked_mint: derive_blocked_mint_pda(&offer.mint).0,
+            seller_ban: derive_banned_wallet_pda(&seller).0,
+            bidder_ban: derive_banned_wallet_pda(&offer.bidder).0,
+            treasury,
+            insurance_fund: insurance_fund.then(|| derive_insurance_fund_pda().0),
+            withholding: withholding.then(|| derive_withholding_pda(&seller).0),
+            creator: creator.filter(|_| !spl),
+            escrow_token_account: (wsol || spl).then(|| derive_offer_escrow_pda(&offer_key).0),
+            wsol_mint: wsol.then_some(token::spl_token::native_mint::ID),
+            seller_wsol_account: (wsol && !unwrap_wsol_proceeds)
+                .then(|| wsol_token_account(&seller)),
+            wsol_token_program: wsol.then_some(token::ID),
+            payment_mint: offer.payment_mint(),
+            payment_token_program: offer.payment_token_program(),
+            seller_payment_account: offer_payment_account(&seller, offer),
+            treasury_payment_account: offer_payment_account(&treasury, offer),
+            bidder_payment_account: offer_payment_account(&offer.bidder, offer),
+            creator_payment_account: creator
+                .and_then(|creator| offer_payment_account(&creator, offer)),
+            token_program: token_2022::ID,
+            system_program: system_program::ID,
+        },
+        instruction::AcceptOffer {},
+    )
+}
+
+/// `mint_root` from `merkle_root`, to only accept some mints of the collection.
+pub fn make_collection_offer(
+    bidder: Pubkey,
+    collection: Pubkey,
+    amount: u64,
+    mint_root: Option<[u8; 32]>,
+    expires_at: Option<i64>,
+) -> Instruction {
+    build(
+        accounts::MakeCollectionOffer {
+            offer: derive_collection_offer_pda(&collection, &bidder).0,
+            bidder,
+            bidder_ban: derive_banned_wallet_pda(&bidder).0,
+            system_program: system_program::ID,
+        },
+        instruction::MakeCollectionOffer {
+            collection,
+            amount,
+            mint_root,
+            expires_at,
+        },
+    )
+}
+
+pub fn cancel_collection_offer(bidder: Pubkey, collection: Pubkey) -> Instruction {
+    build(
+        accounts::CancelCollectionOffer {
+            offer: derive_collection_offer_pda(&collection, &bidder).0,
+            bidder,
+        },
+        instruction::CancelCollectionOffer {},
+    )
+}
+
+/// `proof` from `merkle_proof` for offers with a `mint_root`, empty otherwise.
+/// `treasury` & `creator` as in `accept_offer`.
+#[allow(clippy::too_many_arguments)]
+pub fn accept_collection_offer(
+    offer: &CollectionOffer,
+    seller: Pubkey,
+    mint: Pubkey,
+    treasury: Pubkey,
+    creator: Option<Pubkey>,
+    sale_number: u64,
+    proof: Vec<[u8; 32]>,
+    insurance_fund: bool,
+    withholding: bool,
+) -> Instruction {
+    build(
+        accounts::AcceptCollectionOffer {
+            #[cfg(feature = "event-cpi")]
+            event_authority: derive_event_authority_pda().0,
+            #[cfg(feature = "event-cpi")]
+            program: PROGRAM_ID,
+            offer: derive_collection_offer_pda(&offer.collection, &offer.bidder).0,
+            seller,
+            bidder: offer.bidder,
+            seller_nft_account: nft_token_account(&seller, &mint),
+            bidder_nft_account: nft_token_account(&offer.bidder, &mint),
+            mint,
+            metadata: derive_metadata_pda(&mint),
+            config: derive_config_pda().0,
+            provenance: derive_provenance_pda(&mint).0,
+            sale_record: derive_sale_record_pda(&mint, sale_number).0,
+            global_stats: derive_global_stats_pda().0,
+            blocked_mint: derive_blocked_mint_pda(&mint).0,
+            seller_ban: derive_banned_wallet_pda(&seller).0,
+            bidder_ban: derive_banned_wallet_pda(&offer.bidder).0,
+            treasury,
+            insurance_fund: insurance_fund.then(|| derive_insurance_fund_pda().0),
+            withholding: withholding.then(|| derive_withholding_pda(&seller).0),
+            creator,
+            token_program: token_2022::ID,
+            system_program: system_program::ID,
+        },
+        instruction::AcceptCollectionOffer { proof },
+    )
+}
+
+pub fn gift_nft(giver: Pubkey, recipient: Pubkey, mint: Pubkey) -> Instruction {
+    build(
+        accounts::GiftNft {
+            #[cfg(feature = "event-cpi")]
+            event_authority: derive_event_authority_pda().0,
+            #[cfg(feature = "event-cpi")]
+            program: PROGRAM_ID,
+            giver,
+            giver_nft_account: nft_token_account(&giver, &mint),
+            recipient,
+            recipient_nft_account: nft_token_account(&recipient, &mint),
+            mint,
+            provenance: derive_provenance_pda(&mint).0,
+            blocked_mint: derive_blocked_mint_pda(&mint).0,
+            token_program: token_2022::ID,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::GiftNft {},
+    )
+}
+
+pub fn counter_offer(offer: &Offer, seller: Pubkey, amount: u64) -> Instruction {
+    build(
+        accounts::CounterOffer {
+            offer: derive_offer_pda(&offer.mint, &offer.bidder).0,
+            seller,
+            seller_nft_account: nft_token_account(&seller, &offer.mint),
+            mint: offer.mint,
+            delegate: derive_delegate_pda().0,
+            seller_ban: derive_banned_wallet_pda(&seller).0,
+            token_program: token_2022::ID,
+        },
+        instruction::CounterOffer { amount },
+    )
+}
+
+/// Takes the offer's counter by `seller`; `treasury` & `creator` as in [`accept_offer`].
+#[allow(clippy::too_many_arguments)]
+pub fn accept_counter_offer(
+    offer: &Offer,
+    seller: Pubkey,
+    treasury: Pubkey,
+    creator: Option<Pubkey>,
+    sale_number: u64,
+    offer_book: bool,
+    insurance_fund: bool,
+    withholding: bool,
+) -> Instruction {
+    build(
+        accounts::AcceptCounterOffer {
+            best_offer: derive_best_offer_pda(&offer.mint).0,
+            offer_book: offer_book.then(|| derive_offer_book_pda(&offer.mint).0),
+            #[cfg(feature = "event-cpi")]
+            event_authority: derive_event_authority_pda().0,
+            #[cfg(feature = "event-cpi")]
+            program: PROGRAM_ID,
+            offer: derive_offer_pda(&offer.mint, &offer.bidder).0,
+            bidder: offer.bidder,
+            rent_payer: offer.rent_payer,
+            seller,
+            seller_nft_account: nft_token_account(&seller, &offer.mint),
+            bidder_nft_account: nft_token_account(&offer.bidder, &offer.mint),
+            mint: offer.mint,
+            config: derive_config_pda().0,
+            provenance: derive_provenance_pda(&offer.mint).0,
+            sale_record: derive_sale_record_pda(&offer.mint, sale_number).0,
+            global_stats: derive_global_stats_pda().0,
+            blocked_mint: derive_blocked_mint_pda(&offer.mint).0,
+            seller_ban: derive_banned_wallet_pda(&seller).0,
+            bidder_ban: derive_banned_wallet_pda(&offer.bidder).0,
+            treasury,
+            insurance_fund: insurance_fund.then(|| derive_insurance_fund_pda().0),
+            withholding: withholding.then(|| derive_withholding_pda(&seller).0),
+            creator,
+            delegate: derive_delegate_pda().0,
+            token_program: token_2022::ID,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::AcceptCounterOffer {},
+    )
+}
+
+pub fn list_edition(
+    seller: Pubkey,
+    mint: Pubkey,
+    base_price: u64,
+    supply: u64,
+    curve: BondingCurve,
+) -> Instruction {
+    let edition_listing = derive_edition_listing_pda(&mint, &seller).0;
+    build(
+        accounts::ListEdition {
+            edition_listing,
+            seller,
+            seller_token_account: nft_token_account(&seller, &mint),
+            mint,
+            vault: derive_listing_vault_pda(&mint, &edition_listing).0,
+            blocked_mint: derive_blocked_mint_pda(&mint).0,
+            config: derive_config_pda().0,
+            system_program: system_program::ID,
+            token_program: token_2022::ID,
+        },
+        instruction::ListEdition {
+            base_price,
+            supply,
+            curve,
+        },
+    )
+}
+
+/// Buys `quantity` units; `max_price` bounds their total, see [`EditionListing::fill_price`]. `treasury` is
+/// the config treasury.
+pub fn buy_edition(
+    buyer: Pubkey,
+    edition_listing: &EditionListing,
+    treasury: Pubkey,
+    quantity: u64,
+    max_price: u64,
+    insurance_fund: bool,
+    withholding: bool,
+) -> Instruction {
+    let mint = edition_listing.mint;
+    let edition_listing_key = derive_edition_listing_pda(&mint, &edition_listing.seller).0;
+    build(
+        accounts::BuyEdition {
+            edition_listing: edition_listing_key,
+            buyer,
+            seller: edition_listing.seller,
+            mint,
+            vault: derive_listing_vault_pda(&mint, &edition_listing_key).0,
+            buyer_token_account: nft_token_account(&buyer, &mint),
+            config: derive_config_pda().0,
+            treasury,
+            insurance_fund: insurance_fund.then(|| derive_insurance_fund_pda().0),
+            withholding: withholding.then(|| derive_withholding_pda(&edition_listing.seller).0),
+            global_stats: derive_global_stats_pda().0,
+            system_program: system_program::ID,
+            token_program: token_2022::ID,
+            associated_token_program: associated_token::ID,
+        },
+        instruction::BuyEdition {
+            quantity,
+            max_price,
+        },
+    )
+}
+
+pub fn close_edition(seller: Pubkey, mint: Pubkey) -> Instruction {
+    let edition_listing = derive_edition_listing_pda(&mint, &seller).0;
+    build(
+        accounts::CloseEdition {
+            edition_listing,
+            seller,
+            seller_token_account: nft_token_account(&seller, &mint),
+            mint,
+            vault: derive_listing_vault_pda(&mint, &edition_listing).0,
+            token_program: token_2022::ID,
+        },
+        instruction::CloseEdition {},
+    )
+}
+
+pub fn create_pool(
+    owner: Pubkey,
+    collection: Pubkey,
+    spot_price: u64,
+    curve: BondingCurve,
+    fee_bps: u16,
+) -> Instruction {
+    build(
+        accounts::CreatePool {
+            pool: derive_pool_pda(&owner, &collection).0,
+            owner,
+            config: derive_config_pda().0,
+            system_program: system_program::ID,
+        },
+        instruction::CreatePool {
+            collection,
+            spot_price,
+            curve,
+            fee_bps,
+        },
+    )
+}
+
+fn update_pool_sol_accounts(owner: Pubkey, collection: Pubkey) -> accounts::UpdatePoolSol {
+    accounts::UpdatePoolSol {
+        pool: derive_pool_pda(&owner, &collection).0,
+        owner,
+        system_program: system_program::ID,
+    }
+}
+
+pub fn deposit_pool_sol(owner: Pubkey, collection: Pubkey, amount: u64) -> Instruction {
+    build(
+        update_pool_sol_accounts(owner, collection),
+        instruction::DepositPoolSol { amount },
+    )
+}
+
+pub fn withdraw_pool_sol(owner: Pubkey, collection: Pubkey, amount: u64) -> Instruction {
+    build(
+        update_pool_sol_accounts(owner, collection),
+        instruction::WithdrawPoolSol { amount },
+    )
+}
+
+pub fn deposit_pool_nft(owner: Pubkey, collection: Pubkey, mint: Pubkey) -> Instruction {
+    let pool = derive_pool_pda(&owner, &collection).0;
+    build(
+        accounts::DepositPoolNft {
+            pool,
+            owner,
+            nft_account: nft_token_account(&owner, &mint),
+            mint,
+            metadata: derive_metadata_pda(&mint),
+            vault: derive_listing_vault_pda(&mint, &pool).0,
+            provenance: derive_provenance_pda(&mint).0,
+            blocked_mint: derive_blocked_mint_pda(&mint).0,
+            system_program: system_program::ID,
+            token_program: token_2022::ID,
+        },
+        instruction::DepositPoolNft {},
+    )
+}
+
+pub fn withdraw_pool_nft(owner: Pubkey, collection: Pubkey, mint: Pubkey) -> Instruction {
+    let pool = derive_pool_pda(&owner, &collection).0;
+    build(
+        accounts::WithdrawPoolNft {
+            pool,
+            owner,
+            owner_nft_account: nft_token_account(&owner, &mint),
+            mint,
+            vault: derive_listing_vault_pda(&mint, &pool).0,
+            token_program: token_2022::ID,
+        },
+        instruction::WithdrawPoolNft {},
+    )
+}
+
+/// `creator` is the mint's provenance creator, to pay royalties; `treasury` is the config treasury.
+#[allow(clippy::too_many_arguments)]
+pub fn pool_buy(
+    buyer: Pubkey,
+    pool: &Pool,
+    mint: Pubkey,
+    creator: Option<Pubkey>,
+    sale_number: u64,
+    treasury: Pubkey,
+    max_price: u64,
+    insurance_fund: bool,
+    withholding: bool,
+) -> Instruction {
+    let pool_key = derive_pool_pda(&pool.owner, &pool.collection).0;
+    build(
+        accounts::PoolBuy {
+            pool: pool_key,
+            owner: pool.owner,
+            buyer,
+            mint,
+            vault: derive_listing_vault_pda(&mint, &pool_key).0,
+            buyer_token_account: nft_token_account(&buyer, &mint),
+            provenance: derive_provenance_pda(&mint).0,
+            sale_record: derive_sale_record_pda(&mint, sale_number).0,
+            creator,
+            config: derive_config_pda().0,
+            treasury,
+            insurance_fund: insurance_fund.then(|| derive_insurance_fund_pda().0),
+            withholding: withholding.then(|| derive_withholding_pda(&pool.owner).0),
+            global_stats: derive_global_stats_pda().0,
+            blocked_mint: derive_blocked_mint_pda(&mint).0,
+            system_program: system_program::ID,
+            token_program: token_2022::ID,
+            associated_token_program: associated_token::ID,
+        },
+        instruction::PoolBuy { max_price },
+    )
+}
+
+/// `creator` is the mint's provenance creator (the seller if it has none yet), to pay royalties.
+#[allow(clippy::too_many_arguments)]
+pub fn pool_sell(
+    seller: Pubkey,
+    pool: &Pool,
+    mint: Pubkey,
+    creator: Option<Pubkey>,
+    sale_number: u64,
+    treasury: Pubkey,
+    min_price: u64,
+    insurance_fund: bool,
+    withholding: bool,
+) -> Instruction {
+    let pool_key = derive_pool_pda(&pool.owner, &pool.collection).0;
+    build(
+        accounts::PoolSell {
+            pool: pool_key,
+            seller,
+            nft_account: nft_token_account(&seller, &mint),
+            mint,
+            metadata: derive_metadata_pda(&mint),
+            vault: derive_listing_vault_pda(&mint, &pool_key).0,
+            provenance: derive_provenance_pda(&mint).0,
+            sale_record: derive_sale_record_pda(&mint, sale_number).0,
+            creator,
+            config: derive_config_pda().0,
+            treasury,
+            insurance_fund: insurance_fund.then(|| derive_insurance_fund_pda().0),
+            withholding: withholding.then(|| derive_withholding_pda(&seller).0),
+            global_stats: derive_global_stats_pda().0,
+            blocked_mint: derive_blocked_mint_pda(&mint).0,
+            system_program: system_program::ID,
+            token_program: token_2022::ID,
+        },
+        instruction::PoolSell { min_price },
+    )
+}
+
+pub fn close_pool(owner: Pubkey, collection: Pubkey) -> Instruction {
+    build(
+        accounts::ClosePool {
+            pool: derive_pool_pda(&owner, &collection).0,
+            owner,
+        },
+        instruction::ClosePool {},
+    )
+}
+
+/// `commitment` is `sha256(secret)`, see [`raffle_commitment`].
+pub fn create_raffle(
+    seller: Pubkey,
+    mint: Pubkey,
+    ticket_price: u64,
+    max_tickets: u64,
+    end_ts: i64,
+    commitment: [u8; 32],
+) -> Instruction {
+    build(
+        accounts::CreateRaffle {
+            raffle: derive_raffle_pda(&mint).0,
+            seller,
+            nft_account: nft_token_account(&seller, &mint),
+            mint,
+            vault: derive_vault_pda(&mint).0,
+            vault_deposit: derive_vault_deposit_pda(&mint).0,
+            blocked_mint: derive_blocked_mint_pda(&mint).0,
+            config: derive_config_pda().0,
+            system_program: system_program::ID,
+            token_program: token_2022::ID,
+        },
+        instruction::CreateRaffle {
+            ticket_price,
+            max_tickets,
+            end_ts,
+            commitment,
+        },
+    )
+}
+
+pub fn raffle_commitment(secret: &[u8; 32]) -> [u8; 32] {
+    anchor_lang::solana_program::hash::hash(secret).to_bytes()
+}
+
+/// `raffle` must be freshly fetched: the entry is keyed by the current `tickets_sold`.
+pub fn buy_ticket(buyer: Pubkey, raffle: &Raffle, count: u64) -> Instruction {
+    let raffle_key = derive_raffle_pda(&raffle.mint).0;
+    build(
+        accounts::BuyTicket {
+            raffle: raffle_key,
+            entry: derive_raffle_entry_pda(&raffle_key, raffle.tickets_sold).0,
+            buyer,
+            config: derive_config_pda().0,
+            system_program: system_program::ID,
+        },
+        instruction::BuyTicket { count },
+    )
+}
+
+/// Anyone can lock a raffle that ended or sold out.
+pub fn lock_raffle(mint: Pubkey) -> Instruction {
+    build(
+        accounts::LockRaffle {
+            raffle: derive_raffle_pda(&mint).0,
+        },
+        instruction::LockRaffle {},
+    )
+}
+
+/// `treasury` is the config treasury. Send once `raffle.draw_slot` has passed.
+pub fn draw_winner(
+    cranker: Pubkey,
+    raffle: &Raffle,
+    treasury: Pubkey,
+    secret: [u8; 32],
+    withholding: bool,
+) -> Instruction {
+    build(
+        accounts::DrawWinner {
+            raffle: derive_raffle_pda(&raffle.mint).0,
+            seller: raffle.seller,
+            config: derive_config_pda().0,
+            treasury,
+            slot_hashes: sysvar::slot_hashes::ID,
+            withholding: withholding.then(|| derive_withholding_pda(&raffle.seller).0),
+            cranker,
+            system_program: system_program::ID,
+        },
+        instruction::DrawWinner { secret },
+    )
+}
+
+/// `entry` is the key of the entry holding the winning ticket.
+pub fn claim_raffle_prize(mint: Pubkey, entry: Pubkey, winner: Pubkey) -> Instruction {
+    build(
+        accounts::ClaimRafflePrize {
+            raffle: derive_raffle_pda(&mint).0,
+            entry,
+            buyer: winner,
+            winner_nft_account: nft_token_account(&winner, &mint),
+            mint,
+            vault: derive_vault_pda(&mint).0,
+            token_program: token_2022::ID,
+        },
+        instruction::ClaimRafflePrize {},
+    )
+}
+
+pub fn cancel_raffle(seller: Pubkey, mint: Pubkey) -> Instruction {
+    build(
+        accounts::CancelRaffle {
+            raffle: derive_raffle_pda(&mint).0,
+            seller,
+            nft_account: nft_token_account(&seller, &mint),
+            mint,
+            vault: derive_vault_pda(&mint).0,
+            token_program: token_2022::ID,
+        },
+        instruction::CancelRaffle {},
+    )
+}
+
+pub fn refund_raffle_entry(mint: Pubkey, entry: Pubkey, buyer: Pubkey) -> Instruction {
+    build(
+        accounts::RefundRaffleEntry {
+            raffle: derive_raffle_pda(&mint).0,
+            entry,
+            buyer,
+        },
+        instruction::RefundRaffleEntry {},
+    )
+}
+
+pub fn fractionalize_listing(
+    listing_key: Pubkey,
+    listing: &Listing,
+    total_shares: u64,
+    buyout_reserve: u64,
+    buyout_duration: i64,
+) -> Instruction {
+    let mint = listing.mint;
+    build(
+        accounts::FractionalizeListing {
+            listing: listing_key,
+            seller: listing.seller,
+            rent_payer: listing.rent_recipient(),
+            mint,
+            fraction: derive_fraction_pda(&mint).0,
+            share_mint: derive_share_mint_pda(&mint).0,
+            seller_share_account: share_token_account(&listing.seller, &mint),
+            share_token_program: token::ID,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::FractionalizeListing {
+            total_shares,
+            buyout_reserve,
+            buyout_duration,
+        },
+    )
+}
+
+pub fn redeem_fraction(holder: Pubkey, fraction: &Fraction) -> Instruction {
+    let mint = fraction.mint;
+    build(
+        accounts::RedeemFraction {
+            fraction: derive_fraction_pda(&mint).0,
+            holder,
+            holder_share_account: share_token_account(&holder, &mint),
+            share_mint: derive_share_mint_pda(&mint).0,
+            holder_nft_account: nft_token_account(&holder, &mint),
+            mint,
+            curator: fraction.curator,
+            vault: derive_listing_vault_pda(&mint, &fraction.listing).0,
+            share_token_program: token::ID,
+            token_program: token_2022::ID,
+        },
+        instruction::RedeemFraction {},
+    )
+}
+
+pub fn bid_buyout(bidder: Pubkey, fraction: &Fraction, amount: u64) -> Instruction {
+    build(
+        accounts::BidBuyout {
+            fraction: derive_fraction_pda(&fraction.mint).0,
+            bidder,
+            previous_bidder: fraction.highest_bidder,
+            system_program: system_program::ID,
+        },
+        instruction::BidBuyout { amount },
+    )
+}
+
+/// Requires the winner's NFT account to exist.
+pub fn settle_buyout(fraction: &Fraction) -> Instruction {
+    let winner = fraction.highest_bidder.unwrap_or_default();
+    build(
+        accounts::SettleBuyout {
+            fraction: derive_fraction_pda(&fraction.mint).0,
+            winner_nft_account: nft_token_account(&winner, &fraction.mint),
+            mint: fraction.mint,
+            curator: fraction.curator,
+            vault: derive_listing_vault_pda(&fraction.mint, &fraction.listing).0,
+            token_program: token_2022::ID,
+        },
+        instruction::SettleBuyout {},
+    )
+}
+
+pub fn claim_buyout_proceeds(holder: Pubkey, mint: Pubkey) -> Instruction {
+    build(
+        accounts::ClaimBuyoutProceeds {
+            fraction: derive_fraction_pda(&mint).0,
+            holder,
+            holder_share_account: share_token_account(&holder, &mint),
+            share_mint: derive_share_mint_pda(&mint).0,
+            share_token_program: token::ID,
+        },
+        instruction::ClaimBuyoutProceeds {},
+    )
+}
+
+pub fn list_for_rent(
+    owner: Pubkey,
+    mint: Pubkey,
+    fee: u64,
+    collateral: u64,
+    duration: i64,
+) -> Instruction {
+    build(
+        accounts::ListForRent {
+            rental: derive_rental_pda(&mint).0,
+            owner,
+            nft_account: nft_token_account(&owner, &mint),
+            mint,
+            vault: derive_vault_pda(&mint).0,
+            vault_deposit: derive_vault_deposit_pda(&mint).0,
+            blocked_mint: derive_blocked_mint_pda(&mint).0,
+            config: derive_config_pda().0,
+            system_program: system_program::ID,
+            token_program: token_2022::ID,
+        },
+        instruction::ListForRent {
+            fee,
+            collateral,
+            duration,
+        },
+    )
+}
+
+/// `treasury` is the config treasury.
+pub fn rent_nft(renter: Pubkey, rental: &Rental, treasury: Pubkey) -> Instruction {
+    build(
+        accounts::RentNft {
+            rental: derive_rental_pda(&rental.mint).0,
+            renter,
+            owner: rental.owner,
+            renter_nft_account: nft_token_account(&renter, &rental.mint),
+            mint: rental.mint,
+            vault: derive_vault_pda(&rental.mint).0,
+            config: derive_config_pda().0,
+            treasury,
+            system_program: system_program::ID,
+            token_program: token_2022::ID,
+        },
+        instruction::RentNft {},
+    )
+}
+
+pub fn return_nft(renter: Pubkey, mint: Pubkey) -> Instruction {
+    build(
+        accounts::ReturnNft {
+            rental: derive_rental_pda(&mint).0,
+            renter,
+            renter_nft_account: nft_token_account(&renter, &mint),
+            mint,
+            vault: derive_vault_pda(&mint).0,
+            token_program: token_2022::ID,
+        },
+        instruction::ReturnNft {},
+    )
+}
+
+pub fn seize_rental_collateral(owner: Pubkey, mint: Pubkey) -> Instruction {
+    build(
+        accounts::SeizeRentalCollateral {
+            rental: derive_rental_pda(&mint).0,
+            owner,
+        },
+        instruction::SeizeRentalCollateral {},
+    )
+}
+
+pub fn delist_rental(owner: Pubkey, mint: Pubkey) -> Instruction {
+    build(
+        accounts::DelistRental {
+            rental: derive_rental_pda(&mint).0,
+            owner,
+            nft_account: nft_token_account(&owner, &mint),
+            mint,
+            vault: derive_vault_pda(&mint).0,
+            token_program: token_2022::ID,
+        },
+        instruction::DelistRental {},
+    )
+}
+
+/// `None` removes the listing's flash sale.
+pub fn set_flash_sale(
+    seller: Pubkey,
+    listing: Pubkey,
+    flash_sale: Option<FlashSale>,
+) -> Instruction {
+    build(
+        accounts::SetFlashSale { listing, seller },
+        instruction::SetFlashSale { flash_sale },
+    )
+}
+
+pub fn set_installment_terms(
+    seller: Pubkey,
+    listing: Pubkey,
+    terms: Option<InstallmentTerms>,
+) -> Instruction {
+    build(
+        accounts::SetInstallmentTerms { listing, seller },
+        instruction::SetInstallmentTerms { terms },
+    )
+}
+
+pub fn start_installment_plan(
+    buyer: Pubkey,
+    listing_key: Pubkey,
+    listing: &Listing,
+) -> Instruction {
+    build(
+        accounts::StartInstallmentPlan {
+            plan: derive_installment_plan_pda(&listing_key).0,
+            listing: listing_key,
+            buyer,
+            config: derive_config_pda().0,
+            blocked_mint: derive_blocked_mint_pda(&listing.mint).0,
+            system_program: system_program::ID,
+        },
+        instruction::StartInstallmentPlan {},
+    )
+}
+
+pub fn pay_installment(buyer: Pubkey, listing: Pubkey, amount: u64) -> Instruction {
+    build(
+        accounts::PayInstallment {
+            plan: derive_installment_plan_pda(&listing).0,
+            buyer,
+            system_program: system_program::ID,
+        },
+        instruction::PayInstallment { amount },
+    )
+}
+
+/// `fee_recipient` is the listing's storefront treasury, or the config treasury.
+pub fn claim_installment_nft(
+    plan: &InstallmentPlan,
+    listing: &Listing,
+    fee_recipient: Pubkey,
+    creator: Option<Pubkey>,
+    sale_number: u64,
+    insurance_fund: bool,
+    withholding: bool,
+) -> Instruction {
+    build(
+        accounts::ClaimInstallmentNft {
+            #[cfg(feature = "event-cpi")]
+            event_authority: derive_event_authority_pda().0,
+            #[cfg(feature = "event-cpi")]
+            program: PROGRAM_ID,
+            plan: derive_installment_plan_pda(&plan.listing).0,
+            listing: plan.listing,
+            rent_payer: listing.rent_recipient(),
+            buyer: plan.buyer,
+            seller: plan.seller,
+            buyer_token_account: nft_token_account(&plan.buyer, &plan.mint),
+            mint: plan.mint,
+            vault: derive_listing_vault_pda(&plan.mint, &plan.listing).0,
+            config: derive_config_pda().0,
+            provenance: derive_provenance_pda(&plan.mint).0,
+            sale_record: derive_sale_record_pda(&plan.mint, sale_number).0,
+            storefront: listing.storefront,
+            global_stats: derive_global_stats_pda().0,
+            treasury: fee_recipient,
+            insurance_fund: insurance_fund.then(|| derive_insurance_fund_pda().0),
+            withholding: withholding.then(|| derive_withholding_pda(&plan.seller).0),
+            creator,
+            system_program: system_program::ID,
+            token_program: token_2022::ID,
+        },
+        instruction::ClaimInstallmentNft {},
+    )
+}
+
+pub fn reclaim_installment_nft(plan: &InstallmentPlan, listing: &Listing) -> Instruction {
+    build(
+        accounts::ReclaimInstallmentNft {
+            plan: derive_installment_plan_pda(&plan.listing).0,
+            listing: plan.listing,
+            seller: plan.seller,
+            rent_payer: listing.rent_recipient(),
+            buyer: plan.buyer,
+            nft_account: nft_token_account(&plan.seller, &plan.mint),
+            mint: plan.mint,
+            vault: derive_listing_vault_pda(&plan.mint, &plan.listing).0,
+            token_program: token_2022::ID,
+        },
+        instruction::ReclaimInstallmentNft {},
+    )
+}
+
+pub fn open_group_buy(organizer: Pubkey, listing: Pubkey, deadline: i64) -> Instruction {
+    build(
+        accounts::OpenGroupBuy {
+            group_buy: derive_group_buy_pda(&listing).0,
+            listing,
+            organizer,
+            config: derive_config_pda().0,
+            system_program: system_program::ID,
+        },
+        instruction::OpenGroupBuy { deadline },
+    )
+}
+
+pub fn contribute_group_buy(contributor: Pubkey, listing: Pubkey, amount: u64) -> Instruction {
+    let group_buy = derive_group_buy_pda(&listing).0;
+    build(
+        accounts::ContributeGroupBuy {
+            group_buy,
+            contribution: derive_group_buy_contribution_pda(&group_buy, &contributor).0,
+            contributor,
+            system_program: system_program::ID,
+        },
+        instruction::ContributeGroupBuy { amount },
+    )
+}
+
+/// `fee_recipient` is the listing's storefront treasury, or the config treasury.
+#[allow(clippy::too_many_arguments)]
+pub fn execute_group_buy(
+    cranker: Pubkey,
+    listing_key: Pubkey,
+    listing: &Listing,
+    fee_recipient: Pubkey,
+    creator: Option<Pubkey>,
+    sale_number: u64,
+    insurance_fund: bool,
+    withholding: bool,
+) -> Instruction {
+    let mint = listing.mint;
+    let group_buy = derive_group_buy_pda(&listing_key).0;
+    build(
+        accounts::ExecuteGroupBuy {
+            #[cfg(feature = "event-cpi")]
+            event_authority: derive_event_authority_pda().0,
+            #[cfg(feature = "event-cpi")]
+            program: PROGRAM_ID,
+            group_buy,
+            listing: listing_key,
+            cranker,
+            seller: listing.seller,
+            rent_payer: listing.rent_recipient(),
+            mint,
+            vault: derive_listing_vault_pda(&mint, &listing_key).0,
+            group_nft_account: derive_group_nft_account_pda(&group_buy).0,
+            config: derive_config_pda().0,
+            provenance: derive_provenance_pda(&mint).0,
+            sale_record: derive_sale_record_pda(&mint, sale_number).0,
+            storefront: listing.storefront,
+            global_stats: derive_global_stats_pda().0,
+            blocked_mint: derive_blocked_mint_pda(&mint).0,
+            treasury: fee_recipient,
+            insurance_fund: insurance_fund.then(|| derive_insurance_fund_pda().0),
+            withholding: withholding.then(|| derive_withholding_pda(&listing.seller).0),
+            creator,
+            token_program: token_2022::ID,
+            system_program: system_program::ID,
+        },
+        instruction::ExecuteGroupBuy {},
+    )
+}
+
+/// `redeemer` must be a contributor of the group buy of `listing`.
+pub fn redeem_group_nft(listing: Pubkey, mint: Pubkey, redeemer: Pubkey) -> Instruction {
+    let group_buy = derive_group_buy_pda(&listing).0;
+    build(
+        accounts::RedeemGroupNft {
+            group_buy,
+            contribution: derive_group_buy_contribution_pda(&group_buy, &redeemer).0,
+            redeemer,
+            mint,
+            group_nft_account: derive_group_nft_account_pda(&group_buy).0,
+            redeemer_nft_account: nft_token_account(&redeemer, &mint),
+            token_program: token_2022::ID,
+            system_program: system_program::ID,
+        },
+        instruction::RedeemGroupNft {},
+    )
+}
+
+pub fn refund_group_buy_contribution(listing: Pubkey, contributor: Pubkey) -> Instruction {
+    let group_buy = derive_group_buy_pda(&listing).0;
+    build(
+        accounts::RefundGroupBuyContribution {
+            group_buy,
+            contribution: derive_group_buy_contribution_pda(&group_buy, &contributor).0,
+            contributor,
+        },
+        instruction::RefundGroupBuyContribution {},
+    )
+}
+
+fn create_auction_accounts(
+    auction: Pubkey,
+    seller: Pubkey,
+    mint: Pubkey,
+    collection: Option<Pubkey>,
+) -> accounts::CreateAuction {
+    accounts::CreateAuction {
+        auction,
+        seller,
+        nft_account: nft_token_account(&seller, &mint),
+        mint,
+        vault: derive_vault_pda(&mint).0,
+        vault_deposit: derive_vault_deposit_pda(&mint).0,
+        provenance: derive_provenance_pda(&mint).0,
+        config: derive_config_pda().0,
+        global_stats: derive_global_stats_pda().0,
+        blocked_mint: derive_blocked_mint_pda(&mint).0,
+        allowed_collection: collection
+            .map(|collection| derive_allowed_collection_pda(&collection).0),
+        metadata: collection.map(|_| derive_metadata_pda(&mint)),
+        system_program: system_program::ID,
+        token_program: token_2022::ID,
+    }
+}
+
+/// `auction` is a new keypair, signing alongside the seller. `collection` is needed in curated mode.
+#[allow(clippy::too_many_arguments)]
+pub fn create_auction(
+    auction: Pubkey,
+    seller: Pubkey,
+    mint: Pubkey,
+    min_bid: u64,
+    reserve_price: u64,
+    buy_now_price: Option<u64>,
+    duration: i64,
+    collection: Option<Pubkey>,
+) -> Instruction {
+    build(
+        create_auction_accounts(auction, seller, mint, collection),
+        instruction::CreateAuction {
+            min_bid,
+            reserve_price,
+            buy_now_price,
+            duration,
+        },
+    )
+}
+
+/// `auction` is a new keypair, signing alongside the seller. `collection` is needed in curated mode.
+#[allow(clippy::too_many_arguments)]
+pub fn create_sealed_auction(
+    auction: Pubkey,
+    seller: Pubkey,
+    mint: Pubkey,
+    min_bid: u64,
+    reserve_price: u64,
+    bid_deposit: u64,
+    bidding_duration: i64,
+    reveal_duration: i64,
+    collection: Option<Pubkey>,
+) -> Instruction {
+    build(
+        create_auction_accounts(auction, seller, mint, collection),
+        instruction::CreateSealedAuction {
+            min_bid,
+            reserve_price,
+            bid_deposit,
+            bidding_duration,
+            reveal_duration,
+        },
+    )
+}
+
+pub fn place_bid(auction: Pubkey, bidder: Pubkey, amount: u64) -> Instruction {
+    let bid = derive_bid_pda(&auction, &bidder).0;
+    build(
+        accounts::PlaceBid {
+            auction,
+            bid,
+            escrow_yield: derive_escrow_yield_pda(&bid).0,
+            bidder,
+            config: derive_config_pda().0,
+            system_program: system_program::ID,
+        },
+        instruction::PlaceBid { amount },
+    )
+}
+
+/// `commitment` is `marketplace::sealed_bid_commitment(bidder, amount, salt)`.
+pub fn commit_bid(auction: Pubkey, bidder: Pubkey, commitment: [u8; 32]) -> Instruction {
+    build(
+        accounts::CommitBid {
+            auction,
+            bid: derive_bid_pda(&auction, &bidder).0,
+            bidder,
+            config: derive_config_pda().0,
+            system_program: system_program::ID,
+        },
+        instruction::CommitBid { commitment },
+    )
+}
+
+pub fn reveal_bid(auction: Pubkey, bidder: Pubkey, amount: u64, salt: [u8; 32]) -> Instruction {
+    build(
+        accounts::RevealBid {
+            auction,
+            bid: derive_bid_pda(&auction, &bidder).0,
+            bidder,
+            system_program: system_program::ID,
+        },
+        instruction::RevealBid { amount, salt },
+    )
+}
+
+pub fn forfeit_unrevealed_bid(
+    auction_key: Pubkey,
+    auction: &Auction,
+    bidder: Pubkey,
+) -> Instruction {
+    build(
+        accounts::ForfeitUnrevealedBid {
+            auction: auction_key,
+            bid: derive_bid_pda(&auction_key, &bidder).0,
+            seller: auction.seller,
+            bidder,
+        },
+        instruction::ForfeitUnrevealedBid {},
+    )
+}
+
+pub fn cancel_bid(auction: Pubkey, bidder: Pubkey) -> Instruction {
+    build(
+        accounts::CancelBid {
+            auction,
+            bid: derive_bid_pda(&auction, &bidder).0,
+            bidder,
+        },
+        instruction::CancelBid {},
+    )
+}
+
+/// Routes a `kind` cancel instruction `ix` (from `remove_listed_nft`, `cancel_offer`,
+/// `cancel_collection_offer`, `cancel_loan_offer` or `cancel_bid`) of `target` through `cancel`.
+pub fn cancel(kind: CancelKind, target: Pubkey, ix: Instruction) -> Instruction {
+    let mut cancel = build(accounts::Cancel { target }, instruction::Cancel { kind });
+    cancel.accounts.extend(ix.accounts);
+    cancel
+}
+
+pub fn cancel_auction(auction_key: Pubkey, auction: &Auction) -> Instruction {
+    build(
+        accounts::CancelAuction {
+            auction: auction_key,
+            seller: auction.seller,
+            seller_nft_account: nft_token_account(&auction.seller, &auction.mint),
+            mint: auction.mint,
+            vault: derive_vault_pda(&auction.mint).0,
+            token_program: token_2022::ID,
+        },
+        instruction::CancelAuction {},
+    )
+}
+
+/// `auction` is a new keypair, signing alongside the seller. The listing's recorded collection is passed
+/// for curation.
+pub fn convert_listing_to_auction(
+    listing_key: Pubkey,
+    listing: &Listing,
+    auction: Pubkey,
+    min_bid: u64,
+    reserve_price: u64,
+    buy_now_price: Option<u64>,
+    duration: i64,
+) -> Instruction {
+    build(
+        accounts::ConvertListingToAuction {
+            #[cfg(feature = "event-cpi")]
+            event_authority: derive_event_authority_pda().0,
+            #[cfg(feature = "event-cpi")]
+            program: PROGRAM_ID,
+            listing: listing_key,
+            rent_payer: listing.rent_recipient(),
+            listing_vault: derive_listing_vault_pda(&listing.mint, &listing_key).0,
+            auction,
+            seller: listing.seller,
+            mint: listing.mint,
+            vault: derive_vault_pda(&listing.mint).0,
+            vault_deposit: derive_vault_deposit_pda(&listing.mint).0,
+            config: derive_config_pda().0,
+            blocked_mint: derive_blocked_mint_pda(&listing.mint).0,
+            seller_ban: derive_banned_wallet_pda(&listing.seller).0,
+            allowed_collection: listing
+                .collection
+                .map(|collection| derive_allowed_collection_pda(&collection).0),
+            metadata: listing
+                .collection
+                .map(|_| derive_metadata_pda(&listing.mint)),
+            registry_page: listing
+                .registry_page
+                .map(|page| derive_registry_page_pda(page).0),
+            global_stats: derive_global_stats_pda().0,
+            system_program: system_program::ID,
+            token_program: token_2022::ID,
+        },
+        instruction::ConvertListingToAuction {
+            min_bid,
+            reserve_price,
+            buy_now_price,
+            duration,
+        },
+    )
+}
+
+/// `listing` is a new keypair, signing alongside the seller. `collection` is needed in curated mode;
+/// the listing is added to registry page `registry_page` if given.
+pub fn convert_auction_to_listing(
+    auction_key: Pubkey,
+    auction: &Auction,
+    listing: Pubkey,
+    price: u64,
+    collection: Option<Pubkey>,
+    registry_page: Option<u32>,
+) -> Instruction {
+    build(
+        accounts::ConvertAuctionToListing {
+            #[cfg(feature = "event-cpi")]
+            event_authority: derive_event_authority_pda().0,
+            #[cfg(feature = "event-cpi")]
+            program: PROGRAM_ID,
+            auction: auction_key,
+            seller: auction.seller,
+            mint: auction.mint,
+            vault: derive_vault_pda(&auction.mint).0,
+            listing,
+            listing_vault: derive_listing_vault_pda(&auction.mint, &listing).0,
+            config: derive_config_pda().0,
+            blocked_mint: derive_blocked_mint_pda(&auction.mint).0,
+            seller_ban: derive_banned_wallet_pda(&auction.seller).0,
+            allowed_collection: collection
+                .map(|collection| derive_allowed_collection_pda(&collection).0),
+            metadata: collection.map(|_| derive_metadata_pda(&auction.mint)),
+            registry_page: registry_page.map(|page| derive_registry_page_pda(page).0),
+            global_stats: derive_global_stats_pda().0,
+            system_program: system_program::ID,
+            token_program: token_2022::ID,
+        },
+        instruction::ConvertAuctionToListing { price },
+    )
+}
+
+pub fn set_auction_on_no_sale(
+    auction_key: Pubkey,
+    seller: Pubkey,
+    on_no_sale: NoSaleAction,
+) -> Instruction {
+    build(
+        accounts::SetAuctionOnNoSale {
+            auction: auction_key,
+            seller,
+        },
+        instruction::SetAuctionOnNoSale { on_no_sale },
+    )
+}
+
+/// `treasury` is the config treasury, `creator` the mint's provenance creator (for royalties).
+/// `relist` is the new listing keypair (also signing) for auctions relisting on no sale when the
+/// reserve isn't met, optionally added to registry page `registry_page`; `collection` is needed to
+/// relist in curated mode.
+#[allow(clippy::too_many_arguments)]
+pub fn settle_auction(
+    auction_key: Pubkey,
+    auction: &Auction,
+    cranker: Pubkey,
+    treasury: Pubkey,
+    creator: Option<Pubkey>,
+    sale_number: u64,
+    insurance_fund: bool,
+    withholding: bool,
+    relist: Option<Pubkey>,
+    registry_page: Option<u32>,
+    collection: Option<Pubkey>,
+) -> Instruction {
+    let bidder = (auction.highest_bidder != Pubkey::default()).then_some(auction.highest_bidder);
+
+    build(
+        accounts::SettleAuction {
+            #[cfg(feature = "event-cpi")]
+            event_authority: derive_event_authority_pda().0,
+            #[cfg(feature = "event-cpi")]
+            program: PROGRAM_ID,
+            auction: auction_key,
+            cranker,
+            seller: auction.seller,
+            seller_nft_account: nft_token_account(&auction.seller, &auction.mint),
+            mint: auction.mint,
+            vault: derive_vault_pda(&auction.mint).0,
+            bidder,
+            bid: bidder.map(|bidder| derive_bid_pda(&auction_key, &bidder).0),
+            bidder_token_account: bidder.map(|bidder| nft_token_account(&bidder, &auction.mint)),
+            config: derive_config_pda().0,
+            provenance: derive_provenance_pda(&auction.mint).0,
+            sale_record: bidder.map(|_| derive_sale_record_pda(&auction.mint, sale_number).0),
+            global_stats: derive_global_stats_pda().0,
+            treasury,
+            insurance_fund: insurance_fund.then(|| derive_insurance_fund_pda().0),
+            withholding: withholding.then(|| derive_withholding_pda(&auction.seller).0),
+            creator,
+            relist,
+            relist_vault: relist.map(|relist| derive_listing_vault_pda(&auction.mint, &relist).0),
+            registry_page: registry_page.map(|page| derive_registry_page_pda(page).0),
+            blocked_mint: derive_blocked_mint_pda(&auction.mint).0,
+            seller_ban: derive_banned_wallet_pda(&auction.seller).0,
+            allowed_collection: collection
+                .map(|collection| derive_allowed_collection_pda(&collection).0),
+            metadata: collection.map(|_| derive_metadata_pda(&auction.mint)),
+            token_program: token_2022::ID,
+            system_program: system_program::ID,
+        },
+        instruction::SettleAuction {},
+    )
+}
@@ -1,13 +1,56 @@
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::{
-    program::invoke,
+    ed25519_program,
+    hash::hashv,
+    instruction::{get_stack_height, TRANSACTION_LEVEL_STACK_HEIGHT},
+    program::{invoke, invoke_signed},
     system_instruction,
+    sysvar::instructions::{load_current_index_checked, load_instruction_at_checked},
+};
+use anchor_lang::{AccountsClose, Discriminator};
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::memo;
+use anchor_spl::token::Token;
+use anchor_spl::token_2022::Token2022;
+use anchor_spl::token_interface::{self, Mint, TokenAccount, TokenInterface};
+use mpl_token_metadata::accounts::Metadata;
+use spl_token_2022::extension::{
+    default_account_state::DefaultAccountState,
+    group_member_pointer::GroupMemberPointer,
+    memo_transfer::memo_required,
+    metadata_pointer::MetadataPointer,
+    non_transferable::NonTransferable,
+    permanent_delegate::PermanentDelegate,
+    transfer_fee::{self, TransferFeeConfig},
+    transfer_hook, BaseStateWithExtensions, StateWithExtensions,
+};
+use spl_token_group_interface::state::TokenGroupMember;
+use spl_token_metadata_interface::state::TokenMetadata;
+use spl_transfer_hook_interface::onchain::add_extra_accounts_for_execute_cpi;
+
+use crate::constant::{
+    BPS_DENOMINATOR, DEFAULT_ACTION_TIMELOCK, LAMPORTS_PER_SOL, LISTING_VERSION,
+    MAX_ALLOWED_CALLERS, MAX_FEE_TIERS, MAX_FORCED_DELISTS, MAX_PAYEES, MAX_PRICE_LEGS,
+    MAX_ROYALTY_SPLITS, MAX_STOREFRONT_COLLECTIONS, OFFER_BOOK_DEPTH, ORDER_NONCES_PER_PAGE,
+    PREFIX, RAFFLE_BOND_BPS, RAFFLE_DRAW_DELAY_SLOTS, RAFFLE_DRAW_WINDOW, REGISTRY_PAGE_CAPACITY,
+    STAKE_POOL_DEPOSIT_SOL, STAKE_POOL_WITHDRAW_SOL, SWEEP_GRACE_PERIOD,
 };
-use anchor_spl::token_2022::{self, Token2022, Transfer};
-use anchor_spl::token_interface::{TokenAccount, Mint};
-use anchor_lang::AccountsClose;
 
-use crate::constant::PREFIX;
+/// Emits a sale / listing event through a self-CPI (`emit_cpi!`) with the `event-cpi` feature,
+/// so indexers read it from inner instructions instead of truncatable logs; `emit!` otherwise.
+/// `$ctx` is the instruction's context, its accounts built with `#[event_cpi]`.
+macro_rules! emit_activity {
+    ($ctx:ident, $event:expr) => {{
+        let event = $event;
+        #[cfg(feature = "event-cpi")]
+        {
+            let ctx = &$ctx;
+            emit_cpi!(event);
+        }
+        #[cfg(not(feature = "event-cpi"))]
+        emit!(event);
+    }};
+}
 
 // the program ID should be moved out eventually and set based on deployment env ( following best practices )
 declare_id!("GfLfsgUP5dQ2gGN4DAPSGZErKSCVZzsVBtof7ZafUP3n");
@@ -16,240 +59,16493 @@ declare_id!("GfLfsgUP5dQ2gGN4DAPSGZErKSCVZzsVBtof7ZafUP3n");
 pub mod solana_nft_marketplace {
     use super::*;
 
-    /// Creates a new listing, transferring NFT from usr --> vault (PDA).
-    pub fn list_nft(ctx: Context<ListNFT>, price: u64) -> Result<()> {
-        /// Transfer 1 NFT seller --> vault
-        let transfer_ix = spl_token_2022::instruction::transfer_checked(
-            &ctx.accounts.token_program.key(),
-            &ctx.accounts.nft_account.key(),
-            &ctx.accounts.mint.key(),
-            &ctx.accounts.vault.key(),
-            &ctx.accounts.seller.key(),
-            &[],      // No additional signer
-            1,        // 1 NFT
-            0,        // indivisible NFT ( decimal points )
-        )?;
-
-        // Invoke / execute the transfer.
-        invoke(
-            &transfer_ix,
-            &[
-                ctx.accounts.seller.to_account_info(),
-                ctx.accounts.vault.to_account_info(),
-                ctx.accounts.token_program.to_account_info(),
-                ctx.accounts.mint.to_account_info(),
-                ctx.accounts.nft_account.to_account_info(),
-            ],
-        )?;
+    /// Creates the marketplace config (authority, treasury & fee schedules).
+    pub fn initialize_config(
+        ctx: Context<InitializeConfig>,
+        treasury: Pubkey,
+        primary_fees: FeeSchedule,
+        secondary_fees: FeeSchedule,
+    ) -> Result<()> {
+        primary_fees.validate()?;
+        secondary_fees.validate()?;
 
-        // Init listing account data.
-        let listing = &mut ctx.accounts.listing;
-        listing.seller = *ctx.accounts.seller.key;
-        listing.mint = ctx.accounts.mint.key();
-        listing.price = price;
-        listing.is_active = true;
+        let config = &mut ctx.accounts.config;
+        config.authority = ctx.accounts.authority.key();
+        config.treasury = treasury;
+        config.primary_fees = primary_fees;
+        config.secondary_fees = secondary_fees;
+        config.enforce_royalties = false;
+        config.crank_tip_lamports = 0;
+        config.sol_usd_feed_id = [0; 32];
+        config.max_price_age_secs = 0;
+        config.max_price_conf_bps = 0;
+        config.fee_tiers = Vec::new();
+        config.membership_mint = Pubkey::default();
+        config.membership_min_amount = 0;
+        config.membership_fee_bps = 0;
+        config.curated_collections = false;
+        config.unwrap_wsol_proceeds = true;
+        config.reward_mint = Pubkey::default();
+        config.reward_per_point = 0;
+        config.maker_rebate_bps = 0;
+        config.cpi_policy = CpiPolicy::Open;
+        config.allowed_callers = Vec::new();
+        config.arbiter = Pubkey::default();
+        config.withholding_bps = 0;
+        config.withholding_release_ts = 0;
+        config.paused = false;
+        config.action_timelock = DEFAULT_ACTION_TIMELOCK;
+        config.pending_authority = Pubkey::default();
+        config.strict_listing = false;
+        config.insurance_bps = 0;
+        config.buyback_mint = Pubkey::default();
+        config.buyback_amm_program = Pubkey::default();
+        config.max_buyback_lamports = 0;
+        config.buyback_interval = 0;
+        config.min_buyback_rate = 0;
+        config.last_buyback_ts = 0;
+        config.mining_rate = 0;
+        config.mining_epoch_secs = 0;
+        config.mining_epoch_cap = 0;
+        config.loyalty_points_per_sol = 0;
+        config.loyalty_point_value = 0;
+        config.max_listing_price = 0;
+        config.fee_sweep_recipient = treasury;
+        config.fee_sweep_threshold = 0;
+        config.yield_stake_pool_program = Pubkey::default();
+        config.yield_stake_pool = Pubkey::default();
+        config.yield_to_treasury = false;
+        config.bump = ctx.bumps.config;
 
         Ok(())
     }
 
-    /// Remove NFT by transferring it back: vault (PDA) --> seller.
-    pub fn remove_listed_nft(ctx: Context<RemoveListedNFT>) -> Result<()> {
-        // Prep PDA seeds for authority sig
-        let seeds = &[
-            PREFIX.as_bytes(),
-            b"vault",
-            ctx.accounts.nft_account.mint.as_ref(),
-            &[ctx.bumps.vault],
-        ];
-        let signer = &[&seeds[..]];
+    /// Replaces the primary & secondary fee schedules (config authority only).
+    pub fn update_fee_schedules(
+        ctx: Context<UpdateConfig>,
+        primary_fees: FeeSchedule,
+        secondary_fees: FeeSchedule,
+    ) -> Result<()> {
+        primary_fees.validate()?;
+        secondary_fees.validate()?;
 
-        // Transfer back NFT vault --> seller.
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.vault.to_account_info(),
-            to: ctx.accounts.nft_account.to_account_info(),
-            authority: ctx.accounts.vault.to_account_info(),
-        };
-        let cpi_ctx = CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(),
-            cpi_accounts,
-            signer,
+        let config = &mut ctx.accounts.config;
+        config.primary_fees = primary_fees;
+        config.secondary_fees = secondary_fees;
+
+        Ok(())
+    }
+
+    /// Nominates `new_authority` to take over the config (config authority only), which it does by
+    /// signing `accept_authority`, e.g. an SPL Governance PDA through proposal execution so admin
+    /// instructions go through DAO proposals. Nominating the default key withdraws a nomination.
+    pub fn nominate_new_authority(ctx: Context<UpdateConfig>, new_authority: Pubkey) -> Result<()> {
+        require!(
+            new_authority != ctx.accounts.config.authority,
+            ErrorCode::InvalidConfigAuthority
+        );
+        ctx.accounts.config.pending_authority = new_authority;
+        emit!(ConfigAuthorityNominated {
+            authority: ctx.accounts.config.authority,
+            pending_authority: new_authority,
+        });
+        Ok(())
+    }
+
+    /// Takes over the config as its nominated authority.
+    pub fn accept_authority(ctx: Context<AcceptAuthority>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        emit!(ConfigAuthorityChanged {
+            old_authority: config.authority,
+            new_authority: config.pending_authority,
+        });
+        config.authority = config.pending_authority;
+        config.pending_authority = Pubkey::default();
+        Ok(())
+    }
+
+    /// Replaces either fee schedule (config authority only), leaving the other as is, so a
+    /// governance proposal only captures the schedule it changes & can't clobber a later update.
+    pub fn update_fees(
+        ctx: Context<UpdateConfig>,
+        primary_fees: Option<FeeSchedule>,
+        secondary_fees: Option<FeeSchedule>,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        if let Some(primary_fees) = primary_fees {
+            primary_fees.validate()?;
+            config.primary_fees = primary_fees;
+        }
+        if let Some(secondary_fees) = secondary_fees {
+            secondary_fees.validate()?;
+            config.secondary_fees = secondary_fees;
+        }
+
+        emit!(FeesUpdated {
+            primary_fees: config.primary_fees,
+            secondary_fees: config.secondary_fees,
+        });
+        Ok(())
+    }
+
+    /// Replaces the volume-based marketplace fee tiers (config authority only).
+    /// Tiers must be sorted by ascending `min_volume_lamports`; an empty list disables tiering.
+    pub fn set_fee_tiers(ctx: Context<UpdateConfig>, fee_tiers: Vec<FeeTier>) -> Result<()> {
+        require!(fee_tiers.len() <= MAX_FEE_TIERS, ErrorCode::InvalidFeeTiers);
+        require!(
+            fee_tiers
+                .windows(2)
+                .all(|pair| pair[0].min_volume_lamports < pair[1].min_volume_lamports),
+            ErrorCode::InvalidFeeTiers
         );
-        token_2022::transfer(cpi_ctx, 1)?;
+        for tier in &fee_tiers {
+            require!(
+                tier.marketplace_fee_bps as u64 <= BPS_DENOMINATOR,
+                ErrorCode::InvalidFeeBps
+            );
+        }
 
-        // Close the listing, return rent lamports to seller.
-        ctx.accounts.listing.close(ctx.accounts.seller.to_account_info())?;
+        ctx.accounts.config.fee_tiers = fee_tiers;
+        Ok(())
+    }
 
+    /// Makes royalty payment in `buy_nft` mandatory (or optional again), config authority only.
+    pub fn set_royalty_enforcement(
+        ctx: Context<UpdateConfig>,
+        enforce_royalties: bool,
+    ) -> Result<()> {
+        ctx.accounts.config.enforce_royalties = enforce_royalties;
         Ok(())
     }
 
-    /// Buy NFT = SOL --> seller & NFT --> buyer.
-    pub fn buy_nft(ctx: Context<BuyNFT>, vault_bump: u8) -> Result<()> {
-        let listing = &mut ctx.accounts.listing;
+    /// Sets the lamport tip paid to whoever cranks a settlement (config authority only).
+    pub fn set_crank_tip(ctx: Context<UpdateConfig>, crank_tip_lamports: u64) -> Result<()> {
+        ctx.accounts.config.crank_tip_lamports = crank_tip_lamports;
+        Ok(())
+    }
 
-        // Ensure the listing is still active.
-        require!(listing.is_active, ErrorCode::InactiveListing);
+    /// Points USD-priced listings at a Pyth SOL/USD feed with staleness & confidence limits (config authority only).
+    pub fn set_price_feed(
+        ctx: Context<UpdateConfig>,
+        sol_usd_feed_id: [u8; 32],
+        max_price_age_secs: u64,
+        max_price_conf_bps: u16,
+    ) -> Result<()> {
+        require!(
+            max_price_conf_bps as u64 <= BPS_DENOMINATOR,
+            ErrorCode::InvalidFeeBps
+        );
+        let config = &mut ctx.accounts.config;
+        config.sol_usd_feed_id = sol_usd_feed_id;
+        config.max_price_age_secs = max_price_age_secs;
+        config.max_price_conf_bps = max_price_conf_bps;
+        Ok(())
+    }
+
+    /// Sets the membership mint: buys where the buyer or seller holds at least `min_amount` of it pay
+    /// `fee_bps` marketplace fee (if lower). `Pubkey::default()` disables membership (config authority only).
+    pub fn set_membership(
+        ctx: Context<UpdateConfig>,
+        membership_mint: Pubkey,
+        min_amount: u64,
+        fee_bps: u16,
+    ) -> Result<()> {
+        require!(fee_bps as u64 <= BPS_DENOMINATOR, ErrorCode::InvalidFeeBps);
+        let config = &mut ctx.accounts.config;
+        config.membership_mint = membership_mint;
+        config.membership_min_amount = min_amount;
+        config.membership_fee_bps = fee_bps;
+        Ok(())
+    }
+
+    /// Picks how sellers get WSOL-escrowed offers: unwrapped to SOL, or as WSOL (config authority only).
+    pub fn set_wsol_settlement(
+        ctx: Context<UpdateConfig>,
+        unwrap_wsol_proceeds: bool,
+    ) -> Result<()> {
+        ctx.accounts.config.unwrap_wsol_proceeds = unwrap_wsol_proceeds;
+        Ok(())
+    }
 
-        // Transfer SOL (price in SOL * lamports-per-SOL) buyer --> seller.
-        // If the price is already in lamports, we need to remove the multiplication below.
-        const LAMPORTS_PER_SOL: u64 = 1_000_000_000;
-        let transfer_ix = system_instruction::transfer(
-            &ctx.accounts.buyer.key(),
-            &ctx.accounts.seller.key(),
-            listing.price * LAMPORTS_PER_SOL,
+    /// Sets the listing rewards token & rate (config authority only); a default `reward_mint` disables
+    /// new enrollments.
+    pub fn set_listing_rewards(
+        ctx: Context<UpdateConfig>,
+        reward_mint: Pubkey,
+        reward_per_point: u64,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.reward_mint = reward_mint;
+        config.reward_per_point = reward_per_point;
+        Ok(())
+    }
+
+    /// Sets trade mining emissions (config authority only): `buy_nft` pays buyer & seller each
+    /// `mining_rate` base units of `config.reward_mint` per SOL of sale volume, from its rewards vault, at
+    /// most `mining_epoch_cap` in total per `mining_epoch_secs`. A zero `mining_rate` disables it.
+    pub fn set_trade_mining(
+        ctx: Context<UpdateConfig>,
+        mining_rate: u64,
+        mining_epoch_secs: i64,
+        mining_epoch_cap: u64,
+    ) -> Result<()> {
+        require!(
+            mining_rate == 0 || mining_epoch_secs > 0,
+            ErrorCode::InvalidTradeMiningParams
         );
-        invoke(
-            &transfer_ix,
-            &[
-                ctx.accounts.buyer.to_account_info(),
-                ctx.accounts.seller.to_account_info(),
-                ctx.accounts.system_program.to_account_info(),
-            ],
-        )?;
+        let config = &mut ctx.accounts.config;
+        config.mining_rate = mining_rate;
+        config.mining_epoch_secs = mining_epoch_secs;
+        config.mining_epoch_cap = mining_epoch_cap;
+        Ok(())
+    }
+
+    /// Sets the loyalty program (config authority only): buyers earn `loyalty_points_per_sol` points per
+    /// SOL spent in `buy_nft`, each redeemable for `loyalty_point_value` base units of `config.reward_mint`.
+    pub fn set_loyalty_program(
+        ctx: Context<UpdateConfig>,
+        loyalty_points_per_sol: u64,
+        loyalty_point_value: u64,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.loyalty_points_per_sol = loyalty_points_per_sol;
+        config.loyalty_point_value = loyalty_point_value;
+        Ok(())
+    }
+
+    /// Redeems `points` of the wallet's loyalty points for reward tokens from the rewards vault, at
+    /// `config.loyalty_point_value`.
+    pub fn redeem_points(ctx: Context<RedeemPoints>, points: u64) -> Result<()> {
+        let loyalty_account = &mut ctx.accounts.loyalty_account;
+        require!(
+            points > 0 && loyalty_account.points >= points,
+            ErrorCode::InsufficientLoyaltyPoints
+        );
+        let amount = points
+            .checked_mul(ctx.accounts.config.loyalty_point_value)
+            .ok_or(ErrorCode::MathOverflow)?;
+        require!(
+            ctx.accounts.reward_vault.amount >= amount,
+            ErrorCode::RewardVaultUnderfunded
+        );
+        loyalty_account.points -= points;
 
-        // Transfer NFT = vault --> buyer account.
         let seeds = &[
             PREFIX.as_bytes(),
-            b"vault",
-            ctx.accounts.nft_account.mint.as_ref(),
-            &[vault_bump],
+            b"reward_vault",
+            ctx.accounts.config.reward_mint.as_ref(),
+            &[ctx.bumps.reward_vault],
         ];
-        let signer = &[&seeds[..]];
+        transfer_tokens(
+            &ctx.accounts.reward_token_program.to_account_info(),
+            &ctx.accounts.reward_vault.to_account_info(),
+            &ctx.accounts.reward_mint,
+            &ctx.accounts.wallet_reward_account.to_account_info(),
+            &ctx.accounts.reward_vault.to_account_info(),
+            amount,
+            &[&seeds[..]],
+        )?;
 
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.vault.to_account_info(),
-            to: ctx.accounts.buyer_token_account.to_account_info(),
-            authority: ctx.accounts.vault.to_account_info(),
-        };
-        let cpi_ctx = CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(),
-            cpi_accounts,
-            signer,
+        emit!(LoyaltyPointsRedeemed {
+            wallet: ctx.accounts.wallet.key(),
+            points,
+            amount,
+            remaining_points: ctx.accounts.loyalty_account.points,
+        });
+
+        Ok(())
+    }
+
+    /// Creates the trade mining account, tracking emissions against the epoch cap.
+    pub fn initialize_trade_mining(ctx: Context<InitializeTradeMining>) -> Result<()> {
+        ctx.accounts.trade_mining.bump = ctx.bumps.trade_mining;
+        Ok(())
+    }
+
+    /// Tops up the listing rewards vault of `config.reward_mint` (config authority only).
+    pub fn fund_listing_rewards(ctx: Context<FundListingRewards>, amount: u64) -> Result<()> {
+        transfer_tokens(
+            &ctx.accounts.reward_token_program.to_account_info(),
+            &ctx.accounts.authority_reward_account.to_account_info(),
+            &ctx.accounts.reward_mint,
+            &ctx.accounts.reward_vault.to_account_info(),
+            &ctx.accounts.authority.to_account_info(),
+            amount,
+            &[],
+        )
+    }
+
+    /// Sets who may call `buy_nft` via CPI (config authority only).
+    pub fn set_cpi_policy(
+        ctx: Context<UpdateConfig>,
+        cpi_policy: CpiPolicy,
+        allowed_callers: Vec<AllowedCaller>,
+    ) -> Result<()> {
+        require!(
+            allowed_callers.len() <= MAX_ALLOWED_CALLERS,
+            ErrorCode::TooManyAllowedCallers
         );
-        token_2022::transfer(cpi_ctx, 1)?;
+        for caller in &allowed_callers {
+            require!(
+                caller.fee_share_bps as u64 <= BPS_DENOMINATOR,
+                ErrorCode::InvalidFeeBps
+            );
+        }
+        let config = &mut ctx.accounts.config;
+        config.cpi_policy = cpi_policy;
+        config.allowed_callers = allowed_callers;
+        Ok(())
+    }
 
-        // Mark the listing as inactive so it can't be purchased again.
-        listing.is_active = false;
+    /// Sets the share of marketplace fees rebated to makers (config authority only).
+    pub fn set_maker_rebate(ctx: Context<UpdateConfig>, maker_rebate_bps: u16) -> Result<()> {
+        require!(
+            maker_rebate_bps as u64 <= BPS_DENOMINATOR,
+            ErrorCode::InvalidFeeBps
+        );
+        ctx.accounts.config.maker_rebate_bps = maker_rebate_bps;
+        Ok(())
+    }
 
+    /// Restricts listing to mints of allowed collections (or lifts the restriction), config authority only.
+    pub fn set_collection_curation(
+        ctx: Context<UpdateConfig>,
+        curated_collections: bool,
+    ) -> Result<()> {
+        ctx.accounts.config.curated_collections = curated_collections;
         Ok(())
     }
-}
 
-// --------------------------------------------------------------------
-// Contexts & Accounts
-// --------------------------------------------------------------------
-#[derive(Accounts)]
-pub struct ListNFT<'info> {
-    /// Listing account stores seller, price, etc (on chain).
-    #[account(init, payer = seller, space = 80 + 8)]
-    pub listing: Account<'info, Listing>,
+    /// Requires new listings to pass the mint's metadata, with a verified collection & creator (or lifts
+    /// the requirement), config authority only.
+    pub fn set_strict_listing(ctx: Context<UpdateConfig>, strict_listing: bool) -> Result<()> {
+        ctx.accounts.config.strict_listing = strict_listing;
+        Ok(())
+    }
 
-    #[account(mut)]
-    pub seller: Signer<'info>,
+    /// Sets the share of SOL sales' marketplace fee paid into the insurance fund (config authority only);
+    /// sales must then pass the fund (`initialize_insurance_fund`).
+    pub fn set_insurance_bps(ctx: Context<UpdateConfig>, insurance_bps: u16) -> Result<()> {
+        require!(
+            insurance_bps as u64 <= BPS_DENOMINATOR,
+            ErrorCode::InvalidFeeBps
+        );
+        ctx.accounts.config.insurance_bps = insurance_bps;
+        Ok(())
+    }
 
-    #[account(mut, owner = token_program.key())]
-    pub nft_account: InterfaceAccount<'info, TokenAccount>,
+    /// Configures `execute_buyback` (config authority only): the marketplace token bought back & burned,
+    /// the AMM program swaps go through, the most lamports spent per buyback, the seconds between two
+    /// buybacks & the lowest accepted rate, in token base units per SOL. A default `buyback_mint` disables it.
+    pub fn set_buyback(
+        ctx: Context<UpdateConfig>,
+        buyback_mint: Pubkey,
+        buyback_amm_program: Pubkey,
+        max_buyback_lamports: u64,
+        buyback_interval: i64,
+        min_buyback_rate: u64,
+    ) -> Result<()> {
+        require!(buyback_interval >= 0, ErrorCode::InvalidBuyback);
+        let config = &mut ctx.accounts.config;
+        config.buyback_mint = buyback_mint;
+        config.buyback_amm_program = buyback_amm_program;
+        config.max_buyback_lamports = max_buyback_lamports;
+        config.buyback_interval = buyback_interval;
+        config.min_buyback_rate = min_buyback_rate;
+        Ok(())
+    }
 
-    #[account(constraint = mint.key() == nft_account.mint)]
-    pub mint: InterfaceAccount<'info, Mint>,
+    /// Sets who resolves escrowed sale disputes (config authority only); default disables disputes.
+    pub fn set_arbiter(ctx: Context<UpdateConfig>, arbiter: Pubkey) -> Result<()> {
+        ctx.accounts.config.arbiter = arbiter;
+        Ok(())
+    }
 
-    ///  create NFT vault if not present.
-    #[account(
-        init_if_needed,
-        token::mint = mint,
-        payer = seller,
-        token::authority = vault,
-        seeds = [PREFIX.as_bytes(), b"vault", nft_account.mint.as_ref()],
-        bump
-    )]
-    pub vault: InterfaceAccount<'info, TokenAccount>,
+    /// Sets the share of seller proceeds withheld on SOL sales, and from when sellers can
+    /// withdraw their withholding (config authority only).
+    pub fn set_withholding(
+        ctx: Context<UpdateConfig>,
+        withholding_bps: u16,
+        withholding_release_ts: i64,
+    ) -> Result<()> {
+        require!(
+            withholding_bps as u64 <= BPS_DENOMINATOR,
+            ErrorCode::InvalidFeeBps
+        );
+        let config = &mut ctx.accounts.config;
+        config.withholding_bps = withholding_bps;
+        config.withholding_release_ts = withholding_release_ts;
+        Ok(())
+    }
 
-    pub system_program: Program<'info, System>,
-    pub token_program: Program<'info, Token2022>,
-    pub rent: Sysvar<'info, Rent>,
-}
+    /// Queues an emergency action (config authority only), executable from `action_timelock`
+    /// seconds on. `nonce` tells apart actions queued at once.
+    pub fn queue_action(
+        ctx: Context<QueueAction>,
+        nonce: u64,
+        action: EmergencyAction,
+    ) -> Result<()> {
+        action.validate()?;
+        let eta = Clock::get()?
+            .unix_timestamp
+            .checked_add(ctx.accounts.config.action_timelock)
+            .ok_or(ErrorCode::MathOverflow)?;
 
-#[derive(Accounts)]
-pub struct RemoveListedNFT<'info> {
-    #[account(mut)]
-    pub seller: Signer<'info>,
+        let queued_action = &mut ctx.accounts.queued_action;
+        queued_action.nonce = nonce;
+        queued_action.action = action;
+        queued_action.eta = eta;
+        queued_action.bump = ctx.bumps.queued_action;
 
-    #[account(mut)]
-    pub nft_account: InterfaceAccount<'info, TokenAccount>,
+        emit!(ActionQueued {
+            queued_action: queued_action.key(),
+            action: queued_action.action.clone(),
+            eta,
+        });
+        Ok(())
+    }
 
-    /// Validate seller is the same as in the listing, & the mint matches.
-    #[account(mut, has_one = seller, constraint = nft_account.mint == listing.mint)]
-    pub listing: Account<'info, Listing>,
+    /// Drops a queued action before it runs (config authority only).
+    pub fn cancel_queued_action(ctx: Context<SettleQueuedAction>) -> Result<()> {
+        emit!(QueuedActionCancelled {
+            queued_action: ctx.accounts.queued_action.key(),
+        });
+        Ok(())
+    }
 
-    #[account(constraint = mint.key() == nft_account.mint)]
-    pub mint: InterfaceAccount<'info, Mint>,
+    /// Runs a queued action once its timelock is over (config authority only). Forced delists run
+    /// through `force_delist` instead. Reward vault sweeps need the reward accounts.
+    pub fn execute_queued_action(ctx: Context<SettleQueuedAction>) -> Result<()> {
+        let queued_action = &ctx.accounts.queued_action;
+        require!(
+            Clock::get()?.unix_timestamp >= queued_action.eta,
+            ErrorCode::ActionTimelocked
+        );
 
-    #[account(
-        mut,
-        seeds = [PREFIX.as_bytes(), b"vault", nft_account.mint.as_ref()],
-        bump
-    )]
-    pub vault: InterfaceAccount<'info, TokenAccount>,
+        match queued_action.action {
+            EmergencyAction::SetTimelock { timelock } => {
+                ctx.accounts.config.action_timelock = timelock
+            }
+            EmergencyAction::RewardVaultSweep { amount } => {
+                let (
+                    Some(reward_mint),
+                    Some(reward_vault),
+                    Some(treasury_reward_account),
+                    Some(reward_token_program),
+                ) = (
+                    &ctx.accounts.reward_mint,
+                    &ctx.accounts.reward_vault,
+                    &ctx.accounts.treasury_reward_account,
+                    &ctx.accounts.reward_token_program,
+                )
+                else {
+                    return err!(ErrorCode::MissingRewardAccounts);
+                };
+                let seeds = &[
+                    PREFIX.as_bytes(),
+                    b"reward_vault",
+                    ctx.accounts.config.reward_mint.as_ref(),
+                    &[ctx.bumps.reward_vault.unwrap_or_default()],
+                ];
+                transfer_tokens(
+                    &reward_token_program.to_account_info(),
+                    &reward_vault.to_account_info(),
+                    reward_mint,
+                    &treasury_reward_account.to_account_info(),
+                    &reward_vault.to_account_info(),
+                    amount,
+                    &[&seeds[..]],
+                )?;
+            }
+            EmergencyAction::ForceDelist { .. } => return err!(ErrorCode::InvalidQueuedAction),
+        }
 
-    pub system_program: Program<'info, System>,
-    pub token_program: Program<'info, Token2022>,
-    pub rent: Sysvar<'info, Rent>,
-}
+        emit!(QueuedActionExecuted {
+            queued_action: queued_action.key(),
+            action: queued_action.action.clone(),
+        });
+        Ok(())
+    }
 
-#[derive(Accounts)]
-pub struct BuyNFT<'info> {
-    #[account(mut)]
-    pub listing: Account<'info, Listing>,
+    /// Delists one of the listings of a queued `ForceDelist` once its timelock is over, returning the
+    /// NFT & rent to the seller (anyone can crank). The action closes with its last listing.
+    /// Transfer hook extra accounts (if the mint has a hook) go in `remaining_accounts`.
+    pub fn force_delist<'info>(ctx: Context<'_, '_, '_, 'info, ForceDelist<'info>>) -> Result<()> {
+        let queued_action = &mut ctx.accounts.queued_action;
+        require!(
+            Clock::get()?.unix_timestamp >= queued_action.eta,
+            ErrorCode::ActionTimelocked
+        );
+        let EmergencyAction::ForceDelist { listings, reason } = &mut queued_action.action else {
+            return err!(ErrorCode::InvalidQueuedAction);
+        };
+        let reason = *reason;
+        let listing_key = ctx.accounts.listing.key();
+        let index = listings
+            .iter()
+            .position(|listing| listing == &listing_key)
+            .ok_or(ErrorCode::InvalidQueuedAction)?;
+        listings.swap_remove(index);
+        if listings.is_empty() {
+            queued_action.close(ctx.accounts.cranker.to_account_info())?;
+        }
 
-    #[account(mut)]
-    pub buyer: Signer<'info>,
+        let listing = &ctx.accounts.listing;
+        require!(listing.is_active, ErrorCode::InactiveListing);
+        return_listed_nft(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.nft_account.to_account_info(),
+            &ctx.accounts.rent_payer.to_account_info(),
+            listing_key,
+            ctx.bumps.vault,
+            ctx.remaining_accounts,
+        )?;
+        listing.close(ctx.accounts.rent_payer.to_account_info())?;
 
-    /// CHECK: Seller account. Validated to match `listing.seller`.
-    #[account(mut)]
-    pub seller: AccountInfo<'info>,
+        emit_activity!(
+            ctx,
+            ListingModerated {
+                listing: listing_key,
+                mint: listing.mint,
+                seller: listing.seller,
+                authority: ctx.accounts.config.authority,
+                reason,
+            }
+        );
+        Ok(())
+    }
 
-    #[account(mut)]
-    pub nft_account: InterfaceAccount<'info, TokenAccount>,
+    /// Pauses the marketplace straight away (config authority only), for incidents.
+    pub fn pause(ctx: Context<UpdateConfig>) -> Result<()> {
+        ctx.accounts.config.paused = true;
+        Ok(())
+    }
 
-    /// PDA vault holding NFT
-    #[account(
-        mut,
-        seeds = [PREFIX.as_bytes(), b"vault", nft_account.mint.as_ref()],
-        bump
-    )]
-    pub vault: InterfaceAccount<'info, TokenAccount>,
+    /// Lifts a pause straight away (config authority only).
+    pub fn unpause(ctx: Context<UpdateConfig>) -> Result<()> {
+        ctx.accounts.config.paused = false;
+        Ok(())
+    }
 
-    pub token_program: Program<'info, Token2022>,
-    pub system_program: Program<'info, System>,
+    /// Approves a verified collection for listing in curated mode (config authority only).
+    pub fn add_allowed_collection(
+        ctx: Context<AddAllowedCollection>,
+        collection: Pubkey,
+    ) -> Result<()> {
+        let allowed_collection = &mut ctx.accounts.allowed_collection;
+        allowed_collection.collection = collection;
+        allowed_collection.bump = ctx.bumps.allowed_collection;
+        allowed_collection.min_price = None;
+        Ok(())
+    }
 
-    /// buyer account
-    #[account(mut)]
-    pub buyer_token_account: InterfaceAccount<'info, TokenAccount>,
+    /// Sets the highest price (lamports) SOL listings can be opened at without the seller confirming it
+    /// through `ListingOptions::confirm_high_price` (config authority only); 0 removes the cap.
+    pub fn set_max_listing_price(ctx: Context<UpdateConfig>, max_listing_price: u64) -> Result<()> {
+        ctx.accounts.config.max_listing_price = max_listing_price;
+        Ok(())
+    }
 
-    /// seller account
-    #[account(mut)]
-    pub seller_token_account: InterfaceAccount<'info, TokenAccount>,
-}
+    /// Sets who `sweep_fees` pays the fee vault out to, & the lamports it must hold first (config
+    /// authority only).
+    pub fn set_fee_sweep(
+        ctx: Context<UpdateConfig>,
+        fee_sweep_recipient: Pubkey,
+        fee_sweep_threshold: u64,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.fee_sweep_recipient = fee_sweep_recipient;
+        config.fee_sweep_threshold = fee_sweep_threshold;
+        Ok(())
+    }
 
-// --------------------------------------------------------------------
-// Data & Errors
-// --------------------------------------------------------------------
-#[account]
-pub struct Listing {
-    pub seller: Pubkey,
-    pub price: u64,
-    pub mint: Pubkey,
-    pub is_active: bool,
-}
+    /// Whitelists the stake pool escrows can earn yield in, & who gets the yield (config authority only).
+    /// A default `yield_stake_pool` disables new stakes; staked escrows can still be unstaked.
+    pub fn set_escrow_yield(
+        ctx: Context<UpdateConfig>,
+        yield_stake_pool_program: Pubkey,
+        yield_stake_pool: Pubkey,
+        yield_to_treasury: bool,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.yield_stake_pool_program = yield_stake_pool_program;
+        config.yield_stake_pool = yield_stake_pool;
+        config.yield_to_treasury = yield_to_treasury;
+        Ok(())
+    }
 
-#[error_code]
-pub enum ErrorCode {
-    #[msg("Listing is not active")]
-    InactiveListing,
-}
+    /// Sets the lowest price (lamports) SOL listings of an approved collection can be listed or
+    /// repriced at (config authority only); `None` lifts it. Approvals made before `min_price` existed
+    /// are grown to the current layout first, `payer` covering the extra rent.
+    pub fn set_collection_min_price(
+        ctx: Context<SetCollectionMinPrice>,
+        min_price: Option<u64>,
+    ) -> Result<()> {
+        let allowed_collection = &ctx.accounts.allowed_collection;
+        {
+            let data = allowed_collection.try_borrow_data()?;
+            require!(
+                data.len() >= 8 && data[..8] == AllowedCollection::DISCRIMINATOR,
+                ErrorCode::CollectionNotAllowed
+            );
+        }
 
-// --------------------------------------------------------------------
-// Constants
-// --------------------------------------------------------------------
-pub mod constant {
-    pub const PREFIX: &str = "MARKETPLACE";
-}
\ No newline at end of file
+        let new_len = 8 + AllowedCollection::INIT_SPACE;
+        if allowed_collection.data_len() < new_len {
+            let rent_due = Rent::get()?
+                .minimum_balance(new_len)
+                .saturating_sub(allowed_collection.lamports());
+            transfer_lamports(
+                &ctx.accounts.payer.to_account_info(),
+                allowed_collection,
+                &ctx.accounts.system_program.to_account_info(),
+                rent_due,
+            )?;
+            allowed_collection.realloc(new_len, true)?;
+        }
+
+        let mut data = allowed_collection.try_borrow_mut_data()?;
+        let mut upgraded = AllowedCollection::try_deserialize(&mut &data[..])?;
+        upgraded.min_price = min_price;
+        upgraded.try_serialize(&mut &mut data[..])?;
+        Ok(())
+    }
+
+    /// Revokes a collection's approval, returning the rent to the config authority.
+    /// Already listed mints stay listed.
+    pub fn remove_allowed_collection(_ctx: Context<RemoveAllowedCollection>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Blocks a mint from being listed or bought, even if already listed (config authority only).
+    pub fn block_mint(ctx: Context<BlockMint>, mint: Pubkey) -> Result<()> {
+        let blocked_mint = &mut ctx.accounts.blocked_mint;
+        blocked_mint.mint = mint;
+        blocked_mint.bump = ctx.bumps.blocked_mint;
+        Ok(())
+    }
+
+    /// Lifts a mint's block, returning the rent to the config authority.
+    pub fn unblock_mint(_ctx: Context<UnblockMint>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Bans a wallet (sanctioned, exploit-linked) from listing, buying & making or accepting offers
+    /// (config authority only). Its existing listings & offers stay until delisted or cancelled.
+    pub fn ban_wallet(ctx: Context<BanWallet>, wallet: Pubkey) -> Result<()> {
+        let banned_wallet = &mut ctx.accounts.banned_wallet;
+        banned_wallet.wallet = wallet;
+        banned_wallet.bump = ctx.bumps.banned_wallet;
+        Ok(())
+    }
+
+    /// Lifts a wallet's ban, returning the rent to the config authority.
+    pub fn unban_wallet(_ctx: Context<UnbanWallet>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Creates the marketplace-wide stats singleton, which listings, auctions & sales then keep updated.
+    pub fn initialize_global_stats(ctx: Context<InitializeGlobalStats>) -> Result<()> {
+        ctx.accounts.global_stats.bump = ctx.bumps.global_stats;
+        Ok(())
+    }
+
+    /// Creates the marketplace's insurance fund, which then collects `insurance_bps` of sale fees.
+    pub fn initialize_insurance_fund(ctx: Context<InitializeInsuranceFund>) -> Result<()> {
+        ctx.accounts.insurance_fund.bump = ctx.bumps.insurance_fund;
+        Ok(())
+    }
+
+    /// Pays an insurance claim out of the fund, e.g. to users hit by a custody incident (config authority
+    /// only). `claim_id` references the claim off-chain. The fund keeps its rent-exempt minimum.
+    pub fn pay_insurance_claim(
+        ctx: Context<PayInsuranceClaim>,
+        amount: u64,
+        claim_id: u64,
+    ) -> Result<()> {
+        let fund = &mut ctx.accounts.insurance_fund;
+        let fund_info = fund.to_account_info();
+        let reserve = Rent::get()?.minimum_balance(fund_info.data_len());
+        require!(
+            fund_info.lamports().saturating_sub(reserve) >= amount,
+            ErrorCode::InsufficientInsuranceFunds
+        );
+        transfer_lamports_from_pda(&fund_info, &ctx.accounts.recipient, amount)?;
+        fund.total_claimed = fund
+            .total_claimed
+            .checked_add(amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        emit!(InsuranceClaimPaid {
+            recipient: ctx.accounts.recipient.key(),
+            amount,
+            claim_id,
+            balance: fund_info.lamports().saturating_sub(reserve),
+        });
+        Ok(())
+    }
+
+    /// Pays the fee vault's lamports over rent out to `config.fee_sweep_recipient`, once they reach
+    /// `fee_sweep_threshold`. The vault collects marketplace fees by pointing `config.treasury` at it,
+    /// batching them into one transfer; SPL fees land in its token accounts, of which the one passed
+    /// is swept in full. Permissionless.
+    pub fn sweep_fees(ctx: Context<SweepFees>) -> Result<()> {
+        let vault = ctx.accounts.fee_vault.to_account_info();
+        let vault_seeds: &[&[u8]] = &[PREFIX.as_bytes(), b"fee_vault", &[ctx.bumps.fee_vault]];
+
+        let mut lamports = vault
+            .lamports()
+            .saturating_sub(Rent::get()?.minimum_balance(0));
+        if lamports < ctx.accounts.config.fee_sweep_threshold.max(1) {
+            lamports = 0;
+        } else {
+            invoke_signed(
+                &system_instruction::transfer(vault.key, ctx.accounts.recipient.key, lamports),
+                &[
+                    vault.clone(),
+                    ctx.accounts.recipient.to_account_info(),
+                    ctx.accounts.system_program.to_account_info(),
+                ],
+                &[vault_seeds],
+            )?;
+        }
+
+        let tokens = match (
+            &ctx.accounts.payment_mint,
+            &ctx.accounts.vault_payment_account,
+            &ctx.accounts.recipient_payment_account,
+            &ctx.accounts.payment_token_program,
+        ) {
+            (
+                Some(payment_mint),
+                Some(vault_payment_account),
+                Some(recipient_payment_account),
+                Some(token_program),
+            ) => {
+                let amount = vault_payment_account.amount;
+                transfer_tokens(
+                    &token_program.to_account_info(),
+                    &vault_payment_account.to_account_info(),
+                    payment_mint,
+                    &recipient_payment_account.to_account_info(),
+                    &vault,
+                    amount,
+                    &[vault_seeds],
+                )?;
+                amount
+            }
+            (None, None, None, None) => 0,
+            _ => return err!(ErrorCode::MissingPaymentAccounts),
+        };
+        require!(
+            lamports > 0 || tokens > 0,
+            ErrorCode::FeeSweepBelowThreshold
+        );
+
+        emit!(FeesSwept {
+            recipient: ctx.accounts.recipient.key(),
+            lamports,
+            payment_mint: ctx.accounts.payment_mint.as_ref().map(|mint| mint.key()),
+            tokens,
+            cranker: ctx.accounts.cranker.key(),
+        });
+        Ok(())
+    }
+
+    /// Swaps lamports of the buyback vault for the marketplace token through `config.buyback_amm_program`
+    /// & burns them. Permissionless: anyone cranks it once `buyback_interval` has passed. The vault is funded
+    /// by pointing `config.treasury` at it, or by plain transfers. `swap_data` & the remaining accounts
+    /// make up the AMM's swap instruction, the vault signing for its token accounts; at most
+    /// `max_buyback_lamports` of WSOL may be spent, at no less than `min_buyback_rate`.
+    pub fn execute_buyback<'info>(
+        ctx: Context<'_, '_, '_, 'info, ExecuteBuyback<'info>>,
+        swap_data: Vec<u8>,
+    ) -> Result<()> {
+        let config = &ctx.accounts.config;
+        require!(
+            config.buyback_mint != Pubkey::default(),
+            ErrorCode::BuybackDisabled
+        );
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            now >= config
+                .last_buyback_ts
+                .saturating_add(config.buyback_interval),
+            ErrorCode::BuybackTooSoon
+        );
+        let max_buyback_lamports = config.max_buyback_lamports;
+        let min_buyback_rate = config.min_buyback_rate;
+
+        let vault = ctx.accounts.buyback_vault.to_account_info();
+        let vault_seeds: &[&[u8]] = &[
+            PREFIX.as_bytes(),
+            b"buyback_vault",
+            &[ctx.bumps.buyback_vault],
+        ];
+
+        // Wrap what the vault holds over rent, up to the cap.
+        let reserve = Rent::get()?.minimum_balance(0);
+        let amount = vault
+            .lamports()
+            .saturating_sub(reserve)
+            .min(max_buyback_lamports);
+        let wsol_account = ctx.accounts.vault_wsol_account.to_account_info();
+        if amount > 0 {
+            invoke_signed(
+                &system_instruction::transfer(vault.key, wsol_account.key, amount),
+                &[
+                    vault.clone(),
+                    wsol_account.clone(),
+                    ctx.accounts.system_program.to_account_info(),
+                ],
+                &[vault_seeds],
+            )?;
+        }
+        anchor_spl::token::sync_native(CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            anchor_spl::token::SyncNative {
+                account: wsol_account,
+            },
+        ))?;
+        ctx.accounts.vault_wsol_account.reload()?;
+
+        let wsol_before = ctx.accounts.vault_wsol_account.amount;
+        let tokens_before = ctx.accounts.vault_token_account.amount;
+        let vault_lamports = vault.lamports();
+
+        let amm_program = ctx.accounts.amm_program.key();
+        let swap_ix = anchor_lang::solana_program::instruction::Instruction {
+            program_id: amm_program,
+            accounts: ctx
+                .remaining_accounts
+                .iter()
+                .map(|account| AccountMeta {
+                    pubkey: account.key(),
+                    is_signer: account.is_signer || account.key() == vault.key(),
+                    is_writable: account.is_writable,
+                })
+                .collect(),
+            data: swap_data,
+        };
+        invoke_signed(&swap_ix, ctx.remaining_accounts, &[vault_seeds])?;
+
+        // The AMM may only have traded WSOL for tokens, within bounds.
+        ctx.accounts.vault_wsol_account.reload()?;
+        ctx.accounts.vault_token_account.reload()?;
+        let wsol_account = &ctx.accounts.vault_wsol_account;
+        let token_account = &ctx.accounts.vault_token_account;
+        require!(
+            vault.lamports() == vault_lamports,
+            ErrorCode::InvalidBuyback
+        );
+        for account in [wsol_account, token_account] {
+            require!(
+                account.owner == vault.key()
+                    && account.delegate.is_none()
+                    && account.close_authority.is_none(),
+                ErrorCode::InvalidBuyback
+            );
+        }
+        let sol_spent = wsol_before
+            .checked_sub(wsol_account.amount)
+            .ok_or(ErrorCode::InvalidBuyback)?;
+        let tokens_bought = token_account
+            .amount
+            .checked_sub(tokens_before)
+            .ok_or(ErrorCode::InvalidBuyback)?;
+        require!(
+            sol_spent > 0 && sol_spent <= max_buyback_lamports,
+            ErrorCode::InvalidBuyback
+        );
+        require!(
+            tokens_bought as u128 * LAMPORTS_PER_SOL as u128
+                >= sol_spent as u128 * min_buyback_rate as u128,
+            ErrorCode::BuybackSlippage
+        );
+
+        let tokens_burned = token_account.amount;
+        token_interface::burn(
+            CpiContext::new_with_signer(
+                ctx.accounts.buyback_token_program.to_account_info(),
+                token_interface::Burn {
+                    mint: ctx.accounts.buyback_mint.to_account_info(),
+                    from: ctx.accounts.vault_token_account.to_account_info(),
+                    authority: vault,
+                },
+                &[vault_seeds],
+            ),
+            tokens_burned,
+        )?;
+
+        ctx.accounts.config.last_buyback_ts = now;
+        emit!(BuybackExecuted {
+            mint: ctx.accounts.buyback_mint.key(),
+            sol_spent,
+            tokens_burned,
+            cranker: ctx.accounts.cranker.key(),
+        });
+        Ok(())
+    }
+
+    /// Creates the stats account of a collection, updated by every following `buy_nft` of its mints.
+    pub fn create_collection_stats(
+        ctx: Context<CreateCollectionStats>,
+        collection: Pubkey,
+    ) -> Result<()> {
+        let collection_stats = &mut ctx.accounts.collection_stats;
+        collection_stats.collection = collection;
+        collection_stats.bump = ctx.bumps.collection_stats;
+        Ok(())
+    }
+
+    /// Caches the mint's creator shares & verified collection from its metadata, for `buy_nft` to read
+    /// instead of the metadata. Anyone can call it, again to refresh the cache after a metadata update.
+    pub fn cache_royalties(ctx: Context<CacheRoyalties>) -> Result<()> {
+        let mint = ctx.accounts.mint.key();
+        let royalty_cache = &mut ctx.accounts.royalty_cache;
+        royalty_cache.mint = mint;
+        royalty_cache.creators = creator_royalty_splits(&mint, &ctx.accounts.metadata)?;
+        royalty_cache.collection = verified_collection(&mint, &ctx.accounts.metadata)?;
+        royalty_cache.cached_at = Clock::get()?.unix_timestamp;
+        royalty_cache.bump = ctx.bumps.royalty_cache;
+
+        emit!(RoyaltiesCached {
+            mint,
+            creators: royalty_cache.creators.clone(),
+            collection: royalty_cache.collection,
+        });
+        Ok(())
+    }
+
+    /// Points the mint's royalty recipient at its first verified Metaplex creator, for mints whose
+    /// provenance recorded their first lister instead. Anyone can call it.
+    pub fn sync_royalty_creator(ctx: Context<SyncRoyaltyCreator>) -> Result<()> {
+        let mint = ctx.accounts.mint.key();
+        let creator =
+            verified_creator(&mint, &ctx.accounts.metadata)?.ok_or(ErrorCode::InvalidCreator)?;
+        ctx.accounts.provenance.creator = creator;
+
+        emit!(RoyaltyCreatorSynced { mint, creator });
+        Ok(())
+    }
+
+    /// Opts a wallet into on-chain trading stats, updated by every following `buy_nft` it's part of.
+    pub fn create_trader_profile(ctx: Context<CreateTraderProfile>) -> Result<()> {
+        let profile = &mut ctx.accounts.profile;
+        profile.trader = ctx.accounts.trader.key();
+        profile.bump = ctx.bumps.profile;
+        Ok(())
+    }
+
+    /// Opens a branded storefront with its own fee & treasury (config authority only).
+    pub fn create_storefront(
+        ctx: Context<CreateStorefront>,
+        authority: Pubkey,
+        treasury: Pubkey,
+        fee_bps: u16,
+    ) -> Result<()> {
+        validate_storefront_fee(&ctx.accounts.config, fee_bps)?;
+
+        let storefront = &mut ctx.accounts.storefront;
+        storefront.authority = authority;
+        storefront.treasury = treasury;
+        storefront.fee_bps = fee_bps;
+        storefront.allowed_collections = Vec::new();
+
+        Ok(())
+    }
+
+    /// Changes a storefront's fee & treasury (storefront authority only).
+    pub fn update_storefront(
+        ctx: Context<UpdateStorefront>,
+        treasury: Pubkey,
+        fee_bps: u16,
+    ) -> Result<()> {
+        validate_storefront_fee(&ctx.accounts.config, fee_bps)?;
+
+        let storefront = &mut ctx.accounts.storefront;
+        storefront.treasury = treasury;
+        storefront.fee_bps = fee_bps;
+
+        Ok(())
+    }
+
+    /// Adds a verified collection to the storefront's curation list (storefront authority only).
+    pub fn add_storefront_collection(
+        ctx: Context<UpdateStorefront>,
+        collection: Pubkey,
+    ) -> Result<()> {
+        let storefront = &mut ctx.accounts.storefront;
+        if storefront.allowed_collections.contains(&collection) {
+            return Ok(());
+        }
+        require!(
+            storefront.allowed_collections.len() < MAX_STOREFRONT_COLLECTIONS,
+            ErrorCode::StorefrontCollectionsFull
+        );
+        storefront.allowed_collections.push(collection);
+        Ok(())
+    }
+
+    /// Removes a collection from the storefront's curation list (storefront authority only).
+    pub fn remove_storefront_collection(
+        ctx: Context<UpdateStorefront>,
+        collection: Pubkey,
+    ) -> Result<()> {
+        ctx.accounts
+            .storefront
+            .allowed_collections
+            .retain(|allowed| *allowed != collection);
+        Ok(())
+    }
+
+    /// Creates a new listing, transferring NFT from usr --> vault (PDA).
+    /// Transfer hook extra accounts (if the mint has a hook) go in `remaining_accounts`.
+    pub fn list_nft<'info>(
+        ctx: Context<'_, '_, '_, 'info, ListNFT<'info>>,
+        price: u64,
+    ) -> Result<()> {
+        ctx.accounts
+            .escrow_nft(ctx.remaining_accounts, ctx.bumps.provenance)?;
+
+        // Init listing account data.
+        ctx.accounts
+            .open_listing(price, None, &ListingOptions::default())?;
+
+        Ok(())
+    }
+
+    /// Same as `list_nft`, with the optional listing fields (category, metadata hash, expiry, payment mint,
+    /// Dutch auction). With a `payment_mint`, `price` is in that token's base units instead of SOL.
+    pub fn list_nft_v2<'info>(
+        ctx: Context<'_, '_, '_, 'info, ListNFT<'info>>,
+        price: u64,
+        options: ListingOptions,
+    ) -> Result<()> {
+        options.validate(price)?;
+        ctx.accounts
+            .escrow_nft(ctx.remaining_accounts, ctx.bumps.provenance)?;
+
+        ctx.accounts.open_listing(price, None, &options)?;
+
+        Ok(())
+    }
+
+    /// Idempotent `list_nft_v2`, for retried or double-submitted listings: lists the NFT if `listing`
+    /// doesn't exist yet, else re-prices the seller's active listing with `price` & `options` in place.
+    /// An updated listing keeps its collection, storefront, royalty splits & USD price, & drops its
+    /// installment terms; `registry_page` is only needed to list.
+    pub fn list_or_update_nft<'info>(
+        ctx: Context<'_, '_, '_, 'info, ListNFT<'info>>,
+        price: u64,
+        options: ListingOptions,
+    ) -> Result<()> {
+        options.validate(price)?;
+        if ctx.accounts.is_new_listing() {
+            ctx.accounts
+                .escrow_nft(ctx.remaining_accounts, ctx.bumps.provenance)?;
+            return ctx.accounts.open_listing(price, None, &options);
+        }
+        ctx.accounts.update_listing(price, &options)?;
+
+        let listing = &ctx.accounts.listing;
+        emit_activity!(
+            ctx,
+            ListingUpdated {
+                listing: listing.key(),
+                mint: listing.mint,
+                seller: listing.seller,
+                price,
+                payment_mint: listing.payment_mint,
+            }
+        );
+        Ok(())
+    }
+
+    /// Same as `list_nft`, but priced in USD (6 decimals); buyers pay the SOL equivalent at the Pyth rate.
+    pub fn list_nft_usd<'info>(
+        ctx: Context<'_, '_, '_, 'info, ListNFT<'info>>,
+        price_usd: u64,
+    ) -> Result<()> {
+        ctx.accounts
+            .escrow_nft(ctx.remaining_accounts, ctx.bumps.provenance)?;
+
+        ctx.accounts
+            .open_listing(0, Some(price_usd), &ListingOptions::default())?;
+
+        Ok(())
+    }
+
+    /// Grows a listing created with an older, shorter layout to the current one (seller or config authority).
+    /// Newly added fields are optional, so the zero-filled space reads as `None`.
+    pub fn reallocate_listing(ctx: Context<ReallocateListing>) -> Result<()> {
+        let listing = &ctx.accounts.listing;
+        let authority = ctx.accounts.authority.key();
+        {
+            let data = listing.try_borrow_data()?;
+            require!(
+                data.len() >= 8 + 32 && data[..8] == Listing::DISCRIMINATOR,
+                ErrorCode::InvalidListingAccount
+            );
+            // `seller` is the first field.
+            let seller = Pubkey::try_from(&data[8..8 + 32]).unwrap();
+            require!(
+                authority == seller || authority == ctx.accounts.config.authority,
+                ErrorCode::Unauthorized
+            );
+        }
+
+        migrate_listing_layout(
+            listing,
+            &ctx.accounts.authority.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+        )
+    }
+
+    /// Moves the NFT of an active listing from the older mint-only vault to the listing's own vault
+    /// (seller only), closing the old vault. Transfer hook extra accounts go in `remaining_accounts`.
+    pub fn migrate_listing_vault<'info>(
+        ctx: Context<'_, '_, '_, 'info, MigrateListingVault<'info>>,
+    ) -> Result<()> {
+        require!(ctx.accounts.listing.is_active, ErrorCode::InactiveListing);
+
+        migrate_legacy_vault(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.legacy_vault.to_account_info(),
+            ctx.bumps.legacy_vault,
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.seller.to_account_info(),
+            ctx.remaining_accounts,
+        )
+    }
+
+    /// Same as `migrate_listing_vault`, by the config authority (who pays the new vault) on the seller's
+    /// behalf, to move the whole market's listings over without them. The old vault's rent goes to
+    /// the seller.
+    pub fn migrate_vault<'info>(
+        ctx: Context<'_, '_, '_, 'info, MigrateVault<'info>>,
+    ) -> Result<()> {
+        require!(ctx.accounts.listing.is_active, ErrorCode::InactiveListing);
+
+        migrate_legacy_vault(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.legacy_vault.to_account_info(),
+            ctx.bumps.legacy_vault,
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.seller,
+            ctx.remaining_accounts,
+        )?;
+
+        emit!(VaultMigrated {
+            listing: ctx.accounts.listing.key(),
+            mint: ctx.accounts.mint.key(),
+            legacy_vault: ctx.accounts.legacy_vault.key(),
+            vault: ctx.accounts.vault.key(),
+        });
+        Ok(())
+    }
+
+    /// Creates registry page `page` (anyone can, paying its rent); pages are used in order as they fill up.
+    pub fn create_registry_page(ctx: Context<CreateRegistryPage>, page: u32) -> Result<()> {
+        let mut registry_page = ctx.accounts.registry_page.load_init()?;
+        registry_page.page = page;
+        Ok(())
+    }
+
+    /// Drops entries of closed or inactive listings from a registry page (anyone can crank).
+    /// The listings to check go in `remaining_accounts`.
+    pub fn prune_registry(ctx: Context<PruneRegistry>) -> Result<()> {
+        let mut registry_page = ctx.accounts.registry_page.load_mut()?;
+        for listing in ctx.remaining_accounts {
+            let is_active = listing.owner == &crate::ID
+                && Listing::try_deserialize(&mut &listing.try_borrow_data()?[..])
+                    .is_ok_and(|listing| listing.is_active);
+            if !is_active {
+                registry_page.remove(&listing.key());
+            }
+        }
+        Ok(())
+    }
+
+    /// Closes terminal accounts, passed in `remaining_accounts`:
+    /// - sold listings without unclaimed rewards, as (listing, rent payer, listing vault, installment plan):
+    ///   the listing's vault must be closed or empty & its installment plan closed, so nothing's still
+    ///   escrowed against it;
+    /// - auctions settled or cancelled `SWEEP_GRACE_PERIOD` past their settlement time (bids left on them
+    ///   are refunded in full by `cancel_bid`), as (auction, seller).
+    ///
+    /// Anyone can crank; the cranker gets the config's tip out of each account's rent, the seller the rest.
+    pub fn sweep_closed_state<'info>(
+        ctx: Context<'_, '_, '_, 'info, SweepClosedState<'info>>,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let mut accounts = ctx.remaining_accounts.iter();
+        while let Some(account) = accounts.next() {
+            let seller = accounts.next().ok_or(ErrorCode::NotSweepable)?;
+            require_keys_eq!(*account.owner, crate::ID, ErrorCode::NotSweepable);
+            let expected_seller = {
+                let data = account.try_borrow_data()?;
+                if let Ok(listing) = Listing::try_deserialize(&mut &data[..]) {
+                    require!(
+                        !listing.is_active
+                            && listing.reward_points.unwrap_or_default() == 0
+                            && listing.loan.is_none(),
+                        ErrorCode::NotSweepable
+                    );
+                    let (Some(vault), Some(plan)) = (accounts.next(), accounts.next()) else {
+                        return err!(ErrorCode::NotSweepable);
+                    };
+                    require_listing_released(&listing.mint, &account.key(), vault, plan)?;
+                    listing.rent_recipient()
+                } else {
+                    let auction = Auction::try_deserialize(&mut &data[..])
+                        .map_err(|_| error!(ErrorCode::NotSweepable))?;
+                    require!(
+                        auction.status != AuctionStatus::Active
+                            && now >= auction.settle_ts().saturating_add(SWEEP_GRACE_PERIOD),
+                        ErrorCode::NotSweepable
+                    );
+                    auction.seller
+                }
+            };
+            require_keys_eq!(seller.key(), expected_seller, ErrorCode::NotSweepable);
+
+            let crank_tip = ctx
+                .accounts
+                .config
+                .crank_tip_lamports
+                .min(account.lamports());
+            transfer_lamports_from_pda(account, &ctx.accounts.cranker, crank_tip)?;
+            let rent = account.lamports();
+            transfer_lamports_from_pda(account, seller, rent)?;
+            account.assign(&System::id());
+            account.realloc(0, false)?;
+
+            emit!(StateSwept {
+                account: account.key(),
+                seller: seller.key(),
+                cranker: ctx.accounts.cranker.key(),
+                crank_tip,
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns an NFT left in its mint's shared vault (auctions, raffles & rentals) to whoever last
+    /// escrowed it, once the account it was escrowed for is closed, & closes the vault (depositor only).
+    /// Transfer hook extra accounts (if the mint has a hook) go in `remaining_accounts`.
+    pub fn recover_vault<'info>(
+        ctx: Context<'_, '_, '_, 'info, RecoverVault<'info>>,
+    ) -> Result<()> {
+        let mint_key = ctx.accounts.mint.key();
+        let seeds = &[
+            PREFIX.as_bytes(),
+            b"vault",
+            mint_key.as_ref(),
+            &[ctx.bumps.vault],
+        ];
+        let signer = &[&seeds[..]];
+
+        let amount = ctx.accounts.vault.amount;
+        if amount > 0 {
+            transfer_nft(
+                &ctx.accounts.token_program.to_account_info(),
+                &ctx.accounts.vault.to_account_info(),
+                &ctx.accounts.mint.to_account_info(),
+                &ctx.accounts.depositor_nft_account.to_account_info(),
+                &ctx.accounts.vault.to_account_info(),
+                ctx.remaining_accounts,
+                signer,
+            )?;
+        }
+        close_token_account(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.depositor.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            signer,
+        )?;
+
+        emit!(VaultRecovered {
+            vault: ctx.accounts.vault.key(),
+            mint: mint_key,
+            depositor: ctx.accounts.depositor.key(),
+            amount,
+        });
+        Ok(())
+    }
+
+    /// Same as `reallocate_listing`, callable by anyone (who pays the extra rent); stamps `LISTING_VERSION`.
+    pub fn migrate_listing(ctx: Context<MigrateListing>) -> Result<()> {
+        migrate_listing_layout(
+            &ctx.accounts.listing,
+            &ctx.accounts.payer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+        )
+    }
+
+    /// Unwinds an expired listing: NFT vault (PDA) --> seller & listing closed.
+    /// Callable by anyone; the cranker gets the config's tip out of the listing's rent refund.
+    /// Transfer hook extra accounts (if the mint has a hook) go in `remaining_accounts`.
+    pub fn close_expired_listing<'info>(
+        ctx: Context<'_, '_, '_, 'info, CloseExpiredListing<'info>>,
+    ) -> Result<()> {
+        let listing = &ctx.accounts.listing;
+        require!(listing.is_active, ErrorCode::InactiveListing);
+        require!(
+            listing.is_expired(Clock::get()?.unix_timestamp),
+            ErrorCode::ListingNotExpired
+        );
+
+        let listing_key = listing.key();
+        let seeds = &[
+            PREFIX.as_bytes(),
+            b"vault",
+            listing.mint.as_ref(),
+            listing_key.as_ref(),
+            &[ctx.bumps.vault],
+        ];
+        let signer = &[&seeds[..]];
+
+        // Transfer back NFT vault --> seller.
+        transfer_nft(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.nft_account.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            ctx.remaining_accounts,
+            signer,
+        )?;
+
+        close_token_account(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.rent_payer.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            signer,
+        )?;
+
+        // Tip the cranker, close the listing & return the remaining rent to seller.
+        let listing_info = listing.to_account_info();
+        let crank_tip = ctx
+            .accounts
+            .config
+            .crank_tip_lamports
+            .min(listing_info.lamports());
+        transfer_lamports_from_pda(&listing_info, &ctx.accounts.cranker, crank_tip)?;
+        listing.close(ctx.accounts.seller.to_account_info())?;
+
+        emit_activity!(
+            ctx,
+            ListingExpired {
+                listing: listing.key(),
+                mint: listing.mint,
+                seller: listing.seller,
+                cranker: ctx.accounts.cranker.key(),
+                crank_tip,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Queues taking down a listing (config authority only), as a one-listing `ForceDelist` action:
+    /// once `config.action_timelock` passed, `force_delist` returns the NFT to the seller & closes it.
+    pub fn admin_delist(
+        ctx: Context<AdminDelist>,
+        nonce: u64,
+        reason: ModerationReason,
+    ) -> Result<()> {
+        require!(ctx.accounts.listing.is_active, ErrorCode::InactiveListing);
+        let eta = Clock::get()?
+            .unix_timestamp
+            .checked_add(ctx.accounts.config.action_timelock)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        let queued_action = &mut ctx.accounts.queued_action;
+        queued_action.nonce = nonce;
+        queued_action.action = EmergencyAction::ForceDelist {
+            listings: vec![ctx.accounts.listing.key()],
+            reason,
+        };
+        queued_action.eta = eta;
+        queued_action.bump = ctx.bumps.queued_action;
+
+        emit!(ActionQueued {
+            queued_action: queued_action.key(),
+            action: queued_action.action.clone(),
+            eta,
+        });
+        Ok(())
+    }
+
+    /// Remove NFT by transferring it back: vault (PDA) --> seller.
+    /// Transfer hook extra accounts (if the mint has a hook) go in `remaining_accounts`.
+    pub fn remove_listed_nft<'info>(
+        ctx: Context<'_, '_, '_, 'info, RemoveListedNFT<'info>>,
+    ) -> Result<()> {
+        let listing = &ctx.accounts.listing;
+        listing.require_delistable()?;
+        require_seller_authority(
+            &listing.seller,
+            listing.seller_authority,
+            ctx.accounts.authority.key,
+        )?;
+
+        // Prep PDA seeds for authority sig
+        let listing_key = ctx.accounts.listing.key();
+        let seeds = &[
+            PREFIX.as_bytes(),
+            b"vault",
+            ctx.accounts.nft_account.mint.as_ref(),
+            listing_key.as_ref(),
+            &[ctx.bumps.vault],
+        ];
+        let signer = &[&seeds[..]];
+
+        // Transfer back NFT vault --> seller.
+        transfer_nft(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.nft_account.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            ctx.remaining_accounts,
+            signer,
+        )?;
+
+        close_token_account(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.rent_payer.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            signer,
+        )?;
+
+        unregister_listing(&ctx.accounts.listing, ctx.accounts.registry_page.as_ref())?;
+
+        // Close the listing, return rent lamports to whoever paid them.
+        ctx.accounts
+            .listing
+            .close(ctx.accounts.rent_payer.to_account_info())?;
+
+        Ok(())
+    }
+
+    /// Unwinds several of the signer's active listings at once, like `remove_listed_nft`. Each one goes in
+    /// `remaining_accounts` as (listing, vault, seller's NFT account, mint, rent payer, registry page),
+    /// the registry page being any account for unregistered listings. Mints with a transfer hook need
+    /// `remove_listed_nft`, which takes the hook's extra accounts.
+    pub fn delist_batch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, DelistBatch<'info>>,
+    ) -> Result<()> {
+        let sets = ctx.remaining_accounts.chunks_exact(6);
+        require!(
+            !ctx.remaining_accounts.is_empty() && sets.remainder().is_empty(),
+            ErrorCode::InvalidDelistBatch
+        );
+        let seller = ctx.accounts.seller.key();
+        let token_program = ctx.accounts.token_program.to_account_info();
+        for set in sets {
+            let [listing, vault, nft_account, mint, rent_payer, registry_page] = set else {
+                unreachable!()
+            };
+            let listing = Account::<Listing>::try_from(listing)?;
+            require_keys_eq!(listing.seller, seller, ErrorCode::Unauthorized);
+            listing.require_delistable()?;
+            require_keys_eq!(mint.key(), listing.mint, ErrorCode::InvalidDelistBatch);
+            require_keys_eq!(
+                rent_payer.key(),
+                listing.rent_recipient(),
+                ErrorCode::InvalidDelistBatch
+            );
+            let seller_nft_account = InterfaceAccount::<TokenAccount>::try_from(nft_account)?;
+            require!(
+                seller_nft_account.owner == seller && seller_nft_account.mint == listing.mint,
+                ErrorCode::InvalidDelistBatch
+            );
+
+            let listing_key = listing.key();
+            let (vault_key, vault_bump) = Pubkey::find_program_address(
+                &[
+                    PREFIX.as_bytes(),
+                    b"vault",
+                    listing.mint.as_ref(),
+                    listing_key.as_ref(),
+                ],
+                &crate::ID,
+            );
+            require_keys_eq!(vault.key(), vault_key, ErrorCode::InvalidDelistBatch);
+            let seeds = &[
+                PREFIX.as_bytes(),
+                b"vault",
+                listing.mint.as_ref(),
+                listing_key.as_ref(),
+                &[vault_bump],
+            ];
+            let signer = &[&seeds[..]];
+
+            transfer_nft(&token_program, vault, mint, nft_account, vault, &[], signer)?;
+            close_token_account(&token_program, vault, rent_payer, vault, signer)?;
+
+            if listing.registry_page.is_some() {
+                let registry_page = AccountLoader::<ListingRegistryPage>::try_from(registry_page)?;
+                unregister_listing(&listing, Some(&registry_page))?;
+            }
+            listing.close(rent_payer.clone())?;
+        }
+        Ok(())
+    }
+
+    /// Hands an active listing to `new_seller` (seller only), who then gets the proceeds & can delist;
+    /// the NFT stays in escrow. Clears the listing's seller authority.
+    pub fn transfer_listing_authority(
+        ctx: Context<TransferListingAuthority>,
+        new_seller: Pubkey,
+    ) -> Result<()> {
+        let listing = &mut ctx.accounts.listing;
+        require!(listing.is_active, ErrorCode::InactiveListing);
+        require!(
+            new_seller != Pubkey::default() && new_seller != listing.seller,
+            ErrorCode::InvalidNewSeller
+        );
+
+        emit!(ListingAuthorityTransferred {
+            listing: listing.key(),
+            mint: listing.mint,
+            old_seller: listing.seller,
+            new_seller,
+        });
+        listing.seller = new_seller;
+        // The old seller's delegated authority doesn't carry over.
+        listing.seller_authority = None;
+        Ok(())
+    }
+
+    /// Switches an active listing's currency & price in one go (seller only): `payment_mint` as in
+    /// `list_nft_v2`, `None` for SOL. A USD price is replaced by the fixed `price`. Not for Dutch auctions.
+    pub fn update_payment_terms(
+        ctx: Context<UpdatePaymentTerms>,
+        payment_mint: Option<Pubkey>,
+        price: u64,
+    ) -> Result<()> {
+        let listing = &mut ctx.accounts.listing;
+        require!(listing.is_active, ErrorCode::InactiveListing);
+        // Vesting, installments & escrowed sales settle in lamports only.
+        require!(
+            payment_mint.is_none()
+                || (listing.vesting_duration.is_none()
+                    && listing.installment_terms.is_none()
+                    && listing.receipt_window.is_none()),
+            ErrorCode::InvalidPaymentTerms
+        );
+        require!(
+            listing.dutch_auction.is_none(),
+            ErrorCode::InvalidPaymentTerms
+        );
+
+        listing.payment_mint = payment_mint;
+        listing.price = price;
+        listing.price_usd = None;
+        if ctx.accounts.config.curated_collections
+            && !ctx.accounts.allowed_collection.data_is_empty()
+        {
+            let data = ctx.accounts.allowed_collection.try_borrow_data()?;
+            let allowed_collection = AllowedCollection::try_deserialize(&mut &data[..])?;
+            require_min_price(Some(&allowed_collection), listing)?;
+        }
+
+        emit!(PaymentTermsUpdated {
+            listing: listing.key(),
+            mint: listing.mint,
+            payment_mint,
+            price,
+        });
+        Ok(())
+    }
+
+    /// Schedules a timed discount on an active listing (seller only), applied automatically to purchases
+    /// during its window; `None` removes it.
+    pub fn set_flash_sale(ctx: Context<SetFlashSale>, flash_sale: Option<FlashSale>) -> Result<()> {
+        let listing = &mut ctx.accounts.listing;
+        require!(listing.is_active, ErrorCode::InactiveListing);
+        if let Some(flash_sale) = &flash_sale {
+            flash_sale.validate()?;
+        }
+        listing.flash_sale = flash_sale;
+        Ok(())
+    }
+
+    /// Quotes the purchase of a listing (price, fees & royalty) without executing it.
+    pub fn quote_buy(ctx: Context<QuoteBuy>) -> Result<SaleQuote> {
+        let price = ctx
+            .accounts
+            .listing
+            .sale_price(&ctx.accounts.config, ctx.accounts.price_update.as_ref())?;
+        let is_primary = ctx.accounts.provenance.is_primary();
+        let trader_volume = ctx
+            .accounts
+            .trader_stats
+            .as_ref()
+            .map_or(0, |stats| stats.volume_lamports);
+        let is_member = ctx
+            .accounts
+            .config
+            .is_member(ctx.accounts.membership_account.as_deref());
+        let schedule = listing_fee_schedule(
+            &ctx.accounts.config,
+            &ctx.accounts.listing,
+            ctx.accounts.storefront.as_ref(),
+            is_primary,
+            trader_volume,
+            is_member,
+        )?;
+        let mut quote = SaleQuote::new(price, is_primary, &schedule)?;
+        quote.apply_maker_rebate(&ctx.accounts.config)?;
+        quote.apply_charity_split(ctx.accounts.listing.charity_split)?;
+        if ctx.accounts.listing.payment_mint.is_none() {
+            quote.apply_withholding(&ctx.accounts.config)?;
+            quote.apply_insurance(&ctx.accounts.config)?;
+        }
+        Ok(quote)
+    }
+
+    /// Buy NFT = SOL --> seller (+ fee --> treasury, royalty --> creator) & NFT --> buyer.
+    /// Listings with payees take them first in `remaining_accounts`, in the listing's order (wallets, or
+    /// their payment token accounts for SPL-priced listings), then the creators of its royalty splits
+    /// (same order & form, `creator` being unused); transfer hook extra accounts follow.
+    /// `allowlist_proof` is the buyer's merkle proof for allowlisted listings, empty otherwise.
+    /// `price_leg` picks one of the listing's `alt_prices` to pay, its own price when `None`.
+    /// The price is settled on-chain (Dutch auctions, USD listings) & only that is pulled from the buyer;
+    /// `max_price` (in the leg's payment units) bounds it instead of the buyer sending a buffer.
+    pub fn buy_nft<'info>(
+        ctx: Context<'_, '_, '_, 'info, BuyNFT<'info>>,
+        vault_bump: u8,
+        allowlist_proof: Vec<[u8; 32]>,
+        price_leg: Option<u8>,
+        max_price: Option<u64>,
+    ) -> Result<()> {
+        let listing = &mut ctx.accounts.listing;
+        listing.select_price_leg(price_leg)?;
+
+        // Ensure the listing is still active.
+        require!(listing.is_active, ErrorCode::InactiveListing);
+        require!(!ctx.accounts.config.paused, ErrorCode::MarketplacePaused);
+        let now = Clock::get()?.unix_timestamp;
+        require!(!listing.is_expired(now), ErrorCode::ListingHasExpired);
+        require!(listing.has_started(now), ErrorCode::ListingNotStarted);
+        require_not_blocked(&ctx.accounts.blocked_mint)?;
+        require_not_banned(
+            &ctx.accounts.buyer_ban,
+            ctx.accounts.buyer.key(),
+            TradeAction::Buy,
+        )?;
+        require_not_banned(&ctx.accounts.seller_ban, listing.seller, TradeAction::Sell)?;
+        if let Some(gate_mint) = listing.gate_mint {
+            let buyer = ctx.accounts.buyer.key();
+            require!(
+                ctx.accounts
+                    .gate_token_account
+                    .as_ref()
+                    .is_some_and(|account| {
+                        account.mint == gate_mint && account.owner == buyer && account.amount >= 1
+                    }),
+                ErrorCode::GateTokenRequired
+            );
+        }
+        if let Some(allowlist_root) = &listing.allowlist_root {
+            require!(
+                verify_merkle_proof(&ctx.accounts.buyer.key(), &allowlist_proof, allowlist_root),
+                ErrorCode::BuyerNotAllowlisted
+            );
+        }
+        if let Some(attestation) = &listing.attestation {
+            let credential = ctx
+                .accounts
+                .attestation
+                .as_ref()
+                .ok_or(ErrorCode::AttestationRequired)?;
+            verify_attestation(attestation, credential, &ctx.accounts.buyer.key(), now)?;
+        }
+        if let Some(purchase_limit) = listing.purchase_limit {
+            let wallet_purchases = ctx
+                .accounts
+                .wallet_purchases
+                .as_mut()
+                .ok_or(ErrorCode::MissingWalletPurchases)?;
+            require!(
+                wallet_purchases.count < purchase_limit.max_per_wallet,
+                ErrorCode::PurchaseLimitReached
+            );
+            wallet_purchases.count += 1;
+            wallet_purchases.bump = ctx.bumps.wallet_purchases.unwrap_or_default();
+        }
+        require!(
+            ctx.accounts.vesting.is_some() == listing.vesting_duration.is_some(),
+            ErrorCode::MissingVestingAccount
+        );
+        require!(
+            ctx.accounts.escrowed_sale.is_some() == listing.receipt_window.is_some(),
+            ErrorCode::MissingEscrowedSale
+        );
+
+        // Primary (first marketplace sale of the mint) vs. secondary schedules.
+        let is_primary = ctx.accounts.provenance.is_primary();
+        let price = listing.sale_price(&ctx.accounts.config, ctx.accounts.price_update.as_ref())?;
+        let schedule = listing_fee_schedule(
+            &ctx.accounts.config,
+            listing,
+            ctx.accounts.storefront.as_ref(),
+            is_primary,
+            ctx.accounts.trader_stats.volume_lamports,
+            ctx.accounts
+                .config
+                .is_member(ctx.accounts.membership_account.as_deref()),
+        )?;
+        if let Some(max_price) = max_price {
+            require!(price <= max_price, ErrorCode::SalePriceAboveMax);
+        }
+        let mut quote = SaleQuote::new(price, is_primary, &schedule)?;
+        quote.route_royalty(&ctx.accounts.config, ctx.accounts.has_royalty_recipient())?;
+        quote.apply_maker_rebate(&ctx.accounts.config)?;
+        quote.apply_charity_split(ctx.accounts.listing.charity_split)?;
+        if ctx.accounts.listing.payment_mint.is_none() {
+            quote.apply_withholding(&ctx.accounts.config)?;
+            quote.apply_insurance(&ctx.accounts.config)?;
+        }
+        ctx.accounts.enforce_cpi_policy(&mut quote)?;
+
+        let payee_count = ctx.accounts.listing.payees.as_ref().map_or(0, Vec::len);
+        let royalty_count = ctx.accounts.royalty_splits().map_or(0, Vec::len);
+        require!(
+            ctx.remaining_accounts.len() >= payee_count + royalty_count,
+            ErrorCode::InvalidPayee
+        );
+        let (payee_accounts, rest) = ctx.remaining_accounts.split_at(payee_count);
+        let (royalty_accounts, hook_accounts) = rest.split_at(royalty_count);
+
+        // Pay out from buyer: seller proceeds (with the maker rebate), charity, marketplace fee & royalty.
+        ctx.accounts
+            .pay_out(&quote, payee_accounts, royalty_accounts)?;
+        pay_insurance(
+            ctx.accounts.insurance_fund.as_mut(),
+            &ctx.accounts.buyer.to_account_info(),
+            Some(&ctx.accounts.system_program.to_account_info()),
+            quote.insurance,
+        )?;
+        ctx.accounts.burn_voucher()?;
+        if let Some(withholding) = &mut ctx.accounts.withholding {
+            withholding.seller = ctx.accounts.listing.seller;
+            withholding.amount = withholding
+                .amount
+                .checked_add(quote.withheld)
+                .ok_or(ErrorCode::MathOverflow)?;
+            withholding.bump = ctx.bumps.withholding.unwrap_or_default();
+        }
+        if let (Some(duration), Some(vesting)) = (
+            ctx.accounts.listing.vesting_duration,
+            &mut ctx.accounts.vesting,
+        ) {
+            vesting.seller = ctx.accounts.listing.seller;
+            vesting.seller_authority = ctx.accounts.listing.seller_authority;
+            vesting.listing = ctx.accounts.listing.key();
+            vesting.total = quote.seller_proceeds + quote.maker_rebate;
+            vesting.claimed = 0;
+            vesting.start_ts = Clock::get()?.unix_timestamp;
+            vesting.duration = duration;
+            vesting.bump = ctx.bumps.vesting.unwrap_or_default();
+        }
+        if let (Some(receipt_window), Some(escrowed_sale)) = (
+            ctx.accounts.listing.receipt_window,
+            &mut ctx.accounts.escrowed_sale,
+        ) {
+            escrowed_sale.listing = ctx.accounts.listing.key();
+            escrowed_sale.seller = ctx.accounts.listing.seller;
+            escrowed_sale.buyer = ctx.accounts.buyer.key();
+            escrowed_sale.amount = quote.seller_proceeds + quote.maker_rebate;
+            escrowed_sale.release_ts = Clock::get()?
+                .unix_timestamp
+                .checked_add(receipt_window)
+                .ok_or(ErrorCode::MathOverflow)?;
+            escrowed_sale.disputed = false;
+            escrowed_sale.bump = ctx.bumps.escrowed_sale.unwrap_or_default();
+        }
+        let listing = &mut ctx.accounts.listing;
+
+        // Transfer NFT = vault --> buyer account.
+        let listing_key = listing.key();
+        let seeds = &[
+            PREFIX.as_bytes(),
+            b"vault",
+            ctx.accounts.nft_account.mint.as_ref(),
+            listing_key.as_ref(),
+            &[vault_bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        transfer_nft(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.buyer_token_account.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            hook_accounts,
+            signer,
+        )?;
+
+        close_token_account(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.rent_payer.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            signer,
+        )?;
+
+        // Mark the listing as inactive so it can't be purchased again.
+        listing.accrue_rewards(Clock::get()?.slot);
+        listing.is_active = false;
+        unregister_listing(listing, ctx.accounts.registry_page.as_ref())?;
+
+        if let Some(sale_receipt) = &mut ctx.accounts.sale_receipt {
+            sale_receipt.mint = listing.mint;
+            sale_receipt.listing = listing.key();
+            sale_receipt.seller = listing.seller;
+            sale_receipt.buyer = ctx.accounts.buyer.key();
+            sale_receipt.price = quote.price;
+            sale_receipt.payment_mint = listing.payment_mint;
+            sale_receipt.is_primary = is_primary;
+            sale_receipt.marketplace_fee = quote.marketplace_fee;
+            sale_receipt.royalty = quote.royalty;
+            sale_receipt.slot = Clock::get()?.slot;
+            sale_receipt.bump = ctx.bumps.sale_receipt.unwrap_or_default();
+        }
+
+        // Record the sale so following sales of this mint are secondary.
+        ctx.accounts.provenance.record_sale(
+            &mut ctx.accounts.sale_record,
+            listing.seller,
+            ctx.accounts.buyer.key(),
+            quote.price,
+            listing.payment_mint,
+            ctx.bumps.sale_record,
+        )?;
+
+        // Buyer volume for fee tiers, counted in lamports (SPL-priced sales don't count).
+        let volume = match listing.payment_mint {
+            Some(_) => 0,
+            None => quote.price,
+        };
+        ctx.accounts.trader_stats.record(
+            ctx.accounts.buyer.key(),
+            volume,
+            ctx.bumps.trader_stats,
+        )?;
+        if let Some(loyalty_account) = &mut ctx.accounts.loyalty_account {
+            loyalty_account.earn(
+                ctx.accounts.buyer.key(),
+                volume,
+                &ctx.accounts.config,
+                ctx.bumps.loyalty_account.unwrap_or_default(),
+            )?;
+        }
+
+        ctx.accounts
+            .global_stats
+            .record_sale(listing.payment_mint.is_none().then_some(quote.price))?;
+
+        // Stats of the listing's verified collection, always updated once created. Self-trades don't count.
+        if listing.collection.is_some() && ctx.accounts.buyer.key() != listing.seller {
+            update_if_initialized(
+                &ctx.accounts.collection_stats,
+                |stats: &mut CollectionStats| {
+                    stats.record_sale(listing.payment_mint.is_none().then_some(quote.price))
+                },
+            )?;
+        }
+
+        // Opt-in trading profiles of either side, always updated once created. Self-trades don't count.
+        let slot = Clock::get()?.slot;
+        if ctx.accounts.buyer.key() != listing.seller {
+            update_if_initialized(
+                &ctx.accounts.buyer_profile,
+                |profile: &mut TraderProfile| profile.record_buy(volume, slot),
+            )?;
+            update_if_initialized(
+                &ctx.accounts.seller_profile,
+                |profile: &mut TraderProfile| profile.record_sale(volume, slot),
+            )?;
+        }
+
+        emit_activity!(
+            ctx,
+            NftSold {
+                listing: listing.key(),
+                mint: listing.mint,
+                seller: listing.seller,
+                buyer: ctx.accounts.buyer.key(),
+                price: quote.price,
+                marketplace_fee: quote.marketplace_fee,
+                marketplace_fee_bps: schedule.marketplace_fee_bps,
+                royalty: quote.royalty,
+                seller_proceeds: quote.seller_proceeds,
+                maker_rebate: quote.maker_rebate,
+                is_primary,
+                payment_mint: listing.payment_mint,
+                charity: listing.charity_split.map(|split| split.charity),
+                charity_amount: quote.charity,
+                withheld: quote.withheld,
+            }
+        );
+
+        ctx.accounts
+            .mine_trade_rewards(volume, ctx.bumps.reward_vault)?;
+
+        Ok(())
+    }
+
+    /// Fills an order the seller signed off-chain: NFT seller --> buyer through the marketplace delegate,
+    /// buyer pays price, fees & royalty. The seller's Ed25519 signature over `signed_order_message(order)`
+    /// must be verified by the Ed25519 program instruction right before this one.
+    /// An order whose NFT the seller has since moved or un-delegated is invalidated instead (its nonce
+    /// is used, `ListingInvalidated` emitted), the buyer paying nothing.
+    /// Transfer hook extra accounts (if the mint has a hook) go in `remaining_accounts`.
+    pub fn fill_signed_order<'info>(
+        ctx: Context<'_, '_, '_, 'info, FillSignedOrder<'info>>,
+        order: SignedOrder,
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.paused, ErrorCode::MarketplacePaused);
+        require!(
+            Clock::get()?.unix_timestamp < order.expires_at,
+            ErrorCode::OrderExpired
+        );
+        verify_ed25519_signature(
+            &ctx.accounts.instructions,
+            &order.seller,
+            &signed_order_message(&order),
+        )?;
+        require_not_banned(
+            &ctx.accounts.buyer_ban,
+            ctx.accounts.buyer.key(),
+            TradeAction::Buy,
+        )?;
+        require_not_banned(&ctx.accounts.seller_ban, order.seller, TradeAction::Sell)?;
+        // Marking the nonce used up front makes filled & cancelled orders unreplayable.
+        require!(
+            ctx.accounts.legacy_order_nonce.data_is_empty(),
+            ErrorCode::OrderNonceUsed
+        );
+        let order_nonces = &mut ctx.accounts.order_nonces;
+        order_nonces.init_if_new(order.seller, order.nonce, ctx.bumps.order_nonces);
+        order_nonces.mark_used(order.nonce)?;
+
+        // The NFT stays with the seller until the fill: make sure it's still there & delegated.
+        let seller_nft_account = &ctx.accounts.seller_nft_account;
+        if seller_nft_account.amount == 0
+            || seller_nft_account.delegated_amount == 0
+            || Option::<Pubkey>::from(seller_nft_account.delegate)
+                != Some(ctx.accounts.delegate.key())
+        {
+            emit_activity!(
+                ctx,
+                ListingInvalidated {
+                    order_nonces: ctx.accounts.order_nonces.key(),
+                    mint: order.mint,
+                    seller: order.seller,
+                    nonce: order.nonce,
+                    nft_balance: seller_nft_account.amount,
+                    delegated_amount: seller_nft_account.delegated_amount,
+                }
+            );
+            return Ok(());
+        }
+        validate_listable_mint(&ctx.accounts.mint.to_account_info())?;
+        require_not_blocked(&ctx.accounts.blocked_mint)?;
+
+        let provenance = &mut ctx.accounts.provenance;
+        provenance.init_if_new(order.mint, order.seller, ctx.bumps.provenance);
+        let is_primary = provenance.is_primary();
+        if let Some(creator) = &ctx.accounts.creator {
+            require_keys_eq!(creator.key(), provenance.creator, ErrorCode::InvalidCreator);
+        }
+
+        let schedule = *ctx.accounts.config.fee_schedule(is_primary);
+        let mut quote = SaleQuote::new(order.price, is_primary, &schedule)?;
+        quote.route_royalty(&ctx.accounts.config, ctx.accounts.creator.is_some())?;
+        quote.apply_withholding(&ctx.accounts.config)?;
+
+        // Pay out from buyer: seller proceeds, withholding, marketplace fee & royalty.
+        let buyer = ctx.accounts.buyer.to_account_info();
+        let system_program = ctx.accounts.system_program.to_account_info();
+        transfer_lamports(
+            &buyer,
+            &ctx.accounts.seller,
+            &system_program,
+            quote.seller_proceeds,
+        )?;
+        pay_withholding(
+            ctx.accounts.withholding.as_mut(),
+            ctx.bumps.withholding,
+            order.seller,
+            &buyer,
+            Some(&system_program),
+            quote.withheld,
+        )?;
+        quote.apply_insurance(&ctx.accounts.config)?;
+        transfer_lamports(
+            &buyer,
+            &ctx.accounts.treasury,
+            &system_program,
+            quote.marketplace_fee,
+        )?;
+        pay_insurance(
+            ctx.accounts.insurance_fund.as_mut(),
+            &buyer,
+            Some(&system_program),
+            quote.insurance,
+        )?;
+        if let Some(creator) = &ctx.accounts.creator {
+            transfer_lamports(&buyer, creator, &system_program, quote.royalty)?;
+        }
+
+        // Transfer NFT = seller --> buyer, the delegate PDA signing.
+        let seeds = &[
+            PREFIX.as_bytes(),
+            b"delegate".as_ref(),
+            &[ctx.bumps.delegate],
+        ];
+        let signer = &[&seeds[..]];
+        transfer_nft(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.seller_nft_account.to_account_info(),
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.buyer_nft_account.to_account_info(),
+            &ctx.accounts.delegate.to_account_info(),
+            ctx.remaining_accounts,
+            signer,
+        )?;
+
+        ctx.accounts.provenance.record_sale(
+            &mut ctx.accounts.sale_record,
+            order.seller,
+            ctx.accounts.buyer.key(),
+            quote.price,
+            None,
+            ctx.bumps.sale_record,
+        )?;
+        ctx.accounts.global_stats.record_sale(Some(quote.price))?;
+
+        emit_activity!(
+            ctx,
+            SignedOrderFilled {
+                order_nonces: ctx.accounts.order_nonces.key(),
+                mint: order.mint,
+                seller: order.seller,
+                buyer: ctx.accounts.buyer.key(),
+                nonce: order.nonce,
+                price: quote.price,
+                marketplace_fee: quote.marketplace_fee,
+                royalty: quote.royalty,
+                seller_proceeds: quote.seller_proceeds,
+                is_primary,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Cancels a signed order before it's filled, by using up its nonce (seller only).
+    pub fn cancel_signed_order(ctx: Context<CancelSignedOrder>, nonce: u64) -> Result<()> {
+        require!(
+            ctx.accounts.legacy_order_nonce.data_is_empty(),
+            ErrorCode::OrderNonceUsed
+        );
+        let order_nonces = &mut ctx.accounts.order_nonces;
+        order_nonces.init_if_new(ctx.accounts.seller.key(), nonce, ctx.bumps.order_nonces);
+        order_nonces.mark_used(nonce)
+    }
+
+    /// Offers `amount` lamports for a mint, escrowed in the offer PDA. One offer per mint & bidder.
+    /// Unfillable from `expires_at` (unix timestamp) on, when anyone can close it.
+    pub fn make_offer(ctx: Context<MakeOffer>, amount: u64, expires_at: Option<i64>) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidOffer);
+        require_not_banned(
+            &ctx.accounts.bidder_ban,
+            ctx.accounts.bidder.key(),
+            TradeAction::MakeOffer,
+        )?;
+        require_future_expiry(expires_at)?;
+        transfer_lamports(
+            &ctx.accounts.bidder.to_account_info(),
+            &ctx.accounts.offer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            amount,
+        )?;
+        ctx.accounts.offer.open(
+            ctx.accounts.bidder.key(),
+            ctx.accounts.mint.key(),
+            amount,
+            OfferEscrow::Lamports,
+            expires_at,
+            ctx.bumps.offer,
+        );
+        ctx.accounts.offer.rent_payer = ctx.accounts.payer.key();
+        let now = Clock::get()?.unix_timestamp;
+        let best_offer = &mut ctx.accounts.best_offer;
+        best_offer.mint = ctx.accounts.offer.mint;
+        best_offer.bump = ctx.bumps.best_offer;
+        best_offer.consider(ctx.accounts.offer.key(), &ctx.accounts.offer, now);
+        book_offer(ctx.accounts.offer_book.as_ref(), &ctx.accounts.offer, now)?;
+        emit_offer_made(&ctx.accounts.offer);
+        Ok(())
+    }
+
+    /// Offers `amount` WSOL for a mint, escrowed in a WSOL token account owned by the offer PDA.
+    /// Unfillable from `expires_at` (unix timestamp) on, when anyone can close it.
+    pub fn make_wsol_offer(
+        ctx: Context<MakeWsolOffer>,
+        amount: u64,
+        expires_at: Option<i64>,
+    ) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidOffer);
+        require_not_banned(
+            &ctx.accounts.bidder_ban,
+            ctx.accounts.bidder.key(),
+            TradeAction::MakeOffer,
+        )?;
+        require_future_expiry(expires_at)?;
+        transfer_tokens(
+            &ctx.accounts.wsol_token_program.to_account_info(),
+            &ctx.accounts.bidder_wsol_account.to_account_info(),
+            &ctx.accounts.wsol_mint,
+            &ctx.accounts.escrow_token_account.to_account_info(),
+            &ctx.accounts.bidder.to_account_info(),
+            amount,
+            &[],
+        )?;
+        ctx.accounts.offer.open(
+            ctx.accounts.bidder.key(),
+            ctx.accounts.mint.key(),
+            amount,
+            OfferEscrow::Wsol,
+            expires_at,
+            ctx.bumps.offer,
+        );
+        ctx.accounts.offer.rent_payer = ctx.accounts.payer.key();
+        let now = Clock::get()?.unix_timestamp;
+        let best_offer = &mut ctx.accounts.best_offer;
+        best_offer.mint = ctx.accounts.offer.mint;
+        best_offer.bump = ctx.bumps.best_offer;
+        best_offer.consider(ctx.accounts.offer.key(), &ctx.accounts.offer, now);
+        book_offer(ctx.accounts.offer_book.as_ref(), &ctx.accounts.offer, now)?;
+        emit_offer_made(&ctx.accounts.offer);
+        Ok(())
+    }
+
+    /// Offers `amount` of an SPL token (e.g. USDC) for a mint, escrowed in a token account owned by the
+    /// offer PDA; `accept_offer` pays the seller in that token. Left out of the mint's best offer & order
+    /// book, which rank SOL offers. Unfillable from `expires_at` (unix timestamp) on, when anyone can close it.
+    /// Payment mints with a transfer fee are rejected: the escrow must hold exactly `amount`.
+    pub fn make_spl_offer(
+        ctx: Context<MakeSplOffer>,
+        amount: u64,
+        expires_at: Option<i64>,
+    ) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidOffer);
+        require_not_banned(
+            &ctx.accounts.bidder_ban,
+            ctx.accounts.bidder.key(),
+            TradeAction::MakeOffer,
+        )?;
+        require_future_expiry(expires_at)?;
+        require!(
+            !has_transfer_fee(&ctx.accounts.payment_mint.to_account_info())?,
+            ErrorCode::PaymentMintTransferFee
+        );
+        transfer_tokens(
+            &ctx.accounts.payment_token_program.to_account_info(),
+            &ctx.accounts.bidder_payment_account.to_account_info(),
+            &ctx.accounts.payment_mint,
+            &ctx.accounts.escrow_token_account.to_account_info(),
+            &ctx.accounts.bidder.to_account_info(),
+            amount,
+            &[],
+        )?;
+        ctx.accounts.offer.open(
+            ctx.accounts.bidder.key(),
+            ctx.accounts.mint.key(),
+            amount,
+            OfferEscrow::Token {
+                mint: ctx.accounts.payment_mint.key(),
+                token_program: ctx.accounts.payment_token_program.key(),
+            },
+            expires_at,
+            ctx.bumps.offer,
+        );
+        ctx.accounts.offer.rent_payer = ctx.accounts.payer.key();
+        let best_offer = &mut ctx.accounts.best_offer;
+        best_offer.mint = ctx.accounts.offer.mint;
+        best_offer.bump = ctx.bumps.best_offer;
+        emit_offer_made(&ctx.accounts.offer);
+        Ok(())
+    }
+
+    /// Withdraws an offer, returning the escrow (lamports, WSOL or SPL tokens) to the bidder & rent to its payer.
+    pub fn cancel_offer(ctx: Context<CancelOffer>) -> Result<()> {
+        let offer = &ctx.accounts.offer;
+        match offer.escrow {
+            OfferEscrow::Lamports => {
+                let bidder = ctx.accounts.bidder.to_account_info();
+                transfer_lamports_from_pda(&offer.to_account_info(), &bidder, offer.amount)?;
+            }
+            OfferEscrow::Wsol => refund_wsol_escrow(
+                offer,
+                ctx.accounts.escrow_token_account.as_ref(),
+                ctx.accounts.bidder_wsol_account.as_ref(),
+                ctx.accounts.wsol_mint.as_ref(),
+                ctx.accounts.wsol_token_program.as_ref(),
+                &ctx.accounts.rent_payer,
+            )?,
+            OfferEscrow::Token { .. } => refund_spl_escrow(
+                offer,
+                ctx.accounts.escrow_token_account.as_ref(),
+                ctx.accounts.bidder_payment_account.as_ref(),
+                ctx.accounts.payment_mint.as_ref(),
+                ctx.accounts.payment_token_program.as_ref(),
+                &ctx.accounts.rent_payer,
+            )?,
+        }
+
+        ctx.accounts.best_offer.remove(offer.key());
+        unbook_offer(ctx.accounts.offer_book.as_ref(), offer.key())?;
+
+        emit!(OfferCancelled {
+            offer: offer.key(),
+            mint: offer.mint,
+            bidder: offer.bidder,
+            amount: offer.amount,
+        });
+        Ok(())
+    }
+
+    /// Re-points a mint's best offer at the best live one of the offers in `remaining_accounts`,
+    /// e.g. after the best one was withdrawn or expired (anyone can crank). Only ever replaces a
+    /// live best offer with a higher one.
+    pub fn refresh_best_offer(ctx: Context<RefreshBestOffer>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let best_offer = &mut ctx.accounts.best_offer;
+        for account in ctx.remaining_accounts {
+            require_keys_eq!(*account.owner, crate::ID, ErrorCode::InvalidOffer);
+            let offer = Offer::try_deserialize(&mut &account.try_borrow_data()?[..])?;
+            require_keys_eq!(offer.mint, best_offer.mint, ErrorCode::InvalidOffer);
+            best_offer.consider(account.key(), &offer, now);
+        }
+        Ok(())
+    }
+
+    /// Creates the bid order book of a mint (anyone can, paying its rent). Offers made, cancelled,
+    /// filled or closed with it passed keep it in sync.
+    pub fn create_offer_book(ctx: Context<CreateOfferBook>) -> Result<()> {
+        let mut offer_book = ctx.accounts.offer_book.load_init()?;
+        offer_book.mint = ctx.accounts.mint.key();
+        offer_book.bump = ctx.bumps.offer_book;
+        Ok(())
+    }
+
+    /// Syncs a mint's order book with the offers in `remaining_accounts` (anyone can crank): live
+    /// offers are booked, e.g. ones made before the book existed, & closed or expired ones dropped.
+    pub fn refresh_offer_book(ctx: Context<RefreshOfferBook>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let mut offer_book = ctx.accounts.offer_book.load_mut()?;
+        for account in ctx.remaining_accounts {
+            let offer = if account.owner == &crate::ID {
+                Offer::try_deserialize(&mut &account.try_borrow_data()?[..]).ok()
+            } else {
+                None
+            };
+            match offer {
+                Some(offer) if offer.mint == offer_book.mint && !offer.is_expired(now) => {
+                    offer_book.insert(account.key(), &offer, now)
+                }
+                _ => offer_book.remove(&account.key()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Closes an expired offer, returning the escrow (lamports, WSOL or SPL tokens) & rent to the bidder.
+    /// Callable by anyone; the cranker gets the config's tip out of the offer's rent refund.
+    pub fn close_expired_offer(ctx: Context<CloseExpiredOffer>) -> Result<()> {
+        let offer = &ctx.accounts.offer;
+        require!(
+            offer.is_expired(Clock::get()?.unix_timestamp),
+            ErrorCode::OfferNotExpired
+        );
+        let escrowed = match offer.escrow {
+            OfferEscrow::Lamports => offer.amount,
+            OfferEscrow::Wsol => {
+                refund_wsol_escrow(
+                    offer,
+                    ctx.accounts.escrow_token_account.as_ref(),
+                    ctx.accounts.bidder_wsol_account.as_ref(),
+                    ctx.accounts.wsol_mint.as_ref(),
+                    ctx.accounts.wsol_token_program.as_ref(),
+                    &ctx.accounts.rent_payer,
+                )?;
+                0
+            }
+            OfferEscrow::Token { .. } => {
+                refund_spl_escrow(
+                    offer,
+                    ctx.accounts.escrow_token_account.as_ref(),
+                    ctx.accounts.bidder_payment_account.as_ref(),
+                    ctx.accounts.payment_mint.as_ref(),
+                    ctx.accounts.payment_token_program.as_ref(),
+                    &ctx.accounts.rent_payer,
+                )?;
+                0
+            }
+        };
+
+        // Return the escrowed lamports to the bidder & tip the cranker; closing the offer returns the
+        // remaining rent to its payer.
+        let offer_info = offer.to_account_info();
+        transfer_lamports_from_pda(&offer_info, &ctx.accounts.bidder, escrowed)?;
+        let crank_tip = ctx
+            .accounts
+            .config
+            .crank_tip_lamports
+            .min(offer_info.lamports());
+        transfer_lamports_from_pda(&offer_info, &ctx.accounts.cranker, crank_tip)?;
+        ctx.accounts.best_offer.remove(offer.key());
+        unbook_offer(ctx.accounts.offer_book.as_ref(), offer.key())?;
+
+        emit!(OfferExpired {
+            offer: offer.key(),
+            mint: offer.mint,
+            bidder: offer.bidder,
+            cranker: ctx.accounts.cranker.key(),
+            crank_tip,
+        });
+        Ok(())
+    }
+
+    /// Sells the signer's NFT to an offer: NFT seller --> bidder, escrow pays price, fees & royalty.
+    /// WSOL escrows pay the seller in SOL or WSOL per `config.unwrap_wsol_proceeds`, fees & royalty in SOL.
+    /// SPL escrows pay everyone in their token, without an insurance slice (like SPL-priced listings).
+    /// Transfer hook extra accounts (if the mint has a hook) go in `remaining_accounts`.
+    pub fn accept_offer<'info>(ctx: Context<'_, '_, '_, 'info, AcceptOffer<'info>>) -> Result<()> {
+        require!(!ctx.accounts.config.paused, ErrorCode::MarketplacePaused);
+        validate_listable_mint(&ctx.accounts.mint.to_account_info())?;
+        require_not_blocked(&ctx.accounts.blocked_mint)?;
+        require_not_banned(
+            &ctx.accounts.seller_ban,
+            ctx.accounts.seller.key(),
+            TradeAction::AcceptOffer,
+        )?;
+        require_not_banned(
+            &ctx.accounts.bidder_ban,
+            ctx.accounts.offer.bidder,
+            TradeAction::AcceptOffer,
+        )?;
+
+        let offer = &ctx.accounts.offer;
+        require!(
+            !offer.is_expired(Clock::get()?.unix_timestamp),
+            ErrorCode::ExpiredOffer
+        );
+        let provenance = &mut ctx.accounts.provenance;
+        provenance.init_if_new(offer.mint, ctx.accounts.seller.key(), ctx.bumps.provenance);
+        let is_primary = provenance.is_primary();
+        if let Some(creator) = &ctx.accounts.creator {
+            require_keys_eq!(creator.key(), provenance.creator, ErrorCode::InvalidCreator);
+        }
+
+        let schedule = *ctx.accounts.config.fee_schedule(is_primary);
+        let mut quote = SaleQuote::new(offer.amount, is_primary, &schedule)?;
+        let payment_mint = offer.payment_mint();
+        let has_creator = match payment_mint {
+            Some(_) => ctx.accounts.creator_payment_account.is_some(),
+            None => ctx.accounts.creator.is_some(),
+        };
+        quote.route_royalty(&ctx.accounts.config, has_creator)?;
+        quote.apply_maker_rebate(&ctx.accounts.config)?;
+
+        // Transfer NFT = seller --> bidder.
+        transfer_nft(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.seller_nft_account.to_account_info(),
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.bidder_nft_account.to_account_info(),
+            &ctx.accounts.seller.to_account_info(),
+            ctx.remaining_accounts,
+            &[],
+        )?;
+
+        if payment_mint.is_some() {
+            ctx.accounts.pay_out_token_escrow(&quote)?;
+        } else {
+            ctx.accounts
+                .pay_out_sol_escrow(&mut quote, ctx.bumps.withholding)?;
+        }
+
+        ctx.accounts.provenance.record_sale(
+            &mut ctx.accounts.sale_record,
+            ctx.accounts.seller.key(),
+            ctx.accounts.offer.bidder,
+            quote.price,
+            payment_mint,
+            ctx.bumps.sale_record,
+        )?;
+        ctx.accounts
+            .global_stats
+            .record_sale(payment_mint.is_none().then_some(quote.price))?;
+
+        let offer = &ctx.accounts.offer;
+        ctx.accounts.best_offer.remove(offer.key());
+        unbook_offer(ctx.accounts.offer_book.as_ref(), offer.key())?;
+        emit_activity!(
+            ctx,
+            OfferAccepted {
+                offer: offer.key(),
+                mint: offer.mint,
+                seller: ctx.accounts.seller.key(),
+                bidder: offer.bidder,
+                price: quote.price,
+                marketplace_fee: quote.marketplace_fee,
+                royalty: quote.royalty,
+                seller_proceeds: quote.seller_proceeds,
+                maker_rebate: quote.maker_rebate,
+                is_primary,
+                escrow: offer.escrow,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Auction House-style settlement of a listing (the seller's side) against a lamport offer on its mint
+    /// (the buyer's side), for aggregators routing `execute_sale(buyer_price, token_size)`. Anyone can
+    /// crank it: `buyer_price` must be the offer's amount & at least the listing's price, `token_size` 1.
+    /// The offer escrow pays, the vault releases the NFT to the bidder; plain SOL listings only.
+    /// Transfer hook extra accounts (if the mint has a hook) go in `remaining_accounts`.
+    pub fn execute_sale<'info>(
+        ctx: Context<'_, '_, '_, 'info, ExecuteSale<'info>>,
+        buyer_price: u64,
+        token_size: u64,
+    ) -> Result<()> {
+        require!(token_size == 1, ErrorCode::InvalidTokenSize);
+        require!(!ctx.accounts.config.paused, ErrorCode::MarketplacePaused);
+        require_not_blocked(&ctx.accounts.blocked_mint)?;
+        require_not_banned(
+            &ctx.accounts.seller_ban,
+            ctx.accounts.listing.seller,
+            TradeAction::Sell,
+        )?;
+        require_not_banned(
+            &ctx.accounts.bidder_ban,
+            ctx.accounts.offer.bidder,
+            TradeAction::Buy,
+        )?;
+
+        let listing = &ctx.accounts.listing;
+        require!(listing.is_active, ErrorCode::InactiveListing);
+        let now = Clock::get()?.unix_timestamp;
+        require!(!listing.is_expired(now), ErrorCode::ListingHasExpired);
+        require!(listing.has_started(now), ErrorCode::ListingNotStarted);
+        require!(
+            listing.price_usd.is_none()
+                && listing.payment_mint.is_none()
+                && listing.payees.is_none()
+                && listing.vesting_duration.is_none()
+                && listing.gate_mint.is_none()
+                && listing.allowlist_root.is_none()
+                && listing.attestation.is_none()
+                && listing.purchase_limit.is_none()
+                && listing.voucher.is_none()
+                && listing.receipt_window.is_none()
+                && listing.charity_split.is_none()
+                && listing.royalty_splits.is_none(),
+            ErrorCode::ExecuteSaleNotSupported
+        );
+        let offer = &ctx.accounts.offer;
+        require!(
+            offer.escrow == OfferEscrow::Lamports,
+            ErrorCode::ExecuteSaleNotSupported
+        );
+        require!(!offer.is_expired(now), ErrorCode::ExpiredOffer);
+        require!(
+            buyer_price == offer.amount
+                && buyer_price >= listing.sale_price(&ctx.accounts.config, None)?,
+            ErrorCode::ExecuteSalePriceMismatch
+        );
+
+        let is_primary = ctx.accounts.provenance.is_primary();
+        let schedule = listing_fee_schedule(
+            &ctx.accounts.config,
+            listing,
+            ctx.accounts.storefront.as_ref(),
+            is_primary,
+            0,
+            false,
+        )?;
+        let mut quote = SaleQuote::new(buyer_price, is_primary, &schedule)?;
+        quote.route_royalty(&ctx.accounts.config, ctx.accounts.creator.is_some())?;
+
+        let fee_recipient = match &ctx.accounts.storefront {
+            Some(storefront) => storefront.treasury,
+            None => ctx.accounts.config.treasury,
+        };
+        require_keys_eq!(
+            ctx.accounts.treasury.key(),
+            fee_recipient,
+            ErrorCode::InvalidTreasury
+        );
+
+        quote.apply_withholding(&ctx.accounts.config)?;
+        quote.apply_insurance(&ctx.accounts.config)?;
+        pay_out_from_escrow(
+            &offer.to_account_info(),
+            &ctx.accounts.seller,
+            &ctx.accounts.treasury,
+            ctx.accounts.insurance_fund.as_mut(),
+            ctx.accounts.creator.as_ref(),
+            &quote,
+        )?;
+        pay_withholding(
+            ctx.accounts.withholding.as_mut(),
+            ctx.bumps.withholding,
+            ctx.accounts.seller.key(),
+            &offer.to_account_info(),
+            None,
+            quote.withheld,
+        )?;
+
+        // Transfer NFT = vault --> bidder.
+        let listing_key = listing.key();
+        let seeds = &[
+            PREFIX.as_bytes(),
+            b"vault",
+            listing.mint.as_ref(),
+            listing_key.as_ref(),
+            &[ctx.bumps.vault],
+        ];
+        let signer = &[&seeds[..]];
+        transfer_nft(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.bidder_nft_account.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            ctx.remaining_accounts,
+            signer,
+        )?;
+
+        close_token_account(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.listing_rent_payer.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            signer,
+        )?;
+
+        ctx.accounts.listing.accrue_rewards(Clock::get()?.slot);
+        ctx.accounts.listing.is_active = false;
+        unregister_listing(&ctx.accounts.listing, ctx.accounts.registry_page.as_ref())?;
+        ctx.accounts.provenance.record_sale(
+            &mut ctx.accounts.sale_record,
+            ctx.accounts.listing.seller,
+            ctx.accounts.offer.bidder,
+            quote.price,
+            None,
+            ctx.bumps.sale_record,
+        )?;
+        ctx.accounts.global_stats.record_sale(Some(quote.price))?;
+
+        let offer = ctx.accounts.offer.key();
+        ctx.accounts.best_offer.remove(offer);
+        unbook_offer(ctx.accounts.offer_book.as_ref(), offer)?;
+
+        let listing = &ctx.accounts.listing;
+        emit_activity!(
+            ctx,
+            NftSold {
+                listing: listing.key(),
+                mint: listing.mint,
+                seller: listing.seller,
+                buyer: ctx.accounts.offer.bidder,
+                price: quote.price,
+                marketplace_fee: quote.marketplace_fee,
+                marketplace_fee_bps: schedule.marketplace_fee_bps,
+                royalty: quote.royalty,
+                seller_proceeds: quote.seller_proceeds,
+                maker_rebate: quote.maker_rebate,
+                is_primary,
+                payment_mint: None,
+                charity: None,
+                charity_amount: 0,
+                withheld: quote.withheld,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Gives the signer's NFT to `recipient` for free (e.g. a creator giveaway), through the same
+    /// mint checks & provenance as a sale, so it's indexed as marketplace activity. The giver pays for
+    /// the recipient's associated token account if missing.
+    /// Transfer hook extra accounts (if the mint has a hook) go in `remaining_accounts`.
+    pub fn gift_nft<'info>(ctx: Context<'_, '_, '_, 'info, GiftNft<'info>>) -> Result<()> {
+        validate_listable_mint(&ctx.accounts.mint.to_account_info())?;
+        require_not_blocked(&ctx.accounts.blocked_mint)?;
+
+        let mint = ctx.accounts.mint.key();
+        let giver = ctx.accounts.giver.key();
+        ctx.accounts
+            .provenance
+            .init_if_new(mint, giver, ctx.bumps.provenance);
+
+        transfer_nft(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.giver_nft_account.to_account_info(),
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.recipient_nft_account.to_account_info(),
+            &ctx.accounts.giver.to_account_info(),
+            ctx.remaining_accounts,
+            &[],
+        )?;
+
+        emit_activity!(
+            ctx,
+            NftGifted {
+                mint,
+                giver,
+                recipient: ctx.accounts.recipient.key(),
+            }
+        );
+        Ok(())
+    }
+
+    /// Counters a lamport offer with a higher `amount` (NFT owner only), replacing any earlier counter.
+    /// Approves the marketplace delegate on the seller's NFT account, so the bidder can settle alone.
+    pub fn counter_offer(ctx: Context<CounterOffer>, amount: u64) -> Result<()> {
+        require_not_banned(
+            &ctx.accounts.seller_ban,
+            ctx.accounts.seller.key(),
+            TradeAction::CounterOffer,
+        )?;
+        let offer = &mut ctx.accounts.offer;
+        require!(
+            offer.escrow == OfferEscrow::Lamports,
+            ErrorCode::CounterOfferNotSupported
+        );
+        require!(
+            !offer.is_expired(Clock::get()?.unix_timestamp),
+            ErrorCode::ExpiredOffer
+        );
+        require!(amount > offer.amount, ErrorCode::InvalidCounterOffer);
+
+        token_interface::approve(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                token_interface::Approve {
+                    to: ctx.accounts.seller_nft_account.to_account_info(),
+                    delegate: ctx.accounts.delegate.to_account_info(),
+                    authority: ctx.accounts.seller.to_account_info(),
+                },
+            ),
+            1,
+        )?;
+
+        offer.counter = Some(OfferCounter {
+            seller: ctx.accounts.seller.key(),
+            amount,
+        });
+
+        emit!(OfferCountered {
+            offer: offer.key(),
+            mint: offer.mint,
+            bidder: offer.bidder,
+            seller: ctx.accounts.seller.key(),
+            bid: offer.amount,
+            amount,
+        });
+        Ok(())
+    }
+
+    /// Takes the seller's counter to the signer's offer: tops up the escrow to the countered amount &
+    /// settles like `accept_offer`, the delegate PDA moving the NFT. The maker rebate goes to the seller.
+    /// Transfer hook extra accounts (if the mint has a hook) go in `remaining_accounts`.
+    pub fn accept_counter_offer<'info>(
+        ctx: Context<'_, '_, '_, 'info, AcceptCounterOffer<'info>>,
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.paused, ErrorCode::MarketplacePaused);
+        validate_listable_mint(&ctx.accounts.mint.to_account_info())?;
+        require_not_blocked(&ctx.accounts.blocked_mint)?;
+        require_not_banned(
+            &ctx.accounts.seller_ban,
+            ctx.accounts.seller.key(),
+            TradeAction::AcceptOffer,
+        )?;
+        require_not_banned(
+            &ctx.accounts.bidder_ban,
+            ctx.accounts.bidder.key(),
+            TradeAction::AcceptOffer,
+        )?;
+
+        let offer = &ctx.accounts.offer;
+        require!(
+            !offer.is_expired(Clock::get()?.unix_timestamp),
+            ErrorCode::ExpiredOffer
+        );
+        let counter = offer.counter.ok_or(ErrorCode::NoCounterOffer)?;
+        require_keys_eq!(
+            ctx.accounts.seller.key(),
+            counter.seller,
+            ErrorCode::InvalidCounterOffer
+        );
+
+        // Top up the escrow: bidder --> offer PDA.
+        transfer_lamports(
+            &ctx.accounts.bidder.to_account_info(),
+            &offer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            counter.amount - offer.amount,
+        )?;
+        ctx.accounts.offer.amount = counter.amount;
+
+        let provenance = &mut ctx.accounts.provenance;
+        provenance.init_if_new(
+            ctx.accounts.mint.key(),
+            counter.seller,
+            ctx.bumps.provenance,
+        );
+        let is_primary = provenance.is_primary();
+        if let Some(creator) = &ctx.accounts.creator {
+            require_keys_eq!(creator.key(), provenance.creator, ErrorCode::InvalidCreator);
+        }
+
+        let schedule = *ctx.accounts.config.fee_schedule(is_primary);
+        let mut quote = SaleQuote::new(counter.amount, is_primary, &schedule)?;
+        quote.route_royalty(&ctx.accounts.config, ctx.accounts.creator.is_some())?;
+        quote.apply_maker_rebate(&ctx.accounts.config)?;
+
+        // Transfer NFT = seller --> bidder, the delegate PDA signing.
+        let seeds = &[
+            PREFIX.as_bytes(),
+            b"delegate".as_ref(),
+            &[ctx.bumps.delegate],
+        ];
+        let signer = &[&seeds[..]];
+        transfer_nft(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.seller_nft_account.to_account_info(),
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.bidder_nft_account.to_account_info(),
+            &ctx.accounts.delegate.to_account_info(),
+            ctx.remaining_accounts,
+            signer,
+        )?;
+
+        // Pay out from the offer escrow: seller proceeds with the maker rebate, withholding, marketplace fee
+        // & royalty.
+        quote.apply_withholding(&ctx.accounts.config)?;
+        let offer_info = ctx.accounts.offer.to_account_info();
+        transfer_lamports_from_pda(
+            &offer_info,
+            &ctx.accounts.seller,
+            quote.seller_proceeds + quote.maker_rebate,
+        )?;
+        pay_withholding(
+            ctx.accounts.withholding.as_mut(),
+            ctx.bumps.withholding,
+            ctx.accounts.seller.key(),
+            &offer_info,
+            None,
+            quote.withheld,
+        )?;
+        quote.apply_insurance(&ctx.accounts.config)?;
+        transfer_lamports_from_pda(&offer_info, &ctx.accounts.treasury, quote.marketplace_fee)?;
+        pay_insurance(
+            ctx.accounts.insurance_fund.as_mut(),
+            &offer_info,
+            None,
+            quote.insurance,
+        )?;
+        if let Some(creator) = &ctx.accounts.creator {
+            transfer_lamports_from_pda(&offer_info, creator, quote.royalty)?;
+        }
+
+        ctx.accounts.provenance.record_sale(
+            &mut ctx.accounts.sale_record,
+            counter.seller,
+            ctx.accounts.offer.bidder,
+            quote.price,
+            None,
+            ctx.bumps.sale_record,
+        )?;
+        ctx.accounts.global_stats.record_sale(Some(quote.price))?;
+
+        let offer = &ctx.accounts.offer;
+        ctx.accounts.best_offer.remove(offer.key());
+        unbook_offer(ctx.accounts.offer_book.as_ref(), offer.key())?;
+        emit_activity!(
+            ctx,
+            OfferAccepted {
+                offer: offer.key(),
+                mint: offer.mint,
+                seller: counter.seller,
+                bidder: offer.bidder,
+                price: quote.price,
+                marketplace_fee: quote.marketplace_fee,
+                royalty: quote.royalty,
+                seller_proceeds: quote.seller_proceeds,
+                maker_rebate: quote.maker_rebate,
+                is_primary,
+                escrow: offer.escrow,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Offers `amount` lamports for any one NFT of verified `collection`, escrowed in the offer PDA.
+    /// With a `mint_root`, only mints proven to be in that merkle tree are eligible (e.g. one trait).
+    /// One collection offer per collection & bidder, unfillable from `expires_at` (unix timestamp) on.
+    pub fn make_collection_offer(
+        ctx: Context<MakeCollectionOffer>,
+        collection: Pubkey,
+        amount: u64,
+        mint_root: Option<[u8; 32]>,
+        expires_at: Option<i64>,
+    ) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidOffer);
+        require_not_banned(
+            &ctx.accounts.bidder_ban,
+            ctx.accounts.bidder.key(),
+            TradeAction::MakeOffer,
+        )?;
+        require_future_expiry(expires_at)?;
+        transfer_lamports(
+            &ctx.accounts.bidder.to_account_info(),
+            &ctx.accounts.offer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            amount,
+        )?;
+
+        let offer = &mut ctx.accounts.offer;
+        offer.bidder = ctx.accounts.bidder.key();
+        offer.collection = collection;
+        offer.amount = amount;
+        offer.mint_root = mint_root;
+        offer.expires_at = expires_at;
+        offer.bump = ctx.bumps.offer;
+
+        emit!(CollectionOfferMade {
+            offer: offer.key(),
+            collection,
+            bidder: offer.bidder,
+            amount,
+            mint_root,
+            expires_at,
+        });
+        Ok(())
+    }
+
+    /// Withdraws a collection offer, returning the escrow & rent to the bidder.
+    pub fn cancel_collection_offer(ctx: Context<CancelCollectionOffer>) -> Result<()> {
+        let offer = &ctx.accounts.offer;
+        emit!(CollectionOfferCancelled {
+            offer: offer.key(),
+            collection: offer.collection,
+            bidder: offer.bidder,
+            amount: offer.amount,
+        });
+        Ok(())
+    }
+
+    /// Sells the signer's NFT of the offer's collection to a collection offer: NFT seller --> bidder, escrow
+    /// pays price, fees & royalty. `proof` is the mint's merkle proof for offers with a `mint_root`.
+    /// Transfer hook extra accounts (if the mint has a hook) go in `remaining_accounts`.
+    pub fn accept_collection_offer<'info>(
+        ctx: Context<'_, '_, '_, 'info, AcceptCollectionOffer<'info>>,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.paused, ErrorCode::MarketplacePaused);
+        validate_listable_mint(&ctx.accounts.mint.to_account_info())?;
+        require_not_blocked(&ctx.accounts.blocked_mint)?;
+        require_not_banned(
+            &ctx.accounts.seller_ban,
+            ctx.accounts.seller.key(),
+            TradeAction::AcceptOffer,
+        )?;
+        require_not_banned(
+            &ctx.accounts.bidder_ban,
+            ctx.accounts.bidder.key(),
+            TradeAction::AcceptOffer,
+        )?;
+
+        let offer = &ctx.accounts.offer;
+        let mint = ctx.accounts.mint.key();
+        require!(
+            !offer.is_expired(Clock::get()?.unix_timestamp),
+            ErrorCode::ExpiredOffer
+        );
+        require!(
+            verified_collection(&mint, &ctx.accounts.metadata)? == Some(offer.collection),
+            ErrorCode::IneligibleMint
+        );
+        if let Some(mint_root) = &offer.mint_root {
+            require!(
+                verify_merkle_proof(&mint, &proof, mint_root),
+                ErrorCode::IneligibleMint
+            );
+        }
+
+        let provenance = &mut ctx.accounts.provenance;
+        provenance.init_if_new(mint, ctx.accounts.seller.key(), ctx.bumps.provenance);
+        let is_primary = provenance.is_primary();
+        if let Some(creator) = &ctx.accounts.creator {
+            require_keys_eq!(creator.key(), provenance.creator, ErrorCode::InvalidCreator);
+        }
+
+        let schedule = *ctx.accounts.config.fee_schedule(is_primary);
+        let mut quote = SaleQuote::new(offer.amount, is_primary, &schedule)?;
+        quote.route_royalty(&ctx.accounts.config, ctx.accounts.creator.is_some())?;
+        quote.apply_maker_rebate(&ctx.accounts.config)?;
+
+        // Transfer NFT = seller --> bidder.
+        transfer_nft(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.seller_nft_account.to_account_info(),
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.bidder_nft_account.to_account_info(),
+            &ctx.accounts.seller.to_account_info(),
+            ctx.remaining_accounts,
+            &[],
+        )?;
+
+        let offer_info = offer.to_account_info();
+        quote.apply_withholding(&ctx.accounts.config)?;
+        quote.apply_insurance(&ctx.accounts.config)?;
+        pay_out_from_escrow(
+            &offer_info,
+            &ctx.accounts.seller.to_account_info(),
+            &ctx.accounts.treasury,
+            ctx.accounts.insurance_fund.as_mut(),
+            ctx.accounts.creator.as_ref(),
+            &quote,
+        )?;
+        pay_withholding(
+            ctx.accounts.withholding.as_mut(),
+            ctx.bumps.withholding,
+            ctx.accounts.seller.key(),
+            &offer_info,
+            None,
+            quote.withheld,
+        )?;
+        transfer_lamports_from_pda(&offer_info, &ctx.accounts.bidder, quote.maker_rebate)?;
+
+        ctx.accounts.provenance.record_sale(
+            &mut ctx.accounts.sale_record,
+            ctx.accounts.seller.key(),
+            offer.bidder,
+            quote.price,
+            None,
+            ctx.bumps.sale_record,
+        )?;
+        ctx.accounts.global_stats.record_sale(Some(quote.price))?;
+
+        let offer = &ctx.accounts.offer;
+        emit_activity!(
+            ctx,
+            CollectionOfferAccepted {
+                offer: offer.key(),
+                collection: offer.collection,
+                mint,
+                seller: ctx.accounts.seller.key(),
+                bidder: offer.bidder,
+                price: quote.price,
+                marketplace_fee: quote.marketplace_fee,
+                royalty: quote.royalty,
+                seller_proceeds: quote.seller_proceeds,
+                maker_rebate: quote.maker_rebate,
+                is_primary,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Opens a crowdfunded purchase of a SOL-priced listing, funded until `deadline` (unix timestamp).
+    pub fn open_group_buy(ctx: Context<OpenGroupBuy>, deadline: i64) -> Result<()> {
+        let listing = &ctx.accounts.listing;
+        require!(listing.is_active, ErrorCode::InactiveListing);
+        require!(
+            listing.price_usd.is_none()
+                && listing.payment_mint.is_none()
+                && listing.payees.is_none()
+                && listing.vesting_duration.is_none()
+                && listing.dutch_auction.is_none()
+                && listing.flash_sale.is_none()
+                && listing.gate_mint.is_none()
+                && listing.allowlist_root.is_none()
+                && listing.attestation.is_none()
+                && listing.purchase_limit.is_none()
+                && listing.voucher.is_none()
+                && listing.receipt_window.is_none()
+                && listing.charity_split.is_none()
+                && listing.royalty_splits.is_none(),
+            ErrorCode::GroupBuyNotSupported
+        );
+        require!(
+            deadline > Clock::get()?.unix_timestamp,
+            ErrorCode::InvalidGroupBuyParams
+        );
+
+        let group_buy = &mut ctx.accounts.group_buy;
+        group_buy.listing = listing.key();
+        group_buy.mint = listing.mint;
+        group_buy.organizer = ctx.accounts.organizer.key();
+        group_buy.target = listing.sale_price(&ctx.accounts.config, None)?;
+        group_buy.raised = 0;
+        group_buy.deadline = deadline;
+        group_buy.status = GroupBuyStatus::Open;
+        group_buy.bump = ctx.bumps.group_buy;
+
+        emit!(GroupBuyOpened {
+            group_buy: group_buy.key(),
+            listing: group_buy.listing,
+            organizer: group_buy.organizer,
+            target: group_buy.target,
+            deadline,
+        });
+
+        Ok(())
+    }
+
+    /// Adds lamports to an open group buy, capped to what's still missing.
+    pub fn contribute_group_buy(ctx: Context<ContributeGroupBuy>, amount: u64) -> Result<()> {
+        let group_buy = &ctx.accounts.group_buy;
+        require!(
+            group_buy.status == GroupBuyStatus::Open,
+            ErrorCode::GroupBuyNotOpen
+        );
+        require!(
+            Clock::get()?.unix_timestamp < group_buy.deadline,
+            ErrorCode::GroupBuyDeadlinePassed
+        );
+        let amount = amount.min(group_buy.target - group_buy.raised);
+        require!(amount > 0, ErrorCode::InvalidGroupBuyParams);
+
+        transfer_lamports(
+            &ctx.accounts.contributor.to_account_info(),
+            &ctx.accounts.group_buy.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            amount,
+        )?;
+
+        let contribution = &mut ctx.accounts.contribution;
+        contribution.group_buy = ctx.accounts.group_buy.key();
+        contribution.contributor = ctx.accounts.contributor.key();
+        contribution.amount = contribution
+            .amount
+            .checked_add(amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+        contribution.bump = ctx.bumps.contribution;
+
+        let group_buy = &mut ctx.accounts.group_buy;
+        group_buy.raised += amount;
+
+        emit!(GroupBuyContributed {
+            group_buy: group_buy.key(),
+            contributor: contribution.contributor,
+            amount,
+            raised: group_buy.raised,
+            target: group_buy.target,
+        });
+
+        Ok(())
+    }
+
+    /// Buys the listing once fully funded (anyone can crank): the group PDA pays, & holds the NFT.
+    /// Transfer hook extra accounts (if the mint has a hook) go in `remaining_accounts`.
+    pub fn execute_group_buy<'info>(
+        ctx: Context<'_, '_, '_, 'info, ExecuteGroupBuy<'info>>,
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.paused, ErrorCode::MarketplacePaused);
+        let group_buy = &ctx.accounts.group_buy;
+        require!(
+            group_buy.status == GroupBuyStatus::Open,
+            ErrorCode::GroupBuyNotOpen
+        );
+        require!(
+            group_buy.raised == group_buy.target,
+            ErrorCode::GroupBuyNotFunded
+        );
+
+        let listing = &ctx.accounts.listing;
+        require!(listing.is_active, ErrorCode::InactiveListing);
+        let now = Clock::get()?.unix_timestamp;
+        require!(!listing.is_expired(now), ErrorCode::ListingHasExpired);
+        require!(listing.has_started(now), ErrorCode::ListingNotStarted);
+        require_not_blocked(&ctx.accounts.blocked_mint)?;
+        // The listing's price (in SOL) must still be the one that was funded.
+        let price = listing.sale_price(&ctx.accounts.config, None)?;
+        require!(
+            listing.payment_mint.is_none() && price == group_buy.target,
+            ErrorCode::GroupBuyNotFunded
+        );
+
+        let is_primary = ctx.accounts.provenance.is_primary();
+        let schedule = listing_fee_schedule(
+            &ctx.accounts.config,
+            listing,
+            ctx.accounts.storefront.as_ref(),
+            is_primary,
+            0,
+            false,
+        )?;
+        let mut quote = SaleQuote::new(price, is_primary, &schedule)?;
+        quote.route_royalty(&ctx.accounts.config, ctx.accounts.creator.is_some())?;
+
+        let fee_recipient = match &ctx.accounts.storefront {
+            Some(storefront) => storefront.treasury,
+            None => ctx.accounts.config.treasury,
+        };
+        require_keys_eq!(
+            ctx.accounts.treasury.key(),
+            fee_recipient,
+            ErrorCode::InvalidTreasury
+        );
+
+        quote.apply_withholding(&ctx.accounts.config)?;
+        quote.apply_insurance(&ctx.accounts.config)?;
+        pay_out_from_escrow(
+            &group_buy.to_account_info(),
+            &ctx.accounts.seller,
+            &ctx.accounts.treasury,
+            ctx.accounts.insurance_fund.as_mut(),
+            ctx.accounts.creator.as_ref(),
+            &quote,
+        )?;
+        pay_withholding(
+            ctx.accounts.withholding.as_mut(),
+            ctx.bumps.withholding,
+            ctx.accounts.seller.key(),
+            &group_buy.to_account_info(),
+            None,
+            quote.withheld,
+        )?;
+
+        // Transfer NFT = vault --> group's token account.
+        let listing_key = listing.key();
+        let seeds = &[
+            PREFIX.as_bytes(),
+            b"vault",
+            listing.mint.as_ref(),
+            listing_key.as_ref(),
+            &[ctx.bumps.vault],
+        ];
+        let signer = &[&seeds[..]];
+        transfer_nft(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.group_nft_account.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            ctx.remaining_accounts,
+            signer,
+        )?;
+
+        close_token_account(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.rent_payer.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            signer,
+        )?;
+
+        ctx.accounts.listing.accrue_rewards(Clock::get()?.slot);
+        ctx.accounts.listing.is_active = false;
+        ctx.accounts.provenance.record_sale(
+            &mut ctx.accounts.sale_record,
+            ctx.accounts.listing.seller,
+            ctx.accounts.group_buy.key(),
+            quote.price,
+            None,
+            ctx.bumps.sale_record,
+        )?;
+        ctx.accounts.global_stats.record_sale(Some(quote.price))?;
+        ctx.accounts.group_buy.status = GroupBuyStatus::Executed;
+
+        let listing = &ctx.accounts.listing;
+        emit_activity!(
+            ctx,
+            NftSold {
+                listing: listing.key(),
+                mint: listing.mint,
+                seller: listing.seller,
+                buyer: ctx.accounts.group_buy.key(),
+                price: quote.price,
+                marketplace_fee: quote.marketplace_fee,
+                marketplace_fee_bps: schedule.marketplace_fee_bps,
+                royalty: quote.royalty,
+                seller_proceeds: quote.seller_proceeds,
+                maker_rebate: quote.maker_rebate,
+                is_primary,
+                payment_mint: None,
+                charity: None,
+                charity_amount: 0,
+                withheld: quote.withheld,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Takes the group's NFT (contributors only) by paying the other contributors' shares back in, at their
+    /// purchase price: group's NFT account (PDA) --> redeemer, who gets their contribution's rent back.
+    /// The others then get their shares with `refund_group_buy_contribution`.
+    /// Transfer hook extra accounts (if the mint has a hook) go in `remaining_accounts`.
+    pub fn redeem_group_nft<'info>(
+        ctx: Context<'_, '_, '_, 'info, RedeemGroupNft<'info>>,
+    ) -> Result<()> {
+        let group_buy = &ctx.accounts.group_buy;
+        require!(
+            group_buy.status == GroupBuyStatus::Executed,
+            ErrorCode::GroupBuyNotExecuted
+        );
+
+        let own_share = ctx.accounts.contribution.amount;
+        let others_shares = group_buy
+            .raised
+            .checked_sub(own_share)
+            .ok_or(ErrorCode::MathOverflow)?;
+        transfer_lamports(
+            &ctx.accounts.redeemer.to_account_info(),
+            &group_buy.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            others_shares,
+        )?;
+
+        let seeds = &[
+            PREFIX.as_bytes(),
+            b"group_buy",
+            group_buy.listing.as_ref(),
+            &[group_buy.bump],
+        ];
+        let signer = &[&seeds[..]];
+        let token_program = ctx.accounts.token_program.to_account_info();
+        let group_nft_account = ctx.accounts.group_nft_account.to_account_info();
+        transfer_nft(
+            &token_program,
+            &group_nft_account,
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.redeemer_nft_account.to_account_info(),
+            &group_buy.to_account_info(),
+            ctx.remaining_accounts,
+            signer,
+        )?;
+        close_token_account(
+            &token_program,
+            &group_nft_account,
+            &ctx.accounts.redeemer.to_account_info(),
+            &group_buy.to_account_info(),
+            signer,
+        )?;
+
+        let group_buy = &mut ctx.accounts.group_buy;
+        group_buy.raised = others_shares;
+        group_buy.status = GroupBuyStatus::Redeemed;
+
+        emit!(GroupNftRedeemed {
+            group_buy: group_buy.key(),
+            redeemer: ctx.accounts.redeemer.key(),
+            paid: others_shares,
+        });
+
+        Ok(())
+    }
+
+    /// Refunds a contribution (anyone can crank) once the deadline passed without the purchase executing,
+    /// or once the group's NFT was redeemed.
+    pub fn refund_group_buy_contribution(ctx: Context<RefundGroupBuyContribution>) -> Result<()> {
+        let group_buy = &ctx.accounts.group_buy;
+        if group_buy.status != GroupBuyStatus::Redeemed {
+            require!(
+                group_buy.status == GroupBuyStatus::Open,
+                ErrorCode::GroupBuyNotOpen
+            );
+            require!(
+                Clock::get()?.unix_timestamp >= group_buy.deadline,
+                ErrorCode::GroupBuyDeadlineNotPassed
+            );
+        }
+
+        let amount = ctx.accounts.contribution.amount;
+        transfer_lamports_from_pda(
+            &ctx.accounts.group_buy.to_account_info(),
+            &ctx.accounts.contributor,
+            amount,
+        )?;
+        let group_buy = &mut ctx.accounts.group_buy;
+        group_buy.raised -= amount;
+
+        emit!(GroupBuyRefunded {
+            group_buy: group_buy.key(),
+            contributor: ctx.accounts.contributor.key(),
+            amount,
+        });
+
+        Ok(())
+    }
+
+    /// Lets buyers of a SOL-priced listing pay in installments (seller only); `None` turns it off.
+    pub fn set_installment_terms(
+        ctx: Context<SetInstallmentTerms>,
+        terms: Option<InstallmentTerms>,
+    ) -> Result<()> {
+        let listing = &mut ctx.accounts.listing;
+        require!(listing.is_active, ErrorCode::InactiveListing);
+        if let Some(terms) = &terms {
+            require!(
+                listing.price_usd.is_none()
+                    && listing.payment_mint.is_none()
+                    && listing.payees.is_none()
+                    && listing.vesting_duration.is_none()
+                    && listing.gate_mint.is_none()
+                    && listing.allowlist_root.is_none()
+                    && listing.attestation.is_none()
+                    && listing.purchase_limit.is_none()
+                    && listing.voucher.is_none()
+                    && listing.receipt_window.is_none()
+                    && listing.charity_split.is_none()
+                    && listing.royalty_splits.is_none(),
+                ErrorCode::InstallmentsNotSupported
+            );
+            terms.validate()?;
+        }
+        listing.installment_terms = terms;
+
+        Ok(())
+    }
+
+    /// Buys a listing on its installment terms: the buyer pays the deposit now, & the NFT stays in the
+    /// vault until the full price is paid.
+    pub fn start_installment_plan(ctx: Context<StartInstallmentPlan>) -> Result<()> {
+        let listing = &ctx.accounts.listing;
+        require!(listing.is_active, ErrorCode::InactiveListing);
+        let now = Clock::get()?.unix_timestamp;
+        require!(!listing.is_expired(now), ErrorCode::ListingHasExpired);
+        require!(listing.has_started(now), ErrorCode::ListingNotStarted);
+        require_not_blocked(&ctx.accounts.blocked_mint)?;
+        let terms = listing
+            .installment_terms
+            .ok_or(ErrorCode::InstallmentsNotSupported)?;
+
+        let price = listing.sale_price(&ctx.accounts.config, None)?;
+        let deposit = bps_of(price, terms.deposit_bps)?;
+        let installment_amount = (price - deposit).div_ceil(terms.installments as u64);
+
+        transfer_lamports(
+            &ctx.accounts.buyer.to_account_info(),
+            &ctx.accounts.plan.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            deposit,
+        )?;
+
+        let plan = &mut ctx.accounts.plan;
+        plan.buyer = ctx.accounts.buyer.key();
+        plan.seller = listing.seller;
+        plan.listing = listing.key();
+        plan.mint = listing.mint;
+        plan.price = price;
+        plan.deposit = deposit;
+        plan.paid = deposit;
+        plan.installment_amount = installment_amount;
+        plan.period = terms.period;
+        plan.start_ts = now;
+        plan.next_due_ts = now
+            .checked_add(terms.period)
+            .ok_or(ErrorCode::MathOverflow)?;
+        plan.bump = ctx.bumps.plan;
+
+        // Reserved for the buyer: no one else can buy it meanwhile.
+        ctx.accounts.listing.accrue_rewards(Clock::get()?.slot);
+        ctx.accounts.listing.is_active = false;
+
+        emit!(InstallmentPlanStarted {
+            plan: plan.key(),
+            listing: plan.listing,
+            buyer: plan.buyer,
+            price,
+            deposit,
+            installment_amount,
+            next_due_ts: plan.next_due_ts,
+        });
+
+        Ok(())
+    }
+
+    /// Pays towards an installment plan (capped to the balance), before the current installment is overdue.
+    pub fn pay_installment(ctx: Context<PayInstallment>, amount: u64) -> Result<()> {
+        let plan = &ctx.accounts.plan;
+        require!(
+            Clock::get()?.unix_timestamp <= plan.next_due_ts,
+            ErrorCode::InstallmentOverdue
+        );
+        let amount = amount.min(plan.balance());
+        require!(amount > 0, ErrorCode::InvalidInstallmentAmount);
+
+        transfer_lamports(
+            &ctx.accounts.buyer.to_account_info(),
+            &ctx.accounts.plan.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            amount,
+        )?;
+
+        let plan = &mut ctx.accounts.plan;
+        plan.record_payment(amount)?;
+
+        emit!(InstallmentPaid {
+            plan: plan.key(),
+            buyer: plan.buyer,
+            amount,
+            balance: plan.balance(),
+            next_due_ts: plan.next_due_ts,
+        });
+
+        Ok(())
+    }
+
+    /// Completes a fully paid installment plan: pays out the sale, releases the NFT to the buyer & closes
+    /// the listing.
+    /// Transfer hook extra accounts (if the mint has a hook) go in `remaining_accounts`.
+    pub fn claim_installment_nft<'info>(
+        ctx: Context<'_, '_, '_, 'info, ClaimInstallmentNft<'info>>,
+    ) -> Result<()> {
+        let plan = &ctx.accounts.plan;
+        require!(plan.balance() == 0, ErrorCode::InstallmentPlanNotPaid);
+
+        let listing = &ctx.accounts.listing;
+        let is_primary = ctx.accounts.provenance.is_primary();
+        let schedule = listing_fee_schedule(
+            &ctx.accounts.config,
+            listing,
+            ctx.accounts.storefront.as_ref(),
+            is_primary,
+            0,
+            false,
+        )?;
+        let mut quote = SaleQuote::new(plan.price, is_primary, &schedule)?;
+        quote.route_royalty(&ctx.accounts.config, ctx.accounts.creator.is_some())?;
+
+        let fee_recipient = match &ctx.accounts.storefront {
+            Some(storefront) => storefront.treasury,
+            None => ctx.accounts.config.treasury,
+        };
+        require_keys_eq!(
+            ctx.accounts.treasury.key(),
+            fee_recipient,
+            ErrorCode::InvalidTreasury
+        );
+
+        quote.apply_withholding(&ctx.accounts.config)?;
+        quote.apply_insurance(&ctx.accounts.config)?;
+        pay_out_from_escrow(
+            &plan.to_account_info(),
+            &ctx.accounts.seller,
+            &ctx.accounts.treasury,
+            ctx.accounts.insurance_fund.as_mut(),
+            ctx.accounts.creator.as_ref(),
+            &quote,
+        )?;
+        pay_withholding(
+            ctx.accounts.withholding.as_mut(),
+            ctx.bumps.withholding,
+            ctx.accounts.seller.key(),
+            &plan.to_account_info(),
+            None,
+            quote.withheld,
+        )?;
+
+        // Transfer NFT = vault --> buyer.
+        let seeds = &[
+            PREFIX.as_bytes(),
+            b"vault",
+            plan.mint.as_ref(),
+            plan.listing.as_ref(),
+            &[ctx.bumps.vault],
+        ];
+        let signer = &[&seeds[..]];
+        transfer_nft(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.buyer_token_account.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            ctx.remaining_accounts,
+            signer,
+        )?;
+
+        close_token_account(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.rent_payer.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            signer,
+        )?;
+
+        ctx.accounts.provenance.record_sale(
+            &mut ctx.accounts.sale_record,
+            listing.seller,
+            plan.buyer,
+            quote.price,
+            None,
+            ctx.bumps.sale_record,
+        )?;
+        ctx.accounts.global_stats.record_sale(Some(quote.price))?;
+
+        emit_activity!(
+            ctx,
+            NftSold {
+                listing: listing.key(),
+                mint: listing.mint,
+                seller: listing.seller,
+                buyer: plan.buyer,
+                price: quote.price,
+                marketplace_fee: quote.marketplace_fee,
+                marketplace_fee_bps: schedule.marketplace_fee_bps,
+                royalty: quote.royalty,
+                seller_proceeds: quote.seller_proceeds,
+                maker_rebate: quote.maker_rebate,
+                is_primary,
+                payment_mint: None,
+                charity: None,
+                charity_amount: 0,
+                withheld: quote.withheld,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Takes back the NFT of an overdue installment plan (seller only): the deposit is forfeited to the seller,
+    /// later installments are refunded to the buyer, & the listing is closed.
+    /// Transfer hook extra accounts (if the mint has a hook) go in `remaining_accounts`.
+    pub fn reclaim_installment_nft<'info>(
+        ctx: Context<'_, '_, '_, 'info, ReclaimInstallmentNft<'info>>,
+    ) -> Result<()> {
+        let plan = &ctx.accounts.plan;
+        require!(plan.balance() > 0, ErrorCode::InstallmentPlanPaid);
+        require!(
+            Clock::get()?.unix_timestamp > plan.next_due_ts,
+            ErrorCode::InstallmentNotOverdue
+        );
+
+        let forfeited = plan.deposit;
+        let refunded = plan.paid - plan.deposit;
+        transfer_lamports_from_pda(
+            &plan.to_account_info(),
+            &ctx.accounts.seller.to_account_info(),
+            forfeited,
+        )?;
+
+        // Transfer back NFT vault --> seller.
+        let seeds = &[
+            PREFIX.as_bytes(),
+            b"vault",
+            plan.mint.as_ref(),
+            plan.listing.as_ref(),
+            &[ctx.bumps.vault],
+        ];
+        let signer = &[&seeds[..]];
+        transfer_nft(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.nft_account.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            ctx.remaining_accounts,
+            signer,
+        )?;
+
+        close_token_account(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.rent_payer.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            signer,
+        )?;
+
+        // The plan's remaining lamports (refund & rent) go back to the buyer when it closes.
+        emit!(InstallmentPlanDefaulted {
+            plan: plan.key(),
+            listing: plan.listing,
+            buyer: plan.buyer,
+            forfeited,
+            refunded,
+        });
+
+        Ok(())
+    }
+
+    /// Deposits `lamports` of an installment plan or auction bid escrow into `config.yield_stake_pool`
+    /// (escrow owner only: the plan's buyer, the bid's bidder), through the yield vault. The stake pool's
+    /// `DepositSol` accounts go in `remaining_accounts`, the yield vault as the (non-signer) depositor.
+    /// Settling or closing the escrow needs its lamports back: `unstake_escrow` first. Only what isn't owed
+    /// to the seller can be staked: a plan's installments past its deposit, or a revealed bid that's been
+    /// outbid (`place_bid` refuses staked bids, so it can't win while staked).
+    pub fn stake_escrow<'info>(
+        ctx: Context<'_, '_, '_, 'info, StakeEscrow<'info>>,
+        lamports: u64,
+    ) -> Result<()> {
+        let config = &ctx.accounts.config;
+        require!(
+            config.yield_stake_pool != Pubkey::default(),
+            ErrorCode::EscrowYieldDisabled
+        );
+        require!(
+            ctx.remaining_accounts.first().map(|account| account.key())
+                == Some(config.yield_stake_pool),
+            ErrorCode::InvalidEscrowYield
+        );
+        let escrow = ctx.accounts.escrow.to_account_info();
+        require_keys_eq!(
+            escrow_beneficiary(&escrow)?,
+            ctx.accounts.owner.key(),
+            ErrorCode::Unauthorized
+        );
+        let staked = ctx
+            .accounts
+            .escrow_yield
+            .principal
+            .checked_add(lamports)
+            .ok_or(ErrorCode::MathOverflow)?;
+        require_stakeable(&escrow, ctx.accounts.auction.as_ref(), staked)?;
+        let reserve = Rent::get()?.minimum_balance(escrow.data_len());
+        require!(
+            lamports > 0 && escrow.lamports().saturating_sub(reserve) >= lamports,
+            ErrorCode::InvalidEscrowYield
+        );
+
+        let vault = ctx.accounts.yield_vault.to_account_info();
+        let vault_seeds: &[&[u8]] = &[PREFIX.as_bytes(), b"yield_vault", &[ctx.bumps.yield_vault]];
+        transfer_lamports_from_pda(&escrow, &vault, lamports)?;
+
+        let vault_lamports = vault.lamports();
+        let pool_tokens_before = ctx.accounts.vault_pool_account.amount;
+        let mut data = vec![STAKE_POOL_DEPOSIT_SOL];
+        data.extend_from_slice(&lamports.to_le_bytes());
+        invoke_stake_pool(
+            &ctx.accounts.stake_pool_program,
+            &vault,
+            ctx.remaining_accounts,
+            data,
+            vault_seeds,
+        )?;
+
+        // The pool may only have taken the lamports for pool tokens.
+        ctx.accounts.vault_pool_account.reload()?;
+        let pool_account = &ctx.accounts.vault_pool_account;
+        require!(
+            vault_lamports - vault.lamports() == lamports
+                && pool_account.owner == vault.key()
+                && pool_account.delegate.is_none()
+                && pool_account.close_authority.is_none(),
+            ErrorCode::InvalidEscrowYield
+        );
+        let pool_tokens = pool_account
+            .amount
+            .checked_sub(pool_tokens_before)
+            .filter(|minted| *minted > 0)
+            .ok_or(ErrorCode::InvalidEscrowYield)?;
+
+        let escrow_yield = &mut ctx.accounts.escrow_yield;
+        escrow_yield.escrow = escrow.key();
+        escrow_yield.beneficiary = ctx.accounts.owner.key();
+        escrow_yield.principal = escrow_yield
+            .principal
+            .checked_add(lamports)
+            .ok_or(ErrorCode::MathOverflow)?;
+        escrow_yield.pool_tokens = escrow_yield
+            .pool_tokens
+            .checked_add(pool_tokens)
+            .ok_or(ErrorCode::MathOverflow)?;
+        escrow_yield.bump = ctx.bumps.escrow_yield;
+
+        emit!(EscrowStaked {
+            escrow: escrow.key(),
+            beneficiary: escrow_yield.beneficiary,
+            lamports,
+            pool_tokens,
+        });
+        Ok(())
+    }
+
+    /// Withdraws a staked escrow's pool tokens from the stake pool (anyone can crank, e.g. before
+    /// settlement): the principal goes back to the escrow (to its owner if it's been closed meanwhile),
+    /// the yield to the treasury or the escrow's owner per `config.yield_to_treasury`. A pool loss comes out
+    /// of the owner's side of the escrow: the plan's paid amount (the buyer pays it again) or the bid's
+    /// amount. The stake pool's `WithdrawSol` accounts go in `remaining_accounts`, the yield vault as the (non-signer) authority.
+    pub fn unstake_escrow<'info>(
+        ctx: Context<'_, '_, '_, 'info, UnstakeEscrow<'info>>,
+    ) -> Result<()> {
+        let config = &ctx.accounts.config;
+        require!(
+            ctx.remaining_accounts.first().map(|account| account.key())
+                == Some(config.yield_stake_pool)
+                || config.yield_stake_pool == Pubkey::default(),
+            ErrorCode::InvalidEscrowYield
+        );
+        let escrow_yield = &ctx.accounts.escrow_yield;
+        let (principal, pool_tokens) = (escrow_yield.principal, escrow_yield.pool_tokens);
+
+        let vault = ctx.accounts.yield_vault.to_account_info();
+        let vault_seeds: &[&[u8]] = &[PREFIX.as_bytes(), b"yield_vault", &[ctx.bumps.yield_vault]];
+        let vault_lamports = vault.lamports();
+        let pool_tokens_before = ctx.accounts.vault_pool_account.amount;
+        let mut data = vec![STAKE_POOL_WITHDRAW_SOL];
+        data.extend_from_slice(&pool_tokens.to_le_bytes());
+        invoke_stake_pool(
+            &ctx.accounts.stake_pool_program,
+            &vault,
+            ctx.remaining_accounts,
+            data,
+            vault_seeds,
+        )?;
+
+        ctx.accounts.vault_pool_account.reload()?;
+        require!(
+            pool_tokens_before - ctx.accounts.vault_pool_account.amount == pool_tokens,
+            ErrorCode::InvalidEscrowYield
+        );
+        let withdrawn = vault
+            .lamports()
+            .checked_sub(vault_lamports)
+            .ok_or(ErrorCode::InvalidEscrowYield)?;
+
+        let returned = withdrawn.min(principal);
+        let yield_lamports = withdrawn - returned;
+        let escrow = ctx.accounts.escrow.to_account_info();
+        let beneficiary = ctx.accounts.beneficiary.to_account_info();
+        let escrow_open = *escrow.owner == crate::ID
+            && escrow_beneficiary(&escrow).is_ok_and(|owner| owner == beneficiary.key());
+        let principal_to = if escrow_open { &escrow } else { &beneficiary };
+        let yield_to = if config.yield_to_treasury {
+            ctx.accounts.treasury.to_account_info()
+        } else {
+            beneficiary.clone()
+        };
+        let system_program = ctx.accounts.system_program.to_account_info();
+
+        // A pool loss is the owner's: the escrow's accounting drops with its lamports.
+        let shortfall = if escrow_open { principal - returned } else { 0 };
+        if shortfall > 0 {
+            deduct_escrow_shortfall(&escrow, shortfall)?;
+        }
+        for (to, amount) in [(principal_to, returned), (&yield_to, yield_lamports)] {
+            if amount > 0 {
+                invoke_signed(
+                    &system_instruction::transfer(vault.key, to.key, amount),
+                    &[vault.clone(), to.clone(), system_program.clone()],
+                    &[vault_seeds],
+                )?;
+            }
+        }
+
+        emit!(EscrowUnstaked {
+            escrow: escrow.key(),
+            principal: returned,
+            shortfall,
+            yield_lamports,
+            yield_recipient: yield_to.key(),
+        });
+        Ok(())
+    }
+
+    /// Offers the seller of an active listing a loan of `principal` lamports (lender only), escrowed in
+    /// the offer, against the vaulted NFT: `repayment` is due within `duration` seconds of acceptance.
+    pub fn make_loan_offer(
+        ctx: Context<MakeLoanOffer>,
+        principal: u64,
+        repayment: u64,
+        duration: i64,
+    ) -> Result<()> {
+        let listing = &ctx.accounts.listing;
+        require!(listing.is_active, ErrorCode::InactiveListing);
+        require!(
+            principal > 0 && repayment >= principal && duration > 0,
+            ErrorCode::InvalidLoanOffer
+        );
+        transfer_lamports(
+            &ctx.accounts.lender.to_account_info(),
+            &ctx.accounts.loan_offer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            principal,
+        )?;
+
+        let loan_offer = &mut ctx.accounts.loan_offer;
+        loan_offer.lender = ctx.accounts.lender.key();
+        loan_offer.listing = listing.key();
+        loan_offer.mint = listing.mint;
+        loan_offer.principal = principal;
+        loan_offer.repayment = repayment;
+        loan_offer.duration = duration;
+        loan_offer.borrower = None;
+        loan_offer.due_ts = 0;
+        loan_offer.bump = ctx.bumps.loan_offer;
+
+        emit!(LoanOffered {
+            loan_offer: loan_offer.key(),
+            listing: loan_offer.listing,
+            lender: loan_offer.lender,
+            principal,
+            repayment,
+            duration,
+        });
+        Ok(())
+    }
+
+    /// Withdraws a loan offer not yet accepted (lender only), refunding the principal.
+    pub fn cancel_loan_offer(ctx: Context<CancelLoanOffer>) -> Result<()> {
+        require!(
+            ctx.accounts.loan_offer.borrower.is_none(),
+            ErrorCode::InvalidLoanOffer
+        );
+        Ok(())
+    }
+
+    /// Borrows against the listed NFT (seller only): the offer's principal goes to the seller, the NFT
+    /// stays in the vault as collateral & the listing is paused (unbuyable, undelistable) until repaid.
+    pub fn accept_loan(ctx: Context<AcceptLoan>) -> Result<()> {
+        let listing = &mut ctx.accounts.listing;
+        require!(
+            listing.is_active && listing.loan.is_none(),
+            ErrorCode::InactiveListing
+        );
+        let loan_offer = &mut ctx.accounts.loan_offer;
+        require!(loan_offer.borrower.is_none(), ErrorCode::InvalidLoanOffer);
+
+        transfer_lamports_from_pda(
+            &loan_offer.to_account_info(),
+            &ctx.accounts.seller.to_account_info(),
+            loan_offer.principal,
+        )?;
+        let now = Clock::get()?.unix_timestamp;
+        loan_offer.borrower = Some(listing.seller);
+        loan_offer.due_ts = now
+            .checked_add(loan_offer.duration)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        listing.accrue_rewards(Clock::get()?.slot);
+        listing.is_active = false;
+        listing.loan = Some(loan_offer.key());
+
+        emit!(LoanAccepted {
+            loan_offer: loan_offer.key(),
+            listing: listing.key(),
+            borrower: listing.seller,
+            lender: loan_offer.lender,
+            principal: loan_offer.principal,
+            due_ts: loan_offer.due_ts,
+        });
+        Ok(())
+    }
+
+    /// Repays a loan (borrower only, until the lender forecloses): `repayment` goes to the lender & the
+    /// listing's active again.
+    pub fn repay_loan(ctx: Context<RepayLoan>) -> Result<()> {
+        let loan_offer = &ctx.accounts.loan_offer;
+        transfer_lamports(
+            &ctx.accounts.borrower.to_account_info(),
+            &ctx.accounts.lender.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            loan_offer.repayment,
+        )?;
+
+        let listing = &mut ctx.accounts.listing;
+        listing.accrue_rewards(Clock::get()?.slot);
+        listing.is_active = true;
+        listing.loan = None;
+
+        emit!(LoanRepaid {
+            loan_offer: loan_offer.key(),
+            listing: listing.key(),
+            borrower: ctx.accounts.borrower.key(),
+            lender: loan_offer.lender,
+            repayment: loan_offer.repayment,
+        });
+        Ok(())
+    }
+
+    /// Takes the collateral of an overdue loan (lender only): NFT vault --> lender, & the listing is closed.
+    /// Transfer hook extra accounts (if the mint has a hook) go in `remaining_accounts`.
+    pub fn foreclose_loan<'info>(
+        ctx: Context<'_, '_, '_, 'info, ForecloseLoan<'info>>,
+    ) -> Result<()> {
+        let loan_offer = &ctx.accounts.loan_offer;
+        require!(
+            Clock::get()?.unix_timestamp > loan_offer.due_ts,
+            ErrorCode::LoanNotDue
+        );
+
+        // Transfer NFT = vault --> lender.
+        let listing_key = ctx.accounts.listing.key();
+        let seeds = &[
+            PREFIX.as_bytes(),
+            b"vault",
+            loan_offer.mint.as_ref(),
+            listing_key.as_ref(),
+            &[ctx.bumps.vault],
+        ];
+        let signer = &[&seeds[..]];
+        transfer_nft(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.lender_nft_account.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            ctx.remaining_accounts,
+            signer,
+        )?;
+        close_token_account(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.rent_payer.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            signer,
+        )?;
+
+        emit!(LoanForeclosed {
+            loan_offer: loan_offer.key(),
+            listing: listing_key,
+            borrower: ctx.accounts.listing.seller,
+            lender: loan_offer.lender,
+            mint: loan_offer.mint,
+        });
+        Ok(())
+    }
+
+    /// Offers an NFT for rent: owner --> vault, rentable for `duration` seconds against a `fee` & `collateral`
+    /// (lamports). Transfer hook extra accounts (if the mint has a hook) go in `remaining_accounts`.
+    pub fn list_for_rent<'info>(
+        ctx: Context<'_, '_, '_, 'info, ListForRent<'info>>,
+        fee: u64,
+        collateral: u64,
+        duration: i64,
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.paused, ErrorCode::MarketplacePaused);
+        require!(
+            collateral > 0 && duration > 0,
+            ErrorCode::InvalidRentalTerms
+        );
+        validate_listable_mint(&ctx.accounts.mint.to_account_info())?;
+        require_not_blocked(&ctx.accounts.blocked_mint)?;
+
+        transfer_nft(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.nft_account.to_account_info(),
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.owner.to_account_info(),
+            ctx.remaining_accounts,
+            &[],
+        )?;
+
+        ctx.accounts.vault_deposit.record(
+            ctx.accounts.mint.key(),
+            ctx.accounts.owner.key(),
+            ctx.accounts.rental.key(),
+            ctx.bumps.vault_deposit,
+        );
+
+        let rental = &mut ctx.accounts.rental;
+        rental.owner = ctx.accounts.owner.key();
+        rental.mint = ctx.accounts.mint.key();
+        rental.fee = fee;
+        rental.collateral = collateral;
+        rental.duration = duration;
+        rental.renter = None;
+        rental.rented_until = 0;
+        rental.bump = ctx.bumps.rental;
+
+        Ok(())
+    }
+
+    /// Rents a listed NFT: pays the fee (less the secondary marketplace fee) to the owner, escrows the
+    /// collateral & takes the NFT until `rented_until`.
+    pub fn rent_nft<'info>(ctx: Context<'_, '_, '_, 'info, RentNft<'info>>) -> Result<()> {
+        require!(!ctx.accounts.config.paused, ErrorCode::MarketplacePaused);
+        let rental = &ctx.accounts.rental;
+        require!(rental.renter.is_none(), ErrorCode::NftAlreadyRented);
+
+        let marketplace_fee = bps_of(
+            rental.fee,
+            ctx.accounts.config.fee_schedule(false).marketplace_fee_bps,
+        )?;
+        let system_program = ctx.accounts.system_program.to_account_info();
+        let renter = ctx.accounts.renter.to_account_info();
+        transfer_lamports(
+            &renter,
+            &ctx.accounts.owner,
+            &system_program,
+            rental.fee - marketplace_fee,
+        )?;
+        transfer_lamports(
+            &renter,
+            &ctx.accounts.treasury,
+            &system_program,
+            marketplace_fee,
+        )?;
+        transfer_lamports(
+            &renter,
+            &ctx.accounts.rental.to_account_info(),
+            &system_program,
+            rental.collateral,
+        )?;
+
+        // Transfer NFT = vault --> renter.
+        let seeds = &[
+            PREFIX.as_bytes(),
+            b"vault",
+            rental.mint.as_ref(),
+            &[ctx.bumps.vault],
+        ];
+        let signer = &[&seeds[..]];
+        transfer_nft(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.renter_nft_account.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            ctx.remaining_accounts,
+            signer,
+        )?;
+
+        let rental = &mut ctx.accounts.rental;
+        rental.renter = Some(ctx.accounts.renter.key());
+        rental.rented_until = Clock::get()?
+            .unix_timestamp
+            .checked_add(rental.duration)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        emit!(NftRented {
+            rental: rental.key(),
+            mint: rental.mint,
+            owner: rental.owner,
+            renter: ctx.accounts.renter.key(),
+            fee: rental.fee,
+            collateral: rental.collateral,
+            rented_until: rental.rented_until,
+        });
+
+        Ok(())
+    }
+
+    /// Returns a rented NFT renter --> vault & refunds the collateral; the NFT is rentable again.
+    /// Late returns are accepted as long as the owner hasn't seized the collateral.
+    pub fn return_nft<'info>(ctx: Context<'_, '_, '_, 'info, ReturnNft<'info>>) -> Result<()> {
+        let rental = &ctx.accounts.rental;
+        require!(
+            rental.renter == Some(ctx.accounts.renter.key()),
+            ErrorCode::NotRenter
+        );
+
+        transfer_nft(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.renter_nft_account.to_account_info(),
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.renter.to_account_info(),
+            ctx.remaining_accounts,
+            &[],
+        )?;
+        transfer_lamports_from_pda(
+            &rental.to_account_info(),
+            &ctx.accounts.renter.to_account_info(),
+            rental.collateral,
+        )?;
+
+        let rental = &mut ctx.accounts.rental;
+        rental.renter = None;
+        rental.rented_until = 0;
+
+        emit!(NftReturned {
+            rental: rental.key(),
+            mint: rental.mint,
+            renter: ctx.accounts.renter.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Owner takes the collateral of a rental not returned by its deadline, closing the rental.
+    pub fn seize_rental_collateral(ctx: Context<SeizeRentalCollateral>) -> Result<()> {
+        let rental = &ctx.accounts.rental;
+        let renter = rental.renter.ok_or(ErrorCode::NftNotRented)?;
+        require!(
+            Clock::get()?.unix_timestamp > rental.rented_until,
+            ErrorCode::RentalNotOverdue
+        );
+
+        // The collateral goes to the owner with the rental's lamports on close.
+        emit!(RentalCollateralSeized {
+            rental: rental.key(),
+            mint: rental.mint,
+            owner: rental.owner,
+            renter,
+            collateral: rental.collateral,
+        });
+
+        Ok(())
+    }
+
+    /// Withdraws an NFT from rent while it isn't rented: vault --> owner, closing the rental.
+    /// Transfer hook extra accounts (if the mint has a hook) go in `remaining_accounts`.
+    pub fn delist_rental<'info>(
+        ctx: Context<'_, '_, '_, 'info, DelistRental<'info>>,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.rental.renter.is_none(),
+            ErrorCode::NftAlreadyRented
+        );
+
+        let seeds = &[
+            PREFIX.as_bytes(),
+            b"vault",
+            ctx.accounts.rental.mint.as_ref(),
+            &[ctx.bumps.vault],
+        ];
+        let signer = &[&seeds[..]];
+        transfer_nft(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.nft_account.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            ctx.remaining_accounts,
+            signer,
+        )?;
+
+        Ok(())
+    }
+
+    /// Turns an active listing into `total_shares` fungible share tokens minted to the seller; the NFT stays
+    /// in the vault until redeemed or bought out (bids of at least `buyout_reserve` lamports, each buyout
+    /// auction running `buyout_duration` seconds from its first bid). Closes the listing.
+    pub fn fractionalize_listing(
+        ctx: Context<FractionalizeListing>,
+        total_shares: u64,
+        buyout_reserve: u64,
+        buyout_duration: i64,
+    ) -> Result<()> {
+        require!(ctx.accounts.listing.is_active, ErrorCode::InactiveListing);
+        require!(
+            total_shares > 0 && buyout_reserve > 0 && buyout_duration > 0,
+            ErrorCode::InvalidFractionParams
+        );
+
+        let fraction = &mut ctx.accounts.fraction;
+        fraction.curator = ctx.accounts.seller.key();
+        fraction.mint = ctx.accounts.mint.key();
+        fraction.listing = ctx.accounts.listing.key();
+        fraction.share_mint = ctx.accounts.share_mint.key();
+        fraction.total_shares = total_shares;
+        fraction.buyout_reserve = buyout_reserve;
+        fraction.buyout_duration = buyout_duration;
+        fraction.status = FractionStatus::Shares;
+        fraction.buyout_end_ts = 0;
+        fraction.highest_bid = 0;
+        fraction.highest_bidder = None;
+        fraction.proceeds_remaining = 0;
+        fraction.bump = ctx.bumps.fraction;
+
+        let seeds = &[
+            PREFIX.as_bytes(),
+            b"fraction",
+            fraction.mint.as_ref(),
+            &[fraction.bump],
+        ];
+        token_interface::mint_to(
+            CpiContext::new_with_signer(
+                ctx.accounts.share_token_program.to_account_info(),
+                token_interface::MintTo {
+                    mint: ctx.accounts.share_mint.to_account_info(),
+                    to: ctx.accounts.seller_share_account.to_account_info(),
+                    authority: fraction.to_account_info(),
+                },
+                &[&seeds[..]],
+            ),
+            total_shares,
+        )?;
+
+        emit!(ListingFractionalized {
+            fraction: fraction.key(),
+            listing: ctx.accounts.listing.key(),
+            mint: fraction.mint,
+            share_mint: fraction.share_mint,
+            total_shares,
+        });
+
+        Ok(())
+    }
+
+    /// Burns every share of a fractionalized NFT to withdraw it: vault --> holder.
+    /// Not possible once a buyout auction started. Transfer hook extra accounts go in `remaining_accounts`.
+    pub fn redeem_fraction<'info>(
+        ctx: Context<'_, '_, '_, 'info, RedeemFraction<'info>>,
+    ) -> Result<()> {
+        let fraction = &ctx.accounts.fraction;
+        require!(
+            fraction.status == FractionStatus::Shares,
+            ErrorCode::InvalidFractionStatus
+        );
+        require!(
+            ctx.accounts.share_mint.supply == fraction.total_shares
+                && ctx.accounts.holder_share_account.amount == fraction.total_shares,
+            ErrorCode::InsufficientShares
+        );
+
+        burn_tokens(
+            &ctx.accounts.share_token_program.to_account_info(),
+            &ctx.accounts.share_mint.to_account_info(),
+            &ctx.accounts.holder_share_account.to_account_info(),
+            &ctx.accounts.holder.to_account_info(),
+            fraction.total_shares,
+        )?;
+
+        let seeds = &[
+            PREFIX.as_bytes(),
+            b"vault",
+            fraction.mint.as_ref(),
+            fraction.listing.as_ref(),
+            &[ctx.bumps.vault],
+        ];
+        let signer = &[&seeds[..]];
+        transfer_nft(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.holder_nft_account.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            ctx.remaining_accounts,
+            signer,
+        )?;
+
+        close_token_account(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.curator.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            signer,
+        )?;
+
+        ctx.accounts.fraction.status = FractionStatus::Redeemed;
+
+        emit!(FractionRedeemed {
+            fraction: ctx.accounts.fraction.key(),
+            mint: ctx.accounts.fraction.mint,
+            holder: ctx.accounts.holder.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Bids lamports to buy out a fractionalized NFT; the first bid starts the buyout auction.
+    /// Bids are escrowed in the fraction PDA, & the previous highest bidder is refunded.
+    pub fn bid_buyout(ctx: Context<BidBuyout>, amount: u64) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let fraction = &ctx.accounts.fraction;
+        match fraction.status {
+            FractionStatus::Shares => {}
+            FractionStatus::Buyout => {
+                require!(now < fraction.buyout_end_ts, ErrorCode::AuctionEnded)
+            }
+            _ => return err!(ErrorCode::InvalidFractionStatus),
+        }
+        require!(
+            amount >= fraction.buyout_reserve && amount > fraction.highest_bid,
+            ErrorCode::BidTooLow
+        );
+
+        transfer_lamports(
+            &ctx.accounts.bidder.to_account_info(),
+            &fraction.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            amount,
+        )?;
+        if let Some(previous_bidder) = fraction.highest_bidder {
+            let previous = ctx
+                .accounts
+                .previous_bidder
+                .as_ref()
+                .ok_or(ErrorCode::MissingPreviousBidder)?;
+            require_keys_eq!(
+                previous.key(),
+                previous_bidder,
+                ErrorCode::MissingPreviousBidder
+            );
+            transfer_lamports_from_pda(
+                &fraction.to_account_info(),
+                previous,
+                fraction.highest_bid,
+            )?;
+        }
+
+        let fraction = &mut ctx.accounts.fraction;
+        if fraction.status == FractionStatus::Shares {
+            fraction.status = FractionStatus::Buyout;
+            fraction.buyout_end_ts = now + fraction.buyout_duration;
+        }
+        fraction.highest_bid = amount;
+        fraction.highest_bidder = Some(ctx.accounts.bidder.key());
+
+        emit!(BuyoutBidPlaced {
+            fraction: fraction.key(),
+            bidder: ctx.accounts.bidder.key(),
+            amount,
+            buyout_end_ts: fraction.buyout_end_ts,
+        });
+
+        Ok(())
+    }
+
+    /// Ends a buyout auction (anyone can crank): NFT vault --> winner, the winning bid becomes claimable
+    /// by share holders. Transfer hook extra accounts (if the mint has a hook) go in `remaining_accounts`.
+    pub fn settle_buyout<'info>(
+        ctx: Context<'_, '_, '_, 'info, SettleBuyout<'info>>,
+    ) -> Result<()> {
+        let fraction = &ctx.accounts.fraction;
+        require!(
+            fraction.status == FractionStatus::Buyout,
+            ErrorCode::InvalidFractionStatus
+        );
+        require!(
+            Clock::get()?.unix_timestamp >= fraction.buyout_end_ts,
+            ErrorCode::AuctionNotEnded
+        );
+
+        let seeds = &[
+            PREFIX.as_bytes(),
+            b"vault",
+            fraction.mint.as_ref(),
+            fraction.listing.as_ref(),
+            &[ctx.bumps.vault],
+        ];
+        let signer = &[&seeds[..]];
+        transfer_nft(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.winner_nft_account.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            ctx.remaining_accounts,
+            signer,
+        )?;
+
+        close_token_account(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.curator.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            signer,
+        )?;
+
+        let fraction = &mut ctx.accounts.fraction;
+        fraction.status = FractionStatus::BoughtOut;
+        fraction.proceeds_remaining = fraction.highest_bid;
+
+        emit!(BuyoutSettled {
+            fraction: fraction.key(),
+            mint: fraction.mint,
+            winner: ctx.accounts.winner_nft_account.owner,
+            price: fraction.highest_bid,
+        });
+
+        Ok(())
+    }
+
+    /// Burns the holder's shares of a bought-out NFT for their pro-rata part of the winning bid.
+    pub fn claim_buyout_proceeds(ctx: Context<ClaimBuyoutProceeds>) -> Result<()> {
+        let fraction = &ctx.accounts.fraction;
+        require!(
+            fraction.status == FractionStatus::BoughtOut,
+            ErrorCode::InvalidFractionStatus
+        );
+        let shares = ctx.accounts.holder_share_account.amount;
+        require!(shares > 0, ErrorCode::InsufficientShares);
+
+        // Pro-rata of what's left over the shares left, so the last holder gets any rounding dust.
+        let payout = (fraction.proceeds_remaining as u128 * shares as u128
+            / ctx.accounts.share_mint.supply as u128) as u64;
+
+        burn_tokens(
+            &ctx.accounts.share_token_program.to_account_info(),
+            &ctx.accounts.share_mint.to_account_info(),
+            &ctx.accounts.holder_share_account.to_account_info(),
+            &ctx.accounts.holder.to_account_info(),
+            shares,
+        )?;
+        transfer_lamports_from_pda(
+            &fraction.to_account_info(),
+            &ctx.accounts.holder.to_account_info(),
+            payout,
+        )?;
+
+        let fraction = &mut ctx.accounts.fraction;
+        fraction.proceeds_remaining -= payout;
+
+        emit!(BuyoutProceedsClaimed {
+            fraction: fraction.key(),
+            holder: ctx.accounts.holder.key(),
+            shares,
+            amount: payout,
+        });
+
+        Ok(())
+    }
+
+    /// Lists `supply` units of an open edition mint (0 decimals) for primary sale: seller --> vault,
+    /// each unit priced along `curve` from `base_price` lamports by the units already sold.
+    pub fn list_edition(
+        ctx: Context<ListEdition>,
+        base_price: u64,
+        supply: u64,
+        curve: BondingCurve,
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.paused, ErrorCode::MarketplacePaused);
+        require!(
+            base_price > 0 && supply > 0 && ctx.accounts.mint.decimals == 0 && curve.is_valid(),
+            ErrorCode::InvalidEditionParams
+        );
+        require_not_blocked(&ctx.accounts.blocked_mint)?;
+
+        transfer_tokens(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.seller_token_account.to_account_info(),
+            &ctx.accounts.mint,
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.seller.to_account_info(),
+            supply,
+            &[],
+        )?;
+
+        let edition_listing = &mut ctx.accounts.edition_listing;
+        edition_listing.seller = ctx.accounts.seller.key();
+        edition_listing.mint = ctx.accounts.mint.key();
+        edition_listing.base_price = base_price;
+        edition_listing.curve = curve;
+        edition_listing.supply = supply;
+        edition_listing.units_sold = 0;
+        edition_listing.bump = ctx.bumps.edition_listing;
+
+        emit!(EditionListed {
+            edition_listing: edition_listing.key(),
+            mint: edition_listing.mint,
+            seller: edition_listing.seller,
+            base_price,
+            supply,
+        });
+
+        Ok(())
+    }
+
+    /// Buys the next `quantity` units of an edition listing along its curve (a partial fill when fewer
+    /// than remain), the total capped at `max_price` since earlier purchases in the same slot raise it.
+    /// Selling the last unit closes the listing & its vault to the seller. Always a primary sale:
+    /// primary fees apply & the royalty stays with the seller (the creator).
+    pub fn buy_edition(ctx: Context<BuyEdition>, quantity: u64, max_price: u64) -> Result<()> {
+        require!(!ctx.accounts.config.paused, ErrorCode::MarketplacePaused);
+        let edition_listing = &ctx.accounts.edition_listing;
+        require!(
+            edition_listing.remaining_units() > 0,
+            ErrorCode::EditionSoldOut
+        );
+        require!(
+            quantity > 0 && quantity <= edition_listing.remaining_units(),
+            ErrorCode::InvalidEditionQuantity
+        );
+        let price = edition_listing.fill_price(quantity)?;
+        require!(price <= max_price, ErrorCode::EditionPriceAboveMax);
+
+        let mut quote = SaleQuote::new(price, true, &ctx.accounts.config.primary_fees)?;
+        quote.seller_proceeds += quote.royalty;
+        quote.royalty = 0;
+        quote.apply_maker_rebate(&ctx.accounts.config)?;
+        quote.apply_withholding(&ctx.accounts.config)?;
+        let seller_amount = quote.seller_proceeds + quote.maker_rebate;
+
+        let buyer = ctx.accounts.buyer.to_account_info();
+        let system_program = ctx.accounts.system_program.to_account_info();
+        transfer_lamports(&buyer, &ctx.accounts.seller, &system_program, seller_amount)?;
+        pay_withholding(
+            ctx.accounts.withholding.as_mut(),
+            ctx.bumps.withholding,
+            ctx.accounts.seller.key(),
+            &buyer,
+            Some(&system_program),
+            quote.withheld,
+        )?;
+        quote.apply_insurance(&ctx.accounts.config)?;
+        transfer_lamports(
+            &buyer,
+            &ctx.accounts.treasury,
+            &system_program,
+            quote.marketplace_fee,
+        )?;
+        pay_insurance(
+            ctx.accounts.insurance_fund.as_mut(),
+            &buyer,
+            Some(&system_program),
+            quote.insurance,
+        )?;
+
+        let edition_listing_key = edition_listing.key();
+        let mint = edition_listing.mint;
+        let seeds = &[
+            PREFIX.as_bytes(),
+            b"vault",
+            mint.as_ref(),
+            edition_listing_key.as_ref(),
+            &[ctx.bumps.vault],
+        ];
+        transfer_tokens(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.mint,
+            &ctx.accounts.buyer_token_account.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            quantity,
+            &[&seeds[..]],
+        )?;
+
+        let edition_listing = &mut ctx.accounts.edition_listing;
+        edition_listing.units_sold += quantity;
+        ctx.accounts.global_stats.record_sale(Some(price))?;
+
+        let units_remaining = edition_listing.remaining_units();
+        emit!(EditionSold {
+            edition_listing: edition_listing_key,
+            mint: edition_listing.mint,
+            seller: edition_listing.seller,
+            buyer: ctx.accounts.buyer.key(),
+            price,
+            marketplace_fee: quote.marketplace_fee,
+            seller_proceeds: seller_amount,
+            quantity,
+            units_sold: edition_listing.units_sold,
+            units_remaining,
+        });
+
+        // Fully filled: nothing left to sell, the listing & its vault close.
+        if units_remaining == 0 {
+            close_token_account(
+                &ctx.accounts.token_program.to_account_info(),
+                &ctx.accounts.vault.to_account_info(),
+                &ctx.accounts.seller,
+                &ctx.accounts.vault.to_account_info(),
+                &[&seeds[..]],
+            )?;
+            ctx.accounts
+                .edition_listing
+                .close(ctx.accounts.seller.to_account_info())?;
+        }
+
+        Ok(())
+    }
+
+    /// Ends an edition listing (seller only): unsold units go back to the seller, the vault & listing close.
+    pub fn close_edition(ctx: Context<CloseEdition>) -> Result<()> {
+        let edition_listing = &ctx.accounts.edition_listing;
+        let edition_listing_key = edition_listing.key();
+        let seeds = &[
+            PREFIX.as_bytes(),
+            b"vault",
+            edition_listing.mint.as_ref(),
+            edition_listing_key.as_ref(),
+            &[ctx.bumps.vault],
+        ];
+        let signer = &[&seeds[..]];
+        let token_program = ctx.accounts.token_program.to_account_info();
+        let vault = ctx.accounts.vault.to_account_info();
+        transfer_tokens(
+            &token_program,
+            &vault,
+            &ctx.accounts.mint,
+            &ctx.accounts.seller_token_account.to_account_info(),
+            &vault,
+            ctx.accounts.vault.amount,
+            signer,
+        )?;
+        close_token_account(
+            &token_program,
+            &vault,
+            &ctx.accounts.seller.to_account_info(),
+            &vault,
+            signer,
+        )?;
+
+        emit!(EditionClosed {
+            edition_listing: edition_listing_key,
+            mint: edition_listing.mint,
+            seller: edition_listing.seller,
+            units_sold: edition_listing.units_sold,
+        });
+
+        Ok(())
+    }
+
+    /// Opens a two-sided liquidity pool for `collection`: the owner deposits SOL & NFTs of the
+    /// collection, traders buy from & sell into it at `spot_price`, which moves along `curve` with each
+    /// trade. Traders pay the owner `fee_bps` of the spot price on top, accruing in the pool.
+    pub fn create_pool(
+        ctx: Context<CreatePool>,
+        collection: Pubkey,
+        spot_price: u64,
+        curve: BondingCurve,
+        fee_bps: u16,
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.paused, ErrorCode::MarketplacePaused);
+        require!(
+            spot_price > 0 && curve.is_valid() && fee_bps as u64 <= BPS_DENOMINATOR,
+            ErrorCode::InvalidPoolParams
+        );
+        let pool = &mut ctx.accounts.pool;
+        pool.owner = ctx.accounts.owner.key();
+        pool.collection = collection;
+        pool.spot_price = spot_price;
+        pool.curve = curve;
+        pool.fee_bps = fee_bps;
+        pool.sol_balance = 0;
+        pool.nft_count = 0;
+        pool.bump = ctx.bumps.pool;
+
+        emit!(PoolCreated {
+            pool: pool.key(),
+            owner: pool.owner,
+            collection,
+            spot_price,
+            fee_bps,
+        });
+
+        Ok(())
+    }
+
+    /// Adds `amount` lamports of buy-side liquidity (owner only).
+    pub fn deposit_pool_sol(ctx: Context<UpdatePoolSol>, amount: u64) -> Result<()> {
+        transfer_lamports(
+            &ctx.accounts.owner.to_account_info(),
+            &ctx.accounts.pool.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            amount,
+        )?;
+        let pool = &mut ctx.accounts.pool;
+        pool.sol_balance = pool
+            .sol_balance
+            .checked_add(amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Takes `amount` lamports (deposits & accrued trades) out of the pool (owner only).
+    pub fn withdraw_pool_sol(ctx: Context<UpdatePoolSol>, amount: u64) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        pool.sol_balance = pool
+            .sol_balance
+            .checked_sub(amount)
+            .ok_or(ErrorCode::InsufficientPoolFunds)?;
+        transfer_lamports_from_pda(&pool.to_account_info(), &ctx.accounts.owner, amount)
+    }
+
+    /// Adds an NFT of the pool's collection to its sell side: owner --> per-pool vault (owner only).
+    /// Transfer hook extra accounts (if the mint has a hook) go in `remaining_accounts`.
+    pub fn deposit_pool_nft<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositPoolNft<'info>>,
+    ) -> Result<()> {
+        let mint = ctx.accounts.mint.key();
+        require!(
+            verified_collection(&mint, &ctx.accounts.metadata)?
+                == Some(ctx.accounts.pool.collection),
+            ErrorCode::CollectionMismatch
+        );
+        validate_listable_mint(&ctx.accounts.mint.to_account_info())?;
+        require_not_blocked(&ctx.accounts.blocked_mint)?;
+        let owner = ctx.accounts.owner.key();
+        ctx.accounts
+            .provenance
+            .init_if_new(mint, owner, ctx.bumps.provenance);
+
+        transfer_nft(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.nft_account.to_account_info(),
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.owner.to_account_info(),
+            ctx.remaining_accounts,
+            &[],
+        )?;
+        ctx.accounts.pool.nft_count += 1;
+        Ok(())
+    }
+
+    /// Takes an NFT back out of the pool (owner only), closing its vault.
+    /// Transfer hook extra accounts (if the mint has a hook) go in `remaining_accounts`.
+    pub fn withdraw_pool_nft<'info>(
+        ctx: Context<'_, '_, '_, 'info, WithdrawPoolNft<'info>>,
+    ) -> Result<()> {
+        let pool_key = ctx.accounts.pool.key();
+        let seeds = &[
+            PREFIX.as_bytes(),
+            b"vault",
+            ctx.accounts.vault.mint.as_ref(),
+            pool_key.as_ref(),
+            &[ctx.bumps.vault],
+        ];
+        let signer = &[&seeds[..]];
+        transfer_nft(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.owner_nft_account.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            ctx.remaining_accounts,
+            signer,
+        )?;
+        close_token_account(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.owner.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            signer,
+        )?;
+        ctx.accounts.pool.nft_count -= 1;
+        Ok(())
+    }
+
+    /// Buys `mint` out of a pool for its spot price (at most `max_price`) plus the pool fee. The
+    /// marketplace fee & royalty come out of the spot price, the rest accrues in the pool; the spot
+    /// price then steps up. Transfer hook extra accounts (if the mint has a hook) go in `remaining_accounts`.
+    pub fn pool_buy<'info>(
+        ctx: Context<'_, '_, '_, 'info, PoolBuy<'info>>,
+        max_price: u64,
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.paused, ErrorCode::MarketplacePaused);
+        let pool = &ctx.accounts.pool;
+        let price = pool.spot_price;
+        require!(price <= max_price, ErrorCode::PoolPriceOutOfBounds);
+        require_not_blocked(&ctx.accounts.blocked_mint)?;
+
+        let is_primary = ctx.accounts.provenance.is_primary();
+        let schedule = *ctx.accounts.config.fee_schedule(is_primary);
+        let mut quote = SaleQuote::new(price, is_primary, &schedule)?;
+        quote.route_royalty(&ctx.accounts.config, ctx.accounts.creator.is_some())?;
+        // The pool is the maker: its rebate accrues with the proceeds, less the owner's withholding.
+        quote.apply_maker_rebate(&ctx.accounts.config)?;
+        quote.apply_withholding(&ctx.accounts.config)?;
+        let lp_fee = bps_of(price, pool.fee_bps)?;
+        let pool_amount = quote
+            .seller_proceeds
+            .checked_add(quote.maker_rebate)
+            .and_then(|amount| amount.checked_add(lp_fee))
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        let buyer = ctx.accounts.buyer.to_account_info();
+        let system_program = ctx.accounts.system_program.to_account_info();
+        transfer_lamports(
+            &buyer,
+            &pool.to_account_info(),
+            &system_program,
+            pool_amount,
+        )?;
+        pay_withholding(
+            ctx.accounts.withholding.as_mut(),
+            ctx.bumps.withholding,
+            pool.owner,
+            &buyer,
+            Some(&system_program),
+            quote.withheld,
+        )?;
+        quote.apply_insurance(&ctx.accounts.config)?;
+        transfer_lamports(
+            &buyer,
+            &ctx.accounts.treasury,
+            &system_program,
+            quote.marketplace_fee,
+        )?;
+        pay_insurance(
+            ctx.accounts.insurance_fund.as_mut(),
+            &buyer,
+            Some(&system_program),
+            quote.insurance,
+        )?;
+        if let Some(creator) = &ctx.accounts.creator {
+            transfer_lamports(&buyer, creator, &system_program, quote.royalty)?;
+        }
+
+        let pool_key = pool.key();
+        let seeds = &[
+            PREFIX.as_bytes(),
+            b"vault",
+            ctx.accounts.vault.mint.as_ref(),
+            pool_key.as_ref(),
+            &[ctx.bumps.vault],
+        ];
+        let signer = &[&seeds[..]];
+        transfer_nft(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.buyer_token_account.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            ctx.remaining_accounts,
+            signer,
+        )?;
+        close_token_account(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.owner.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            signer,
+        )?;
+
+        let pool = &mut ctx.accounts.pool;
+        pool.sol_balance = pool
+            .sol_balance
+            .checked_add(pool_amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+        pool.nft_count -= 1;
+        pool.spot_price = pool.curve.step_up(pool.spot_price)?;
+        ctx.accounts.provenance.record_sale(
+            &mut ctx.accounts.sale_record,
+            pool.owner,
+            ctx.accounts.buyer.key(),
+            price,
+            None,
+            ctx.bumps.sale_record,
+        )?;
+        ctx.accounts.global_stats.record_sale(Some(price))?;
+
+        emit!(PoolTrade {
+            pool: pool_key,
+            mint: ctx.accounts.mint.key(),
+            trader: ctx.accounts.buyer.key(),
+            is_buy: true,
+            price,
+            pool_fee: lp_fee,
+            marketplace_fee: quote.marketplace_fee,
+            royalty: quote.royalty,
+            spot_price: pool.spot_price,
+        });
+
+        Ok(())
+    }
+
+    /// Sells an NFT of the pool's collection into it, for the spot price (at least `min_price`) less the
+    /// pool fee, marketplace fee & royalty, paid out of the pool's SOL; the spot price then steps down.
+    /// Transfer hook extra accounts (if the mint has a hook) go in `remaining_accounts`.
+    pub fn pool_sell<'info>(
+        ctx: Context<'_, '_, '_, 'info, PoolSell<'info>>,
+        min_price: u64,
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.paused, ErrorCode::MarketplacePaused);
+        let pool = &ctx.accounts.pool;
+        let price = pool.spot_price;
+        require!(price >= min_price, ErrorCode::PoolPriceOutOfBounds);
+        let mint = ctx.accounts.mint.key();
+        require!(
+            verified_collection(&mint, &ctx.accounts.metadata)? == Some(pool.collection),
+            ErrorCode::CollectionMismatch
+        );
+        validate_listable_mint(&ctx.accounts.mint.to_account_info())?;
+        require_not_blocked(&ctx.accounts.blocked_mint)?;
+
+        let seller_key = ctx.accounts.seller.key();
+        let provenance = &mut ctx.accounts.provenance;
+        provenance.init_if_new(mint, seller_key, ctx.bumps.provenance);
+        let is_primary = provenance.is_primary();
+        if let Some(creator) = &ctx.accounts.creator {
+            require_keys_eq!(creator.key(), provenance.creator, ErrorCode::InvalidCreator);
+        }
+
+        let schedule = *ctx.accounts.config.fee_schedule(is_primary);
+        let mut quote = SaleQuote::new(price, is_primary, &schedule)?;
+        quote.route_royalty(&ctx.accounts.config, ctx.accounts.creator.is_some())?;
+        // The pool is the maker (its bid): its rebate stays in the pool.
+        quote.apply_maker_rebate(&ctx.accounts.config)?;
+        let lp_fee = bps_of(price, pool.fee_bps)?;
+        quote.seller_proceeds = quote
+            .seller_proceeds
+            .checked_sub(lp_fee)
+            .ok_or(ErrorCode::MathOverflow)?;
+        quote.apply_withholding(&ctx.accounts.config)?;
+        let seller_amount = quote.seller_proceeds;
+        let pool_outflow = seller_amount + quote.withheld + quote.marketplace_fee + quote.royalty;
+        require!(
+            pool.sol_balance >= pool_outflow,
+            ErrorCode::InsufficientPoolFunds
+        );
+
+        let pool_info = pool.to_account_info();
+        transfer_lamports_from_pda(
+            &pool_info,
+            &ctx.accounts.seller.to_account_info(),
+            seller_amount,
+        )?;
+        pay_withholding(
+            ctx.accounts.withholding.as_mut(),
+            ctx.bumps.withholding,
+            seller_key,
+            &pool_info,
+            None,
+            quote.withheld,
+        )?;
+        quote.apply_insurance(&ctx.accounts.config)?;
+        transfer_lamports_from_pda(&pool_info, &ctx.accounts.treasury, quote.marketplace_fee)?;
+        pay_insurance(
+            ctx.accounts.insurance_fund.as_mut(),
+            &pool_info,
+            None,
+            quote.insurance,
+        )?;
+        if let Some(creator) = &ctx.accounts.creator {
+            transfer_lamports_from_pda(&pool_info, creator, quote.royalty)?;
+        }
+
+        transfer_nft(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.nft_account.to_account_info(),
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.seller.to_account_info(),
+            ctx.remaining_accounts,
+            &[],
+        )?;
+
+        let pool = &mut ctx.accounts.pool;
+        pool.sol_balance -= pool_outflow;
+        pool.nft_count += 1;
+        pool.spot_price = pool.curve.step_down(pool.spot_price)?;
+        ctx.accounts.provenance.record_sale(
+            &mut ctx.accounts.sale_record,
+            seller_key,
+            pool.owner,
+            price,
+            None,
+            ctx.bumps.sale_record,
+        )?;
+        ctx.accounts.global_stats.record_sale(Some(price))?;
+
+        emit!(PoolTrade {
+            pool: pool.key(),
+            mint,
+            trader: seller_key,
+            is_buy: false,
+            price,
+            pool_fee: lp_fee,
+            marketplace_fee: quote.marketplace_fee,
+            royalty: quote.royalty,
+            spot_price: pool.spot_price,
+        });
+
+        Ok(())
+    }
+
+    /// Closes an emptied pool (owner only), its remaining lamports going to the owner.
+    pub fn close_pool(ctx: Context<ClosePool>) -> Result<()> {
+        require!(ctx.accounts.pool.nft_count == 0, ErrorCode::PoolNotEmpty);
+        emit!(PoolClosed {
+            pool: ctx.accounts.pool.key(),
+            owner: ctx.accounts.owner.key(),
+            sol_balance: ctx.accounts.pool.sol_balance,
+        });
+        Ok(())
+    }
+
+    /// Raffles an NFT: seller --> vault, tickets sell for `ticket_price` lamports until `end_ts`.
+    /// `commitment` is sha256 of a secret the seller reveals in `draw_winner`. The seller also escrows a
+    /// bond of `RAFFLE_BOND_BPS` of the full ticket takings, forfeited to the entrants if they never draw.
+    /// Transfer hook extra accounts (if the mint has a hook) go in `remaining_accounts`.
+    pub fn create_raffle<'info>(
+        ctx: Context<'_, '_, '_, 'info, CreateRaffle<'info>>,
+        ticket_price: u64,
+        max_tickets: u64,
+        end_ts: i64,
+        commitment: [u8; 32],
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.paused, ErrorCode::MarketplacePaused);
+        require!(
+            ticket_price > 0 && max_tickets > 0 && end_ts > Clock::get()?.unix_timestamp,
+            ErrorCode::InvalidRaffleParams
+        );
+        validate_listable_mint(&ctx.accounts.mint.to_account_info())?;
+        require_not_blocked(&ctx.accounts.blocked_mint)?;
+        let bond = bps_of(
+            ticket_price
+                .checked_mul(max_tickets)
+                .ok_or(ErrorCode::MathOverflow)?,
+            RAFFLE_BOND_BPS,
+        )?;
+
+        transfer_nft(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.nft_account.to_account_info(),
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.seller.to_account_info(),
+            ctx.remaining_accounts,
+            &[],
+        )?;
+        transfer_lamports(
+            &ctx.accounts.seller.to_account_info(),
+            &ctx.accounts.raffle.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            bond,
+        )?;
+
+        ctx.accounts.vault_deposit.record(
+            ctx.accounts.mint.key(),
+            ctx.accounts.seller.key(),
+            ctx.accounts.raffle.key(),
+            ctx.bumps.vault_deposit,
+        );
+
+        let raffle = &mut ctx.accounts.raffle;
+        raffle.seller = ctx.accounts.seller.key();
+        raffle.mint = ctx.accounts.mint.key();
+        raffle.ticket_price = ticket_price;
+        raffle.max_tickets = max_tickets;
+        raffle.tickets_sold = 0;
+        raffle.end_ts = end_ts;
+        raffle.commitment = commitment;
+        raffle.bond = bond;
+        raffle.draw_slot = None;
+        raffle.winning_ticket = None;
+        raffle.status = RaffleStatus::Open;
+        raffle.bump = ctx.bumps.raffle;
+
+        emit!(RaffleCreated {
+            raffle: raffle.key(),
+            mint: raffle.mint,
+            seller: raffle.seller,
+            ticket_price,
+            max_tickets,
+            end_ts,
+        });
+
+        Ok(())
+    }
+
+    /// Buys `count` consecutive tickets, recorded in a new entry; the lamports are escrowed in the raffle.
+    pub fn buy_ticket(ctx: Context<BuyTicket>, count: u64) -> Result<()> {
+        require!(!ctx.accounts.config.paused, ErrorCode::MarketplacePaused);
+        let raffle = &ctx.accounts.raffle;
+        require!(
+            raffle.status == RaffleStatus::Open,
+            ErrorCode::InvalidRaffleStatus
+        );
+        require!(
+            Clock::get()?.unix_timestamp < raffle.end_ts,
+            ErrorCode::RaffleEnded
+        );
+        let tickets_sold = raffle
+            .tickets_sold
+            .checked_add(count)
+            .ok_or(ErrorCode::MathOverflow)?;
+        require!(
+            count > 0 && tickets_sold <= raffle.max_tickets,
+            ErrorCode::RaffleSoldOut
+        );
+        let cost = raffle
+            .ticket_price
+            .checked_mul(count)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        transfer_lamports(
+            &ctx.accounts.buyer.to_account_info(),
+            &raffle.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            cost,
+        )?;
+
+        let entry = &mut ctx.accounts.entry;
+        entry.raffle = raffle.key();
+        entry.buyer = ctx.accounts.buyer.key();
+        entry.first_ticket = raffle.tickets_sold;
+        entry.count = count;
+        entry.bump = ctx.bumps.entry;
+
+        ctx.accounts.raffle.tickets_sold = tickets_sold;
+
+        emit!(RaffleTicketsBought {
+            raffle: entry.raffle,
+            entry: entry.key(),
+            buyer: entry.buyer,
+            first_ticket: entry.first_ticket,
+            count,
+        });
+
+        Ok(())
+    }
+
+    /// Closes ticket sales once the raffle ends or sells out (anyone can crank), fixing the draw to the
+    /// hash of a slot `RAFFLE_DRAW_DELAY_SLOTS` ahead. Nobody knows that hash yet, and a raffle locks once.
+    pub fn lock_raffle(ctx: Context<LockRaffle>) -> Result<()> {
+        let raffle = &mut ctx.accounts.raffle;
+        require!(
+            raffle.status == RaffleStatus::Open && raffle.draw_slot.is_none(),
+            ErrorCode::InvalidRaffleStatus
+        );
+        let clock = Clock::get()?;
+        // Sold out raffles can draw early.
+        require!(
+            clock.unix_timestamp >= raffle.end_ts || raffle.tickets_sold == raffle.max_tickets,
+            ErrorCode::RaffleNotEnded
+        );
+        require!(
+            clock.unix_timestamp < raffle.end_ts + RAFFLE_DRAW_WINDOW,
+            ErrorCode::RaffleDrawWindowClosed
+        );
+        require!(raffle.tickets_sold > 0, ErrorCode::RaffleHasNoTickets);
+        let draw_slot = clock.slot + RAFFLE_DRAW_DELAY_SLOTS;
+        raffle.draw_slot = Some(draw_slot);
+
+        emit!(RaffleLocked {
+            raffle: raffle.key(),
+            mint: raffle.mint,
+            draw_slot,
+        });
+
+        Ok(())
+    }
+
+    /// Reveals the seller's secret (anyone holding it can crank) & draws the winning ticket from it & the
+    /// hash of the locked draw slot, while SlotHashes still holds it. Pays the proceeds (less the secondary
+    /// marketplace fee & withholding) & the bond back to the seller.
+    pub fn draw_winner(ctx: Context<DrawWinner>, secret: [u8; 32]) -> Result<()> {
+        let raffle = &ctx.accounts.raffle;
+        require!(
+            raffle.status == RaffleStatus::Open,
+            ErrorCode::InvalidRaffleStatus
+        );
+        let draw_slot = raffle.draw_slot.ok_or(ErrorCode::RaffleNotLocked)?;
+        require!(
+            Clock::get()?.unix_timestamp < raffle.end_ts + RAFFLE_DRAW_WINDOW,
+            ErrorCode::RaffleDrawWindowClosed
+        );
+        require!(
+            hashv(&[&secret]).to_bytes() == raffle.commitment,
+            ErrorCode::InvalidRaffleSecret
+        );
+
+        let slot_hash = draw_slot_hash(&ctx.accounts.slot_hashes, draw_slot)?;
+        let seed = hashv(&[&secret, &slot_hash, raffle.key().as_ref()]).to_bytes();
+        let winning_ticket =
+            u64::from_le_bytes(seed[..8].try_into().unwrap()) % raffle.tickets_sold;
+
+        let proceeds = raffle.ticket_price * raffle.tickets_sold;
+        let marketplace_fee = bps_of(
+            proceeds,
+            ctx.accounts.config.fee_schedule(false).marketplace_fee_bps,
+        )?;
+        let withheld = bps_of(
+            proceeds - marketplace_fee,
+            ctx.accounts.config.withholding_bps,
+        )?;
+        transfer_lamports_from_pda(
+            &raffle.to_account_info(),
+            &ctx.accounts.seller,
+            proceeds - marketplace_fee - withheld + raffle.bond,
+        )?;
+        pay_withholding(
+            ctx.accounts.withholding.as_mut(),
+            ctx.bumps.withholding,
+            raffle.seller,
+            &raffle.to_account_info(),
+            None,
+            withheld,
+        )?;
+        transfer_lamports_from_pda(
+            &raffle.to_account_info(),
+            &ctx.accounts.treasury,
+            marketplace_fee,
+        )?;
+
+        let raffle = &mut ctx.accounts.raffle;
+        raffle.winning_ticket = Some(winning_ticket);
+        raffle.status = RaffleStatus::Drawn;
+
+        emit!(RaffleDrawn {
+            raffle: raffle.key(),
+            mint: raffle.mint,
+            winning_ticket,
+            proceeds,
+        });
+
+        Ok(())
+    }
+
+    /// Delivers the NFT to the entry holding the winning ticket (anyone can crank), closing the entry.
+    /// Transfer hook extra accounts (if the mint has a hook) go in `remaining_accounts`.
+    pub fn claim_raffle_prize<'info>(
+        ctx: Context<'_, '_, '_, 'info, ClaimRafflePrize<'info>>,
+    ) -> Result<()> {
+        let raffle = &ctx.accounts.raffle;
+        require!(
+            raffle.status == RaffleStatus::Drawn,
+            ErrorCode::InvalidRaffleStatus
+        );
+        let winning_ticket = raffle
+            .winning_ticket
+            .ok_or(ErrorCode::InvalidRaffleStatus)?;
+        let entry = &ctx.accounts.entry;
+        require!(
+            (entry.first_ticket..entry.first_ticket + entry.count).contains(&winning_ticket),
+            ErrorCode::NotRaffleWinner
+        );
+
+        let seeds = &[
+            PREFIX.as_bytes(),
+            b"vault",
+            raffle.mint.as_ref(),
+            &[ctx.bumps.vault],
+        ];
+        let signer = &[&seeds[..]];
+        transfer_nft(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.winner_nft_account.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            ctx.remaining_accounts,
+            signer,
+        )?;
+
+        ctx.accounts.raffle.status = RaffleStatus::Claimed;
+
+        emit!(RafflePrizeClaimed {
+            raffle: ctx.accounts.raffle.key(),
+            mint: ctx.accounts.raffle.mint,
+            winner: entry.buyer,
+        });
+
+        Ok(())
+    }
+
+    /// Returns the NFT to the seller of a raffle that can't be drawn: no tickets sold by `end_ts` (the bond
+    /// is returned too), or the draw window passed (entrants then refund with `refund_raffle_entry`, each
+    /// taking their share of the bond).
+    /// Transfer hook extra accounts (if the mint has a hook) go in `remaining_accounts`.
+    pub fn cancel_raffle<'info>(
+        ctx: Context<'_, '_, '_, 'info, CancelRaffle<'info>>,
+    ) -> Result<()> {
+        let raffle = &ctx.accounts.raffle;
+        require!(
+            raffle.status == RaffleStatus::Open,
+            ErrorCode::InvalidRaffleStatus
+        );
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            (raffle.tickets_sold == 0 && now >= raffle.end_ts)
+                || now >= raffle.end_ts + RAFFLE_DRAW_WINDOW,
+            ErrorCode::RaffleNotEnded
+        );
+
+        let seeds = &[
+            PREFIX.as_bytes(),
+            b"vault",
+            raffle.mint.as_ref(),
+            &[ctx.bumps.vault],
+        ];
+        let signer = &[&seeds[..]];
+        transfer_nft(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.nft_account.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            ctx.remaining_accounts,
+            signer,
+        )?;
+        if raffle.tickets_sold == 0 {
+            transfer_lamports_from_pda(
+                &raffle.to_account_info(),
+                &ctx.accounts.seller.to_account_info(),
+                raffle.bond,
+            )?;
+        }
+
+        ctx.accounts.raffle.status = RaffleStatus::Cancelled;
+
+        Ok(())
+    }
+
+    /// Refunds an entry's tickets & pro rata share of the seller's bond (anyone can crank) once the draw
+    /// window passed without a draw.
+    pub fn refund_raffle_entry(ctx: Context<RefundRaffleEntry>) -> Result<()> {
+        let raffle = &ctx.accounts.raffle;
+        let undrawn = raffle.status == RaffleStatus::Cancelled
+            || (raffle.status == RaffleStatus::Open
+                && Clock::get()?.unix_timestamp >= raffle.end_ts + RAFFLE_DRAW_WINDOW);
+        require!(
+            undrawn && raffle.tickets_sold > 0,
+            ErrorCode::InvalidRaffleStatus
+        );
+
+        let count = ctx.accounts.entry.count;
+        let bond_share = (raffle.bond as u128 * count as u128 / raffle.tickets_sold as u128) as u64;
+        let amount = raffle.ticket_price * count + bond_share;
+        transfer_lamports_from_pda(&raffle.to_account_info(), &ctx.accounts.buyer, amount)?;
+
+        emit!(RaffleEntryRefunded {
+            raffle: raffle.key(),
+            entry: ctx.accounts.entry.key(),
+            buyer: ctx.accounts.buyer.key(),
+            amount,
+        });
+
+        Ok(())
+    }
+
+    /// Opts an active listing into rewards (seller only): it accrues a point per slot while active.
+    pub fn enroll_listing_rewards(ctx: Context<EnrollListingRewards>) -> Result<()> {
+        require!(
+            ctx.accounts.config.reward_mint != Pubkey::default(),
+            ErrorCode::ListingRewardsDisabled
+        );
+        let listing = &mut ctx.accounts.listing;
+        require!(listing.is_active, ErrorCode::InactiveListing);
+        require!(
+            listing.reward_checkpoint_slot.is_none(),
+            ErrorCode::AlreadyEnrolledInRewards
+        );
+        listing.reward_checkpoint_slot = Some(Clock::get()?.slot);
+        listing.reward_points = Some(0);
+
+        Ok(())
+    }
+
+    /// Pays a listing's accrued points (seller only) from the rewards vault, at `config.reward_per_point`.
+    /// Works on sold listings too; claim before delisting, which closes the listing.
+    pub fn claim_listing_rewards(ctx: Context<ClaimListingRewards>) -> Result<()> {
+        let listing = &mut ctx.accounts.listing;
+        require!(
+            listing.reward_checkpoint_slot.is_some(),
+            ErrorCode::NotEnrolledInRewards
+        );
+        listing.accrue_rewards(Clock::get()?.slot);
+        let points = listing.reward_points.unwrap_or_default();
+        let amount = points
+            .checked_mul(ctx.accounts.config.reward_per_point)
+            .ok_or(ErrorCode::MathOverflow)?;
+        require!(
+            ctx.accounts.reward_vault.amount >= amount,
+            ErrorCode::RewardVaultUnderfunded
+        );
+        listing.reward_points = Some(0);
+
+        let seeds = &[
+            PREFIX.as_bytes(),
+            b"reward_vault",
+            ctx.accounts.config.reward_mint.as_ref(),
+            &[ctx.bumps.reward_vault],
+        ];
+        transfer_tokens(
+            &ctx.accounts.reward_token_program.to_account_info(),
+            &ctx.accounts.reward_vault.to_account_info(),
+            &ctx.accounts.reward_mint,
+            &ctx.accounts.seller_reward_account.to_account_info(),
+            &ctx.accounts.reward_vault.to_account_info(),
+            amount,
+            &[&seeds[..]],
+        )?;
+
+        emit!(ListingRewardsClaimed {
+            listing: ctx.accounts.listing.key(),
+            seller: ctx.accounts.seller.key(),
+            points,
+            amount,
+        });
+
+        Ok(())
+    }
+
+    /// Pays the seller's withheld proceeds out (seller only), once the config's release date passed.
+    pub fn withdraw_withholding(ctx: Context<WithdrawWithholding>) -> Result<()> {
+        require!(
+            Clock::get()?.unix_timestamp >= ctx.accounts.config.withholding_release_ts,
+            ErrorCode::WithholdingLocked
+        );
+        let withholding = &ctx.accounts.withholding;
+        emit!(WithholdingWithdrawn {
+            withholding: withholding.key(),
+            seller: withholding.seller,
+            amount: withholding.amount,
+        });
+        // Closing pays the withheld lamports out along with the rent.
+        Ok(())
+    }
+
+    /// Pays out the vested part of a vested sale's proceeds (seller or seller authority only); closes the vesting once fully claimed.
+    pub fn claim_vested(ctx: Context<ClaimVested>) -> Result<()> {
+        let vesting = &ctx.accounts.vesting;
+        require_seller_authority(
+            &vesting.seller,
+            vesting.seller_authority,
+            ctx.accounts.authority.key,
+        )?;
+        let amount = vesting.vested(Clock::get()?.unix_timestamp) - vesting.claimed;
+        transfer_lamports_from_pda(
+            &vesting.to_account_info(),
+            &ctx.accounts.seller.to_account_info(),
+            amount,
+        )?;
+
+        let vesting = &mut ctx.accounts.vesting;
+        vesting.claimed += amount;
+
+        emit!(VestedClaimed {
+            vesting: vesting.key(),
+            seller: vesting.seller,
+            amount,
+            claimed: vesting.claimed,
+            total: vesting.total,
+        });
+
+        if vesting.claimed == vesting.total {
+            vesting.close(ctx.accounts.seller.to_account_info())?;
+        }
+
+        Ok(())
+    }
+
+    /// Confirms a phygital purchase arrived (buyer only): releases the escrowed proceeds to the seller.
+    pub fn confirm_receipt(ctx: Context<SettleEscrowedSale>) -> Result<()> {
+        require_keys_eq!(
+            ctx.accounts.authority.key(),
+            ctx.accounts.escrowed_sale.buyer,
+            ErrorCode::Unauthorized
+        );
+        release_escrowed_sale(&ctx.accounts.escrowed_sale, &ctx.accounts.seller, true)
+    }
+
+    /// Releases the escrowed proceeds of a phygital purchase to the seller once the receipt window
+    /// passed without confirmation (anyone can crank).
+    pub fn release_expired_escrow(ctx: Context<SettleEscrowedSale>) -> Result<()> {
+        require!(
+            !ctx.accounts.escrowed_sale.disputed
+                && Clock::get()?.unix_timestamp >= ctx.accounts.escrowed_sale.release_ts,
+            ErrorCode::EscrowNotReleasable
+        );
+        release_escrowed_sale(&ctx.accounts.escrowed_sale, &ctx.accounts.seller, false)
+    }
+
+    /// Refunds the escrowed proceeds of a phygital purchase to the buyer (seller only), e.g. when the
+    /// item can't be delivered. The NFT stays with the buyer.
+    pub fn refund_escrowed_sale(ctx: Context<SettleEscrowedSale>) -> Result<()> {
+        let escrowed_sale = &ctx.accounts.escrowed_sale;
+        require_keys_eq!(
+            ctx.accounts.authority.key(),
+            escrowed_sale.seller,
+            ErrorCode::Unauthorized
+        );
+        transfer_lamports_from_pda(
+            &escrowed_sale.to_account_info(),
+            &ctx.accounts.buyer,
+            escrowed_sale.amount,
+        )?;
+        emit!(EscrowedSaleRefunded {
+            escrowed_sale: escrowed_sale.key(),
+            listing: escrowed_sale.listing,
+            seller: escrowed_sale.seller,
+            buyer: escrowed_sale.buyer,
+            amount: escrowed_sale.amount,
+        });
+        Ok(())
+    }
+
+    /// Disputes an escrowed sale within its receipt window (buyer or seller), blocking the timed
+    /// release until the config arbiter resolves it. Either party can still settle by conceding:
+    /// the buyer confirming receipt or the seller refunding.
+    pub fn open_dispute(ctx: Context<OpenDispute>) -> Result<()> {
+        let escrowed_sale = &mut ctx.accounts.escrowed_sale;
+        let opened_by = ctx.accounts.authority.key();
+        require!(
+            opened_by == escrowed_sale.buyer || opened_by == escrowed_sale.seller,
+            ErrorCode::Unauthorized
+        );
+        require!(
+            ctx.accounts.config.arbiter != Pubkey::default()
+                && !escrowed_sale.disputed
+                && Clock::get()?.unix_timestamp < escrowed_sale.release_ts,
+            ErrorCode::DisputeNotAllowed
+        );
+        escrowed_sale.disputed = true;
+        emit!(DisputeOpened {
+            escrowed_sale: escrowed_sale.key(),
+            listing: escrowed_sale.listing,
+            opened_by,
+        });
+        Ok(())
+    }
+
+    /// Awards a disputed sale's escrowed proceeds to `release_to`, its buyer or seller (arbiter only).
+    pub fn resolve_dispute(ctx: Context<ResolveDispute>, release_to: Pubkey) -> Result<()> {
+        let escrowed_sale = &ctx.accounts.escrowed_sale;
+        require!(escrowed_sale.disputed, ErrorCode::NoDispute);
+        let recipient = if release_to == escrowed_sale.seller {
+            &ctx.accounts.seller
+        } else if release_to == escrowed_sale.buyer {
+            &ctx.accounts.buyer
+        } else {
+            return err!(ErrorCode::InvalidDisputeOutcome);
+        };
+        transfer_lamports_from_pda(
+            &escrowed_sale.to_account_info(),
+            recipient,
+            escrowed_sale.amount,
+        )?;
+        emit!(DisputeResolved {
+            escrowed_sale: escrowed_sale.key(),
+            listing: escrowed_sale.listing,
+            arbiter: ctx.accounts.arbiter.key(),
+            release_to,
+            amount: escrowed_sale.amount,
+        });
+        Ok(())
+    }
+
+    /// Starts an english auction, transferring NFT from usr --> vault (PDA). Prices are in lamports.
+    /// Transfer hook extra accounts (if the mint has a hook) go in `remaining_accounts`.
+    pub fn create_auction<'info>(
+        ctx: Context<'_, '_, '_, 'info, CreateAuction<'info>>,
+        min_bid: u64,
+        reserve_price: u64,
+        buy_now_price: Option<u64>,
+        duration: i64,
+    ) -> Result<()> {
+        ctx.accounts
+            .escrow_nft(ctx.remaining_accounts, ctx.bumps.provenance)?;
+        ctx.accounts.vault_deposit.record(
+            ctx.accounts.mint.key(),
+            ctx.accounts.seller.key(),
+            ctx.accounts.auction.key(),
+            ctx.bumps.vault_deposit,
+        );
+
+        ctx.accounts.auction.start_english(
+            ctx.accounts.seller.key(),
+            ctx.accounts.mint.key(),
+            min_bid,
+            reserve_price,
+            buy_now_price,
+            duration,
+        )
+    }
+
+    /// Starts a sealed-bid auction: bidders commit `hash(bidder, amount, salt)` with a `bid_deposit` during
+    /// `bidding_duration`, then reveal during `reveal_duration`. The highest revealed bid wins.
+    /// Transfer hook extra accounts (if the mint has a hook) go in `remaining_accounts`.
+    pub fn create_sealed_auction<'info>(
+        ctx: Context<'_, '_, '_, 'info, CreateAuction<'info>>,
+        min_bid: u64,
+        reserve_price: u64,
+        bid_deposit: u64,
+        bidding_duration: i64,
+        reveal_duration: i64,
+    ) -> Result<()> {
+        require!(
+            bidding_duration > 0 && reveal_duration > 0 && bid_deposit > 0,
+            ErrorCode::InvalidAuctionParams
+        );
+        ctx.accounts
+            .escrow_nft(ctx.remaining_accounts, ctx.bumps.provenance)?;
+        ctx.accounts.vault_deposit.record(
+            ctx.accounts.mint.key(),
+            ctx.accounts.seller.key(),
+            ctx.accounts.auction.key(),
+            ctx.bumps.vault_deposit,
+        );
+
+        let auction = &mut ctx.accounts.auction;
+        auction.seller = ctx.accounts.seller.key();
+        auction.mint = ctx.accounts.mint.key();
+        auction.min_bid = min_bid;
+        auction.reserve_price = reserve_price;
+        auction.buy_now_price = None;
+        auction.end_ts = Clock::get()?
+            .unix_timestamp
+            .checked_add(bidding_duration)
+            .ok_or(ErrorCode::MathOverflow)?;
+        auction.highest_bid = 0;
+        auction.highest_bidder = Pubkey::default();
+        auction.status = AuctionStatus::Active;
+        auction.mode = AuctionMode::Sealed;
+        auction.reveal_end_ts = auction
+            .end_ts
+            .checked_add(reveal_duration)
+            .ok_or(ErrorCode::MathOverflow)?;
+        auction.bid_deposit = bid_deposit;
+        auction.on_no_sale = NoSaleAction::ReturnToSeller;
+        auction.unrevealed_bids = 0;
+
+        Ok(())
+    }
+
+    /// Raises the bidder's escrowed bid to `amount` (lamports).
+    /// Paying at least the buy-now price ends the auction immediately.
+    pub fn place_bid(ctx: Context<PlaceBid>, amount: u64) -> Result<()> {
+        require!(!ctx.accounts.config.paused, ErrorCode::MarketplacePaused);
+        let now = Clock::get()?.unix_timestamp;
+        let auction = &mut ctx.accounts.auction;
+        require!(
+            auction.status == AuctionStatus::Active,
+            ErrorCode::AuctionNotActive
+        );
+        require!(
+            auction.mode == AuctionMode::English,
+            ErrorCode::WrongAuctionMode
+        );
+        require!(now < auction.end_ts, ErrorCode::AuctionEnded);
+
+        // Anything above the buy-now price would be an overpayment.
+        let amount = match auction.buy_now_price {
+            Some(buy_now_price) => amount.min(buy_now_price),
+            None => amount,
+        };
+        require!(
+            amount >= auction.min_bid && amount > auction.highest_bid,
+            ErrorCode::BidTooLow
+        );
+
+        // A staked bid's escrow is short of its amount.
+        require!(
+            ctx.accounts.escrow_yield.data_is_empty(),
+            ErrorCode::EscrowStaked
+        );
+        let bid = &mut ctx.accounts.bid;
+        if bid.bidder == Pubkey::default() {
+            bid.auction = auction.key();
+            bid.bidder = ctx.accounts.bidder.key();
+            bid.amount = 0;
+            bid.commitment = None;
+            bid.revealed = true;
+            bid.bump = ctx.bumps.bid;
+        }
+
+        // Escrow only the difference to the bidder's previous bid.
+        let top_up = amount
+            .checked_sub(bid.amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+        transfer_lamports(
+            &ctx.accounts.bidder.to_account_info(),
+            &bid.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            top_up,
+        )?;
+        bid.amount = amount;
+
+        auction.highest_bid = amount;
+        auction.highest_bidder = bid.bidder;
+
+        let buy_now = auction.buy_now_price == Some(amount);
+        if buy_now {
+            auction.end_ts = now;
+        }
+
+        emit!(BidPlaced {
+            auction: auction.key(),
+            bidder: bid.bidder,
+            amount,
+            buy_now,
+        });
+
+        Ok(())
+    }
+
+    /// Sealed auctions: commits to a bid, escrowing the auction's deposit. One commitment per bidder.
+    pub fn commit_bid(ctx: Context<CommitBid>, commitment: [u8; 32]) -> Result<()> {
+        require!(!ctx.accounts.config.paused, ErrorCode::MarketplacePaused);
+        let auction = &mut ctx.accounts.auction;
+        require!(
+            auction.status == AuctionStatus::Active,
+            ErrorCode::AuctionNotActive
+        );
+        require!(
+            auction.mode == AuctionMode::Sealed,
+            ErrorCode::WrongAuctionMode
+        );
+        require!(
+            Clock::get()?.unix_timestamp < auction.end_ts,
+            ErrorCode::AuctionEnded
+        );
+
+        let bid = &mut ctx.accounts.bid;
+        bid.auction = auction.key();
+        bid.bidder = ctx.accounts.bidder.key();
+        bid.amount = 0;
+        bid.commitment = Some(commitment);
+        bid.revealed = false;
+        bid.bump = ctx.bumps.bid;
+
+        transfer_lamports(
+            &ctx.accounts.bidder.to_account_info(),
+            &bid.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            auction.bid_deposit,
+        )?;
+        auction.unrevealed_bids = auction
+            .unrevealed_bids
+            .checked_add(1)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        emit!(BidCommitted {
+            auction: auction.key(),
+            bidder: bid.bidder,
+        });
+
+        Ok(())
+    }
+
+    /// Sealed auctions: reveals a committed bid during the reveal window, escrowing the rest of `amount`.
+    pub fn reveal_bid(ctx: Context<RevealBid>, amount: u64, salt: [u8; 32]) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let auction = &mut ctx.accounts.auction;
+        require!(
+            auction.status == AuctionStatus::Active,
+            ErrorCode::AuctionNotActive
+        );
+        require!(
+            auction.mode == AuctionMode::Sealed,
+            ErrorCode::WrongAuctionMode
+        );
+        require!(now >= auction.end_ts, ErrorCode::RevealNotOpen);
+        require!(now < auction.reveal_end_ts, ErrorCode::RevealClosed);
+
+        let bid = &mut ctx.accounts.bid;
+        require!(!bid.revealed, ErrorCode::BidAlreadyRevealed);
+        require!(
+            bid.commitment == Some(sealed_bid_commitment(&bid.bidder, amount, &salt)),
+            ErrorCode::CommitmentMismatch
+        );
+
+        // The deposit counts towards the bid.
+        let top_up = amount.saturating_sub(auction.bid_deposit);
+        transfer_lamports(
+            &ctx.accounts.bidder.to_account_info(),
+            &bid.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            top_up,
+        )?;
+        bid.amount = amount;
+        bid.revealed = true;
+        auction.unrevealed_bids = auction.unrevealed_bids.saturating_sub(1);
+
+        // Ties go to the earlier reveal.
+        if amount >= auction.min_bid && amount > auction.highest_bid {
+            auction.highest_bid = amount;
+            auction.highest_bidder = bid.bidder;
+        }
+
+        emit!(BidRevealed {
+            auction: auction.key(),
+            bidder: bid.bidder,
+            amount,
+        });
+
+        Ok(())
+    }
+
+    /// Sealed auctions: after the reveal window, an unrevealed bid's deposit goes to the seller.
+    /// Callable by anyone; the bid's rent goes back to the bidder.
+    pub fn forfeit_unrevealed_bid(ctx: Context<ForfeitUnrevealedBid>) -> Result<()> {
+        let auction = &mut ctx.accounts.auction;
+        require!(
+            auction.mode == AuctionMode::Sealed,
+            ErrorCode::WrongAuctionMode
+        );
+        require!(
+            Clock::get()?.unix_timestamp >= auction.reveal_end_ts,
+            ErrorCode::RevealNotClosed
+        );
+
+        let bid = &ctx.accounts.bid;
+        require!(!bid.revealed, ErrorCode::BidAlreadyRevealed);
+        transfer_lamports_from_pda(
+            &bid.to_account_info(),
+            &ctx.accounts.seller,
+            auction.bid_deposit,
+        )?;
+        bid.close(ctx.accounts.bidder.to_account_info())?;
+        auction.unrevealed_bids = auction.unrevealed_bids.saturating_sub(1);
+
+        emit!(DepositForfeited {
+            auction: auction.key(),
+            bidder: bid.bidder,
+            amount: auction.bid_deposit,
+        });
+
+        Ok(())
+    }
+
+    /// Refunds & closes a bid that is no longer needed: outbid, or the auction is over.
+    /// Unrevealed sealed bids only come back if the auction was cancelled (otherwise they're forfeited).
+    pub fn cancel_bid(ctx: Context<CancelBid>) -> Result<()> {
+        let bid = &ctx.accounts.bid;
+
+        // Bids left on a closed auction (settled, cancelled or swept) are refunded in full.
+        if ctx.accounts.auction.owner == &crate::ID {
+            let auction =
+                Auction::try_deserialize(&mut &ctx.accounts.auction.try_borrow_data()?[..])?;
+            let cancelled = auction.status == AuctionStatus::Cancelled;
+            let is_highest =
+                auction.status == AuctionStatus::Active && auction.highest_bidder == bid.bidder;
+            require!(!is_highest, ErrorCode::BidIsHighest);
+            require!(bid.revealed || cancelled, ErrorCode::BidNotRevealed);
+        }
+
+        // Close the bid, returning escrow & rent to the bidder.
+        bid.close(ctx.accounts.bidder.to_account_info())?;
+
+        emit!(BidCancelled {
+            auction: ctx.accounts.auction.key(),
+            bidder: bid.bidder,
+            amount: bid.amount,
+        });
+
+        Ok(())
+    }
+
+    /// Cancels `target`, whichever of the `kind`s of cancellable account it is (checked against its
+    /// discriminator), by running that kind's own cancel instruction: `remaining_accounts` are its
+    /// accounts, in its order, then its own remaining accounts.
+    pub fn cancel<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Cancel<'info>>,
+        kind: CancelKind,
+    ) -> Result<()> {
+        let target = &ctx.accounts.target;
+        require_keys_eq!(*target.owner, crate::ID, ErrorCode::InvalidCancelTarget);
+        require!(
+            target.try_borrow_data()?.get(..8) == Some(&kind.discriminator()[..]),
+            ErrorCode::InvalidCancelTarget
+        );
+
+        let (program_id, accounts, target) = (ctx.program_id, ctx.remaining_accounts, target.key());
+        match kind {
+            CancelKind::Listing => run_cancel(
+                program_id,
+                accounts,
+                target,
+                |a: &RemoveListedNFT| a.listing.key(),
+                remove_listed_nft,
+            ),
+            CancelKind::Offer => run_cancel(
+                program_id,
+                accounts,
+                target,
+                |a: &CancelOffer| a.offer.key(),
+                cancel_offer,
+            ),
+            CancelKind::CollectionOffer => run_cancel(
+                program_id,
+                accounts,
+                target,
+                |a: &CancelCollectionOffer| a.offer.key(),
+                cancel_collection_offer,
+            ),
+            CancelKind::LoanOffer => run_cancel(
+                program_id,
+                accounts,
+                target,
+                |a: &CancelLoanOffer| a.loan_offer.key(),
+                cancel_loan_offer,
+            ),
+            CancelKind::Bid => run_cancel(
+                program_id,
+                accounts,
+                target,
+                |a: &CancelBid| a.bid.key(),
+                cancel_bid,
+            ),
+        }
+    }
+
+    /// Sets what settling an active auction does when the reserve isn't met (seller only): return the NFT,
+    /// or relist it at a fixed lamport `price` in the same transaction.
+    pub fn set_auction_on_no_sale(
+        ctx: Context<SetAuctionOnNoSale>,
+        on_no_sale: NoSaleAction,
+    ) -> Result<()> {
+        let auction = &mut ctx.accounts.auction;
+        require!(
+            auction.status == AuctionStatus::Active,
+            ErrorCode::AuctionNotActive
+        );
+        if let NoSaleAction::Relist { price } = on_no_sale {
+            require!(price > 0, ErrorCode::InvalidAuctionParams);
+        }
+        auction.on_no_sale = on_no_sale;
+        Ok(())
+    }
+
+    /// Cancels an auction without a standing bid, transferring NFT back: vault (PDA) --> seller, & closes
+    /// it (rent --> seller). Transfer hook extra accounts (if the mint has a hook) go in `remaining_accounts`.
+    pub fn cancel_auction<'info>(
+        ctx: Context<'_, '_, '_, 'info, CancelAuction<'info>>,
+    ) -> Result<()> {
+        let auction = &ctx.accounts.auction;
+        require!(
+            auction.status == AuctionStatus::Active,
+            ErrorCode::AuctionNotActive
+        );
+        require!(
+            auction.highest_bidder == Pubkey::default(),
+            ErrorCode::AuctionHasBids
+        );
+
+        let seeds = &[
+            PREFIX.as_bytes(),
+            b"vault",
+            auction.mint.as_ref(),
+            &[ctx.bumps.vault],
+        ];
+        let signer = &[&seeds[..]];
+
+        transfer_nft(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.seller_nft_account.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            ctx.remaining_accounts,
+            signer,
+        )?;
+
+        Ok(())
+    }
+
+    /// Turns the seller's active listing into an english auction (as `create_auction`), moving the NFT
+    /// listing vault (PDA) --> auction vault (PDA) & closing the listing & its vault.
+    /// Transfer hook extra accounts (if the mint has a hook) go in `remaining_accounts`.
+    pub fn convert_listing_to_auction<'info>(
+        ctx: Context<'_, '_, '_, 'info, ConvertListingToAuction<'info>>,
+        min_bid: u64,
+        reserve_price: u64,
+        buy_now_price: Option<u64>,
+        duration: i64,
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.paused, ErrorCode::MarketplacePaused);
+        ctx.accounts.listing.require_delistable()?;
+        require_listable(
+            &ctx.accounts.config,
+            &ctx.accounts.blocked_mint,
+            &ctx.accounts.seller_ban,
+            ctx.accounts.seller.key(),
+            &ctx.accounts.mint.key(),
+            ctx.accounts.metadata.as_ref(),
+            ctx.accounts.allowed_collection.as_deref(),
+        )?;
+        ctx.accounts.global_stats.record_listing()?;
+
+        let listing_key = ctx.accounts.listing.key();
+        let mint_key = ctx.accounts.mint.key();
+        let seeds = &[
+            PREFIX.as_bytes(),
+            b"vault",
+            mint_key.as_ref(),
+            listing_key.as_ref(),
+            &[ctx.bumps.listing_vault],
+        ];
+        let signer = &[&seeds[..]];
+        let token_program = ctx.accounts.token_program.to_account_info();
+        let listing_vault = ctx.accounts.listing_vault.to_account_info();
+        transfer_nft(
+            &token_program,
+            &listing_vault,
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &listing_vault,
+            ctx.remaining_accounts,
+            signer,
+        )?;
+        close_token_account(
+            &token_program,
+            &listing_vault,
+            &ctx.accounts.rent_payer,
+            &listing_vault,
+            signer,
+        )?;
+
+        ctx.accounts.vault_deposit.record(
+            mint_key,
+            ctx.accounts.seller.key(),
+            ctx.accounts.auction.key(),
+            ctx.bumps.vault_deposit,
+        );
+        ctx.accounts.auction.start_english(
+            ctx.accounts.seller.key(),
+            mint_key,
+            min_bid,
+            reserve_price,
+            buy_now_price,
+            duration,
+        )?;
+
+        unregister_listing(&ctx.accounts.listing, ctx.accounts.registry_page.as_ref())?;
+        ctx.accounts
+            .listing
+            .close(ctx.accounts.rent_payer.to_account_info())?;
+
+        emit_activity!(
+            ctx,
+            ListingConvertedToAuction {
+                listing: listing_key,
+                auction: ctx.accounts.auction.key(),
+                mint: mint_key,
+                seller: ctx.accounts.seller.key(),
+            }
+        );
+        Ok(())
+    }
+
+    /// Turns the seller's active english auction without bids into a fixed-price listing at `price`
+    /// (lamports), moving the NFT auction vault (PDA) --> listing vault (PDA) & closing the auction & its
+    /// vault (rent --> seller).
+    /// Transfer hook extra accounts (if the mint has a hook) go in `remaining_accounts`.
+    pub fn convert_auction_to_listing<'info>(
+        ctx: Context<'_, '_, '_, 'info, ConvertAuctionToListing<'info>>,
+        price: u64,
+    ) -> Result<()> {
+        let auction = &ctx.accounts.auction;
+        require!(!ctx.accounts.config.paused, ErrorCode::MarketplacePaused);
+        require!(
+            auction.status == AuctionStatus::Active,
+            ErrorCode::AuctionNotActive
+        );
+        require!(
+            auction.mode == AuctionMode::English,
+            ErrorCode::WrongAuctionMode
+        );
+        require!(
+            auction.highest_bidder == Pubkey::default(),
+            ErrorCode::AuctionHasBids
+        );
+        let collection = require_listable(
+            &ctx.accounts.config,
+            &ctx.accounts.blocked_mint,
+            &ctx.accounts.seller_ban,
+            auction.seller,
+            &auction.mint,
+            ctx.accounts.metadata.as_ref(),
+            ctx.accounts.allowed_collection.as_deref(),
+        )?;
+
+        let seeds = &[
+            PREFIX.as_bytes(),
+            b"vault",
+            auction.mint.as_ref(),
+            &[ctx.bumps.vault],
+        ];
+        let signer = &[&seeds[..]];
+        let token_program = ctx.accounts.token_program.to_account_info();
+        let vault = ctx.accounts.vault.to_account_info();
+        transfer_nft(
+            &token_program,
+            &vault,
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.listing_vault.to_account_info(),
+            &vault,
+            ctx.remaining_accounts,
+            signer,
+        )?;
+        close_token_account(&token_program, &vault, &ctx.accounts.seller, &vault, signer)?;
+
+        let listing = &mut ctx.accounts.listing;
+        listing.open(
+            auction.seller,
+            auction.mint,
+            price,
+            None,
+            &ListingOptions::default(),
+        );
+        require_price_limits(
+            &ctx.accounts.config,
+            ctx.accounts.allowed_collection.as_deref(),
+            listing,
+            false,
+        )?;
+        listing.rent_payer = Some(auction.seller);
+        listing.collection = collection;
+        listing.embedded_metadata = embedded_metadata(&ctx.accounts.mint.to_account_info())?;
+        if let Some(registry_page) = &ctx.accounts.registry_page {
+            let mut registry_page = registry_page.load_mut()?;
+            registry_page.push(listing.key())?;
+            listing.registry_page = Some(registry_page.page);
+        }
+        ctx.accounts.global_stats.record_listing()?;
+
+        emit_activity!(
+            ctx,
+            AuctionConvertedToListing {
+                auction: auction.key(),
+                listing: ctx.accounts.listing.key(),
+                mint: auction.mint,
+                seller: auction.seller,
+                price,
+            }
+        );
+        Ok(())
+    }
+
+    /// Ends the auction: NFT --> highest bidder & bid --> seller (+ fee & royalty) when the reserve is met,
+    /// otherwise NFT --> seller (or a new fixed-price listing, for auctions relisting on no sale) & the
+    /// highest bid is refunded. The auction is closed, unless sealed bids are left to forfeit.
+    /// Callable by anyone; the cranker gets the config's tip out of the auction's rent refund (the seller
+    /// the rest), sold or not. Sealed auctions left open pay it on `sweep_closed_state` instead.
+    /// Transfer hook extra accounts (if the mint has a hook) go in `remaining_accounts`.
+    pub fn settle_auction<'info>(
+        ctx: Context<'_, '_, '_, 'info, SettleAuction<'info>>,
+    ) -> Result<()> {
+        let auction = &ctx.accounts.auction;
+        require!(
+            auction.status == AuctionStatus::Active,
+            ErrorCode::AuctionNotActive
+        );
+        require!(
+            Clock::get()?.unix_timestamp >= auction.settle_ts(),
+            ErrorCode::AuctionNotEnded
+        );
+
+        let has_bid = auction.highest_bidder != Pubkey::default();
+        let sold = has_bid && auction.highest_bid >= auction.reserve_price;
+        let relist_price = match auction.on_no_sale {
+            NoSaleAction::Relist { price } if !sold => Some(price),
+            _ => None,
+        };
+
+        let seeds = &[
+            PREFIX.as_bytes(),
+            b"vault",
+            auction.mint.as_ref(),
+            &[ctx.bumps.vault],
+        ];
+        let signer = &[&seeds[..]];
+
+        // NFT goes to the winner, the relisting's vault, or back to the seller.
+        let nft_destination = if sold {
+            ctx.accounts
+                .bidder_token_account
+                .as_ref()
+                .ok_or(ErrorCode::MissingBidderAccounts)?
+                .to_account_info()
+        } else if relist_price.is_some() {
+            ctx.accounts
+                .relist_vault
+                .as_ref()
+                .ok_or(ErrorCode::MissingRelistAccounts)?
+                .to_account_info()
+        } else {
+            ctx.accounts.seller_nft_account.to_account_info()
+        };
+        transfer_nft(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.mint.to_account_info(),
+            &nft_destination,
+            &ctx.accounts.vault.to_account_info(),
+            ctx.remaining_accounts,
+            signer,
+        )?;
+
+        let is_primary = ctx.accounts.provenance.is_primary();
+        let schedule = *ctx.accounts.config.fee_schedule(is_primary);
+        let mut quote = SaleQuote::new(0, is_primary, &schedule)?;
+
+        if has_bid {
+            let (Some(bidder), Some(bid)) = (&ctx.accounts.bidder, &ctx.accounts.bid) else {
+                return err!(ErrorCode::MissingBidderAccounts);
+            };
+
+            // Pay out from the winning bid escrow: seller proceeds, withholding, marketplace fee & royalty.
+            if sold {
+                quote = SaleQuote::new(auction.highest_bid, is_primary, &schedule)?;
+                quote.route_royalty(&ctx.accounts.config, ctx.accounts.creator.is_some())?;
+
+                quote.apply_withholding(&ctx.accounts.config)?;
+
+                let escrow = bid.to_account_info();
+                transfer_lamports_from_pda(&escrow, &ctx.accounts.seller, quote.seller_proceeds)?;
+                pay_withholding(
+                    ctx.accounts.withholding.as_mut(),
+                    ctx.bumps.withholding,
+                    auction.seller,
+                    &escrow,
+                    None,
+                    quote.withheld,
+                )?;
+                quote.apply_insurance(&ctx.accounts.config)?;
+                transfer_lamports_from_pda(&escrow, &ctx.accounts.treasury, quote.marketplace_fee)?;
+                pay_insurance(
+                    ctx.accounts.insurance_fund.as_mut(),
+                    &escrow,
+                    None,
+                    quote.insurance,
+                )?;
+                if let Some(creator) = &ctx.accounts.creator {
+                    transfer_lamports_from_pda(&escrow, creator, quote.royalty)?;
+                }
+
+                let sale_record = ctx
+                    .accounts
+                    .sale_record
+                    .as_mut()
+                    .ok_or(ErrorCode::MissingSaleRecord)?;
+                ctx.accounts.provenance.record_sale(
+                    sale_record,
+                    auction.seller,
+                    auction.highest_bidder,
+                    quote.price,
+                    None,
+                    ctx.bumps.sale_record.unwrap_or_default(),
+                )?;
+                ctx.accounts.global_stats.record_sale(Some(quote.price))?;
+            }
+
+            // Close the bid, returning rent (and the escrow if unsold) to the bidder.
+            bid.close(bidder.clone())?;
+        }
+
+        if let Some(price) = relist_price {
+            // Same checks as the seller listing it themselves; if they fail, the seller can switch
+            // `on_no_sale` back to returning the NFT.
+            let collection = require_listable(
+                &ctx.accounts.config,
+                &ctx.accounts.blocked_mint,
+                &ctx.accounts.seller_ban,
+                auction.seller,
+                &auction.mint,
+                ctx.accounts.metadata.as_ref(),
+                ctx.accounts.allowed_collection.as_deref(),
+            )?;
+            let relist = ctx
+                .accounts
+                .relist
+                .as_mut()
+                .ok_or(ErrorCode::MissingRelistAccounts)?;
+            relist.open(
+                auction.seller,
+                auction.mint,
+                price,
+                None,
+                &ListingOptions::default(),
+            );
+            require_price_limits(
+                &ctx.accounts.config,
+                ctx.accounts.allowed_collection.as_deref(),
+                relist,
+                false,
+            )?;
+            relist.rent_payer = Some(ctx.accounts.cranker.key());
+            relist.collection = collection;
+            relist.embedded_metadata = embedded_metadata(&ctx.accounts.mint.to_account_info())?;
+            if let Some(registry_page) = &ctx.accounts.registry_page {
+                let mut registry_page = registry_page.load_mut()?;
+                registry_page.push(relist.key())?;
+                relist.registry_page = Some(registry_page.page);
+            }
+            ctx.accounts.global_stats.record_listing()?;
+
+            emit_activity!(
+                ctx,
+                AuctionRelisted {
+                    auction: auction.key(),
+                    listing: relist.key(),
+                    mint: auction.mint,
+                    seller: auction.seller,
+                    price,
+                }
+            );
+        }
+
+        // Unrevealed sealed bids need the auction until they're forfeited.
+        let closes = auction.mode == AuctionMode::English || auction.unrevealed_bids == 0;
+        let crank_tip = if closes {
+            let auction_info = auction.to_account_info();
+            let crank_tip = ctx
+                .accounts
+                .config
+                .crank_tip_lamports
+                .min(auction_info.lamports());
+            transfer_lamports_from_pda(&auction_info, &ctx.accounts.cranker, crank_tip)?;
+            crank_tip
+        } else {
+            0
+        };
+
+        let auction = &mut ctx.accounts.auction;
+        auction.status = AuctionStatus::Settled;
+
+        emit_activity!(
+            ctx,
+            AuctionSettled {
+                auction: auction.key(),
+                mint: auction.mint,
+                seller: auction.seller,
+                winner: if sold {
+                    Some(auction.highest_bidder)
+                } else {
+                    None
+                },
+                price: quote.price,
+                marketplace_fee: quote.marketplace_fee,
+                royalty: quote.royalty,
+                seller_proceeds: quote.seller_proceeds,
+                is_primary,
+                cranker: ctx.accounts.cranker.key(),
+                crank_tip,
+            }
+        );
+
+        if closes {
+            ctx.accounts
+                .auction
+                .close(ctx.accounts.seller.to_account_info())?;
+        }
+
+        Ok(())
+    }
+}
+
+// --------------------------------------------------------------------
+// Helpers
+// --------------------------------------------------------------------
+/// SOL transfer via the system program, no-op for zero amounts.
+fn transfer_lamports<'info>(
+    from: &AccountInfo<'info>,
+    to: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    amount: u64,
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+    let transfer_ix = system_instruction::transfer(from.key, to.key, amount);
+    invoke(
+        &transfer_ix,
+        &[from.clone(), to.clone(), system_program.clone()],
+    )?;
+    Ok(())
+}
+
+/// Moves 1 NFT `from` --> `to`; `signer_seeds` is empty when `authority` signs the tx itself.
+/// Extra accounts of the mint's transfer hook are resolved from `additional_accounts`.
+fn transfer_nft<'info>(
+    token_program: &AccountInfo<'info>,
+    from: &AccountInfo<'info>,
+    mint: &AccountInfo<'info>,
+    to: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    additional_accounts: &[AccountInfo<'info>],
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    let mut transfer_ix = match nft_transfer_fee(mint)? {
+        // Any fee on a single indivisible unit would leave the recipient with nothing.
+        Some(fee) => {
+            require!(fee == 0, ErrorCode::TransferFeeNotSupported);
+            transfer_fee::instruction::transfer_checked_with_fee(
+                token_program.key,
+                from.key,
+                mint.key,
+                to.key,
+                authority.key,
+                &[], // No additional signer
+                1,   // 1 NFT
+                0,   // indivisible NFT ( decimal points )
+                fee,
+            )?
+        }
+        None => spl_token_2022::instruction::transfer_checked(
+            token_program.key,
+            from.key,
+            mint.key,
+            to.key,
+            authority.key,
+            &[], // No additional signer
+            1,   // 1 NFT
+            0,   // indivisible NFT ( decimal points )
+        )?,
+    };
+
+    thaw_default_frozen(token_program, mint, &[from, to], additional_accounts)?;
+    attach_required_memo(from, to, additional_accounts)?;
+
+    let mut account_infos = vec![
+        from.clone(),
+        mint.clone(),
+        to.clone(),
+        authority.clone(),
+        token_program.clone(),
+    ];
+
+    // Hook-enabled mints: append the hook program, its validation account & extra metas.
+    if let Some(hook_program_id) = transfer_hook_program_id(mint)? {
+        add_extra_accounts_for_execute_cpi(
+            &mut transfer_ix,
+            &mut account_infos,
+            &hook_program_id,
+            from.clone(),
+            mint.clone(),
+            to.clone(),
+            authority.clone(),
+            1,
+            additional_accounts,
+        )?;
+    }
+
+    invoke_signed(&transfer_ix, &account_infos, signer_seeds)?;
+    Ok(())
+}
+
+/// Mints with the default account state extension freeze new (vault / buyer) accounts: thaws the frozen
+/// ones through the marketplace freeze authority PDA (`[PREFIX, "freeze_authority"]`, passed among the
+/// `additional_accounts`) when it is the mint's freeze authority, failing with `FrozenByDefaultState` otherwise.
+fn thaw_default_frozen<'info>(
+    token_program: &AccountInfo<'info>,
+    mint: &AccountInfo<'info>,
+    accounts: &[&AccountInfo<'info>],
+    additional_accounts: &[AccountInfo<'info>],
+) -> Result<()> {
+    let freeze_authority = {
+        let mint_data = mint.try_borrow_data()?;
+        let mint_state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&mint_data)?;
+        if mint_state.get_extension::<DefaultAccountState>().is_err() {
+            return Ok(());
+        }
+        Option::<Pubkey>::from(mint_state.base.freeze_authority)
+    };
+
+    for account in accounts {
+        let is_frozen = {
+            let account_data = account.try_borrow_data()?;
+            StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account_data)?
+                .base
+                .is_frozen()
+        };
+        if !is_frozen {
+            continue;
+        }
+        let (marketplace_authority, bump) =
+            Pubkey::find_program_address(&[PREFIX.as_bytes(), b"freeze_authority"], &crate::ID);
+        require!(
+            freeze_authority == Some(marketplace_authority),
+            ErrorCode::FrozenByDefaultState
+        );
+        let authority_info = additional_accounts
+            .iter()
+            .find(|info| info.key() == marketplace_authority)
+            .ok_or(ErrorCode::FrozenByDefaultState)?;
+        invoke_signed(
+            &spl_token_2022::instruction::thaw_account(
+                token_program.key,
+                account.key,
+                mint.key,
+                &marketplace_authority,
+                &[],
+            )?,
+            &[(*account).clone(), mint.clone(), authority_info.clone()],
+            &[&[PREFIX.as_bytes(), b"freeze_authority", &[bump]]],
+        )?;
+    }
+    Ok(())
+}
+
+/// Recipients with the memo transfer extension only accept transfers right after a memo: logs one
+/// naming the transfer's source (the sale's vault / escrow) through the SPL Memo program, which must
+/// be passed among the `additional_accounts`.
+fn attach_required_memo<'info>(
+    from: &AccountInfo<'info>,
+    to: &AccountInfo<'info>,
+    additional_accounts: &[AccountInfo<'info>],
+) -> Result<()> {
+    let requires_memo = {
+        let account_data = to.try_borrow_data()?;
+        memo_required(
+            &StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account_data)?,
+        )
+    };
+    if !requires_memo {
+        return Ok(());
+    }
+    let memo_program = additional_accounts
+        .iter()
+        .find(|info| info.key() == memo::ID)
+        .ok_or(ErrorCode::MissingMemoProgram)?;
+    memo::build_memo(
+        CpiContext::new(memo_program.clone(), memo::BuildMemo {}),
+        format!("{PREFIX}: sale {}", from.key).as_bytes(),
+    )
+}
+
+/// Rejects mints whose extensions break custody: non-transferable or with a permanent delegate.
+fn validate_listable_mint(mint: &AccountInfo) -> Result<()> {
+    let mint_data = mint.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&mint_data)?;
+
+    require!(
+        mint_state.get_extension::<NonTransferable>().is_err(),
+        ErrorCode::NonTransferableMint
+    );
+    if let Ok(permanent_delegate) = mint_state.get_extension::<PermanentDelegate>() {
+        require!(
+            Option::<Pubkey>::from(permanent_delegate.delegate).is_none(),
+            ErrorCode::PermanentDelegatePresent
+        );
+    }
+    Ok(())
+}
+
+/// Program of the mint's transfer hook extension, if set.
+fn transfer_hook_program_id(mint: &AccountInfo) -> Result<Option<Pubkey>> {
+    let mint_data = mint.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&mint_data)?;
+    Ok(transfer_hook::get_program_id(&mint_state))
+}
+
+/// Fee the mint's transfer fee extension charges on moving 1 NFT this epoch, `None` when it has no such extension.
+fn nft_transfer_fee(mint: &AccountInfo) -> Result<Option<u64>> {
+    let mint_data = mint.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&mint_data)?;
+    let Ok(fee_config) = mint_state.get_extension::<TransferFeeConfig>() else {
+        return Ok(None);
+    };
+    let fee = fee_config
+        .calculate_epoch_fee(Clock::get()?.epoch, 1)
+        .ok_or(ErrorCode::MathOverflow)?;
+    Ok(Some(fee))
+}
+
+/// Whether the mint has a transfer fee extension, whose fee (even if zero now) could shrink an escrow.
+fn has_transfer_fee(mint: &AccountInfo) -> Result<bool> {
+    let mint_data = mint.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&mint_data)?;
+    Ok(mint_state.get_extension::<TransferFeeConfig>().is_ok())
+}
+
+/// SPL transfer `from` --> `to` signed by `authority` (a PDA when `signer_seeds` is set), no-op for zero amounts.
+fn transfer_tokens<'info>(
+    token_program: &AccountInfo<'info>,
+    from: &AccountInfo<'info>,
+    mint: &InterfaceAccount<'info, Mint>,
+    to: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    amount: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+    let cpi_accounts = token_interface::TransferChecked {
+        from: from.clone(),
+        mint: mint.to_account_info(),
+        to: to.clone(),
+        authority: authority.clone(),
+    };
+    token_interface::transfer_checked(
+        CpiContext::new_with_signer(token_program.clone(), cpi_accounts, signer_seeds),
+        amount,
+        mint.decimals,
+    )
+}
+
+/// Closes a token account (empty, or native) owned by `authority`, its lamports going to `destination`.
+fn close_token_account<'info>(
+    token_program: &AccountInfo<'info>,
+    account: &AccountInfo<'info>,
+    destination: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    let cpi_accounts = token_interface::CloseAccount {
+        account: account.clone(),
+        destination: destination.clone(),
+        authority: authority.clone(),
+    };
+    token_interface::close_account(CpiContext::new_with_signer(
+        token_program.clone(),
+        cpi_accounts,
+        signer_seeds,
+    ))
+}
+
+fn emit_offer_made(offer: &Account<Offer>) {
+    emit!(OfferMade {
+        offer: offer.key(),
+        mint: offer.mint,
+        bidder: offer.bidder,
+        amount: offer.amount,
+        escrow: offer.escrow,
+        expires_at: offer.expires_at,
+    });
+}
+
+/// Books `offer` in its mint's order book, if passed.
+fn book_offer(
+    offer_book: Option<&AccountLoader<OfferBook>>,
+    offer: &Account<Offer>,
+    now: i64,
+) -> Result<()> {
+    if let Some(offer_book) = offer_book {
+        offer_book.load_mut()?.insert(offer.key(), offer, now);
+    }
+    Ok(())
+}
+
+/// Drops `offer` from its mint's order book, if passed.
+fn unbook_offer(offer_book: Option<&AccountLoader<OfferBook>>, offer: Pubkey) -> Result<()> {
+    if let Some(offer_book) = offer_book {
+        offer_book.load_mut()?.remove(&offer);
+    }
+    Ok(())
+}
+
+fn require_future_expiry(expires_at: Option<i64>) -> Result<()> {
+    if let Some(expires_at) = expires_at {
+        require!(
+            expires_at > Clock::get()?.unix_timestamp,
+            ErrorCode::InvalidOffer
+        );
+    }
+    Ok(())
+}
+
+/// Returns a WSOL offer's escrow to the bidder's WSOL account & closes it, its rent going to `bidder`.
+fn refund_wsol_escrow<'info>(
+    offer: &Account<'info, Offer>,
+    escrow: Option<&InterfaceAccount<'info, TokenAccount>>,
+    bidder_wsol_account: Option<&InterfaceAccount<'info, TokenAccount>>,
+    wsol_mint: Option<&InterfaceAccount<'info, Mint>>,
+    wsol_token_program: Option<&Program<'info, Token>>,
+    rent_payer: &AccountInfo<'info>,
+) -> Result<()> {
+    let (Some(escrow), Some(bidder_wsol_account), Some(wsol_mint), Some(wsol_token_program)) =
+        (escrow, bidder_wsol_account, wsol_mint, wsol_token_program)
+    else {
+        return err!(ErrorCode::MissingWsolAccounts);
+    };
+    refund_token_escrow(
+        offer,
+        escrow,
+        bidder_wsol_account,
+        wsol_mint,
+        &wsol_token_program.to_account_info(),
+        rent_payer,
+    )
+}
+
+/// Returns an SPL offer's escrow to the bidder's token account & closes it, its rent going to `rent_payer`.
+fn refund_spl_escrow<'info>(
+    offer: &Account<'info, Offer>,
+    escrow: Option<&InterfaceAccount<'info, TokenAccount>>,
+    bidder_payment_account: Option<&InterfaceAccount<'info, TokenAccount>>,
+    payment_mint: Option<&InterfaceAccount<'info, Mint>>,
+    payment_token_program: Option<&Interface<'info, TokenInterface>>,
+    rent_payer: &AccountInfo<'info>,
+) -> Result<()> {
+    let (
+        Some(escrow),
+        Some(bidder_payment_account),
+        Some(payment_mint),
+        Some(payment_token_program),
+    ) = (
+        escrow,
+        bidder_payment_account,
+        payment_mint,
+        payment_token_program,
+    )
+    else {
+        return err!(ErrorCode::MissingPaymentAccounts);
+    };
+    refund_token_escrow(
+        offer,
+        escrow,
+        bidder_payment_account,
+        payment_mint,
+        &payment_token_program.to_account_info(),
+        rent_payer,
+    )
+}
+
+/// Runs a cancel instruction's `handler` for `cancel`, its accounts `T` parsed (& validated) from `accounts`
+/// & the rest passed as its remaining accounts, then exits them as if it had been called directly.
+/// `target_of` picks the account it cancels, which must be `target`.
+fn run_cancel<'info, T>(
+    program_id: &Pubkey,
+    mut accounts: &'info [AccountInfo<'info>],
+    target: Pubkey,
+    target_of: impl Fn(&T) -> Pubkey,
+    handler: impl FnOnce(Context<'_, '_, 'info, 'info, T>) -> Result<()>,
+) -> Result<()>
+where
+    T: Accounts<'info, T::Bumps> + AccountsExit<'info> + anchor_lang::Bumps,
+    T::Bumps: Default,
+{
+    let mut bumps = T::Bumps::default();
+    let mut reallocs = std::collections::BTreeSet::new();
+    let mut cancelled = T::try_accounts(program_id, &mut accounts, &[], &mut bumps, &mut reallocs)?;
+    require_keys_eq!(
+        target_of(&cancelled),
+        target,
+        ErrorCode::InvalidCancelTarget
+    );
+    handler(Context::new(program_id, &mut cancelled, accounts, bumps))?;
+    cancelled.exit(program_id)
+}
+
+/// Moves a token escrow's `offer.amount` back to the bidder & closes it.
+fn refund_token_escrow<'info>(
+    offer: &Account<'info, Offer>,
+    escrow: &InterfaceAccount<'info, TokenAccount>,
+    bidder_account: &InterfaceAccount<'info, TokenAccount>,
+    mint: &InterfaceAccount<'info, Mint>,
+    token_program: &AccountInfo<'info>,
+    rent_payer: &AccountInfo<'info>,
+) -> Result<()> {
+    let bump = [offer.bump];
+    let seeds = &[
+        PREFIX.as_bytes(),
+        b"offer",
+        offer.mint.as_ref(),
+        offer.bidder.as_ref(),
+        &bump,
+    ];
+    let signer = &[&seeds[..]];
+    transfer_tokens(
+        token_program,
+        &escrow.to_account_info(),
+        mint,
+        &bidder_account.to_account_info(),
+        &offer.to_account_info(),
+        offer.amount,
+        signer,
+    )?;
+    close_token_account(
+        token_program,
+        &escrow.to_account_info(),
+        rent_payer,
+        &offer.to_account_info(),
+        signer,
+    )
+}
+
+/// Moves lamports out of an account owned by this program (e.g. an escrow PDA), no-op for zero amounts.
+fn transfer_lamports_from_pda<'info>(
+    from: &AccountInfo<'info>,
+    to: &AccountInfo<'info>,
+    amount: u64,
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+    let from_lamports = from
+        .lamports()
+        .checked_sub(amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+    let to_lamports = to
+        .lamports()
+        .checked_add(amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+    **from.try_borrow_mut_lamports()? = from_lamports;
+    **to.try_borrow_mut_lamports()? = to_lamports;
+    Ok(())
+}
+
+/// Returns a listing's NFT vault --> seller & closes the vault, rent to `rent_payer`.
+#[allow(clippy::too_many_arguments)]
+fn return_listed_nft<'info>(
+    token_program: &AccountInfo<'info>,
+    vault: &AccountInfo<'info>,
+    mint: &AccountInfo<'info>,
+    nft_account: &AccountInfo<'info>,
+    rent_payer: &AccountInfo<'info>,
+    listing: Pubkey,
+    vault_bump: u8,
+    additional_accounts: &[AccountInfo<'info>],
+) -> Result<()> {
+    let mint_key = mint.key();
+    let seeds = &[
+        PREFIX.as_bytes(),
+        b"vault",
+        mint_key.as_ref(),
+        listing.as_ref(),
+        &[vault_bump],
+    ];
+    let signer = &[&seeds[..]];
+    transfer_nft(
+        token_program,
+        vault,
+        mint,
+        nft_account,
+        vault,
+        additional_accounts,
+        signer,
+    )?;
+    close_token_account(token_program, vault, rent_payer, vault, signer)
+}
+
+/// Removes a registered listing from its registry page, which must be the one passed.
+fn unregister_listing(
+    listing: &Account<Listing>,
+    registry_page: Option<&AccountLoader<ListingRegistryPage>>,
+) -> Result<()> {
+    let Some(page) = listing.registry_page else {
+        return Ok(());
+    };
+    let registry_page = registry_page.ok_or(ErrorCode::InvalidRegistryPage)?;
+    let mut registry_page = registry_page.load_mut()?;
+    require!(registry_page.page == page, ErrorCode::InvalidRegistryPage);
+    registry_page.remove(&listing.key());
+    Ok(())
+}
+
+/// Grows a listing (any older layout) to the current one & stamps `LISTING_VERSION`; the payer tops up rent.
+fn migrate_listing_layout<'info>(
+    listing: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> Result<()> {
+    {
+        let data = listing.try_borrow_data()?;
+        require!(
+            data.len() >= 8 && data[..8] == Listing::DISCRIMINATOR,
+            ErrorCode::InvalidListingAccount
+        );
+    }
+
+    let new_len = 8 + Listing::INIT_SPACE;
+    if listing.data_len() < new_len {
+        // Top up rent for the larger account.
+        let rent_due = Rent::get()?
+            .minimum_balance(new_len)
+            .saturating_sub(listing.lamports());
+        transfer_lamports(payer, listing, system_program, rent_due)?;
+        listing.realloc(new_len, true)?;
+    }
+
+    let mut data = listing.try_borrow_mut_data()?;
+    let mut upgraded = Listing::try_deserialize(&mut &data[..])?;
+    if upgraded.version < LISTING_VERSION {
+        upgraded.version = LISTING_VERSION;
+        upgraded.try_serialize(&mut &mut data[..])?;
+    }
+    Ok(())
+}
+
+/// Program that composed this instruction via CPI, or `None` for a top-level instruction. The instructions
+/// sysvar only records top-level instructions, so only the outermost program can be identified: a CPI
+/// nested any deeper fails with `CpiCallerUnknown`.
+fn cpi_caller(instructions: &AccountInfo) -> Result<Option<Pubkey>> {
+    let stack_height = get_stack_height();
+    if stack_height == TRANSACTION_LEVEL_STACK_HEIGHT {
+        return Ok(None);
+    }
+    require!(
+        stack_height == TRANSACTION_LEVEL_STACK_HEIGHT + 1,
+        ErrorCode::CpiCallerUnknown
+    );
+    let current = load_current_index_checked(instructions)?;
+    let ix = load_instruction_at_checked(current as usize, instructions)?;
+    Ok(Some(ix.program_id))
+}
+
+/// Requires the (signing) `authority` to be the seller or the seller authority.
+fn require_seller_authority(
+    seller: &Pubkey,
+    seller_authority: Option<Pubkey>,
+    authority: &Pubkey,
+) -> Result<()> {
+    require!(
+        authority == seller || Some(*authority) == seller_authority,
+        ErrorCode::UnauthorizedSeller
+    );
+    Ok(())
+}
+
+/// Splits `amount` by the payees' shares; the last payee gets the rounding remainder.
+fn split_proceeds(payees: &[Payee], amount: u64) -> Result<Vec<(Pubkey, u64)>> {
+    let mut rest = amount;
+    let mut splits = Vec::with_capacity(payees.len());
+    for (i, payee) in payees.iter().enumerate() {
+        let share = if i + 1 == payees.len() {
+            rest
+        } else {
+            bps_of(amount, payee.share_bps)?
+        };
+        rest = rest.checked_sub(share).ok_or(ErrorCode::MathOverflow)?;
+        splits.push((payee.payee, share));
+    }
+    Ok(splits)
+}
+
+/// Burns `amount` tokens (e.g. fraction shares or vouchers) from `from`, signed by its owner `authority`.
+fn burn_tokens<'info>(
+    token_program: &AccountInfo<'info>,
+    mint: &AccountInfo<'info>,
+    from: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    amount: u64,
+) -> Result<()> {
+    token_interface::burn(
+        CpiContext::new(
+            token_program.clone(),
+            token_interface::Burn {
+                mint: mint.clone(),
+                from: from.clone(),
+                authority: authority.clone(),
+            },
+        ),
+        amount,
+    )
+}
+
+/// Pays an escrowed sale's proceeds to the seller; the account itself closes to the buyer.
+fn release_escrowed_sale<'info>(
+    escrowed_sale: &Account<'info, EscrowedSale>,
+    seller: &AccountInfo<'info>,
+    confirmed: bool,
+) -> Result<()> {
+    transfer_lamports_from_pda(
+        &escrowed_sale.to_account_info(),
+        seller,
+        escrowed_sale.amount,
+    )?;
+    emit!(EscrowedSaleReleased {
+        escrowed_sale: escrowed_sale.key(),
+        listing: escrowed_sale.listing,
+        seller: escrowed_sale.seller,
+        buyer: escrowed_sale.buyer,
+        amount: escrowed_sale.amount,
+        confirmed,
+    });
+    Ok(())
+}
+
+/// Requires nothing to still be escrowed against a closed listing: its per-listing `vault` closed or
+/// empty, & its installment `plan` closed.
+fn require_listing_released(
+    mint: &Pubkey,
+    listing: &Pubkey,
+    vault: &AccountInfo,
+    plan: &AccountInfo,
+) -> Result<()> {
+    let (vault_key, _) = Pubkey::find_program_address(
+        &[PREFIX.as_bytes(), b"vault", mint.as_ref(), listing.as_ref()],
+        &crate::ID,
+    );
+    let (plan_key, _) = Pubkey::find_program_address(
+        &[PREFIX.as_bytes(), b"installment", listing.as_ref()],
+        &crate::ID,
+    );
+    require_keys_eq!(vault.key(), vault_key, ErrorCode::NotSweepable);
+    require_keys_eq!(plan.key(), plan_key, ErrorCode::NotSweepable);
+    require!(plan.data_is_empty(), ErrorCode::NotSweepable);
+    if !vault.data_is_empty() {
+        require_keys_eq!(*vault.owner, Token2022::id(), ErrorCode::NotSweepable);
+        let vault = TokenAccount::try_deserialize(&mut &vault.try_borrow_data()?[..])?;
+        require!(vault.amount == 0, ErrorCode::NotSweepable);
+    }
+    Ok(())
+}
+
+/// Pays `amount` (a quote's insurance slice) into the insurance fund, from a wallet through the system
+/// program or, without one, from a PDA of this program.
+fn pay_insurance<'info>(
+    insurance_fund: Option<&mut Account<'info, InsuranceFund>>,
+    from: &AccountInfo<'info>,
+    system_program: Option<&AccountInfo<'info>>,
+    amount: u64,
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+    let fund = insurance_fund.ok_or(ErrorCode::MissingInsuranceFund)?;
+    let fund_info = fund.to_account_info();
+    match system_program {
+        Some(system_program) => transfer_lamports(from, &fund_info, system_program, amount)?,
+        None => transfer_lamports_from_pda(from, &fund_info, amount)?,
+    }
+    fund.total_deposited = fund
+        .total_deposited
+        .checked_add(amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+    Ok(())
+}
+
+/// Parks `amount` (a quote's withheld slice) in the seller's withholding, from a wallet through the
+/// system program or, without one, from a PDA of this program.
+fn pay_withholding<'info>(
+    withholding: Option<&mut Account<'info, Withholding>>,
+    bump: Option<u8>,
+    seller: Pubkey,
+    from: &AccountInfo<'info>,
+    system_program: Option<&AccountInfo<'info>>,
+    amount: u64,
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+    let withholding = withholding.ok_or(ErrorCode::MissingWithholdingAccount)?;
+    let withholding_info = withholding.to_account_info();
+    match system_program {
+        Some(system_program) => transfer_lamports(from, &withholding_info, system_program, amount)?,
+        None => transfer_lamports_from_pda(from, &withholding_info, amount)?,
+    }
+    withholding.seller = seller;
+    withholding.amount = withholding
+        .amount
+        .checked_add(amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+    withholding.bump = bump.unwrap_or_default();
+    Ok(())
+}
+
+/// Pays a sale out of an escrow PDA: seller proceeds, marketplace fee, insurance & (when routed to the
+/// creator) royalty.
+fn pay_out_from_escrow<'info>(
+    escrow: &AccountInfo<'info>,
+    seller: &AccountInfo<'info>,
+    treasury: &AccountInfo<'info>,
+    insurance_fund: Option<&mut Account<'info, InsuranceFund>>,
+    creator: Option<&AccountInfo<'info>>,
+    quote: &SaleQuote,
+) -> Result<()> {
+    transfer_lamports_from_pda(escrow, seller, quote.seller_proceeds)?;
+    transfer_lamports_from_pda(escrow, treasury, quote.marketplace_fee)?;
+    pay_insurance(insurance_fund, escrow, None, quote.insurance)?;
+    if let Some(creator) = creator {
+        transfer_lamports_from_pda(escrow, creator, quote.royalty)?;
+    }
+    Ok(())
+}
+
+/// Commitment a sealed-auction bidder submits: sha256(bidder || amount (le) || salt).
+pub fn sealed_bid_commitment(bidder: &Pubkey, amount: u64, salt: &[u8; 32]) -> [u8; 32] {
+    hashv(&[bidder.as_ref(), &amount.to_le_bytes(), salt]).to_bytes()
+}
+
+/// Hash of an embedded metadata's name & symbol: sha256(u32 LE name length || name || symbol).
+pub fn embedded_metadata_hash(name: &str, symbol: &str) -> [u8; 32] {
+    hashv(&[
+        &(name.len() as u32).to_le_bytes(),
+        name.as_bytes(),
+        symbol.as_bytes(),
+    ])
+    .to_bytes()
+}
+
+/// Merkle leaf of a key (a collection offer's eligible mint, a listing's allowlisted buyer): sha256(0x00 || key).
+pub fn merkle_leaf(key: &Pubkey) -> [u8; 32] {
+    hashv(&[&[0], key.as_ref()]).to_bytes()
+}
+
+/// Parent of two merkle nodes: sha256(0x01 || min || max), sorted so proofs need no left/right flags.
+pub fn merkle_parent(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let (left, right) = if a <= b { (a, b) } else { (b, a) };
+    hashv(&[&[1], left, right]).to_bytes()
+}
+
+fn verify_merkle_proof(key: &Pubkey, proof: &[[u8; 32]], root: &[u8; 32]) -> bool {
+    proof.iter().fold(merkle_leaf(key), |node, sibling| {
+        merkle_parent(&node, sibling)
+    }) == *root
+}
+
+/// Fee schedule of a listing's sale: the storefront's fee replaces the marketplace fee for storefront listings,
+/// others get the buyer's volume tier & the membership discount (whichever is lowest).
+fn listing_fee_schedule(
+    config: &MarketplaceConfig,
+    listing: &Listing,
+    storefront: Option<&Account<Storefront>>,
+    is_primary: bool,
+    trader_volume: u64,
+    is_member: bool,
+) -> Result<FeeSchedule> {
+    let schedule = *config.fee_schedule(is_primary);
+    let marketplace_fee_bps = match listing.storefront {
+        Some(_) => storefront.ok_or(ErrorCode::MissingStorefront)?.fee_bps,
+        None => {
+            let fee_bps = config.tiered_fee_bps(schedule.marketplace_fee_bps, trader_volume);
+            if is_member {
+                fee_bps.min(config.membership_fee_bps)
+            } else {
+                fee_bps
+            }
+        }
+    };
+    Ok(FeeSchedule {
+        marketplace_fee_bps,
+        ..schedule
+    })
+}
+
+/// Requires a storefront's `fee_bps`, in place of the marketplace fee, to fit with the royalty of both
+/// the primary & secondary schedules.
+fn validate_storefront_fee(config: &MarketplaceConfig, fee_bps: u16) -> Result<()> {
+    for is_primary in [true, false] {
+        FeeSchedule {
+            marketplace_fee_bps: fee_bps,
+            ..*config.fee_schedule(is_primary)
+        }
+        .validate()?;
+    }
+    Ok(())
+}
+
+/// Applies `update` to the `T` in `account`, a (possibly uninitialized) PDA validated by the caller's
+/// seeds: opt-in state, which callers can't leave out once it's been created.
+fn update_if_initialized<T: AccountSerialize + AccountDeserialize>(
+    account: &AccountInfo,
+    update: impl FnOnce(&mut T) -> Result<()>,
+) -> Result<()> {
+    if account.data_is_empty() {
+        return Ok(());
+    }
+    let mut data = account.try_borrow_mut_data()?;
+    let mut state = T::try_deserialize(&mut &data[..])?;
+    update(&mut state)?;
+    state.try_serialize(&mut &mut data[..])
+}
+
+/// Rejects mints with a `BlockedMint`, given the (possibly uninitialized) PDA.
+fn require_not_blocked(blocked_mint: &AccountInfo) -> Result<()> {
+    require!(blocked_mint.data_is_empty(), ErrorCode::MintBlocked);
+    Ok(())
+}
+
+/// Rejects `wallet` if it has a `BannedWallet`, given the (possibly uninitialized) PDA. The rejection
+/// is logged as a `BannedWalletRejected` event, kept in the failed transaction's logs.
+fn require_not_banned(
+    banned_wallet: &AccountInfo,
+    wallet: Pubkey,
+    action: TradeAction,
+) -> Result<()> {
+    if banned_wallet.data_is_empty() {
+        return Ok(());
+    }
+    emit!(BannedWalletRejected { wallet, action });
+    err!(ErrorCode::WalletBanned)
+}
+
+/// Checks `seller` may list `mint`: the mint isn't blocked, the seller isn't banned & the mint passes
+/// curation. Returns the mint's verified collection to record on the listing, required in strict mode.
+fn require_listable(
+    config: &MarketplaceConfig,
+    blocked_mint: &AccountInfo,
+    seller_ban: &AccountInfo,
+    seller: Pubkey,
+    mint: &Pubkey,
+    metadata: Option<&AccountInfo>,
+    allowed_collection: Option<&AllowedCollection>,
+) -> Result<Option<Pubkey>> {
+    require_not_blocked(blocked_mint)?;
+    require_not_banned(seller_ban, seller, TradeAction::List)?;
+    require_curated(config, mint, metadata, allowed_collection)?;
+
+    match metadata {
+        Some(metadata) if config.strict_listing => Ok(Some(strict_collection(mint, metadata)?)),
+        Some(metadata) => verified_collection(mint, metadata),
+        None => {
+            require!(!config.strict_listing, ErrorCode::InvalidMetadata);
+            Ok(None)
+        }
+    }
+}
+
+/// In curated mode, requires `mint` to belong to an admin-approved verified collection.
+fn require_curated(
+    config: &MarketplaceConfig,
+    mint: &Pubkey,
+    metadata: Option<&AccountInfo>,
+    allowed_collection: Option<&AllowedCollection>,
+) -> Result<()> {
+    if !config.curated_collections {
+        return Ok(());
+    }
+    let metadata = metadata.ok_or(ErrorCode::InvalidMetadata)?;
+    let collection = verified_collection(mint, metadata)?.ok_or(ErrorCode::CollectionNotAllowed)?;
+    let allowed_collection = allowed_collection.ok_or(ErrorCode::CollectionNotAllowed)?;
+    require_keys_eq!(
+        allowed_collection.collection,
+        collection,
+        ErrorCode::CollectionNotAllowed
+    );
+    Ok(())
+}
+
+/// Requires a SOL listing's price (its floor, for Dutch auctions) to be at least its collection's
+/// `min_price`, if any. SPL & USD-priced listings aren't bounded.
+fn require_min_price(
+    allowed_collection: Option<&AllowedCollection>,
+    listing: &Listing,
+) -> Result<()> {
+    let Some(min_price) =
+        allowed_collection.and_then(|allowed_collection| allowed_collection.min_price)
+    else {
+        return Ok(());
+    };
+    if listing.payment_mint.is_some() || listing.price_usd.is_some() {
+        return Ok(());
+    }
+    let lowest = match &listing.dutch_auction {
+        Some(dutch_auction) => dutch_auction.floor_price,
+        None => listing.price,
+    };
+    require!(
+        listing.to_payment_units(lowest)? >= min_price,
+        ErrorCode::PriceBelowCollectionMin
+    );
+    Ok(())
+}
+
+/// Bounds a (new) listing price: the collection's min price in curated mode, & the fat-finger cap.
+fn require_price_limits(
+    config: &MarketplaceConfig,
+    allowed_collection: Option<&AllowedCollection>,
+    listing: &Listing,
+    confirm_high_price: bool,
+) -> Result<()> {
+    if config.curated_collections {
+        require_min_price(allowed_collection, listing)?;
+    }
+    // Fat-finger protection: prices over the cap need the seller's explicit confirmation.
+    if config.max_listing_price > 0 && listing.payment_mint.is_none() && listing.price_usd.is_none()
+    {
+        require!(
+            listing.to_payment_units(listing.price)? <= config.max_listing_price
+                || confirm_high_price,
+            ErrorCode::PriceAboveListingMax
+        );
+    }
+    Ok(())
+}
+
+/// Metaplex metadata of `mint`, checked to be its metadata PDA.
+fn read_metadata(mint: &Pubkey, metadata: &AccountInfo) -> Result<Metadata> {
+    require_keys_eq!(
+        *metadata.owner,
+        mpl_token_metadata::ID,
+        ErrorCode::InvalidMetadata
+    );
+    require_keys_eq!(
+        metadata.key(),
+        Metadata::find_pda(mint).0,
+        ErrorCode::InvalidMetadata
+    );
+    Metadata::safe_deserialize(&metadata.try_borrow_data()?)
+        .map_err(|_| error!(ErrorCode::InvalidMetadata))
+}
+
+/// Requires `credential` to be an active, unexpired credential of `buyer` issued by the
+/// requirement's program & issuer.
+fn verify_attestation(
+    requirement: &AttestationRequirement,
+    credential: &AccountInfo,
+    buyer: &Pubkey,
+    now: i64,
+) -> Result<()> {
+    require_keys_eq!(
+        *credential.owner,
+        requirement.program,
+        ErrorCode::InvalidAttestation
+    );
+    let token = GatewayToken::deserialize(&mut &credential.try_borrow_data()?[..])
+        .map_err(|_| error!(ErrorCode::InvalidAttestation))?;
+    require!(
+        token.owner_wallet == *buyer
+            && token.gatekeeper_network == requirement.issuer
+            && token.state == GatewayTokenState::Active,
+        ErrorCode::InvalidAttestation
+    );
+    require!(
+        token
+            .expire_time
+            .is_none_or(|expire_time| now < expire_time),
+        ErrorCode::AttestationExpired
+    );
+    Ok(())
+}
+
+/// Verified collection of `mint`: its Metaplex collection read from its metadata account, or its
+/// Token-2022 group (e.g. a WNS collection) when the mint account itself is passed as `metadata`.
+fn verified_collection(mint: &Pubkey, metadata: &AccountInfo) -> Result<Option<Pubkey>> {
+    if metadata.key() == *mint {
+        return group_collection(metadata);
+    }
+    Ok(read_metadata(mint, metadata)?
+        .collection
+        .filter(|collection| collection.verified)
+        .map(|collection| collection.key))
+}
+
+/// Strict listing mode: `mint`'s verified collection, requiring at least one verified creator too.
+/// Token-2022 group members need none: joining the group takes its update authority's signature.
+fn strict_collection(mint: &Pubkey, metadata: &AccountInfo) -> Result<Pubkey> {
+    if metadata.key() == *mint {
+        return group_collection(metadata)?.ok_or(error!(ErrorCode::UnverifiedCollection));
+    }
+    let metadata = read_metadata(mint, metadata)?;
+    require!(
+        metadata
+            .creators
+            .is_some_and(|creators| creators.iter().any(|creator| creator.verified)),
+        ErrorCode::UnverifiedCreator
+    );
+    metadata
+        .collection
+        .filter(|collection| collection.verified)
+        .map(|collection| collection.key)
+        .ok_or(error!(ErrorCode::UnverifiedCollection))
+}
+
+/// Token-2022 group of `mint`, from the group member extension the mint's group member pointer points
+/// back to (members held in other accounts aren't read).
+fn group_collection(mint: &AccountInfo) -> Result<Option<Pubkey>> {
+    require_keys_eq!(*mint.owner, spl_token_2022::ID, ErrorCode::InvalidMetadata);
+    let mint_data = mint.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&mint_data)?;
+    let Ok(pointer) = mint_state.get_extension::<GroupMemberPointer>() else {
+        return Ok(None);
+    };
+    if Option::<Pubkey>::from(pointer.member_address) != Some(mint.key()) {
+        return Ok(None);
+    }
+    let Ok(member) = mint_state.get_extension::<TokenGroupMember>() else {
+        return Ok(None);
+    };
+    Ok((member.mint == mint.key()).then_some(member.group))
+}
+
+/// Token-2022 metadata `mint` embeds (its metadata pointer pointing back at it), `None` for mints
+/// without. Whoever can repoint the metadata must be its update authority too, or it could be swapped
+/// for another account's.
+fn embedded_metadata(mint: &AccountInfo) -> Result<Option<EmbeddedMetadata>> {
+    let mint_data = mint.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&mint_data)?;
+    let Ok(pointer) = mint_state.get_extension::<MetadataPointer>() else {
+        return Ok(None);
+    };
+    if Option::<Pubkey>::from(pointer.metadata_address) != Some(mint.key()) {
+        return Ok(None);
+    }
+    let metadata = mint_state
+        .get_variable_len_extension::<TokenMetadata>()
+        .map_err(|_| error!(ErrorCode::InvalidTokenMetadata))?;
+    let update_authority = Option::<Pubkey>::from(metadata.update_authority);
+    let pointer_authority = Option::<Pubkey>::from(pointer.authority);
+    require!(
+        metadata.mint == mint.key()
+            && (pointer_authority.is_none() || pointer_authority == update_authority),
+        ErrorCode::InvalidTokenMetadata
+    );
+    Ok(Some(EmbeddedMetadata {
+        name_symbol_hash: embedded_metadata_hash(&metadata.name, &metadata.symbol),
+        update_authority,
+    }))
+}
+
+/// First verified Metaplex creator of `mint` with a royalty share, if any.
+fn verified_creator(mint: &Pubkey, metadata: &AccountInfo) -> Result<Option<Pubkey>> {
+    Ok(read_metadata(mint, metadata)?
+        .creators
+        .unwrap_or_default()
+        .iter()
+        .find(|creator| creator.verified && creator.share > 0)
+        .map(|creator| creator.address))
+}
+
+/// Royalty share table of `mint`'s Metaplex creators, whose shares are percentages summing to 100.
+fn creator_royalty_splits(mint: &Pubkey, metadata: &AccountInfo) -> Result<Vec<Payee>> {
+    let splits: Vec<Payee> = read_metadata(mint, metadata)?
+        .creators
+        .unwrap_or_default()
+        .iter()
+        .filter(|creator| creator.share > 0)
+        .map(|creator| Payee {
+            payee: creator.address,
+            share_bps: creator.share as u16 * 100,
+        })
+        .collect();
+    let total_bps: u64 = splits.iter().map(|split| split.share_bps as u64).sum();
+    require!(
+        splits.len() <= MAX_ROYALTY_SPLITS && total_bps == BPS_DENOMINATOR,
+        ErrorCode::InvalidRoyaltySplits
+    );
+    Ok(splits)
+}
+
+/// Moves a listing's NFT from its mint's `legacy_vault` (bump `legacy_bump`) to the listing's own
+/// `vault`, closing the old vault to `rent_recipient`.
+fn migrate_legacy_vault<'info>(
+    token_program: &AccountInfo<'info>,
+    legacy_vault: &AccountInfo<'info>,
+    legacy_bump: u8,
+    mint: &AccountInfo<'info>,
+    vault: &AccountInfo<'info>,
+    rent_recipient: &AccountInfo<'info>,
+    hook_accounts: &[AccountInfo<'info>],
+) -> Result<()> {
+    let mint_key = mint.key();
+    let seeds = &[
+        PREFIX.as_bytes(),
+        b"vault",
+        mint_key.as_ref(),
+        &[legacy_bump],
+    ];
+    let signer = &[&seeds[..]];
+    transfer_nft(
+        token_program,
+        legacy_vault,
+        mint,
+        vault,
+        legacy_vault,
+        hook_accounts,
+        signer,
+    )?;
+    close_token_account(
+        token_program,
+        legacy_vault,
+        rent_recipient,
+        legacy_vault,
+        signer,
+    )
+}
+
+/// Owner of a stakeable escrow: an installment plan's buyer, or an auction bid's bidder.
+fn escrow_beneficiary(escrow: &AccountInfo) -> Result<Pubkey> {
+    require_keys_eq!(*escrow.owner, crate::ID, ErrorCode::InvalidEscrowYield);
+    let data = escrow.try_borrow_data()?;
+    if let Ok(plan) = InstallmentPlan::try_deserialize(&mut &data[..]) {
+        return Ok(plan.buyer);
+    }
+    let bid =
+        Bid::try_deserialize(&mut &data[..]).map_err(|_| error!(ErrorCode::InvalidEscrowYield))?;
+    Ok(bid.bidder)
+}
+
+/// Requires `staked` lamports (all of the escrow's position) to be out of the seller's reach: within a
+/// plan's installments past the deposit (forfeited on default), or a revealed bid that's been outbid.
+fn require_stakeable(
+    escrow: &AccountInfo,
+    auction: Option<&Account<Auction>>,
+    staked: u64,
+) -> Result<()> {
+    let data = escrow.try_borrow_data()?;
+    if let Ok(plan) = InstallmentPlan::try_deserialize(&mut &data[..]) {
+        require!(
+            staked <= plan.paid.saturating_sub(plan.deposit),
+            ErrorCode::EscrowOwedToSeller
+        );
+        return Ok(());
+    }
+    let bid =
+        Bid::try_deserialize(&mut &data[..]).map_err(|_| error!(ErrorCode::InvalidEscrowYield))?;
+    let auction = auction.ok_or(ErrorCode::InvalidEscrowYield)?;
+    require_keys_eq!(auction.key(), bid.auction, ErrorCode::InvalidEscrowYield);
+    require!(
+        bid.revealed && auction.highest_bidder != bid.bidder,
+        ErrorCode::EscrowOwedToSeller
+    );
+    Ok(())
+}
+
+/// Takes a pool loss of `shortfall` lamports out of the escrow owner's balance: a plan's paid amount,
+/// or a bid's amount.
+fn deduct_escrow_shortfall(escrow: &AccountInfo, shortfall: u64) -> Result<()> {
+    let mut data = escrow.try_borrow_mut_data()?;
+    if let Ok(mut plan) = InstallmentPlan::try_deserialize(&mut &data[..]) {
+        plan.paid = plan
+            .paid
+            .checked_sub(shortfall)
+            .ok_or(ErrorCode::MathOverflow)?;
+        return plan.try_serialize(&mut &mut data[..]);
+    }
+    let mut bid = Bid::try_deserialize(&mut &data[..])?;
+    bid.amount = bid
+        .amount
+        .checked_sub(shortfall)
+        .ok_or(ErrorCode::MathOverflow)?;
+    bid.try_serialize(&mut &mut data[..])
+}
+
+/// Calls the yield stake pool with `accounts` as its instruction's accounts, the yield `vault` signing.
+fn invoke_stake_pool<'info>(
+    stake_pool_program: &AccountInfo<'info>,
+    vault: &AccountInfo<'info>,
+    accounts: &[AccountInfo<'info>],
+    data: Vec<u8>,
+    vault_seeds: &[&[u8]],
+) -> Result<()> {
+    let ix = anchor_lang::solana_program::instruction::Instruction {
+        program_id: stake_pool_program.key(),
+        accounts: accounts
+            .iter()
+            .map(|account| AccountMeta {
+                pubkey: account.key(),
+                is_signer: account.is_signer || account.key() == vault.key(),
+                is_writable: account.is_writable,
+            })
+            .collect(),
+        data,
+    };
+    invoke_signed(&ix, accounts, &[vault_seeds])?;
+    Ok(())
+}
+
+/// Message a seller signs to authorize `fill_signed_order`, bound to this program.
+pub fn signed_order_message(order: &SignedOrder) -> Vec<u8> {
+    let mut message = Vec::new();
+    message.extend_from_slice(PREFIX.as_bytes());
+    message.extend_from_slice(b"signed_order");
+    message.extend_from_slice(crate::ID.as_ref());
+    message.extend_from_slice(&order.try_to_vec().unwrap_or_default());
+    message
+}
+
+/// Requires the instruction before the current one to be an Ed25519 program verification of `signer`'s
+/// signature over exactly `message`, with all data inline.
+fn verify_ed25519_signature(
+    instructions: &AccountInfo,
+    signer: &Pubkey,
+    message: &[u8],
+) -> Result<()> {
+    let current = load_current_index_checked(instructions)?;
+    require!(current > 0, ErrorCode::MissingSignatureVerification);
+    let ix = load_instruction_at_checked(current as usize - 1, instructions)?;
+    require_keys_eq!(
+        ix.program_id,
+        ed25519_program::ID,
+        ErrorCode::MissingSignatureVerification
+    );
+
+    // Header: 1 signature, padding, then 7 u16 offsets (sig, sig ix, pubkey, pubkey ix, msg, msg size, msg ix).
+    let data = &ix.data;
+    require!(
+        data.len() >= 16 && data[0] == 1,
+        ErrorCode::InvalidSignatureVerification
+    );
+    let read_u16 = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]);
+    let (pubkey_offset, message_offset, message_size) = (
+        read_u16(6) as usize,
+        read_u16(10) as usize,
+        read_u16(12) as usize,
+    );
+
+    // Data must live in the Ed25519 instruction itself (index u16::MAX), not in another instruction.
+    require!(
+        [read_u16(4), read_u16(8), read_u16(14)]
+            .iter()
+            .all(|index| *index == u16::MAX),
+        ErrorCode::InvalidSignatureVerification
+    );
+    let pubkey = data
+        .get(pubkey_offset..pubkey_offset + 32)
+        .ok_or(ErrorCode::InvalidSignatureVerification)?;
+    let signed = data
+        .get(message_offset..message_offset + message_size)
+        .ok_or(ErrorCode::InvalidSignatureVerification)?;
+    require!(
+        pubkey == signer.as_ref() && signed == message,
+        ErrorCode::InvalidSignatureVerification
+    );
+    Ok(())
+}
+
+/// Hash of the first slot produced at or after `draw_slot`, read from the SlotHashes sysvar. Skipped slots
+/// have no entry, so that's the one a draw locked to `draw_slot` uses, and it's fixed once produced. Fails
+/// until then, and once the sysvar no longer reaches back past `draw_slot`.
+fn draw_slot_hash(slot_hashes: &AccountInfo, draw_slot: u64) -> Result<[u8; 32]> {
+    // SlotHashes layout: u64 length, then (slot u64, hash [u8; 32]) entries, most recent first.
+    let data = slot_hashes.try_borrow_data()?;
+    let len = data
+        .get(..8)
+        .map(|len| u64::from_le_bytes(len.try_into().unwrap()))
+        .ok_or(ErrorCode::InvalidSlotHashes)?;
+    let mut drawn = None;
+    for entry in data[8..].chunks_exact(40).take(len as usize) {
+        if u64::from_le_bytes(entry[..8].try_into().unwrap()) < draw_slot {
+            return drawn.ok_or_else(|| error!(ErrorCode::RaffleDrawSlotPending));
+        }
+        drawn = Some(entry[8..].try_into().unwrap());
+    }
+    err!(ErrorCode::InvalidSlotHashes)
+}
+
+/// `amount * bps / 10_000`, rounded down.
+fn bps_of(amount: u64, bps: u16) -> Result<u64> {
+    let value = (amount as u128) * (bps as u128) / (BPS_DENOMINATOR as u128);
+    u64::try_from(value).map_err(|_| error!(ErrorCode::MathOverflow))
+}
+
+// --------------------------------------------------------------------
+// Contexts & Accounts
+// --------------------------------------------------------------------
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + MarketplaceConfig::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"config"],
+        bump
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    #[account(
+        mut,
+        has_one = authority,
+        seeds = [PREFIX.as_bytes(), b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAuthority<'info> {
+    #[account(
+        mut,
+        constraint = config.pending_authority == pending_authority.key() @ ErrorCode::InvalidConfigAuthority,
+        seeds = [PREFIX.as_bytes(), b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    pub pending_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(nonce: u64)]
+pub struct QueueAction<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + QueuedAction::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"queued_action", &nonce.to_le_bytes()],
+        bump
+    )]
+    pub queued_action: Account<'info, QueuedAction>,
+
+    #[account(has_one = authority, seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    pub authority: Signer<'info>,
+
+    /// Pays the rent, so the authority can be a PDA holding data (e.g. a governance account).
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SettleQueuedAction<'info> {
+    #[account(
+        mut,
+        close = authority,
+        seeds = [PREFIX.as_bytes(), b"queued_action", &queued_action.nonce.to_le_bytes()],
+        bump = queued_action.bump
+    )]
+    pub queued_action: Account<'info, QueuedAction>,
+
+    #[account(
+        mut,
+        has_one = authority,
+        seeds = [PREFIX.as_bytes(), b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// Reward vault sweeps: the config's reward mint, its vault & the treasury's account of it.
+    #[account(address = config.reward_mint)]
+    pub reward_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"reward_vault", config.reward_mint.as_ref()], bump)]
+    pub reward_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut, token::mint = config.reward_mint, token::authority = config.treasury)]
+    pub treasury_reward_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub reward_token_program: Option<Interface<'info, TokenInterface>>,
+}
+
+#[derive(Accounts)]
+#[instruction(collection: Pubkey)]
+pub struct AddAllowedCollection<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + AllowedCollection::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"allowed_collection", collection.as_ref()],
+        bump
+    )]
+    pub allowed_collection: Account<'info, AllowedCollection>,
+
+    #[account(has_one = authority, seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    pub authority: Signer<'info>,
+
+    /// Pays the rent, so the authority can be a PDA holding data (e.g. a governance account).
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetCollectionMinPrice<'info> {
+    /// CHECK: Approval in a possibly older layout; discriminator checked in the handler.
+    #[account(mut, owner = crate::ID)]
+    pub allowed_collection: AccountInfo<'info>,
+
+    #[account(has_one = authority, seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    pub authority: Signer<'info>,
+
+    /// Pays the extra rent of an approval grown to the current layout.
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveAllowedCollection<'info> {
+    #[account(
+        mut,
+        close = authority,
+        seeds = [PREFIX.as_bytes(), b"allowed_collection", allowed_collection.collection.as_ref()],
+        bump = allowed_collection.bump
+    )]
+    pub allowed_collection: Account<'info, AllowedCollection>,
+
+    #[account(has_one = authority, seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(mint: Pubkey)]
+pub struct BlockMint<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + BlockedMint::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"blocked_mint", mint.as_ref()],
+        bump
+    )]
+    pub blocked_mint: Account<'info, BlockedMint>,
+
+    #[account(has_one = authority, seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    pub authority: Signer<'info>,
+
+    /// Pays the rent, so the authority can be a PDA holding data (e.g. a governance account).
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(wallet: Pubkey)]
+pub struct BanWallet<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + BannedWallet::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"banned_wallet", wallet.as_ref()],
+        bump
+    )]
+    pub banned_wallet: Account<'info, BannedWallet>,
+
+    #[account(has_one = authority, seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    pub authority: Signer<'info>,
+
+    /// Pays the rent, so the authority can be a PDA holding data (e.g. a governance account).
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UnbanWallet<'info> {
+    #[account(
+        mut,
+        close = authority,
+        seeds = [PREFIX.as_bytes(), b"banned_wallet", banned_wallet.wallet.as_ref()],
+        bump = banned_wallet.bump
+    )]
+    pub banned_wallet: Account<'info, BannedWallet>,
+
+    #[account(has_one = authority, seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UnblockMint<'info> {
+    #[account(
+        mut,
+        close = authority,
+        seeds = [PREFIX.as_bytes(), b"blocked_mint", blocked_mint.mint.as_ref()],
+        bump = blocked_mint.bump
+    )]
+    pub blocked_mint: Account<'info, BlockedMint>,
+
+    #[account(has_one = authority, seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeInsuranceFund<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + InsuranceFund::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"insurance_fund"],
+        bump
+    )]
+    pub insurance_fund: Account<'info, InsuranceFund>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct PayInsuranceClaim<'info> {
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"insurance_fund"], bump = insurance_fund.bump)]
+    pub insurance_fund: Account<'info, InsuranceFund>,
+
+    #[account(has_one = authority, seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    pub authority: Signer<'info>,
+
+    /// CHECK: Any account receiving the claim.
+    #[account(mut)]
+    pub recipient: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SweepFees<'info> {
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    pub cranker: Signer<'info>,
+
+    /// Collects the marketplace fees, as `config.treasury`.
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"fee_vault"], bump)]
+    pub fee_vault: SystemAccount<'info>,
+
+    /// CHECK: Gets the sweep. Validated to match `config.fee_sweep_recipient`.
+    #[account(mut, address = config.fee_sweep_recipient)]
+    pub recipient: AccountInfo<'info>,
+
+    /// Mint of the SPL fees to sweep too (optional, with the accounts below).
+    #[account(mint::token_program = payment_token_program)]
+    pub payment_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(mut, token::mint = payment_mint, token::authority = fee_vault)]
+    pub vault_payment_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut, token::mint = payment_mint, token::authority = config.fee_sweep_recipient)]
+    pub recipient_payment_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub payment_token_program: Option<Interface<'info, TokenInterface>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteBuyback<'info> {
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    /// Permissionless; pays for the vault's token accounts the first time.
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+
+    /// Holds the lamports set aside for buybacks.
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"buyback_vault"], bump)]
+    pub buyback_vault: SystemAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = cranker,
+        associated_token::mint = wsol_mint,
+        associated_token::authority = buyback_vault,
+        associated_token::token_program = token_program
+    )]
+    pub vault_wsol_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = cranker,
+        associated_token::mint = buyback_mint,
+        associated_token::authority = buyback_vault,
+        associated_token::token_program = buyback_token_program
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = anchor_spl::token::spl_token::native_mint::ID)]
+    pub wsol_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut, address = config.buyback_mint, mint::token_program = buyback_token_program)]
+    pub buyback_mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: The whitelisted AMM, invoked with the remaining accounts.
+    #[account(executable, address = config.buyback_amm_program)]
+    pub amm_program: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub buyback_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeGlobalStats<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + GlobalStats::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"global_stats"],
+        bump
+    )]
+    pub global_stats: Account<'info, GlobalStats>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(collection: Pubkey)]
+pub struct CreateCollectionStats<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + CollectionStats::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"collection_stats", collection.as_ref()],
+        bump
+    )]
+    pub collection_stats: Account<'info, CollectionStats>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CacheRoyalties<'info> {
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + RoyaltyCache::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"royalty_cache", mint.key().as_ref()],
+        bump
+    )]
+    pub royalty_cache: Account<'info, RoyaltyCache>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: Metaplex metadata of `mint`. Validated in `read_metadata`.
+    pub metadata: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SyncRoyaltyCreator<'info> {
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"provenance", mint.key().as_ref()],
+        bump = provenance.bump
+    )]
+    pub provenance: Account<'info, Provenance>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: Metaplex metadata of `mint`. Validated in `read_metadata`.
+    pub metadata: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreateTraderProfile<'info> {
+    #[account(
+        init,
+        payer = trader,
+        space = 8 + TraderProfile::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"trader_profile", trader.key().as_ref()],
+        bump
+    )]
+    pub profile: Account<'info, TraderProfile>,
+
+    #[account(mut)]
+    pub trader: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CreateStorefront<'info> {
+    #[account(init, payer = authority, space = 8 + Storefront::INIT_SPACE)]
+    pub storefront: Account<'info, Storefront>,
+
+    #[account(has_one = authority, seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    /// Config authority.
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateStorefront<'info> {
+    #[account(mut, has_one = authority)]
+    pub storefront: Account<'info, Storefront>,
+
+    pub authority: Signer<'info>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct ListNFT<'info> {
+    /// Listing account stores seller, price, etc (on chain). New, except for `list_or_update_nft`.
+    #[account(init_if_needed, payer = payer, space = 8 + Listing::INIT_SPACE)]
+    pub listing: Account<'info, Listing>,
+
+    #[account(mut)]
+    pub seller: Signer<'info>,
+
+    /// Pays the listing's, vault's & provenance's rent (the seller, or a relayer listing for them);
+    /// gets the listing's & vault's rent back when the NFT is delisted or sold.
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(mut, owner = token_program.key())]
+    pub nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(constraint = mint.key() == nft_account.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// Fresh NFT vault for this listing, closed when the NFT leaves it.
+    #[account(
+        init_if_needed,
+        token::mint = mint,
+        payer = payer,
+        token::authority = vault,
+        seeds = [PREFIX.as_bytes(), b"vault", nft_account.mint.as_ref(), listing.key().as_ref()],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// Per-mint marketplace history, created on the mint's first listing.
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + Provenance::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"provenance", mint.key().as_ref()],
+        bump
+    )]
+    pub provenance: Account<'info, Provenance>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"global_stats"], bump = global_stats.bump)]
+    pub global_stats: Account<'info, GlobalStats>,
+
+    /// CHECK: `BlockedMint` PDA of `mint`, must be uninitialized. Validated in `require_not_blocked`.
+    #[account(seeds = [PREFIX.as_bytes(), b"blocked_mint", mint.key().as_ref()], bump)]
+    pub blocked_mint: AccountInfo<'info>,
+
+    /// CHECK: `BannedWallet` PDA of `seller`, must be uninitialized. Validated in `require_not_banned`.
+    #[account(seeds = [PREFIX.as_bytes(), b"banned_wallet", seller.key().as_ref()], bump)]
+    pub seller_ban: AccountInfo<'info>,
+
+    /// Approval of the mint's collection, required in curated mode.
+    pub allowed_collection: Option<Account<'info, AllowedCollection>>,
+
+    /// Storefront to list through (optional); its fee & curation apply to the listing.
+    pub storefront: Option<Account<'info, Storefront>>,
+
+    /// CHECK: Metaplex metadata of `mint`, required in curated & strict listing modes & by curated
+    /// storefronts. Validated in `verified_collection` / `strict_collection`.
+    pub metadata: Option<AccountInfo<'info>>,
+
+    /// Registry page (with room left) the listing is added to, required unless `list_or_update_nft`
+    /// updates an existing listing.
+    #[account(mut)]
+    pub registry_page: Option<AccountLoader<'info, ListingRegistryPage>>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token2022>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+impl<'info> ListNFT<'info> {
+    /// Whether `listing` was just created, rather than an existing one passed to `list_or_update_nft`.
+    fn is_new_listing(&self) -> bool {
+        self.listing.seller == Pubkey::default()
+    }
+
+    /// Transfers 1 NFT seller --> vault & records provenance, shared by all listing modes.
+    fn escrow_nft(
+        &mut self,
+        remaining_accounts: &[AccountInfo<'info>],
+        provenance_bump: u8,
+    ) -> Result<()> {
+        require!(self.is_new_listing(), ErrorCode::ListingAlreadyExists);
+        require!(!self.config.paused, ErrorCode::MarketplacePaused);
+        // Soulbound or permanent-delegate mints can't be safely escrowed & sold.
+        validate_listable_mint(&self.mint.to_account_info())?;
+
+        // Transfer 1 NFT seller --> vault
+        transfer_nft(
+            &self.token_program.to_account_info(),
+            &self.nft_account.to_account_info(),
+            &self.mint.to_account_info(),
+            &self.vault.to_account_info(),
+            &self.seller.to_account_info(),
+            remaining_accounts,
+            &[],
+        )?;
+
+        // First time this mint goes through the marketplace: royalties go to its verified creator, or
+        // else to the lister as its originator.
+        let creator = match &self.metadata {
+            Some(metadata) => verified_creator(&self.mint.key(), metadata)?,
+            None => None,
+        };
+        self.provenance.init_if_new(
+            self.mint.key(),
+            creator.unwrap_or(self.seller.key()),
+            provenance_bump,
+        );
+        Ok(())
+    }
+
+    /// Inits listing account data, after checking the storefront's curation (if listed through one).
+    fn open_listing(
+        &mut self,
+        price: u64,
+        price_usd: Option<u64>,
+        options: &ListingOptions,
+    ) -> Result<()> {
+        let collection = require_listable(
+            &self.config,
+            &self.blocked_mint,
+            &self.seller_ban,
+            self.seller.key(),
+            &self.mint.key(),
+            self.metadata.as_ref(),
+            self.allowed_collection.as_deref(),
+        )?;
+
+        let royalty_splits = match &self.metadata {
+            Some(metadata) if options.split_royalties => {
+                Some(creator_royalty_splits(&self.mint.key(), metadata)?)
+            }
+            None if options.split_royalties => return err!(ErrorCode::InvalidMetadata),
+            _ => None,
+        };
+
+        let storefront = match &self.storefront {
+            Some(storefront) => {
+                // Curated storefronts only take mints of their allowlisted collections.
+                if !storefront.allowed_collections.is_empty() {
+                    require!(self.metadata.is_some(), ErrorCode::InvalidMetadata);
+                    require!(
+                        collection.is_some_and(|collection| storefront
+                            .allowed_collections
+                            .contains(&collection)),
+                        ErrorCode::CollectionNotAllowed
+                    );
+                }
+                Some(storefront.key())
+            }
+            None => None,
+        };
+
+        self.listing.open(
+            self.seller.key(),
+            self.mint.key(),
+            price,
+            price_usd,
+            options,
+        );
+        require_price_limits(
+            &self.config,
+            self.allowed_collection.as_deref(),
+            &self.listing,
+            options.confirm_high_price,
+        )?;
+        self.listing.storefront = storefront;
+        self.listing.rent_payer = Some(self.payer.key());
+        self.listing.collection = collection;
+        self.listing.royalty_splits = royalty_splits;
+        self.listing.embedded_metadata = embedded_metadata(&self.mint.to_account_info())?;
+
+        let mut registry_page = self
+            .registry_page
+            .as_ref()
+            .ok_or(ErrorCode::InvalidRegistryPage)?
+            .load_mut()?;
+        registry_page.push(self.listing.key())?;
+        self.listing.registry_page = Some(registry_page.page);
+
+        self.global_stats.record_listing()
+    }
+
+    /// Re-prices & re-terms the seller's active listing, for `list_or_update_nft`, under the same
+    /// pause, ban & block checks as a new listing.
+    fn update_listing(&mut self, price: u64, options: &ListingOptions) -> Result<()> {
+        require!(!self.config.paused, ErrorCode::MarketplacePaused);
+        require_not_blocked(&self.blocked_mint)?;
+        require_not_banned(&self.seller_ban, self.seller.key(), TradeAction::List)?;
+        let listing = &mut self.listing;
+        require_keys_eq!(listing.seller, self.seller.key(), ErrorCode::Unauthorized);
+        require_keys_eq!(
+            listing.mint,
+            self.mint.key(),
+            ErrorCode::InvalidListingAccount
+        );
+        require!(listing.is_active, ErrorCode::InactiveListing);
+        require!(
+            !options.split_royalties || listing.royalty_splits.is_some(),
+            ErrorCode::InvalidListingOptions
+        );
+
+        let price_usd = listing.price_usd;
+        listing.set_terms(price, price_usd, options);
+        listing.installment_terms = None;
+        require_price_limits(
+            &self.config,
+            self.allowed_collection.as_deref(),
+            &self.listing,
+            options.confirm_high_price,
+        )
+    }
+}
+
+#[derive(Accounts)]
+pub struct RemoveListedNFT<'info> {
+    /// CHECK: Gets the NFT back. Validated to match `listing.seller`.
+    #[account(mut)]
+    pub seller: AccountInfo<'info>,
+
+    /// The seller or the listing's `seller_authority`. Validated in `require_seller_authority`.
+    pub authority: Signer<'info>,
+
+    #[account(mut, token::authority = seller)]
+    pub nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Validate seller is the same as in the listing, & the mint matches.
+    #[account(mut, has_one = seller, constraint = nft_account.mint == listing.mint)]
+    pub listing: Account<'info, Listing>,
+
+    /// CHECK: Gets the listing's rent back. Validated to match `listing.rent_recipient()`.
+    #[account(mut, address = listing.rent_recipient())]
+    pub rent_payer: AccountInfo<'info>,
+
+    #[account(constraint = mint.key() == nft_account.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"vault", nft_account.mint.as_ref(), listing.key().as_ref()],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// Registry page of the listing, required for registered listings.
+    #[account(mut)]
+    pub registry_page: Option<AccountLoader<'info, ListingRegistryPage>>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token2022>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct DelistBatch<'info> {
+    /// Seller of every listing in the batch; gets the NFTs back.
+    pub seller: Signer<'info>,
+
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct ReallocateListing<'info> {
+    /// CHECK: Listing in a possibly older layout; discriminator & seller checked in the handler.
+    #[account(mut, owner = crate::ID)]
+    pub listing: AccountInfo<'info>,
+
+    /// Seller or config authority, pays the extra rent.
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateListingVault<'info> {
+    #[account(has_one = seller, has_one = mint)]
+    pub listing: Account<'info, Listing>,
+
+    /// Pays for the new vault & gets the old one's rent back.
+    #[account(mut)]
+    pub seller: Signer<'info>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// Mint-only vault of listings created before vaults were per listing.
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"vault", mint.key().as_ref()],
+        bump
+    )]
+    pub legacy_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init,
+        token::mint = mint,
+        payer = seller,
+        token::authority = vault,
+        seeds = [PREFIX.as_bytes(), b"vault", mint.key().as_ref(), listing.key().as_ref()],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateVault<'info> {
+    #[account(has_one = authority, seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    /// Pays for the new vault.
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(has_one = seller, has_one = mint)]
+    pub listing: Account<'info, Listing>,
+
+    /// CHECK: Listing's seller, gets the old vault's rent back. Validated by `has_one` on `listing`.
+    #[account(mut)]
+    pub seller: AccountInfo<'info>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// Mint-only vault of listings created before vaults were per listing.
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"vault", mint.key().as_ref()],
+        bump
+    )]
+    pub legacy_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init,
+        token::mint = mint,
+        payer = authority,
+        token::authority = vault,
+        seeds = [PREFIX.as_bytes(), b"vault", mint.key().as_ref(), listing.key().as_ref()],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+#[instruction(page: u32)]
+pub struct CreateRegistryPage<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + std::mem::size_of::<ListingRegistryPage>(),
+        seeds = [PREFIX.as_bytes(), b"registry", &page.to_le_bytes()],
+        bump
+    )]
+    pub registry_page: AccountLoader<'info, ListingRegistryPage>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct PruneRegistry<'info> {
+    #[account(mut)]
+    pub registry_page: AccountLoader<'info, ListingRegistryPage>,
+}
+
+#[derive(Accounts)]
+pub struct SweepClosedState<'info> {
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+}
+
+#[derive(Accounts)]
+pub struct RecoverVault<'info> {
+    #[account(
+        mut,
+        close = depositor,
+        has_one = depositor,
+        has_one = mint,
+        seeds = [PREFIX.as_bytes(), b"vault_deposit", mint.key().as_ref()],
+        bump = vault_deposit.bump
+    )]
+    pub vault_deposit: Account<'info, VaultDeposit>,
+
+    /// CHECK: Auction, raffle or rental the NFT was escrowed for, which must be closed.
+    #[account(
+        address = vault_deposit.holder,
+        constraint = holder.owner != &crate::ID @ ErrorCode::VaultNotOrphaned
+    )]
+    pub holder: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub depositor: Signer<'info>,
+
+    #[account(mut, token::mint = mint, token::authority = depositor)]
+    pub depositor_nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"vault", mint.key().as_ref()],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateListing<'info> {
+    /// CHECK: Listing in a possibly older layout; discriminator checked in the handler.
+    #[account(mut, owner = crate::ID)]
+    pub listing: AccountInfo<'info>,
+
+    /// Pays the extra rent.
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct CloseExpiredListing<'info> {
+    #[account(mut, has_one = seller, has_one = mint)]
+    pub listing: Account<'info, Listing>,
+
+    /// Anyone can close an expired listing.
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+
+    /// CHECK: Gets the NFT back. Validated to match `listing.seller`.
+    #[account(mut)]
+    pub seller: AccountInfo<'info>,
+
+    /// CHECK: Gets the listing's rent back. Validated to match `listing.rent_recipient()`.
+    #[account(mut, address = listing.rent_recipient())]
+    pub rent_payer: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        constraint = nft_account.mint == listing.mint,
+        constraint = nft_account.owner == listing.seller
+    )]
+    pub nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"vault", mint.key().as_ref(), listing.key().as_ref()],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+#[instruction(nonce: u64)]
+pub struct AdminDelist<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + QueuedAction::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"queued_action", &nonce.to_le_bytes()],
+        bump
+    )]
+    pub queued_action: Account<'info, QueuedAction>,
+
+    pub listing: Account<'info, Listing>,
+
+    #[account(has_one = authority, seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    /// Config authority.
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct ForceDelist<'info> {
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"queued_action", &queued_action.nonce.to_le_bytes()], bump = queued_action.bump)]
+    pub queued_action: Account<'info, QueuedAction>,
+
+    #[account(mut, has_one = seller, has_one = mint)]
+    pub listing: Account<'info, Listing>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    /// Anyone can run a due forced delist; gets the action's rent with its last listing.
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+
+    /// CHECK: Gets the NFT back. Validated to match `listing.seller`.
+    #[account(mut)]
+    pub seller: AccountInfo<'info>,
+
+    /// CHECK: Gets the listing's rent back. Validated to match `listing.rent_recipient()`.
+    #[account(mut, address = listing.rent_recipient())]
+    pub rent_payer: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        constraint = nft_account.mint == listing.mint,
+        constraint = nft_account.owner == listing.seller
+    )]
+    pub nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"vault", mint.key().as_ref(), listing.key().as_ref()],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct QuoteBuy<'info> {
+    pub listing: Account<'info, Listing>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(
+        seeds = [PREFIX.as_bytes(), b"provenance", listing.mint.as_ref()],
+        bump = provenance.bump
+    )]
+    pub provenance: Account<'info, Provenance>,
+
+    /// CHECK: Pyth `PriceUpdateV2` for the SOL/USD feed, required for USD listings. Parsed & validated in `pyth`.
+    pub price_update: Option<AccountInfo<'info>>,
+
+    /// Required for storefront listings.
+    #[account(address = listing.storefront.unwrap_or_default())]
+    pub storefront: Option<Account<'info, Storefront>>,
+
+    /// Prospective buyer's stats (optional), for their fee tier.
+    pub trader_stats: Option<Account<'info, TraderStats>>,
+
+    /// Membership token account (optional), for the membership discount.
+    #[account(constraint = membership_account.mint == config.membership_mint)]
+    pub membership_account: Option<InterfaceAccount<'info, TokenAccount>>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+#[instruction(vault_bump: u8, allowlist_proof: Vec<[u8; 32]>, price_leg: Option<u8>)]
+pub struct BuyNFT<'info> {
+    #[account(mut)]
+    pub listing: Account<'info, Listing>,
+
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+
+    /// Receives the seller's proceeds of vested listings, required for those.
+    #[account(
+        init,
+        payer = buyer,
+        space = 8 + Vesting::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"vesting", listing.key().as_ref()],
+        bump
+    )]
+    pub vesting: Option<Account<'info, Vesting>>,
+
+    /// Holds the seller's proceeds of phygital listings until receipt, required for those.
+    #[account(
+        init,
+        payer = buyer,
+        space = 8 + EscrowedSale::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"escrowed_sale", listing.key().as_ref()],
+        bump
+    )]
+    pub escrowed_sale: Option<Account<'info, EscrowedSale>>,
+
+    /// CHECK: Seller account. Validated to match `listing.seller`.
+    #[account(mut, address = listing.seller)]
+    pub seller: AccountInfo<'info>,
+
+    /// CHECK: Gets the listing vault's rent back. Validated to match `listing.rent_recipient()`.
+    #[account(mut, address = listing.rent_recipient())]
+    pub rent_payer: AccountInfo<'info>,
+
+    #[account(mut, constraint = nft_account.mint == listing.mint)]
+    pub nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = listing.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// PDA vault holding NFT
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"vault", nft_account.mint.as_ref(), listing.key().as_ref()],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    /// Decides primary vs. secondary sale, holds the royalty recipient.
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"provenance", listing.mint.as_ref()],
+        bump = provenance.bump
+    )]
+    pub provenance: Account<'info, Provenance>,
+
+    /// History record of this sale, keyed by the mint's sale number.
+    #[account(
+        init,
+        payer = buyer,
+        space = 8 + SaleRecord::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"sale_record", listing.mint.as_ref(), &provenance.sales_count.to_le_bytes()],
+        bump
+    )]
+    pub sale_record: Account<'info, SaleRecord>,
+
+    /// Required for storefront listings.
+    #[account(address = listing.storefront.unwrap_or_default())]
+    pub storefront: Option<Account<'info, Storefront>>,
+
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"global_stats"], bump = global_stats.bump)]
+    pub global_stats: Account<'info, GlobalStats>,
+
+    /// CHECK: `BlockedMint` PDA of `mint`, must be uninitialized. Validated in `require_not_blocked`.
+    #[account(seeds = [PREFIX.as_bytes(), b"blocked_mint", mint.key().as_ref()], bump)]
+    pub blocked_mint: AccountInfo<'info>,
+
+    /// CHECK: `BannedWallet` PDA of `buyer`, must be uninitialized. Validated in `require_not_banned`.
+    #[account(seeds = [PREFIX.as_bytes(), b"banned_wallet", buyer.key().as_ref()], bump)]
+    pub buyer_ban: AccountInfo<'info>,
+
+    /// CHECK: `BannedWallet` PDA of `seller`, must be uninitialized. Validated in `require_not_banned`.
+    #[account(seeds = [PREFIX.as_bytes(), b"banned_wallet", listing.seller.as_ref()], bump)]
+    pub seller_ban: AccountInfo<'info>,
+
+    /// Buyer's cumulative volume, for fee tiers.
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        space = 8 + TraderStats::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"trader_stats", buyer.key().as_ref()],
+        bump
+    )]
+    pub trader_stats: Account<'info, TraderStats>,
+
+    /// Buyer's loyalty points (optional), earned on SOL-priced purchases.
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        space = 8 + LoyaltyAccount::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"loyalty", buyer.key().as_ref()],
+        bump
+    )]
+    pub loyalty_account: Option<Account<'info, LoyaltyAccount>>,
+
+    /// Buyer's purchases in the listing's drop, required for listings with a purchase limit.
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        space = 8 + WalletPurchases::INIT_SPACE,
+        seeds = [
+            PREFIX.as_bytes(),
+            b"wallet_purchases",
+            listing.seller.as_ref(),
+            listing.purchase_drop().as_ref(),
+            buyer.key().as_ref()
+        ],
+        bump
+    )]
+    pub wallet_purchases: Option<Account<'info, WalletPurchases>>,
+
+    /// Seller's withholding, required for SOL listings while `config.withholding_bps` is set.
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        space = 8 + Withholding::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"withholding", listing.seller.as_ref()],
+        bump
+    )]
+    pub withholding: Option<Account<'info, Withholding>>,
+
+    /// Purchase record kept by the buyer (optional), keyed by the mint's sale number.
+    #[account(
+        init,
+        payer = buyer,
+        space = 8 + SaleReceipt::INIT_SPACE,
+        seeds = [
+            PREFIX.as_bytes(),
+            b"sale_receipt",
+            buyer.key().as_ref(),
+            listing.mint.as_ref(),
+            &provenance.sales_count.to_le_bytes()
+        ],
+        bump
+    )]
+    pub sale_receipt: Option<Account<'info, SaleReceipt>>,
+
+    /// CHECK: Buyer's `TraderProfile` PDA, updated if created. Deserialized in `update_if_initialized`.
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"trader_profile", buyer.key().as_ref()],
+        bump
+    )]
+    pub buyer_profile: AccountInfo<'info>,
+
+    /// CHECK: `CollectionStats` PDA of the listing's verified collection, updated if created (of the default key
+    /// for listings without a collection). Deserialized in `update_if_initialized`.
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"collection_stats", listing.collection.unwrap_or_default().as_ref()],
+        bump
+    )]
+    pub collection_stats: AccountInfo<'info>,
+
+    /// Mint's cached creators & collection (optional), used instead of the listing's royalty splits.
+    #[account(
+        seeds = [PREFIX.as_bytes(), b"royalty_cache", listing.mint.as_ref()],
+        bump = royalty_cache.bump
+    )]
+    pub royalty_cache: Option<Account<'info, RoyaltyCache>>,
+
+    /// CHECK: Seller's `TraderProfile` PDA, updated if created. Deserialized in `update_if_initialized`.
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"trader_profile", listing.seller.as_ref()],
+        bump
+    )]
+    pub seller_profile: AccountInfo<'info>,
+
+    /// CHECK: Fee recipient. Validated to match the storefront's treasury, or `config.treasury`.
+    #[account(mut)]
+    pub treasury: AccountInfo<'info>,
+
+    /// Insurance fund, required while `config.insurance_bps` is set.
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"insurance_fund"], bump = insurance_fund.bump)]
+    pub insurance_fund: Option<Account<'info, InsuranceFund>>,
+
+    /// CHECK: Royalty recipient (optional). Validated to match `provenance.creator`.
+    #[account(mut, address = provenance.creator)]
+    pub creator: Option<AccountInfo<'info>>,
+
+    /// CHECK: Pyth `PriceUpdateV2` for the SOL/USD feed, required for USD listings. Parsed & validated in `pyth`.
+    pub price_update: Option<AccountInfo<'info>>,
+
+    pub token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+
+    /// Buyer's associated token account of `mint`, created (paid by the buyer) if missing.
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        associated_token::mint = mint,
+        associated_token::authority = buyer,
+        associated_token::token_program = token_program
+    )]
+    pub buyer_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// seller account
+    #[account(mut)]
+    pub seller_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// SPL-priced listings: payment mint, its token program & each party's token account.
+    #[account(address = listing.leg_payment_mint(price_leg).unwrap_or_default())]
+    pub payment_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    pub payment_token_program: Option<Interface<'info, TokenInterface>>,
+
+    #[account(mut)]
+    pub buyer_payment_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        constraint = seller_payment_account.mint == listing.leg_payment_mint(price_leg).unwrap_or_default(),
+        constraint = seller_payment_account.owner == listing.seller
+    )]
+    pub seller_payment_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Owner validated to match the fee recipient, like `treasury`.
+    #[account(
+        mut,
+        constraint = treasury_payment_account.mint == listing.leg_payment_mint(price_leg).unwrap_or_default()
+    )]
+    pub treasury_payment_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Royalty recipient's token account (optional).
+    #[account(
+        mut,
+        constraint = creator_payment_account.mint == listing.leg_payment_mint(price_leg).unwrap_or_default(),
+        constraint = creator_payment_account.owner == provenance.creator
+    )]
+    pub creator_payment_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: Charity of the listing, required for those with a charity split.
+    #[account(mut, address = listing.charity_split.map(|split| split.charity).unwrap_or_default())]
+    pub charity: Option<AccountInfo<'info>>,
+
+    /// Charity's token account, required for SPL-priced listings with a charity split.
+    #[account(
+        mut,
+        constraint = charity_payment_account.mint == listing.leg_payment_mint(price_leg).unwrap_or_default(),
+        constraint = charity_payment_account.owner
+            == listing.charity_split.map(|split| split.charity).unwrap_or_default()
+    )]
+    pub charity_payment_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Buyer's or seller's membership token account (optional), for the membership discount.
+    #[account(
+        constraint = membership_account.mint == config.membership_mint,
+        constraint = membership_account.owner == buyer.key() || membership_account.owner == listing.seller
+    )]
+    pub membership_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Buyer's token account of `listing.gate_mint`, required for token-gated listings.
+    pub gate_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: Buyer's credential, required for attestation-gated listings. Validated in `verify_attestation`.
+    pub attestation: Option<AccountInfo<'info>>,
+
+    /// Burn-to-redeem listings: voucher mint, the buyer's voucher account & its token program.
+    #[account(mut, address = listing.voucher.map(|voucher| voucher.mint).unwrap_or_default())]
+    pub voucher_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(mut, token::authority = buyer)]
+    pub buyer_voucher_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub voucher_token_program: Option<Interface<'info, TokenInterface>>,
+
+    /// CHECK: Instructions sysvar, required unless `config.cpi_policy` is `Open`.
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: Option<AccountInfo<'info>>,
+
+    /// CHECK: Fee share recipient of an allowlisted CPI caller. Validated in `enforce_cpi_policy`.
+    #[account(mut)]
+    pub caller_fee_recipient: Option<AccountInfo<'info>>,
+
+    /// Registry page of the listing, required for registered listings.
+    #[account(mut)]
+    pub registry_page: Option<AccountLoader<'info, ListingRegistryPage>>,
+
+    /// Trade mining (optional): emissions account, `config.reward_mint`, its rewards vault, both sides'
+    /// reward token accounts & the reward token program. Buyer & seller are only rewarded when passed.
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"trade_mining"], bump = trade_mining.bump)]
+    pub trade_mining: Option<Account<'info, TradeMining>>,
+
+    #[account(address = config.reward_mint)]
+    pub reward_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"reward_vault", config.reward_mint.as_ref()], bump)]
+    pub reward_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut, token::mint = config.reward_mint, token::authority = buyer)]
+    pub buyer_reward_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut, token::mint = config.reward_mint, token::authority = listing.seller)]
+    pub seller_reward_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub reward_token_program: Option<Interface<'info, TokenInterface>>,
+}
+
+impl<'info> BuyNFT<'info> {
+    /// Trade mining: pays buyer & seller their share of `volume` (lamports) in reward tokens, within
+    /// the epoch cap & what's left in the rewards vault.
+    fn mine_trade_rewards(&mut self, volume: u64, vault_bump: Option<u8>) -> Result<()> {
+        let (
+            Some(trade_mining),
+            Some(reward_mint),
+            Some(reward_vault),
+            Some(buyer_reward_account),
+            Some(seller_reward_account),
+            Some(reward_token_program),
+        ) = (
+            &mut self.trade_mining,
+            &self.reward_mint,
+            &self.reward_vault,
+            &self.buyer_reward_account,
+            &self.seller_reward_account,
+            &self.reward_token_program,
+        )
+        else {
+            return Ok(());
+        };
+        let per_side = (volume as u128 * self.config.mining_rate as u128 / LAMPORTS_PER_SOL as u128)
+            .min(u64::MAX as u128) as u64;
+        let wanted = per_side.saturating_mul(2).min(reward_vault.amount);
+        let granted = trade_mining.grant(&self.config, Clock::get()?.unix_timestamp, wanted)?;
+        if granted == 0 {
+            return Ok(());
+        }
+
+        let seeds = &[
+            PREFIX.as_bytes(),
+            b"reward_vault",
+            self.config.reward_mint.as_ref(),
+            &[vault_bump.unwrap_or_default()],
+        ];
+        let buyer_reward = granted / 2;
+        let seller_reward = granted - buyer_reward;
+        for (account, amount) in [
+            (buyer_reward_account, buyer_reward),
+            (seller_reward_account, seller_reward),
+        ] {
+            transfer_tokens(
+                &reward_token_program.to_account_info(),
+                &reward_vault.to_account_info(),
+                reward_mint,
+                &account.to_account_info(),
+                &reward_vault.to_account_info(),
+                amount,
+                &[&seeds[..]],
+            )?;
+        }
+
+        emit!(TradeRewardsMined {
+            listing: self.listing.key(),
+            buyer: self.buyer.key(),
+            seller: self.listing.seller,
+            buyer_reward,
+            seller_reward,
+            epoch: trade_mining.epoch,
+        });
+        Ok(())
+    }
+
+    /// Burn-to-redeem listings: burns the listing's voucher amount from the buyer.
+    fn burn_voucher(&self) -> Result<()> {
+        let Some(voucher) = self.listing.voucher else {
+            return Ok(());
+        };
+        let (Some(voucher_mint), Some(buyer_voucher_account), Some(voucher_token_program)) = (
+            &self.voucher_mint,
+            &self.buyer_voucher_account,
+            &self.voucher_token_program,
+        ) else {
+            return err!(ErrorCode::MissingVoucherAccounts);
+        };
+        burn_tokens(
+            &voucher_token_program.to_account_info(),
+            &voucher_mint.to_account_info(),
+            &buyer_voucher_account.to_account_info(),
+            &self.buyer.to_account_info(),
+            voucher.amount,
+        )
+    }
+
+    /// Applies `config.cpi_policy` to the program that invoked this purchase, if not called directly;
+    /// allowlisted callers with a fee share get it on SOL-priced listings.
+    fn enforce_cpi_policy(&self, quote: &mut SaleQuote) -> Result<()> {
+        if self.config.cpi_policy == CpiPolicy::Open {
+            return Ok(());
+        }
+        let instructions = self
+            .instructions
+            .as_ref()
+            .ok_or(ErrorCode::MissingInstructionsSysvar)?;
+        let Some(caller) = cpi_caller(instructions)? else {
+            return Ok(());
+        };
+        let allowed = match self.config.cpi_policy {
+            CpiPolicy::Allowlist => self
+                .config
+                .allowed_callers
+                .iter()
+                .find(|allowed| allowed.program_id == caller),
+            _ => None,
+        };
+        let allowed = allowed.ok_or(ErrorCode::CpiCallerNotAllowed)?;
+
+        if allowed.fee_share_bps > 0 && self.listing.payment_mint.is_none() {
+            let recipient = self
+                .caller_fee_recipient
+                .as_ref()
+                .ok_or(ErrorCode::InvalidCallerFeeRecipient)?;
+            require_keys_eq!(
+                recipient.key(),
+                allowed.fee_recipient,
+                ErrorCode::InvalidCallerFeeRecipient
+            );
+            quote.apply_caller_fee_share(allowed.fee_share_bps)?;
+        }
+        Ok(())
+    }
+
+    /// Listing's royalty splits, as currently cached for the mint when `royalty_cache` is passed.
+    fn royalty_splits(&self) -> Option<&Vec<Payee>> {
+        let splits = self.listing.royalty_splits.as_ref()?;
+        Some(
+            self.royalty_cache
+                .as_ref()
+                .map_or(splits, |royalty_cache| &royalty_cache.creators),
+        )
+    }
+
+    /// Whether the royalty recipient was passed, in the listing's payment currency.
+    /// Royalty splits' creators are checked as they're paid, in `pay_out`.
+    fn has_royalty_recipient(&self) -> bool {
+        if self.royalty_splits().is_some() {
+            return true;
+        }
+        match self.listing.payment_mint {
+            Some(_) => self.creator_payment_account.is_some(),
+            None => self.creator.is_some(),
+        }
+    }
+
+    fn emit_royalty_paid(&self, creator: Pubkey, amount: u64) {
+        emit!(RoyaltyPaid {
+            listing: self.listing.key(),
+            mint: self.listing.mint,
+            creator,
+            amount,
+            payment_mint: self.listing.payment_mint,
+        });
+    }
+
+    /// Storefront treasury for storefront listings, the marketplace treasury otherwise.
+    fn fee_recipient(&self) -> Pubkey {
+        match &self.storefront {
+            Some(storefront) => storefront.treasury,
+            None => self.config.treasury,
+        }
+    }
+
+    /// Pays the quote from the buyer, in SOL or the listing's payment mint.
+    /// The seller's part goes to the listing's payees (`payee_accounts`, in order) when it has any, the
+    /// royalty to the creators of its royalty splits (`royalty_accounts`, in order) likewise.
+    fn pay_out(
+        &self,
+        quote: &SaleQuote,
+        payee_accounts: &[AccountInfo<'info>],
+        royalty_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        let buyer = self.buyer.to_account_info();
+        let fee_recipient = self.fee_recipient();
+        // The seller made the listing: the maker rebate comes with the proceeds.
+        let seller_amount = quote
+            .seller_proceeds
+            .checked_add(quote.maker_rebate)
+            .ok_or(ErrorCode::MathOverflow)?;
+        let splits = match &self.listing.payees {
+            Some(payees) => split_proceeds(payees, seller_amount)?,
+            None => Vec::new(),
+        };
+        let royalty_splits = match self.royalty_splits() {
+            Some(splits) => split_proceeds(splits, quote.royalty)?,
+            None => Vec::new(),
+        };
+
+        if self.listing.payment_mint.is_none() {
+            require_keys_eq!(
+                self.treasury.key(),
+                fee_recipient,
+                ErrorCode::InvalidTreasury
+            );
+            let system_program = self.system_program.to_account_info();
+            if self.listing.vesting_duration.is_some() {
+                // Vested listings: the seller's part is streamed from the vesting PDA.
+                let vesting = self
+                    .vesting
+                    .as_ref()
+                    .ok_or(ErrorCode::MissingVestingAccount)?;
+                transfer_lamports(
+                    &buyer,
+                    &vesting.to_account_info(),
+                    &system_program,
+                    seller_amount,
+                )?;
+            } else if self.listing.receipt_window.is_some() {
+                // Phygital listings: the seller's part waits in escrow for the buyer's receipt.
+                let escrowed_sale = self
+                    .escrowed_sale
+                    .as_ref()
+                    .ok_or(ErrorCode::MissingEscrowedSale)?;
+                transfer_lamports(
+                    &buyer,
+                    &escrowed_sale.to_account_info(),
+                    &system_program,
+                    seller_amount,
+                )?;
+            } else if splits.is_empty() {
+                transfer_lamports(&buyer, &self.seller, &system_program, seller_amount)?;
+            }
+            for ((payee, amount), account) in splits.iter().zip(payee_accounts) {
+                require_keys_eq!(account.key(), *payee, ErrorCode::InvalidPayee);
+                transfer_lamports(&buyer, account, &system_program, *amount)?;
+            }
+            transfer_lamports(
+                &buyer,
+                &self.treasury,
+                &system_program,
+                quote.marketplace_fee,
+            )?;
+            if let Some(caller_fee_recipient) = &self.caller_fee_recipient {
+                transfer_lamports(
+                    &buyer,
+                    caller_fee_recipient,
+                    &system_program,
+                    quote.caller_fee,
+                )?;
+            }
+            if let Some(creator) = self.creator.as_ref().filter(|_| royalty_splits.is_empty()) {
+                transfer_lamports(&buyer, creator, &system_program, quote.royalty)?;
+            }
+            for ((creator, amount), account) in royalty_splits.iter().zip(royalty_accounts) {
+                require_keys_eq!(account.key(), *creator, ErrorCode::InvalidCreator);
+                transfer_lamports(&buyer, account, &system_program, *amount)?;
+                self.emit_royalty_paid(*creator, *amount);
+            }
+            if quote.withheld > 0 {
+                let withholding = self
+                    .withholding
+                    .as_ref()
+                    .ok_or(ErrorCode::MissingWithholdingAccount)?;
+                transfer_lamports(
+                    &buyer,
+                    &withholding.to_account_info(),
+                    &system_program,
+                    quote.withheld,
+                )?;
+            }
+            if self.listing.charity_split.is_some() {
+                let charity = self
+                    .charity
+                    .as_ref()
+                    .ok_or(ErrorCode::MissingCharityAccount)?;
+                transfer_lamports(&buyer, charity, &system_program, quote.charity)?;
+            }
+            return Ok(());
+        }
+
+        let (
+            Some(payment_mint),
+            Some(payment_token_program),
+            Some(buyer_payment_account),
+            Some(seller_payment_account),
+            Some(treasury_payment_account),
+        ) = (
+            &self.payment_mint,
+            &self.payment_token_program,
+            &self.buyer_payment_account,
+            &self.seller_payment_account,
+            &self.treasury_payment_account,
+        )
+        else {
+            return err!(ErrorCode::MissingPaymentAccounts);
+        };
+
+        require_keys_eq!(
+            treasury_payment_account.owner,
+            fee_recipient,
+            ErrorCode::InvalidTreasury
+        );
+
+        let token_program = payment_token_program.to_account_info();
+        let from = buyer_payment_account.to_account_info();
+        if splits.is_empty() {
+            transfer_tokens(
+                &token_program,
+                &from,
+                payment_mint,
+                &seller_payment_account.to_account_info(),
+                &buyer,
+                seller_amount,
+                &[],
+            )?;
+        }
+        for ((payee, amount), account) in splits.iter().zip(payee_accounts) {
+            require_keys_eq!(
+                *account.owner,
+                payment_token_program.key(),
+                ErrorCode::InvalidPayee
+            );
+            let payee_account =
+                TokenAccount::try_deserialize(&mut &account.try_borrow_data()?[..])?;
+            require!(
+                payee_account.owner == *payee && payee_account.mint == payment_mint.key(),
+                ErrorCode::InvalidPayee
+            );
+            transfer_tokens(
+                &token_program,
+                &from,
+                payment_mint,
+                account,
+                &buyer,
+                *amount,
+                &[],
+            )?;
+        }
+        transfer_tokens(
+            &token_program,
+            &from,
+            payment_mint,
+            &treasury_payment_account.to_account_info(),
+            &buyer,
+            quote.marketplace_fee,
+            &[],
+        )?;
+        if let Some(creator_payment_account) = self
+            .creator_payment_account
+            .as_ref()
+            .filter(|_| royalty_splits.is_empty())
+        {
+            transfer_tokens(
+                &token_program,
+                &from,
+                payment_mint,
+                &creator_payment_account.to_account_info(),
+                &buyer,
+                quote.royalty,
+                &[],
+            )?;
+        }
+        for ((creator, amount), account) in royalty_splits.iter().zip(royalty_accounts) {
+            require_keys_eq!(
+                *account.owner,
+                payment_token_program.key(),
+                ErrorCode::InvalidCreator
+            );
+            let creator_account =
+                TokenAccount::try_deserialize(&mut &account.try_borrow_data()?[..])?;
+            require!(
+                creator_account.owner == *creator && creator_account.mint == payment_mint.key(),
+                ErrorCode::InvalidCreator
+            );
+            transfer_tokens(
+                &token_program,
+                &from,
+                payment_mint,
+                account,
+                &buyer,
+                *amount,
+                &[],
+            )?;
+            self.emit_royalty_paid(*creator, *amount);
+        }
+        if self.listing.charity_split.is_some() {
+            let charity_payment_account = self
+                .charity_payment_account
+                .as_ref()
+                .ok_or(ErrorCode::MissingCharityAccount)?;
+            transfer_tokens(
+                &token_program,
+                &from,
+                payment_mint,
+                &charity_payment_account.to_account_info(),
+                &buyer,
+                quote.charity,
+                &[],
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+#[instruction(order: SignedOrder)]
+pub struct FillSignedOrder<'info> {
+    /// Bitmap page of the seller's nonces the order's nonce is marked used in.
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        space = 8 + OrderNonceBitmap::INIT_SPACE,
+        seeds = [
+            PREFIX.as_bytes(),
+            b"order_nonces",
+            order.seller.as_ref(),
+            &(order.nonce / ORDER_NONCES_PER_PAGE).to_le_bytes()
+        ],
+        bump
+    )]
+    pub order_nonces: Account<'info, OrderNonceBitmap>,
+
+    /// CHECK: `OrderNonce` PDA of the order's nonce from before nonce bitmaps, must be uninitialized.
+    #[account(seeds = [PREFIX.as_bytes(), b"order", order.seller.as_ref(), &order.nonce.to_le_bytes()], bump)]
+    pub legacy_order_nonce: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+
+    /// CHECK: Receives the proceeds. Validated to match `order.seller`.
+    #[account(mut, address = order.seller)]
+    pub seller: AccountInfo<'info>,
+
+    /// Seller's NFT account, delegated to `delegate`.
+    #[account(
+        mut,
+        constraint = seller_nft_account.mint == order.mint,
+        constraint = seller_nft_account.owner == order.seller
+    )]
+    pub seller_nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, constraint = buyer_nft_account.mint == order.mint)]
+    pub buyer_nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = order.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: Marketplace transfer delegate PDA, signs the NFT transfer.
+    #[account(seeds = [PREFIX.as_bytes(), b"delegate"], bump)]
+    pub delegate: AccountInfo<'info>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        space = 8 + Provenance::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"provenance", mint.key().as_ref()],
+        bump
+    )]
+    pub provenance: Account<'info, Provenance>,
+
+    /// History record of this sale, keyed by the mint's sale number.
+    #[account(
+        init,
+        payer = buyer,
+        space = 8 + SaleRecord::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"sale_record", mint.key().as_ref(), &provenance.sales_count.to_le_bytes()],
+        bump
+    )]
+    pub sale_record: Account<'info, SaleRecord>,
+
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"global_stats"], bump = global_stats.bump)]
+    pub global_stats: Account<'info, GlobalStats>,
+
+    /// CHECK: `BlockedMint` PDA of `mint`, must be uninitialized. Validated in `require_not_blocked`.
+    #[account(seeds = [PREFIX.as_bytes(), b"blocked_mint", mint.key().as_ref()], bump)]
+    pub blocked_mint: AccountInfo<'info>,
+
+    /// CHECK: `BannedWallet` PDA of `seller`, must be uninitialized. Validated in `require_not_banned`.
+    #[account(seeds = [PREFIX.as_bytes(), b"banned_wallet", seller.key().as_ref()], bump)]
+    pub seller_ban: AccountInfo<'info>,
+
+    /// CHECK: `BannedWallet` PDA of `buyer`, must be uninitialized. Validated in `require_not_banned`.
+    #[account(seeds = [PREFIX.as_bytes(), b"banned_wallet", buyer.key().as_ref()], bump)]
+    pub buyer_ban: AccountInfo<'info>,
+
+    /// CHECK: Marketplace fee recipient. Validated to match `config.treasury`.
+    #[account(mut, address = config.treasury)]
+    pub treasury: AccountInfo<'info>,
+
+    /// Insurance fund, required while `config.insurance_bps` is set.
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"insurance_fund"], bump = insurance_fund.bump)]
+    pub insurance_fund: Option<Account<'info, InsuranceFund>>,
+
+    /// Seller's withholding, required while `config.withholding_bps` is set.
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        space = 8 + Withholding::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"withholding", seller.key().as_ref()],
+        bump
+    )]
+    pub withholding: Option<Account<'info, Withholding>>,
+
+    /// CHECK: Royalty recipient (optional). Validated to match `provenance.creator` in the handler,
+    /// as provenance may be created by this instruction.
+    #[account(mut)]
+    pub creator: Option<AccountInfo<'info>>,
+
+    /// CHECK: Instructions sysvar, for the Ed25519 signature verification.
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(nonce: u64)]
+pub struct CancelSignedOrder<'info> {
+    #[account(
+        init_if_needed,
+        payer = seller,
+        space = 8 + OrderNonceBitmap::INIT_SPACE,
+        seeds = [
+            PREFIX.as_bytes(),
+            b"order_nonces",
+            seller.key().as_ref(),
+            &(nonce / ORDER_NONCES_PER_PAGE).to_le_bytes()
+        ],
+        bump
+    )]
+    pub order_nonces: Account<'info, OrderNonceBitmap>,
+
+    /// CHECK: `OrderNonce` PDA of the nonce from before nonce bitmaps, must be uninitialized.
+    #[account(seeds = [PREFIX.as_bytes(), b"order", seller.key().as_ref(), &nonce.to_le_bytes()], bump)]
+    pub legacy_order_nonce: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub seller: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MakeOffer<'info> {
+    /// Offer escrow, holds the offered lamports.
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + Offer::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"offer", mint.key().as_ref(), bidder.key().as_ref()],
+        bump
+    )]
+    pub offer: Account<'info, Offer>,
+
+    #[account(mut)]
+    pub bidder: Signer<'info>,
+
+    /// CHECK: `BannedWallet` PDA of `bidder`, must be uninitialized. Validated in `require_not_banned`.
+    #[account(seeds = [PREFIX.as_bytes(), b"banned_wallet", bidder.key().as_ref()], bump)]
+    pub bidder_ban: AccountInfo<'info>,
+
+    /// Pays the offer's rent (the bidder, or a relayer bidding for them); gets it back on close.
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// Best offer on `mint`, updated with this one.
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + BestOffer::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"best_offer", mint.key().as_ref()],
+        bump
+    )]
+    pub best_offer: Account<'info, BestOffer>,
+
+    /// Bid order book of the mint, kept in sync when passed.
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"offer_book", mint.key().as_ref()], bump)]
+    pub offer_book: Option<AccountLoader<'info, OfferBook>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MakeWsolOffer<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + Offer::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"offer", mint.key().as_ref(), bidder.key().as_ref()],
+        bump
+    )]
+    pub offer: Account<'info, Offer>,
+
+    /// Offer escrow, holds the offered WSOL.
+    #[account(
+        init,
+        payer = payer,
+        token::mint = wsol_mint,
+        token::authority = offer,
+        token::token_program = wsol_token_program,
+        seeds = [PREFIX.as_bytes(), b"offer_escrow", offer.key().as_ref()],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub bidder: Signer<'info>,
+
+    /// CHECK: `BannedWallet` PDA of `bidder`, must be uninitialized. Validated in `require_not_banned`.
+    #[account(seeds = [PREFIX.as_bytes(), b"banned_wallet", bidder.key().as_ref()], bump)]
+    pub bidder_ban: AccountInfo<'info>,
+
+    /// Pays the offer's rent (the bidder, or a relayer bidding for them); gets it back on close.
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(mut, token::mint = wsol_mint, token::authority = bidder)]
+    pub bidder_wsol_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(address = anchor_spl::token::spl_token::native_mint::ID)]
+    pub wsol_mint: InterfaceAccount<'info, Mint>,
+
+    /// Best offer on `mint`, updated with this one.
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + BestOffer::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"best_offer", mint.key().as_ref()],
+        bump
+    )]
+    pub best_offer: Account<'info, BestOffer>,
+
+    /// Bid order book of the mint, kept in sync when passed.
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"offer_book", mint.key().as_ref()], bump)]
+    pub offer_book: Option<AccountLoader<'info, OfferBook>>,
+
+    pub wsol_token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MakeSplOffer<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + Offer::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"offer", mint.key().as_ref(), bidder.key().as_ref()],
+        bump
+    )]
+    pub offer: Account<'info, Offer>,
+
+    /// Offer escrow, holds the offered tokens.
+    #[account(
+        init,
+        payer = payer,
+        token::mint = payment_mint,
+        token::authority = offer,
+        token::token_program = payment_token_program,
+        seeds = [PREFIX.as_bytes(), b"offer_escrow", offer.key().as_ref()],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub bidder: Signer<'info>,
+
+    /// CHECK: `BannedWallet` PDA of `bidder`, must be uninitialized. Validated in `require_not_banned`.
+    #[account(seeds = [PREFIX.as_bytes(), b"banned_wallet", bidder.key().as_ref()], bump)]
+    pub bidder_ban: AccountInfo<'info>,
+
+    /// Pays the offer's rent (the bidder, or a relayer bidding for them); gets it back on close.
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        token::mint = payment_mint,
+        token::authority = bidder,
+        token::token_program = payment_token_program
+    )]
+    pub bidder_payment_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// WSOL offers go through `make_wsol_offer`, to be ranked with the lamport ones.
+    #[account(constraint = payment_mint.key() != anchor_spl::token::spl_token::native_mint::ID @ ErrorCode::InvalidOffer)]
+    pub payment_mint: InterfaceAccount<'info, Mint>,
+
+    /// Best offer on `mint`, created if needed for the instructions closing offers (SPL ones aren't ranked in it).
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + BestOffer::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"best_offer", mint.key().as_ref()],
+        bump
+    )]
+    pub best_offer: Account<'info, BestOffer>,
+
+    pub payment_token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelOffer<'info> {
+    #[account(
+        mut,
+        close = rent_payer,
+        has_one = bidder,
+        seeds = [PREFIX.as_bytes(), b"offer", offer.mint.as_ref(), bidder.key().as_ref()],
+        bump = offer.bump
+    )]
+    pub offer: Account<'info, Offer>,
+
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"best_offer", offer.mint.as_ref()], bump = best_offer.bump)]
+    pub best_offer: Account<'info, BestOffer>,
+
+    /// Bid order book of the mint, kept in sync when passed.
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"offer_book", offer.mint.as_ref()], bump)]
+    pub offer_book: Option<AccountLoader<'info, OfferBook>>,
+
+    #[account(mut)]
+    pub bidder: Signer<'info>,
+
+    /// CHECK: Gets the offer's rent back. Validated to match `offer.rent_payer`.
+    #[account(mut, address = offer.rent_payer)]
+    pub rent_payer: AccountInfo<'info>,
+
+    /// Required for WSOL & SPL offers.
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"offer_escrow", offer.key().as_ref()], bump)]
+    pub escrow_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut, token::mint = anchor_spl::token::spl_token::native_mint::ID, token::authority = bidder)]
+    pub bidder_wsol_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(address = anchor_spl::token::spl_token::native_mint::ID)]
+    pub wsol_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    pub wsol_token_program: Option<Program<'info, Token>>,
+
+    /// Required for SPL offers, with the bidder's token account of the offer's mint.
+    #[account(address = offer.payment_mint().unwrap_or_default())]
+    pub payment_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(address = offer.payment_token_program().unwrap_or_default())]
+    pub payment_token_program: Option<Interface<'info, TokenInterface>>,
+
+    #[account(mut, constraint = bidder_payment_account.owner == offer.bidder)]
+    pub bidder_payment_account: Option<InterfaceAccount<'info, TokenAccount>>,
+}
+
+#[derive(Accounts)]
+pub struct RefreshBestOffer<'info> {
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"best_offer", best_offer.mint.as_ref()], bump = best_offer.bump)]
+    pub best_offer: Account<'info, BestOffer>,
+}
+
+#[derive(Accounts)]
+pub struct CreateOfferBook<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + std::mem::size_of::<OfferBook>(),
+        seeds = [PREFIX.as_bytes(), b"offer_book", mint.key().as_ref()],
+        bump
+    )]
+    pub offer_book: AccountLoader<'info, OfferBook>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RefreshOfferBook<'info> {
+    #[account(mut)]
+    pub offer_book: AccountLoader<'info, OfferBook>,
+}
+
+#[derive(Accounts)]
+pub struct CloseExpiredOffer<'info> {
+    #[account(
+        mut,
+        close = rent_payer,
+        has_one = bidder,
+        seeds = [PREFIX.as_bytes(), b"offer", offer.mint.as_ref(), bidder.key().as_ref()],
+        bump = offer.bump
+    )]
+    pub offer: Account<'info, Offer>,
+
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"best_offer", offer.mint.as_ref()], bump = best_offer.bump)]
+    pub best_offer: Account<'info, BestOffer>,
+
+    /// Bid order book of the mint, kept in sync when passed.
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"offer_book", offer.mint.as_ref()], bump)]
+    pub offer_book: Option<AccountLoader<'info, OfferBook>>,
+
+    /// Anyone can close an expired offer.
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+
+    /// CHECK: Gets the escrow back. Validated to match `offer.bidder`.
+    #[account(mut)]
+    pub bidder: AccountInfo<'info>,
+
+    /// CHECK: Gets the offer's rent back. Validated to match `offer.rent_payer`.
+    #[account(mut, address = offer.rent_payer)]
+    pub rent_payer: AccountInfo<'info>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    /// Required for WSOL & SPL offers.
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"offer_escrow", offer.key().as_ref()], bump)]
+    pub escrow_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut, token::mint = anchor_spl::token::spl_token::native_mint::ID, token::authority = bidder)]
+    pub bidder_wsol_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(address = anchor_spl::token::spl_token::native_mint::ID)]
+    pub wsol_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    pub wsol_token_program: Option<Program<'info, Token>>,
+
+    /// Required for SPL offers, with the bidder's token account of the offer's mint.
+    #[account(address = offer.payment_mint().unwrap_or_default())]
+    pub payment_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(address = offer.payment_token_program().unwrap_or_default())]
+    pub payment_token_program: Option<Interface<'info, TokenInterface>>,
+
+    #[account(mut, constraint = bidder_payment_account.owner == offer.bidder)]
+    pub bidder_payment_account: Option<InterfaceAccount<'info, TokenAccount>>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct AcceptOffer<'info> {
+    #[account(
+        mut,
+        close = rent_payer,
+        has_one = bidder,
+        has_one = mint,
+        seeds = [PREFIX.as_bytes(), b"offer", mint.key().as_ref(), bidder.key().as_ref()],
+        bump = offer.bump
+    )]
+    pub offer: Account<'info, Offer>,
+
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"best_offer", offer.mint.as_ref()], bump = best_offer.bump)]
+    pub best_offer: Account<'info, BestOffer>,
+
+    /// Bid order book of the mint, kept in sync when passed.
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"offer_book", offer.mint.as_ref()], bump)]
+    pub offer_book: Option<AccountLoader<'info, OfferBook>>,
+
+    /// NFT owner accepting the offer.
+    #[account(mut)]
+    pub seller: Signer<'info>,
+
+    /// CHECK: Gets the NFT. Validated to match `offer.bidder`.
+    #[account(mut)]
+    pub bidder: AccountInfo<'info>,
+
+    /// CHECK: Gets the offer's rent back. Validated to match `offer.rent_payer`.
+    #[account(mut, address = offer.rent_payer)]
+    pub rent_payer: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        constraint = seller_nft_account.mint == mint.key(),
+        constraint = seller_nft_account.owner == seller.key()
+    )]
+    pub seller_nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = bidder_nft_account.mint == mint.key(),
+        constraint = bidder_nft_account.owner == offer.bidder
+    )]
+    pub bidder_nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(
+        init_if_needed,
+        payer = seller,
+        space = 8 + Provenance::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"provenance", mint.key().as_ref()],
+        bump
+    )]
+    pub provenance: Account<'info, Provenance>,
+
+    /// History record of this sale, keyed by the mint's sale number.
+    #[account(
+        init,
+        payer = seller,
+        space = 8 + SaleRecord::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"sale_record", mint.key().as_ref(), &provenance.sales_count.to_le_bytes()],
+        bump
+    )]
+    pub sale_record: Account<'info, SaleRecord>,
+
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"global_stats"], bump = global_stats.bump)]
+    pub global_stats: Account<'info, GlobalStats>,
+
+    /// CHECK: `BlockedMint` PDA of `mint`, must be uninitialized. Validated in `require_not_blocked`.
+    #[account(seeds = [PREFIX.as_bytes(), b"blocked_mint", mint.key().as_ref()], bump)]
+    pub blocked_mint: AccountInfo<'info>,
+
+    /// CHECK: `BannedWallet` PDA of `seller`, must be uninitialized. Validated in `require_not_banned`.
+    #[account(seeds = [PREFIX.as_bytes(), b"banned_wallet", seller.key().as_ref()], bump)]
+    pub seller_ban: AccountInfo<'info>,
+
+    /// CHECK: `BannedWallet` PDA of `bidder`, must be uninitialized. Validated in `require_not_banned`.
+    #[account(seeds = [PREFIX.as_bytes(), b"banned_wallet", offer.bidder.as_ref()], bump)]
+    pub bidder_ban: AccountInfo<'info>,
+
+    /// CHECK: Marketplace fee recipient. Validated to match `config.treasury`.
+    #[account(mut, address = config.treasury)]
+    pub treasury: AccountInfo<'info>,
+
+    /// Insurance fund, required while `config.insurance_bps` is set.
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"insurance_fund"], bump = insurance_fund.bump)]
+    pub insurance_fund: Option<Account<'info, InsuranceFund>>,
+
+    /// Seller's withholding, required while `config.withholding_bps` is set.
+    #[account(
+        init_if_needed,
+        payer = seller,
+        space = 8 + Withholding::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"withholding", seller.key().as_ref()],
+        bump
+    )]
+    pub withholding: Option<Account<'info, Withholding>>,
+
+    /// CHECK: Royalty recipient (optional). Validated to match `provenance.creator` in the handler,
+    /// as provenance may be created by this instruction.
+    #[account(mut)]
+    pub creator: Option<AccountInfo<'info>>,
+
+    /// Required for WSOL & SPL offers.
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"offer_escrow", offer.key().as_ref()], bump)]
+    pub escrow_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(address = anchor_spl::token::spl_token::native_mint::ID)]
+    pub wsol_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    /// Seller's WSOL account, required for WSOL offers unless proceeds are unwrapped.
+    #[account(mut, token::mint = anchor_spl::token::spl_token::native_mint::ID, token::authority = seller)]
+    pub seller_wsol_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub wsol_token_program: Option<Program<'info, Token>>,
+
+    /// SPL offers: payment mint, its token program & each party's token account of it.
+    #[account(address = offer.payment_mint().unwrap_or_default())]
+    pub payment_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(address = offer.payment_token_program().unwrap_or_default())]
+    pub payment_token_program: Option<Interface<'info, TokenInterface>>,
+
+    #[account(
+        mut,
+        constraint = seller_payment_account.mint == offer.payment_mint().unwrap_or_default(),
+        constraint = seller_payment_account.owner == seller.key()
+    )]
+    pub seller_payment_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        constraint = treasury_payment_account.mint == offer.payment_mint().unwrap_or_default(),
+        constraint = treasury_payment_account.owner == config.treasury @ ErrorCode::InvalidTreasury
+    )]
+    pub treasury_payment_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Gets the maker rebate.
+    #[account(
+        mut,
+        constraint = bidder_payment_account.mint == offer.payment_mint().unwrap_or_default(),
+        constraint = bidder_payment_account.owner == offer.bidder
+    )]
+    pub bidder_payment_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Royalty recipient's token account (optional). Owner validated to match `provenance.creator` in
+    /// the handler, like `creator`.
+    #[account(mut, constraint = creator_payment_account.mint == offer.payment_mint().unwrap_or_default())]
+    pub creator_payment_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> AcceptOffer<'info> {
+    /// Lamport & WSOL offers: pays the sale out of the offer PDA, after unwrapping a WSOL escrow (the seller
+    /// optionally paid in WSOL first).
+    /// `withholding_bump` is the bump of the seller's withholding, if passed.
+    fn pay_out_sol_escrow(
+        &mut self,
+        quote: &mut SaleQuote,
+        withholding_bump: Option<u8>,
+    ) -> Result<()> {
+        quote.apply_withholding(&self.config)?;
+
+        // WSOL escrow: optionally pay the seller in WSOL, then unwrap the rest into the offer PDA.
+        let offer = &self.offer;
+        let offer_info = offer.to_account_info();
+        let mut seller_paid = false;
+        if offer.escrow == OfferEscrow::Wsol {
+            let (Some(escrow), Some(wsol_mint), Some(wsol_token_program)) = (
+                &self.escrow_token_account,
+                &self.wsol_mint,
+                &self.wsol_token_program,
+            ) else {
+                return err!(ErrorCode::MissingWsolAccounts);
+            };
+            let bump = [offer.bump];
+            let seeds = &[
+                PREFIX.as_bytes(),
+                b"offer",
+                offer.mint.as_ref(),
+                offer.bidder.as_ref(),
+                &bump,
+            ];
+            let signer = &[&seeds[..]];
+            if !self.config.unwrap_wsol_proceeds {
+                let seller_wsol_account = self
+                    .seller_wsol_account
+                    .as_ref()
+                    .ok_or(ErrorCode::MissingWsolAccounts)?;
+                transfer_tokens(
+                    &wsol_token_program.to_account_info(),
+                    &escrow.to_account_info(),
+                    wsol_mint,
+                    &seller_wsol_account.to_account_info(),
+                    &offer_info,
+                    quote.seller_proceeds,
+                    signer,
+                )?;
+                seller_paid = true;
+            }
+            close_token_account(
+                &wsol_token_program.to_account_info(),
+                &escrow.to_account_info(),
+                &offer_info,
+                &offer_info,
+                signer,
+            )?;
+        }
+
+        // Pay out from the offer escrow: seller proceeds (unless paid in WSOL), withholding, marketplace fee,
+        // maker rebate to the bidder & royalty.
+        if !seller_paid {
+            transfer_lamports_from_pda(&offer_info, &self.seller, quote.seller_proceeds)?;
+        }
+        pay_withholding(
+            self.withholding.as_mut(),
+            withholding_bump,
+            self.seller.key(),
+            &offer_info,
+            None,
+            quote.withheld,
+        )?;
+        quote.apply_insurance(&self.config)?;
+        transfer_lamports_from_pda(&offer_info, &self.treasury, quote.marketplace_fee)?;
+        pay_insurance(
+            self.insurance_fund.as_mut(),
+            &offer_info,
+            None,
+            quote.insurance,
+        )?;
+        transfer_lamports_from_pda(&offer_info, &self.bidder, quote.maker_rebate)?;
+        if let Some(creator) = &self.creator {
+            transfer_lamports_from_pda(&offer_info, creator, quote.royalty)?;
+        }
+        Ok(())
+    }
+
+    /// SPL offers: pays the sale out of the offer's token escrow in its token & closes the escrow.
+    fn pay_out_token_escrow(&self, quote: &SaleQuote) -> Result<()> {
+        let (
+            Some(escrow),
+            Some(payment_mint),
+            Some(payment_token_program),
+            Some(seller_payment_account),
+            Some(treasury_payment_account),
+            Some(bidder_payment_account),
+        ) = (
+            &self.escrow_token_account,
+            &self.payment_mint,
+            &self.payment_token_program,
+            &self.seller_payment_account,
+            &self.treasury_payment_account,
+            &self.bidder_payment_account,
+        )
+        else {
+            return err!(ErrorCode::MissingPaymentAccounts);
+        };
+        let offer = &self.offer;
+        let bump = [offer.bump];
+        let seeds = &[
+            PREFIX.as_bytes(),
+            b"offer",
+            offer.mint.as_ref(),
+            offer.bidder.as_ref(),
+            &bump,
+        ];
+        let signer = &[&seeds[..]];
+        let token_program = payment_token_program.to_account_info();
+        let from = escrow.to_account_info();
+        let offer_info = offer.to_account_info();
+
+        // Seller proceeds, marketplace fee, maker rebate to the bidder & royalty.
+        let payouts = [
+            (seller_payment_account, quote.seller_proceeds),
+            (treasury_payment_account, quote.marketplace_fee),
+            (bidder_payment_account, quote.maker_rebate),
+        ];
+        for (to, amount) in payouts {
+            transfer_tokens(
+                &token_program,
+                &from,
+                payment_mint,
+                &to.to_account_info(),
+                &offer_info,
+                amount,
+                signer,
+            )?;
+        }
+        if let Some(creator_payment_account) = &self.creator_payment_account {
+            require_keys_eq!(
+                creator_payment_account.owner,
+                self.provenance.creator,
+                ErrorCode::InvalidCreator
+            );
+            transfer_tokens(
+                &token_program,
+                &from,
+                payment_mint,
+                &creator_payment_account.to_account_info(),
+                &offer_info,
+                quote.royalty,
+                signer,
+            )?;
+        }
+        close_token_account(&token_program, &from, &offer_info, &offer_info, signer)
+    }
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct ExecuteSale<'info> {
+    /// Seller's side.
+    #[account(mut, has_one = seller, has_one = mint)]
+    pub listing: Account<'info, Listing>,
+
+    /// Buyer's side, a lamport offer on the listed mint.
+    #[account(
+        mut,
+        close = rent_payer,
+        has_one = mint,
+        seeds = [PREFIX.as_bytes(), b"offer", mint.key().as_ref(), offer.bidder.as_ref()],
+        bump = offer.bump
+    )]
+    pub offer: Account<'info, Offer>,
+
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"best_offer", mint.key().as_ref()], bump = best_offer.bump)]
+    pub best_offer: Account<'info, BestOffer>,
+
+    /// Bid order book of the mint, kept in sync when passed.
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"offer_book", mint.key().as_ref()], bump)]
+    pub offer_book: Option<AccountLoader<'info, OfferBook>>,
+
+    /// Anyone can execute a matching listing & offer, & pays for the bidder's NFT account if missing.
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+
+    /// CHECK: Receives the proceeds. Validated to match `listing.seller`.
+    #[account(mut)]
+    pub seller: AccountInfo<'info>,
+
+    /// CHECK: Gets the NFT. Validated to match `offer.bidder`.
+    #[account(address = offer.bidder)]
+    pub bidder: AccountInfo<'info>,
+
+    /// CHECK: Gets the offer's rent back. Validated to match `offer.rent_payer`.
+    #[account(mut, address = offer.rent_payer)]
+    pub rent_payer: AccountInfo<'info>,
+
+    /// CHECK: Gets the listing vault's rent back. Validated to match `listing.rent_recipient()`.
+    #[account(mut, address = listing.rent_recipient())]
+    pub listing_rent_payer: AccountInfo<'info>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"vault", mint.key().as_ref(), listing.key().as_ref()],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = cranker,
+        associated_token::mint = mint,
+        associated_token::authority = bidder,
+        associated_token::token_program = token_program
+    )]
+    pub bidder_nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"provenance", mint.key().as_ref()],
+        bump = provenance.bump
+    )]
+    pub provenance: Account<'info, Provenance>,
+
+    /// History record of this sale, keyed by the mint's sale number.
+    #[account(
+        init,
+        payer = cranker,
+        space = 8 + SaleRecord::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"sale_record", mint.key().as_ref(), &provenance.sales_count.to_le_bytes()],
+        bump
+    )]
+    pub sale_record: Account<'info, SaleRecord>,
+
+    /// Required for storefront listings.
+    #[account(address = listing.storefront.unwrap_or_default())]
+    pub storefront: Option<Account<'info, Storefront>>,
+
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"global_stats"], bump = global_stats.bump)]
+    pub global_stats: Account<'info, GlobalStats>,
+
+    /// CHECK: `BlockedMint` PDA of `mint`, must be uninitialized. Validated in `require_not_blocked`.
+    #[account(seeds = [PREFIX.as_bytes(), b"blocked_mint", mint.key().as_ref()], bump)]
+    pub blocked_mint: AccountInfo<'info>,
+
+    /// CHECK: `BannedWallet` PDA of the seller, must be uninitialized. Validated in `require_not_banned`.
+    #[account(seeds = [PREFIX.as_bytes(), b"banned_wallet", listing.seller.as_ref()], bump)]
+    pub seller_ban: AccountInfo<'info>,
+
+    /// CHECK: `BannedWallet` PDA of the bidder, must be uninitialized. Validated in `require_not_banned`.
+    #[account(seeds = [PREFIX.as_bytes(), b"banned_wallet", offer.bidder.as_ref()], bump)]
+    pub bidder_ban: AccountInfo<'info>,
+
+    /// CHECK: Fee recipient. Validated to match the storefront's treasury, or `config.treasury`.
+    #[account(mut)]
+    pub treasury: AccountInfo<'info>,
+
+    /// Insurance fund, required while `config.insurance_bps` is set.
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"insurance_fund"], bump = insurance_fund.bump)]
+    pub insurance_fund: Option<Account<'info, InsuranceFund>>,
+
+    /// Seller's withholding, required while `config.withholding_bps` is set.
+    #[account(
+        init_if_needed,
+        payer = cranker,
+        space = 8 + Withholding::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"withholding", seller.key().as_ref()],
+        bump
+    )]
+    pub withholding: Option<Account<'info, Withholding>>,
+
+    /// CHECK: Royalty recipient (optional). Validated to match `provenance.creator`.
+    #[account(mut, address = provenance.creator)]
+    pub creator: Option<AccountInfo<'info>>,
+
+    /// Registry page of the listing, required for registered listings.
+    #[account(mut)]
+    pub registry_page: Option<AccountLoader<'info, ListingRegistryPage>>,
+
+    pub token_program: Program<'info, Token2022>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct GiftNft<'info> {
+    #[account(mut)]
+    pub giver: Signer<'info>,
+
+    #[account(mut, token::mint = mint, token::authority = giver)]
+    pub giver_nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Any wallet; only receives the NFT.
+    pub recipient: AccountInfo<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = giver,
+        associated_token::mint = mint,
+        associated_token::authority = recipient,
+        associated_token::token_program = token_program
+    )]
+    pub recipient_nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init_if_needed,
+        payer = giver,
+        space = 8 + Provenance::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"provenance", mint.key().as_ref()],
+        bump
+    )]
+    pub provenance: Account<'info, Provenance>,
+
+    /// CHECK: `BlockedMint` PDA of `mint`, must be uninitialized. Validated in `require_not_blocked`.
+    #[account(seeds = [PREFIX.as_bytes(), b"blocked_mint", mint.key().as_ref()], bump)]
+    pub blocked_mint: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token2022>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CounterOffer<'info> {
+    #[account(
+        mut,
+        has_one = mint,
+        seeds = [PREFIX.as_bytes(), b"offer", mint.key().as_ref(), offer.bidder.as_ref()],
+        bump = offer.bump
+    )]
+    pub offer: Account<'info, Offer>,
+
+    /// NFT owner countering the offer.
+    pub seller: Signer<'info>,
+
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = seller,
+        constraint = seller_nft_account.amount == 1 @ ErrorCode::InvalidCounterOffer
+    )]
+    pub seller_nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: Marketplace transfer delegate PDA, approved on `seller_nft_account`.
+    #[account(seeds = [PREFIX.as_bytes(), b"delegate"], bump)]
+    pub delegate: AccountInfo<'info>,
+
+    /// CHECK: `BannedWallet` PDA of `seller`, must be uninitialized. Validated in `require_not_banned`.
+    #[account(seeds = [PREFIX.as_bytes(), b"banned_wallet", seller.key().as_ref()], bump)]
+    pub seller_ban: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct AcceptCounterOffer<'info> {
+    #[account(
+        mut,
+        close = rent_payer,
+        has_one = bidder,
+        has_one = mint,
+        seeds = [PREFIX.as_bytes(), b"offer", mint.key().as_ref(), bidder.key().as_ref()],
+        bump = offer.bump
+    )]
+    pub offer: Account<'info, Offer>,
+
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"best_offer", offer.mint.as_ref()], bump = best_offer.bump)]
+    pub best_offer: Account<'info, BestOffer>,
+
+    /// Bid order book of the mint, kept in sync when passed.
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"offer_book", offer.mint.as_ref()], bump)]
+    pub offer_book: Option<AccountLoader<'info, OfferBook>>,
+
+    /// Tops up the escrow, gets the NFT.
+    #[account(mut)]
+    pub bidder: Signer<'info>,
+
+    /// CHECK: Gets the offer's rent back. Validated to match `offer.rent_payer`.
+    #[account(mut, address = offer.rent_payer)]
+    pub rent_payer: AccountInfo<'info>,
+
+    /// CHECK: Countering NFT owner, gets the proceeds. Validated to match the offer's counter.
+    #[account(mut)]
+    pub seller: AccountInfo<'info>,
+
+    /// Seller's NFT account, delegated to `delegate` by `counter_offer`.
+    #[account(mut, token::mint = mint, token::authority = seller)]
+    pub seller_nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = bidder,
+        associated_token::mint = mint,
+        associated_token::authority = bidder,
+        associated_token::token_program = token_program
+    )]
+    pub bidder_nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(
+        init_if_needed,
+        payer = bidder,
+        space = 8 + Provenance::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"provenance", mint.key().as_ref()],
+        bump
+    )]
+    pub provenance: Account<'info, Provenance>,
+
+    /// History record of this sale, keyed by the mint's sale number.
+    #[account(
+        init,
+        payer = bidder,
+        space = 8 + SaleRecord::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"sale_record", mint.key().as_ref(), &provenance.sales_count.to_le_bytes()],
+        bump
+    )]
+    pub sale_record: Account<'info, SaleRecord>,
+
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"global_stats"], bump = global_stats.bump)]
+    pub global_stats: Account<'info, GlobalStats>,
+
+    /// CHECK: `BlockedMint` PDA of `mint`, must be uninitialized. Validated in `require_not_blocked`.
+    #[account(seeds = [PREFIX.as_bytes(), b"blocked_mint", mint.key().as_ref()], bump)]
+    pub blocked_mint: AccountInfo<'info>,
+
+    /// CHECK: `BannedWallet` PDA of `seller`, must be uninitialized. Validated in `require_not_banned`.
+    #[account(seeds = [PREFIX.as_bytes(), b"banned_wallet", seller.key().as_ref()], bump)]
+    pub seller_ban: AccountInfo<'info>,
+
+    /// CHECK: `BannedWallet` PDA of `bidder`, must be uninitialized. Validated in `require_not_banned`.
+    #[account(seeds = [PREFIX.as_bytes(), b"banned_wallet", bidder.key().as_ref()], bump)]
+    pub bidder_ban: AccountInfo<'info>,
+
+    /// CHECK: Marketplace fee recipient. Validated to match `config.treasury`.
+    #[account(mut, address = config.treasury)]
+    pub treasury: AccountInfo<'info>,
+
+    /// Insurance fund, required while `config.insurance_bps` is set.
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"insurance_fund"], bump = insurance_fund.bump)]
+    pub insurance_fund: Option<Account<'info, InsuranceFund>>,
+
+    /// Seller's withholding, required while `config.withholding_bps` is set.
+    #[account(
+        init_if_needed,
+        payer = bidder,
+        space = 8 + Withholding::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"withholding", seller.key().as_ref()],
+        bump
+    )]
+    pub withholding: Option<Account<'info, Withholding>>,
+
+    /// CHECK: Royalty recipient (optional). Validated to match `provenance.creator` in the handler,
+    /// as provenance may be created by this instruction.
+    #[account(mut)]
+    pub creator: Option<AccountInfo<'info>>,
+
+    /// CHECK: Marketplace transfer delegate PDA, signs the NFT transfer.
+    #[account(seeds = [PREFIX.as_bytes(), b"delegate"], bump)]
+    pub delegate: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token2022>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(collection: Pubkey)]
+pub struct MakeCollectionOffer<'info> {
+    /// Offer escrow, holds the offered lamports.
+    #[account(
+        init,
+        payer = bidder,
+        space = 8 + CollectionOffer::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"collection_offer", collection.as_ref(), bidder.key().as_ref()],
+        bump
+    )]
+    pub offer: Account<'info, CollectionOffer>,
+
+    #[account(mut)]
+    pub bidder: Signer<'info>,
+
+    /// CHECK: `BannedWallet` PDA of `bidder`, must be uninitialized. Validated in `require_not_banned`.
+    #[account(seeds = [PREFIX.as_bytes(), b"banned_wallet", bidder.key().as_ref()], bump)]
+    pub bidder_ban: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelCollectionOffer<'info> {
+    #[account(
+        mut,
+        close = bidder,
+        has_one = bidder,
+        seeds = [PREFIX.as_bytes(), b"collection_offer", offer.collection.as_ref(), bidder.key().as_ref()],
+        bump = offer.bump
+    )]
+    pub offer: Account<'info, CollectionOffer>,
+
+    #[account(mut)]
+    pub bidder: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct AcceptCollectionOffer<'info> {
+    #[account(
+        mut,
+        close = bidder,
+        has_one = bidder,
+        seeds = [PREFIX.as_bytes(), b"collection_offer", offer.collection.as_ref(), bidder.key().as_ref()],
+        bump = offer.bump
+    )]
+    pub offer: Account<'info, CollectionOffer>,
+
+    /// NFT owner accepting the offer.
+    #[account(mut)]
+    pub seller: Signer<'info>,
+
+    /// CHECK: Gets the NFT & the offer's rent. Validated to match `offer.bidder`.
+    #[account(mut)]
+    pub bidder: AccountInfo<'info>,
+
+    #[account(mut, token::mint = mint, token::authority = seller)]
+    pub seller_nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = bidder_nft_account.mint == mint.key(),
+        constraint = bidder_nft_account.owner == offer.bidder
+    )]
+    pub bidder_nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: Metaplex metadata of `mint`, read for its verified collection. Validated in `verified_collection`.
+    pub metadata: AccountInfo<'info>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(
+        init_if_needed,
+        payer = seller,
+        space = 8 + Provenance::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"provenance", mint.key().as_ref()],
+        bump
+    )]
+    pub provenance: Account<'info, Provenance>,
+
+    /// History record of this sale, keyed by the mint's sale number.
+    #[account(
+        init,
+        payer = seller,
+        space = 8 + SaleRecord::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"sale_record", mint.key().as_ref(), &provenance.sales_count.to_le_bytes()],
+        bump
+    )]
+    pub sale_record: Account<'info, SaleRecord>,
+
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"global_stats"], bump = global_stats.bump)]
+    pub global_stats: Account<'info, GlobalStats>,
+
+    /// CHECK: `BlockedMint` PDA of `mint`, must be uninitialized. Validated in `require_not_blocked`.
+    #[account(seeds = [PREFIX.as_bytes(), b"blocked_mint", mint.key().as_ref()], bump)]
+    pub blocked_mint: AccountInfo<'info>,
+
+    /// CHECK: `BannedWallet` PDA of `seller`, must be uninitialized. Validated in `require_not_banned`.
+    #[account(seeds = [PREFIX.as_bytes(), b"banned_wallet", seller.key().as_ref()], bump)]
+    pub seller_ban: AccountInfo<'info>,
+
+    /// CHECK: `BannedWallet` PDA of `bidder`, must be uninitialized. Validated in `require_not_banned`.
+    #[account(seeds = [PREFIX.as_bytes(), b"banned_wallet", bidder.key().as_ref()], bump)]
+    pub bidder_ban: AccountInfo<'info>,
+
+    /// CHECK: Marketplace fee recipient. Validated to match `config.treasury`.
+    #[account(mut, address = config.treasury)]
+    pub treasury: AccountInfo<'info>,
+
+    /// Insurance fund, required while `config.insurance_bps` is set.
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"insurance_fund"], bump = insurance_fund.bump)]
+    pub insurance_fund: Option<Account<'info, InsuranceFund>>,
+
+    /// Seller's withholding, required while `config.withholding_bps` is set.
+    #[account(
+        init_if_needed,
+        payer = seller,
+        space = 8 + Withholding::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"withholding", seller.key().as_ref()],
+        bump
+    )]
+    pub withholding: Option<Account<'info, Withholding>>,
+
+    /// CHECK: Royalty recipient (optional). Validated to match `provenance.creator` in the handler,
+    /// as provenance may be created by this instruction.
+    #[account(mut)]
+    pub creator: Option<AccountInfo<'info>>,
+
+    pub token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct OpenGroupBuy<'info> {
+    #[account(
+        init,
+        payer = organizer,
+        space = 8 + GroupBuy::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"group_buy", listing.key().as_ref()],
+        bump
+    )]
+    pub group_buy: Account<'info, GroupBuy>,
+
+    pub listing: Account<'info, Listing>,
+
+    #[account(mut)]
+    pub organizer: Signer<'info>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ContributeGroupBuy<'info> {
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"group_buy", group_buy.listing.as_ref()],
+        bump = group_buy.bump
+    )]
+    pub group_buy: Account<'info, GroupBuy>,
+
+    #[account(
+        init_if_needed,
+        payer = contributor,
+        space = 8 + GroupBuyContribution::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"contribution", group_buy.key().as_ref(), contributor.key().as_ref()],
+        bump
+    )]
+    pub contribution: Account<'info, GroupBuyContribution>,
+
+    #[account(mut)]
+    pub contributor: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct ExecuteGroupBuy<'info> {
+    #[account(
+        mut,
+        has_one = listing,
+        has_one = mint,
+        seeds = [PREFIX.as_bytes(), b"group_buy", listing.key().as_ref()],
+        bump = group_buy.bump
+    )]
+    pub group_buy: Account<'info, GroupBuy>,
+
+    #[account(mut, has_one = seller, has_one = mint)]
+    pub listing: Account<'info, Listing>,
+
+    /// Anyone can execute a funded group buy, & pays for the group's NFT account.
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+
+    /// CHECK: Receives the proceeds. Validated to match `listing.seller`.
+    #[account(mut)]
+    pub seller: AccountInfo<'info>,
+
+    /// CHECK: Gets the listing vault's rent back. Validated to match `listing.rent_recipient()`.
+    #[account(mut, address = listing.rent_recipient())]
+    pub rent_payer: AccountInfo<'info>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"vault", mint.key().as_ref(), listing.key().as_ref()],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// Group's NFT account, owned by the group PDA.
+    #[account(
+        init,
+        payer = cranker,
+        token::mint = mint,
+        token::authority = group_buy,
+        seeds = [PREFIX.as_bytes(), b"group_vault", group_buy.key().as_ref()],
+        bump
+    )]
+    pub group_nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"provenance", mint.key().as_ref()],
+        bump = provenance.bump
+    )]
+    pub provenance: Account<'info, Provenance>,
+
+    /// History record of this sale, keyed by the mint's sale number.
+    #[account(
+        init,
+        payer = cranker,
+        space = 8 + SaleRecord::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"sale_record", mint.key().as_ref(), &provenance.sales_count.to_le_bytes()],
+        bump
+    )]
+    pub sale_record: Account<'info, SaleRecord>,
+
+    /// Required for storefront listings.
+    #[account(address = listing.storefront.unwrap_or_default())]
+    pub storefront: Option<Account<'info, Storefront>>,
+
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"global_stats"], bump = global_stats.bump)]
+    pub global_stats: Account<'info, GlobalStats>,
+
+    /// CHECK: `BlockedMint` PDA of `mint`, must be uninitialized. Validated in `require_not_blocked`.
+    #[account(seeds = [PREFIX.as_bytes(), b"blocked_mint", mint.key().as_ref()], bump)]
+    pub blocked_mint: AccountInfo<'info>,
+
+    /// CHECK: Fee recipient. Validated to match the storefront's treasury, or `config.treasury`.
+    #[account(mut)]
+    pub treasury: AccountInfo<'info>,
+
+    /// Insurance fund, required while `config.insurance_bps` is set.
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"insurance_fund"], bump = insurance_fund.bump)]
+    pub insurance_fund: Option<Account<'info, InsuranceFund>>,
+
+    /// Seller's withholding, required while `config.withholding_bps` is set.
+    #[account(
+        init_if_needed,
+        payer = cranker,
+        space = 8 + Withholding::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"withholding", seller.key().as_ref()],
+        bump
+    )]
+    pub withholding: Option<Account<'info, Withholding>>,
+
+    /// CHECK: Royalty recipient (optional). Validated to match `provenance.creator`.
+    #[account(mut, address = provenance.creator)]
+    pub creator: Option<AccountInfo<'info>>,
+
+    pub token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RedeemGroupNft<'info> {
+    #[account(
+        mut,
+        has_one = mint,
+        seeds = [PREFIX.as_bytes(), b"group_buy", group_buy.listing.as_ref()],
+        bump = group_buy.bump
+    )]
+    pub group_buy: Account<'info, GroupBuy>,
+
+    #[account(
+        mut,
+        close = redeemer,
+        has_one = group_buy,
+        seeds = [PREFIX.as_bytes(), b"contribution", group_buy.key().as_ref(), redeemer.key().as_ref()],
+        bump = contribution.bump
+    )]
+    pub contribution: Account<'info, GroupBuyContribution>,
+
+    /// Contributor taking the NFT, paying the other shares.
+    #[account(mut)]
+    pub redeemer: Signer<'info>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"group_vault", group_buy.key().as_ref()],
+        bump
+    )]
+    pub group_nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = redeemer_nft_account.mint == mint.key(),
+        constraint = redeemer_nft_account.owner == redeemer.key()
+    )]
+    pub redeemer_nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RefundGroupBuyContribution<'info> {
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"group_buy", group_buy.listing.as_ref()],
+        bump = group_buy.bump
+    )]
+    pub group_buy: Account<'info, GroupBuy>,
+
+    #[account(
+        mut,
+        close = contributor,
+        has_one = group_buy,
+        has_one = contributor,
+        seeds = [PREFIX.as_bytes(), b"contribution", group_buy.key().as_ref(), contributor.key().as_ref()],
+        bump = contribution.bump
+    )]
+    pub contribution: Account<'info, GroupBuyContribution>,
+
+    /// CHECK: Gets the refund & the contribution's rent. Validated to match `contribution.contributor`.
+    #[account(mut)]
+    pub contributor: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct TransferListingAuthority<'info> {
+    #[account(mut, has_one = seller)]
+    pub listing: Account<'info, Listing>,
+
+    pub seller: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdatePaymentTerms<'info> {
+    #[account(mut, has_one = seller)]
+    pub listing: Account<'info, Listing>,
+
+    pub seller: Signer<'info>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    /// CHECK: `AllowedCollection` PDA of the listing's collection, whose `min_price` bounds the new
+    /// price in curated mode; uninitialized when there's none.
+    #[account(
+        seeds = [PREFIX.as_bytes(), b"allowed_collection", listing.collection.unwrap_or_default().as_ref()],
+        bump
+    )]
+    pub allowed_collection: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetFlashSale<'info> {
+    #[account(mut, has_one = seller)]
+    pub listing: Account<'info, Listing>,
+
+    pub seller: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetInstallmentTerms<'info> {
+    #[account(mut, has_one = seller)]
+    pub listing: Account<'info, Listing>,
+
+    pub seller: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct StartInstallmentPlan<'info> {
+    #[account(
+        init,
+        payer = buyer,
+        space = 8 + InstallmentPlan::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"installment", listing.key().as_ref()],
+        bump
+    )]
+    pub plan: Account<'info, InstallmentPlan>,
+
+    #[account(mut)]
+    pub listing: Account<'info, Listing>,
+
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    /// CHECK: `BlockedMint` PDA of the listing's mint, must be uninitialized. Validated in `require_not_blocked`.
+    #[account(seeds = [PREFIX.as_bytes(), b"blocked_mint", listing.mint.as_ref()], bump)]
+    pub blocked_mint: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct PayInstallment<'info> {
+    #[account(
+        mut,
+        has_one = buyer,
+        seeds = [PREFIX.as_bytes(), b"installment", plan.listing.as_ref()],
+        bump = plan.bump
+    )]
+    pub plan: Account<'info, InstallmentPlan>,
+
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct ClaimInstallmentNft<'info> {
+    #[account(
+        mut,
+        close = buyer,
+        has_one = buyer,
+        has_one = seller,
+        has_one = listing,
+        has_one = mint,
+        seeds = [PREFIX.as_bytes(), b"installment", listing.key().as_ref()],
+        bump = plan.bump
+    )]
+    pub plan: Account<'info, InstallmentPlan>,
+
+    /// Closed, rent back to its payer.
+    #[account(mut, close = rent_payer)]
+    pub listing: Account<'info, Listing>,
+
+    /// CHECK: Gets the listing's rent back. Validated to match `listing.rent_recipient()`.
+    #[account(mut, address = listing.rent_recipient())]
+    pub rent_payer: AccountInfo<'info>,
+
+    /// Gets the plan's rent back.
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+
+    /// CHECK: Receives the proceeds. Validated to match `plan.seller`.
+    #[account(mut)]
+    pub seller: AccountInfo<'info>,
+
+    #[account(mut, token::mint = mint, token::authority = buyer)]
+    pub buyer_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"vault", mint.key().as_ref(), listing.key().as_ref()],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"provenance", mint.key().as_ref()],
+        bump = provenance.bump
+    )]
+    pub provenance: Account<'info, Provenance>,
+
+    /// History record of this sale, keyed by the mint's sale number.
+    #[account(
+        init,
+        payer = buyer,
+        space = 8 + SaleRecord::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"sale_record", mint.key().as_ref(), &provenance.sales_count.to_le_bytes()],
+        bump
+    )]
+    pub sale_record: Account<'info, SaleRecord>,
+
+    /// Required for storefront listings.
+    #[account(address = listing.storefront.unwrap_or_default())]
+    pub storefront: Option<Account<'info, Storefront>>,
+
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"global_stats"], bump = global_stats.bump)]
+    pub global_stats: Account<'info, GlobalStats>,
+
+    /// CHECK: Fee recipient. Validated to match the storefront's treasury, or `config.treasury`.
+    #[account(mut)]
+    pub treasury: AccountInfo<'info>,
+
+    /// Insurance fund, required while `config.insurance_bps` is set.
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"insurance_fund"], bump = insurance_fund.bump)]
+    pub insurance_fund: Option<Account<'info, InsuranceFund>>,
+
+    /// Seller's withholding, required while `config.withholding_bps` is set.
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        space = 8 + Withholding::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"withholding", seller.key().as_ref()],
+        bump
+    )]
+    pub withholding: Option<Account<'info, Withholding>>,
+
+    /// CHECK: Royalty recipient (optional). Validated to match `provenance.creator`.
+    #[account(mut, address = provenance.creator)]
+    pub creator: Option<AccountInfo<'info>>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct ReclaimInstallmentNft<'info> {
+    #[account(
+        mut,
+        close = buyer,
+        has_one = buyer,
+        has_one = seller,
+        has_one = listing,
+        has_one = mint,
+        seeds = [PREFIX.as_bytes(), b"installment", listing.key().as_ref()],
+        bump = plan.bump
+    )]
+    pub plan: Account<'info, InstallmentPlan>,
+
+    /// Closed, rent back to its payer.
+    #[account(mut, close = rent_payer)]
+    pub listing: Account<'info, Listing>,
+
+    #[account(mut)]
+    pub seller: Signer<'info>,
+
+    /// CHECK: Gets the listing's rent back. Validated to match `listing.rent_recipient()`.
+    #[account(mut, address = listing.rent_recipient())]
+    pub rent_payer: AccountInfo<'info>,
+
+    /// CHECK: Gets the refunded installments & the plan's rent. Validated to match `plan.buyer`.
+    #[account(mut)]
+    pub buyer: AccountInfo<'info>,
+
+    #[account(mut, token::mint = mint, token::authority = seller)]
+    pub nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"vault", mint.key().as_ref(), listing.key().as_ref()],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct StakeEscrow<'info> {
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    /// Escrow owner; pays for the yield position & vault pool account the first time.
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    /// CHECK: Installment plan or auction bid. Validated in `escrow_beneficiary`.
+    #[account(mut)]
+    pub escrow: AccountInfo<'info>,
+
+    /// Auction of a bid escrow, required to stake a bid. Validated in `require_stakeable`.
+    pub auction: Option<Account<'info, Auction>>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + EscrowYield::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"escrow_yield", escrow.key().as_ref()],
+        bump
+    )]
+    pub escrow_yield: Account<'info, EscrowYield>,
+
+    /// Deposits into & withdraws from the stake pool for all escrows.
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"yield_vault"], bump)]
+    pub yield_vault: SystemAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        associated_token::mint = pool_mint,
+        associated_token::authority = yield_vault,
+        associated_token::token_program = token_program
+    )]
+    pub vault_pool_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Stake pool's pool token mint.
+    pub pool_mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: The whitelisted stake pool program, invoked with the remaining accounts.
+    #[account(executable, address = config.yield_stake_pool_program)]
+    pub stake_pool_program: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UnstakeEscrow<'info> {
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    pub cranker: Signer<'info>,
+
+    /// CHECK: Staked escrow, possibly closed since. Validated to match `escrow_yield.escrow`.
+    #[account(mut, address = escrow_yield.escrow)]
+    pub escrow: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        close = beneficiary,
+        seeds = [PREFIX.as_bytes(), b"escrow_yield", escrow.key().as_ref()],
+        bump = escrow_yield.bump
+    )]
+    pub escrow_yield: Account<'info, EscrowYield>,
+
+    /// CHECK: Escrow owner, gets the position's rent back. Validated to match `escrow_yield.beneficiary`.
+    #[account(mut, address = escrow_yield.beneficiary)]
+    pub beneficiary: AccountInfo<'info>,
+
+    /// CHECK: Marketplace treasury, gets the yield when `config.yield_to_treasury`. Validated to match
+    /// `config.treasury`.
+    #[account(mut, address = config.treasury)]
+    pub treasury: AccountInfo<'info>,
+
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"yield_vault"], bump)]
+    pub yield_vault: SystemAccount<'info>,
+
+    #[account(mut, token::authority = yield_vault)]
+    pub vault_pool_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: The whitelisted stake pool program, invoked with the remaining accounts.
+    #[account(executable, address = config.yield_stake_pool_program)]
+    pub stake_pool_program: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MakeLoanOffer<'info> {
+    #[account(
+        init,
+        payer = lender,
+        space = 8 + LoanOffer::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"loan_offer", listing.key().as_ref(), lender.key().as_ref()],
+        bump
+    )]
+    pub loan_offer: Account<'info, LoanOffer>,
+
+    pub listing: Account<'info, Listing>,
+
+    /// Funds the principal & the offer's rent.
+    #[account(mut)]
+    pub lender: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelLoanOffer<'info> {
+    #[account(
+        mut,
+        close = lender,
+        has_one = lender,
+        seeds = [PREFIX.as_bytes(), b"loan_offer", loan_offer.listing.as_ref(), lender.key().as_ref()],
+        bump = loan_offer.bump
+    )]
+    pub loan_offer: Account<'info, LoanOffer>,
+
+    /// Gets the principal & rent back.
+    #[account(mut)]
+    pub lender: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptLoan<'info> {
+    #[account(
+        mut,
+        has_one = listing,
+        seeds = [PREFIX.as_bytes(), b"loan_offer", listing.key().as_ref(), loan_offer.lender.as_ref()],
+        bump = loan_offer.bump
+    )]
+    pub loan_offer: Account<'info, LoanOffer>,
+
+    #[account(mut, has_one = seller)]
+    pub listing: Account<'info, Listing>,
+
+    /// Borrows the principal.
+    #[account(mut)]
+    pub seller: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RepayLoan<'info> {
+    #[account(
+        mut,
+        close = lender,
+        has_one = listing,
+        has_one = lender,
+        constraint = loan_offer.borrower == Some(borrower.key()) @ ErrorCode::InvalidLoanOffer,
+        seeds = [PREFIX.as_bytes(), b"loan_offer", listing.key().as_ref(), lender.key().as_ref()],
+        bump = loan_offer.bump
+    )]
+    pub loan_offer: Account<'info, LoanOffer>,
+
+    #[account(mut, constraint = listing.loan == Some(loan_offer.key()) @ ErrorCode::InvalidLoanOffer)]
+    pub listing: Account<'info, Listing>,
+
+    #[account(mut)]
+    pub borrower: Signer<'info>,
+
+    /// CHECK: Gets the repayment & the offer's rent. Validated to match `loan_offer.lender`.
+    #[account(mut)]
+    pub lender: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ForecloseLoan<'info> {
+    #[account(
+        mut,
+        close = lender,
+        has_one = listing,
+        has_one = lender,
+        has_one = mint,
+        constraint = loan_offer.borrower.is_some() @ ErrorCode::InvalidLoanOffer,
+        seeds = [PREFIX.as_bytes(), b"loan_offer", listing.key().as_ref(), lender.key().as_ref()],
+        bump = loan_offer.bump
+    )]
+    pub loan_offer: Account<'info, LoanOffer>,
+
+    /// Closed, rent back to its payer.
+    #[account(
+        mut,
+        close = rent_payer,
+        constraint = listing.loan == Some(loan_offer.key()) @ ErrorCode::InvalidLoanOffer
+    )]
+    pub listing: Account<'info, Listing>,
+
+    #[account(mut)]
+    pub lender: Signer<'info>,
+
+    /// CHECK: Gets the listing's & vault's rent back. Validated to match `listing.rent_recipient()`.
+    #[account(mut, address = listing.rent_recipient())]
+    pub rent_payer: AccountInfo<'info>,
+
+    #[account(mut, token::mint = mint, token::authority = lender)]
+    pub lender_nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"vault", mint.key().as_ref(), listing.key().as_ref()],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct ListForRent<'info> {
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + Rental::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"rental", mint.key().as_ref()],
+        bump
+    )]
+    pub rental: Account<'info, Rental>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(mut, token::mint = mint, token::authority = owner)]
+    pub nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    ///  create NFT vault if not present.
+    #[account(
+        init_if_needed,
+        token::mint = mint,
+        payer = owner,
+        token::authority = vault,
+        seeds = [PREFIX.as_bytes(), b"vault", mint.key().as_ref()],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// Who escrowed the NFT in `vault` & for what, so it can be recovered once that's closed.
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + VaultDeposit::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"vault_deposit", mint.key().as_ref()],
+        bump
+    )]
+    pub vault_deposit: Account<'info, VaultDeposit>,
+
+    /// CHECK: `BlockedMint` PDA of `mint`, must be uninitialized. Validated in `require_not_blocked`.
+    #[account(seeds = [PREFIX.as_bytes(), b"blocked_mint", mint.key().as_ref()], bump)]
+    pub blocked_mint: AccountInfo<'info>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct RentNft<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        has_one = mint,
+        seeds = [PREFIX.as_bytes(), b"rental", mint.key().as_ref()],
+        bump = rental.bump
+    )]
+    pub rental: Account<'info, Rental>,
+
+    #[account(mut)]
+    pub renter: Signer<'info>,
+
+    /// CHECK: Receives the rental fee. Validated to match `rental.owner`.
+    #[account(mut)]
+    pub owner: AccountInfo<'info>,
+
+    #[account(mut, token::mint = mint, token::authority = renter)]
+    pub renter_nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"vault", mint.key().as_ref()],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump, has_one = treasury)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    /// CHECK: Marketplace fee recipient. Validated to match `config.treasury`.
+    #[account(mut)]
+    pub treasury: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct ReturnNft<'info> {
+    #[account(
+        mut,
+        has_one = mint,
+        seeds = [PREFIX.as_bytes(), b"rental", mint.key().as_ref()],
+        bump = rental.bump
+    )]
+    pub rental: Account<'info, Rental>,
+
+    #[account(mut)]
+    pub renter: Signer<'info>,
+
+    #[account(mut, token::mint = mint, token::authority = renter)]
+    pub renter_nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"vault", mint.key().as_ref()],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct SeizeRentalCollateral<'info> {
+    /// Closed, collateral & rent to the owner.
+    #[account(
+        mut,
+        close = owner,
+        has_one = owner,
+        seeds = [PREFIX.as_bytes(), b"rental", rental.mint.as_ref()],
+        bump = rental.bump
+    )]
+    pub rental: Account<'info, Rental>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct DelistRental<'info> {
+    #[account(
+        mut,
+        close = owner,
+        has_one = owner,
+        has_one = mint,
+        seeds = [PREFIX.as_bytes(), b"rental", mint.key().as_ref()],
+        bump = rental.bump
+    )]
+    pub rental: Account<'info, Rental>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(mut, token::mint = mint, token::authority = owner)]
+    pub nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"vault", mint.key().as_ref()],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct FractionalizeListing<'info> {
+    /// Closed, rent back to its payer: the NFT stays in the vault under the fraction.
+    #[account(mut, close = rent_payer, has_one = seller, has_one = mint)]
+    pub listing: Account<'info, Listing>,
+
+    #[account(mut)]
+    pub seller: Signer<'info>,
+
+    /// CHECK: Gets the listing's rent back. Validated to match `listing.rent_recipient()`.
+    #[account(mut, address = listing.rent_recipient())]
+    pub rent_payer: AccountInfo<'info>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        payer = seller,
+        space = 8 + Fraction::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"fraction", mint.key().as_ref()],
+        bump
+    )]
+    pub fraction: Account<'info, Fraction>,
+
+    #[account(
+        init,
+        payer = seller,
+        mint::decimals = 0,
+        mint::authority = fraction,
+        mint::token_program = share_token_program,
+        seeds = [PREFIX.as_bytes(), b"share_mint", mint.key().as_ref()],
+        bump
+    )]
+    pub share_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init_if_needed,
+        payer = seller,
+        associated_token::mint = share_mint,
+        associated_token::authority = seller,
+        associated_token::token_program = share_token_program
+    )]
+    pub seller_share_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub share_token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RedeemFraction<'info> {
+    #[account(
+        mut,
+        has_one = mint,
+        has_one = curator,
+        has_one = share_mint,
+        seeds = [PREFIX.as_bytes(), b"fraction", mint.key().as_ref()],
+        bump = fraction.bump
+    )]
+    pub fraction: Account<'info, Fraction>,
+
+    pub holder: Signer<'info>,
+
+    #[account(mut, token::mint = share_mint, token::authority = holder)]
+    pub holder_share_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub share_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut, token::mint = mint, token::authority = holder)]
+    pub holder_nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: Gets the emptied vault's rent back. Validated to match `fraction.curator`.
+    #[account(mut)]
+    pub curator: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"vault", mint.key().as_ref(), fraction.listing.as_ref()],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub share_token_program: Program<'info, Token>,
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct BidBuyout<'info> {
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"fraction", fraction.mint.as_ref()],
+        bump = fraction.bump
+    )]
+    pub fraction: Account<'info, Fraction>,
+
+    #[account(mut)]
+    pub bidder: Signer<'info>,
+
+    /// CHECK: Refunded when outbid, required once there's a bid. Validated to match `fraction.highest_bidder`.
+    #[account(mut)]
+    pub previous_bidder: Option<AccountInfo<'info>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SettleBuyout<'info> {
+    #[account(
+        mut,
+        has_one = mint,
+        has_one = curator,
+        seeds = [PREFIX.as_bytes(), b"fraction", mint.key().as_ref()],
+        bump = fraction.bump
+    )]
+    pub fraction: Account<'info, Fraction>,
+
+    #[account(
+        mut,
+        token::mint = mint,
+        constraint = Some(winner_nft_account.owner) == fraction.highest_bidder @ ErrorCode::NotBuyoutWinner
+    )]
+    pub winner_nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: Gets the emptied vault's rent back. Validated to match `fraction.curator`.
+    #[account(mut)]
+    pub curator: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"vault", mint.key().as_ref(), fraction.listing.as_ref()],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimBuyoutProceeds<'info> {
+    #[account(
+        mut,
+        has_one = share_mint,
+        seeds = [PREFIX.as_bytes(), b"fraction", fraction.mint.as_ref()],
+        bump = fraction.bump
+    )]
+    pub fraction: Account<'info, Fraction>,
+
+    #[account(mut)]
+    pub holder: Signer<'info>,
+
+    #[account(mut, token::mint = share_mint, token::authority = holder)]
+    pub holder_share_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub share_mint: InterfaceAccount<'info, Mint>,
+
+    pub share_token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ListEdition<'info> {
+    #[account(
+        init,
+        payer = seller,
+        space = 8 + EditionListing::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"edition_listing", mint.key().as_ref(), seller.key().as_ref()],
+        bump
+    )]
+    pub edition_listing: Account<'info, EditionListing>,
+
+    #[account(mut)]
+    pub seller: Signer<'info>,
+
+    #[account(mut, token::mint = mint, token::authority = seller)]
+    pub seller_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        token::mint = mint,
+        payer = seller,
+        token::authority = vault,
+        seeds = [PREFIX.as_bytes(), b"vault", mint.key().as_ref(), edition_listing.key().as_ref()],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: `BlockedMint` PDA of `mint`, must be uninitialized. Validated in `require_not_blocked`.
+    #[account(seeds = [PREFIX.as_bytes(), b"blocked_mint", mint.key().as_ref()], bump)]
+    pub blocked_mint: AccountInfo<'info>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct BuyEdition<'info> {
+    #[account(
+        mut,
+        has_one = seller,
+        has_one = mint,
+        seeds = [PREFIX.as_bytes(), b"edition_listing", mint.key().as_ref(), seller.key().as_ref()],
+        bump = edition_listing.bump
+    )]
+    pub edition_listing: Account<'info, EditionListing>,
+
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+
+    /// CHECK: Receives the proceeds. Validated to match `edition_listing.seller`.
+    #[account(mut)]
+    pub seller: AccountInfo<'info>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"vault", mint.key().as_ref(), edition_listing.key().as_ref()],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// Buyer's associated token account of `mint`, created (paid by the buyer) if missing.
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        associated_token::mint = mint,
+        associated_token::authority = buyer,
+        associated_token::token_program = token_program
+    )]
+    pub buyer_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump, has_one = treasury)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    /// CHECK: Marketplace fee recipient. Validated to match `config.treasury`.
+    #[account(mut)]
+    pub treasury: AccountInfo<'info>,
+
+    /// Insurance fund, required while `config.insurance_bps` is set.
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"insurance_fund"], bump = insurance_fund.bump)]
+    pub insurance_fund: Option<Account<'info, InsuranceFund>>,
+
+    /// Seller's withholding, required while `config.withholding_bps` is set.
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        space = 8 + Withholding::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"withholding", seller.key().as_ref()],
+        bump
+    )]
+    pub withholding: Option<Account<'info, Withholding>>,
+
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"global_stats"], bump = global_stats.bump)]
+    pub global_stats: Account<'info, GlobalStats>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token2022>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[derive(Accounts)]
+pub struct CloseEdition<'info> {
+    #[account(
+        mut,
+        close = seller,
+        has_one = seller,
+        has_one = mint,
+        seeds = [PREFIX.as_bytes(), b"edition_listing", mint.key().as_ref(), seller.key().as_ref()],
+        bump = edition_listing.bump
+    )]
+    pub edition_listing: Account<'info, EditionListing>,
+
+    #[account(mut)]
+    pub seller: Signer<'info>,
+
+    #[account(mut, token::mint = mint, token::authority = seller)]
+    pub seller_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"vault", mint.key().as_ref(), edition_listing.key().as_ref()],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+#[instruction(collection: Pubkey)]
+pub struct CreatePool<'info> {
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + Pool::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"pool", owner.key().as_ref(), collection.as_ref()],
+        bump
+    )]
+    pub pool: Account<'info, Pool>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdatePoolSol<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        seeds = [PREFIX.as_bytes(), b"pool", owner.key().as_ref(), pool.collection.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, Pool>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DepositPoolNft<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        seeds = [PREFIX.as_bytes(), b"pool", owner.key().as_ref(), pool.collection.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, Pool>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(mut, token::mint = mint, token::authority = owner)]
+    pub nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: Metaplex metadata of `mint`. Validated in `verified_collection`.
+    pub metadata: AccountInfo<'info>,
+
+    #[account(
+        init,
+        token::mint = mint,
+        payer = owner,
+        token::authority = vault,
+        seeds = [PREFIX.as_bytes(), b"vault", mint.key().as_ref(), pool.key().as_ref()],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + Provenance::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"provenance", mint.key().as_ref()],
+        bump
+    )]
+    pub provenance: Account<'info, Provenance>,
+
+    /// CHECK: `BlockedMint` PDA of `mint`, must be uninitialized. Validated in `require_not_blocked`.
+    #[account(seeds = [PREFIX.as_bytes(), b"blocked_mint", mint.key().as_ref()], bump)]
+    pub blocked_mint: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawPoolNft<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        seeds = [PREFIX.as_bytes(), b"pool", owner.key().as_ref(), pool.collection.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, Pool>,
+
+    /// Gets the vault's rent back.
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(mut, token::mint = mint, token::authority = owner)]
+    pub owner_nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"vault", mint.key().as_ref(), pool.key().as_ref()],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct PoolBuy<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        seeds = [PREFIX.as_bytes(), b"pool", owner.key().as_ref(), pool.collection.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, Pool>,
+
+    /// CHECK: Gets the vault's rent back. Validated to match `pool.owner`.
+    #[account(mut)]
+    pub owner: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"vault", mint.key().as_ref(), pool.key().as_ref()],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// Buyer's associated token account of `mint`, created (paid by the buyer) if missing.
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        associated_token::mint = mint,
+        associated_token::authority = buyer,
+        associated_token::token_program = token_program
+    )]
+    pub buyer_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Created when the NFT was deposited or sold into the pool.
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"provenance", mint.key().as_ref()], bump = provenance.bump)]
+    pub provenance: Account<'info, Provenance>,
+
+    /// History record of this sale, keyed by the mint's sale number.
+    #[account(
+        init,
+        payer = buyer,
+        space = 8 + SaleRecord::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"sale_record", mint.key().as_ref(), &provenance.sales_count.to_le_bytes()],
+        bump
+    )]
+    pub sale_record: Account<'info, SaleRecord>,
+
+    /// CHECK: Royalty recipient (optional). Validated to match `provenance.creator`.
+    #[account(mut, address = provenance.creator)]
+    pub creator: Option<AccountInfo<'info>>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump, has_one = treasury)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    /// CHECK: Marketplace fee recipient. Validated to match `config.treasury`.
+    #[account(mut)]
+    pub treasury: AccountInfo<'info>,
+
+    /// Insurance fund, required while `config.insurance_bps` is set.
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"insurance_fund"], bump = insurance_fund.bump)]
+    pub insurance_fund: Option<Account<'info, InsuranceFund>>,
+
+    /// Pool owner's withholding, required while `config.withholding_bps` is set.
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        space = 8 + Withholding::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"withholding", owner.key().as_ref()],
+        bump
+    )]
+    pub withholding: Option<Account<'info, Withholding>>,
+
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"global_stats"], bump = global_stats.bump)]
+    pub global_stats: Account<'info, GlobalStats>,
+
+    /// CHECK: `BlockedMint` PDA of `mint`, must be uninitialized. Validated in `require_not_blocked`.
+    #[account(seeds = [PREFIX.as_bytes(), b"blocked_mint", mint.key().as_ref()], bump)]
+    pub blocked_mint: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token2022>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[derive(Accounts)]
+pub struct PoolSell<'info> {
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"pool", pool.owner.as_ref(), pool.collection.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, Pool>,
+
+    #[account(mut)]
+    pub seller: Signer<'info>,
+
+    #[account(mut, token::mint = mint, token::authority = seller)]
+    pub nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: Metaplex metadata of `mint`. Validated in `verified_collection`.
+    pub metadata: AccountInfo<'info>,
+
+    #[account(
+        init,
+        token::mint = mint,
+        payer = seller,
+        token::authority = vault,
+        seeds = [PREFIX.as_bytes(), b"vault", mint.key().as_ref(), pool.key().as_ref()],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = seller,
+        space = 8 + Provenance::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"provenance", mint.key().as_ref()],
+        bump
+    )]
+    pub provenance: Account<'info, Provenance>,
+
+    /// History record of this sale, keyed by the mint's sale number.
+    #[account(
+        init,
+        payer = seller,
+        space = 8 + SaleRecord::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"sale_record", mint.key().as_ref(), &provenance.sales_count.to_le_bytes()],
+        bump
+    )]
+    pub sale_record: Account<'info, SaleRecord>,
+
+    /// CHECK: Royalty recipient (optional). Validated to match `provenance.creator` in the handler,
+    /// as provenance may be created by this instruction.
+    #[account(mut)]
+    pub creator: Option<AccountInfo<'info>>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump, has_one = treasury)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    /// CHECK: Marketplace fee recipient. Validated to match `config.treasury`.
+    #[account(mut)]
+    pub treasury: AccountInfo<'info>,
+
+    /// Insurance fund, required while `config.insurance_bps` is set.
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"insurance_fund"], bump = insurance_fund.bump)]
+    pub insurance_fund: Option<Account<'info, InsuranceFund>>,
+
+    /// Seller's withholding, required while `config.withholding_bps` is set.
+    #[account(
+        init_if_needed,
+        payer = seller,
+        space = 8 + Withholding::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"withholding", seller.key().as_ref()],
+        bump
+    )]
+    pub withholding: Option<Account<'info, Withholding>>,
+
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"global_stats"], bump = global_stats.bump)]
+    pub global_stats: Account<'info, GlobalStats>,
+
+    /// CHECK: `BlockedMint` PDA of `mint`, must be uninitialized. Validated in `require_not_blocked`.
+    #[account(seeds = [PREFIX.as_bytes(), b"blocked_mint", mint.key().as_ref()], bump)]
+    pub blocked_mint: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct ClosePool<'info> {
+    #[account(
+        mut,
+        close = owner,
+        has_one = owner,
+        seeds = [PREFIX.as_bytes(), b"pool", owner.key().as_ref(), pool.collection.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, Pool>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreateRaffle<'info> {
+    #[account(
+        init,
+        payer = seller,
+        space = 8 + Raffle::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"raffle", mint.key().as_ref()],
+        bump
+    )]
+    pub raffle: Account<'info, Raffle>,
+
+    #[account(mut)]
+    pub seller: Signer<'info>,
+
+    #[account(mut, token::mint = mint, token::authority = seller)]
+    pub nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    ///  create NFT vault if not present.
+    #[account(
+        init_if_needed,
+        token::mint = mint,
+        payer = seller,
+        token::authority = vault,
+        seeds = [PREFIX.as_bytes(), b"vault", mint.key().as_ref()],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// Who escrowed the NFT in `vault` & for what, so it can be recovered once that's closed.
+    #[account(
+        init_if_needed,
+        payer = seller,
+        space = 8 + VaultDeposit::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"vault_deposit", mint.key().as_ref()],
+        bump
+    )]
+    pub vault_deposit: Account<'info, VaultDeposit>,
+
+    /// CHECK: `BlockedMint` PDA of `mint`, must be uninitialized. Validated in `require_not_blocked`.
+    #[account(seeds = [PREFIX.as_bytes(), b"blocked_mint", mint.key().as_ref()], bump)]
+    pub blocked_mint: AccountInfo<'info>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct BuyTicket<'info> {
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"raffle", raffle.mint.as_ref()],
+        bump = raffle.bump
+    )]
+    pub raffle: Account<'info, Raffle>,
+
+    /// Keyed by the entry's first ticket.
+    #[account(
+        init,
+        payer = buyer,
+        space = 8 + RaffleEntry::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"raffle_entry", raffle.key().as_ref(), &raffle.tickets_sold.to_le_bytes()],
+        bump
+    )]
+    pub entry: Account<'info, RaffleEntry>,
+
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct LockRaffle<'info> {
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"raffle", raffle.mint.as_ref()],
+        bump = raffle.bump
+    )]
+    pub raffle: Account<'info, Raffle>,
+}
+
+#[derive(Accounts)]
+pub struct DrawWinner<'info> {
+    #[account(
+        mut,
+        has_one = seller,
+        seeds = [PREFIX.as_bytes(), b"raffle", raffle.mint.as_ref()],
+        bump = raffle.bump
+    )]
+    pub raffle: Account<'info, Raffle>,
+
+    /// CHECK: Gets the proceeds & bond. Validated to match `raffle.seller`.
+    #[account(mut)]
+    pub seller: AccountInfo<'info>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump, has_one = treasury)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    /// CHECK: Marketplace fee recipient. Validated to match `config.treasury`.
+    #[account(mut)]
+    pub treasury: AccountInfo<'info>,
+
+    /// CHECK: SlotHashes sysvar, read manually (too large to deserialize).
+    #[account(address = anchor_lang::solana_program::sysvar::slot_hashes::ID)]
+    pub slot_hashes: AccountInfo<'info>,
+
+    /// Seller's withholding, required while `config.withholding_bps` is set.
+    #[account(
+        init_if_needed,
+        payer = cranker,
+        space = 8 + Withholding::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"withholding", seller.key().as_ref()],
+        bump
+    )]
+    pub withholding: Option<Account<'info, Withholding>>,
+
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimRafflePrize<'info> {
+    #[account(
+        mut,
+        has_one = mint,
+        seeds = [PREFIX.as_bytes(), b"raffle", mint.key().as_ref()],
+        bump = raffle.bump
+    )]
+    pub raffle: Account<'info, Raffle>,
+
+    /// Winning entry, closed with its rent back to the buyer.
+    #[account(mut, close = buyer, has_one = raffle, has_one = buyer)]
+    pub entry: Account<'info, RaffleEntry>,
+
+    /// CHECK: Winner. Validated to match `entry.buyer`.
+    #[account(mut)]
+    pub buyer: AccountInfo<'info>,
+
+    #[account(mut, token::mint = mint, token::authority = buyer)]
+    pub winner_nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"vault", mint.key().as_ref()],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct CancelRaffle<'info> {
+    #[account(
+        mut,
+        has_one = seller,
+        has_one = mint,
+        seeds = [PREFIX.as_bytes(), b"raffle", mint.key().as_ref()],
+        bump = raffle.bump
+    )]
+    pub raffle: Account<'info, Raffle>,
+
+    #[account(mut)]
+    pub seller: Signer<'info>,
+
+    #[account(mut, token::mint = mint, token::authority = seller)]
+    pub nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"vault", mint.key().as_ref()],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct RefundRaffleEntry<'info> {
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"raffle", raffle.mint.as_ref()],
+        bump = raffle.bump
+    )]
+    pub raffle: Account<'info, Raffle>,
+
+    /// Closed with its rent back to the buyer.
+    #[account(mut, close = buyer, has_one = raffle, has_one = buyer)]
+    pub entry: Account<'info, RaffleEntry>,
+
+    /// CHECK: Gets the refund. Validated to match `entry.buyer`.
+    #[account(mut)]
+    pub buyer: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeTradeMining<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + TradeMining::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"trade_mining"],
+        bump
+    )]
+    pub trade_mining: Account<'info, TradeMining>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FundListingRewards<'info> {
+    #[account(
+        has_one = authority,
+        has_one = reward_mint,
+        seeds = [PREFIX.as_bytes(), b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    pub authority: Signer<'info>,
+
+    /// Pays the vault's rent, so the authority can be a PDA holding data (e.g. a governance account).
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        token::mint = reward_mint,
+        token::authority = reward_vault,
+        token::token_program = reward_token_program,
+        seeds = [PREFIX.as_bytes(), b"reward_vault", reward_mint.key().as_ref()],
+        bump
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, token::mint = reward_mint, token::authority = authority)]
+    pub authority_reward_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub reward_token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct EnrollListingRewards<'info> {
+    #[account(mut, has_one = seller)]
+    pub listing: Account<'info, Listing>,
+
+    pub seller: Signer<'info>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimListingRewards<'info> {
+    #[account(mut, has_one = seller)]
+    pub listing: Account<'info, Listing>,
+
+    pub seller: Signer<'info>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump, has_one = reward_mint)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"reward_vault", reward_mint.key().as_ref()],
+        bump
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, token::mint = reward_mint)]
+    pub seller_reward_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub reward_token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct RedeemPoints<'info> {
+    #[account(
+        mut,
+        has_one = wallet,
+        seeds = [PREFIX.as_bytes(), b"loyalty", wallet.key().as_ref()],
+        bump = loyalty_account.bump
+    )]
+    pub loyalty_account: Account<'info, LoyaltyAccount>,
+
+    pub wallet: Signer<'info>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump, has_one = reward_mint)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"reward_vault", reward_mint.key().as_ref()],
+        bump
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, token::mint = reward_mint)]
+    pub wallet_reward_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub reward_token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawWithholding<'info> {
+    #[account(
+        mut,
+        close = seller,
+        has_one = seller,
+        seeds = [PREFIX.as_bytes(), b"withholding", seller.key().as_ref()],
+        bump = withholding.bump
+    )]
+    pub withholding: Account<'info, Withholding>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(mut)]
+    pub seller: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimVested<'info> {
+    #[account(
+        mut,
+        has_one = seller,
+        seeds = [PREFIX.as_bytes(), b"vesting", vesting.listing.as_ref()],
+        bump = vesting.bump
+    )]
+    pub vesting: Account<'info, Vesting>,
+
+    /// CHECK: Gets the vested lamports. Validated to match `vesting.seller`.
+    #[account(mut)]
+    pub seller: AccountInfo<'info>,
+
+    /// The seller or the vesting's `seller_authority`. Validated in `require_seller_authority`.
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SettleEscrowedSale<'info> {
+    #[account(
+        mut,
+        close = buyer,
+        has_one = seller,
+        has_one = buyer,
+        seeds = [PREFIX.as_bytes(), b"escrowed_sale", escrowed_sale.listing.as_ref()],
+        bump = escrowed_sale.bump
+    )]
+    pub escrowed_sale: Account<'info, EscrowedSale>,
+
+    /// Buyer for `confirm_receipt`, seller for `refund_escrowed_sale`, anyone for `release_expired_escrow`.
+    pub authority: Signer<'info>,
+
+    /// CHECK: Gets released proceeds. Validated to match `escrowed_sale.seller`.
+    #[account(mut)]
+    pub seller: AccountInfo<'info>,
+
+    /// CHECK: Gets refunds & the account's rent. Validated to match `escrowed_sale.buyer`.
+    #[account(mut)]
+    pub buyer: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct OpenDispute<'info> {
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"escrowed_sale", escrowed_sale.listing.as_ref()],
+        bump = escrowed_sale.bump
+    )]
+    pub escrowed_sale: Account<'info, EscrowedSale>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    /// The sale's buyer or seller.
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ResolveDispute<'info> {
+    #[account(
+        mut,
+        close = buyer,
+        has_one = seller,
+        has_one = buyer,
+        seeds = [PREFIX.as_bytes(), b"escrowed_sale", escrowed_sale.listing.as_ref()],
+        bump = escrowed_sale.bump
+    )]
+    pub escrowed_sale: Account<'info, EscrowedSale>,
+
+    #[account(has_one = arbiter, seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    pub arbiter: Signer<'info>,
+
+    /// CHECK: Validated to match `escrowed_sale.seller`.
+    #[account(mut)]
+    pub seller: AccountInfo<'info>,
+
+    /// CHECK: Gets the account's rent. Validated to match `escrowed_sale.buyer`.
+    #[account(mut)]
+    pub buyer: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreateAuction<'info> {
+    /// Auction account stores seller, prices, highest bid, etc (on chain).
+    #[account(init, payer = seller, space = 8 + Auction::INIT_SPACE)]
+    pub auction: Account<'info, Auction>,
+
+    #[account(mut)]
+    pub seller: Signer<'info>,
+
+    #[account(mut, owner = token_program.key())]
+    pub nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(constraint = mint.key() == nft_account.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    ///  create NFT vault if not present.
+    #[account(
+        init_if_needed,
+        token::mint = mint,
+        payer = seller,
+        token::authority = vault,
+        seeds = [PREFIX.as_bytes(), b"vault", nft_account.mint.as_ref()],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// Who escrowed the NFT in `vault` & for what, so it can be recovered once that's closed.
+    #[account(
+        init_if_needed,
+        payer = seller,
+        space = 8 + VaultDeposit::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"vault_deposit", mint.key().as_ref()],
+        bump
+    )]
+    pub vault_deposit: Account<'info, VaultDeposit>,
+
+    #[account(
+        init_if_needed,
+        payer = seller,
+        space = 8 + Provenance::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"provenance", mint.key().as_ref()],
+        bump
+    )]
+    pub provenance: Account<'info, Provenance>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"global_stats"], bump = global_stats.bump)]
+    pub global_stats: Account<'info, GlobalStats>,
+
+    /// CHECK: `BlockedMint` PDA of `mint`, must be uninitialized. Validated in `require_not_blocked`.
+    #[account(seeds = [PREFIX.as_bytes(), b"blocked_mint", mint.key().as_ref()], bump)]
+    pub blocked_mint: AccountInfo<'info>,
+
+    /// Approval of the mint's collection, required in curated mode.
+    pub allowed_collection: Option<Account<'info, AllowedCollection>>,
+
+    /// CHECK: Metaplex metadata of `mint`, required in curated mode. Validated in `verified_collection`.
+    pub metadata: Option<AccountInfo<'info>>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token2022>,
+}
+
+impl<'info> CreateAuction<'info> {
+    /// Transfers 1 NFT seller --> vault & records provenance, shared by both auction modes.
+    fn escrow_nft(
+        &mut self,
+        remaining_accounts: &[AccountInfo<'info>],
+        provenance_bump: u8,
+    ) -> Result<()> {
+        require!(!self.config.paused, ErrorCode::MarketplacePaused);
+        validate_listable_mint(&self.mint.to_account_info())?;
+        require_not_blocked(&self.blocked_mint)?;
+        require_curated(
+            &self.config,
+            &self.mint.key(),
+            self.metadata.as_ref(),
+            self.allowed_collection.as_deref(),
+        )?;
+        self.global_stats.record_listing()?;
+
+        transfer_nft(
+            &self.token_program.to_account_info(),
+            &self.nft_account.to_account_info(),
+            &self.mint.to_account_info(),
+            &self.vault.to_account_info(),
+            &self.seller.to_account_info(),
+            remaining_accounts,
+            &[],
+        )?;
+
+        let creator = match &self.metadata {
+            Some(metadata) => verified_creator(&self.mint.key(), metadata)?,
+            None => None,
+        };
+        self.provenance.init_if_new(
+            self.mint.key(),
+            creator.unwrap_or(self.seller.key()),
+            provenance_bump,
+        );
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct PlaceBid<'info> {
+    #[account(mut)]
+    pub auction: Account<'info, Auction>,
+
+    /// Bidder's escrow for this auction, holds the bid lamports.
+    #[account(
+        init_if_needed,
+        payer = bidder,
+        space = 8 + Bid::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"bid", auction.key().as_ref(), bidder.key().as_ref()],
+        bump
+    )]
+    pub bid: Account<'info, Bid>,
+
+    /// CHECK: `EscrowYield` PDA of `bid`, must be uninitialized (the bid isn't staked).
+    #[account(seeds = [PREFIX.as_bytes(), b"escrow_yield", bid.key().as_ref()], bump)]
+    pub escrow_yield: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub bidder: Signer<'info>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CommitBid<'info> {
+    #[account(mut)]
+    pub auction: Account<'info, Auction>,
+
+    /// Bidder's commitment & deposit escrow for this auction.
+    #[account(
+        init,
+        payer = bidder,
+        space = 8 + Bid::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"bid", auction.key().as_ref(), bidder.key().as_ref()],
+        bump
+    )]
+    pub bid: Account<'info, Bid>,
+
+    #[account(mut)]
+    pub bidder: Signer<'info>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevealBid<'info> {
+    #[account(mut)]
+    pub auction: Account<'info, Auction>,
+
+    #[account(
+        mut,
+        has_one = auction,
+        has_one = bidder,
+        seeds = [PREFIX.as_bytes(), b"bid", auction.key().as_ref(), bidder.key().as_ref()],
+        bump = bid.bump
+    )]
+    pub bid: Account<'info, Bid>,
+
+    #[account(mut)]
+    pub bidder: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ForfeitUnrevealedBid<'info> {
+    #[account(mut, has_one = seller)]
+    pub auction: Account<'info, Auction>,
+
+    #[account(
+        mut,
+        has_one = auction,
+        has_one = bidder,
+        seeds = [PREFIX.as_bytes(), b"bid", auction.key().as_ref(), bidder.key().as_ref()],
+        bump = bid.bump
+    )]
+    pub bid: Account<'info, Bid>,
+
+    /// CHECK: Receives the forfeited deposit. Validated to match `auction.seller`.
+    #[account(mut)]
+    pub seller: AccountInfo<'info>,
+
+    /// CHECK: Receives the bid's rent. Validated to match `bid.bidder`.
+    #[account(mut)]
+    pub bidder: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelBid<'info> {
+    /// CHECK: The bid's auction, deserialized in the handler unless swept (no longer program-owned).
+    pub auction: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        has_one = auction,
+        has_one = bidder,
+        seeds = [PREFIX.as_bytes(), b"bid", auction.key().as_ref(), bidder.key().as_ref()],
+        bump = bid.bump
+    )]
+    pub bid: Account<'info, Bid>,
+
+    #[account(mut)]
+    pub bidder: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Cancel<'info> {
+    /// CHECK: Listing, offer, collection offer, loan offer or bid to cancel. Discriminator validated to
+    /// match the `CancelKind` in the handler.
+    pub target: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelAuction<'info> {
+    #[account(mut, has_one = seller, has_one = mint, close = seller)]
+    pub auction: Account<'info, Auction>,
+
+    #[account(mut)]
+    pub seller: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = seller_nft_account.mint == auction.mint,
+        constraint = seller_nft_account.owner == auction.seller
+    )]
+    pub seller_nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"vault", mint.key().as_ref()],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct ConvertListingToAuction<'info> {
+    #[account(mut, has_one = seller, has_one = mint)]
+    pub listing: Account<'info, Listing>,
+
+    /// CHECK: Gets the listing's (& its vault's) rent back. Validated to match `listing.rent_recipient()`.
+    #[account(mut, address = listing.rent_recipient())]
+    pub rent_payer: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"vault", mint.key().as_ref(), listing.key().as_ref()],
+        bump
+    )]
+    pub listing_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(init, payer = seller, space = 8 + Auction::INIT_SPACE)]
+    pub auction: Account<'info, Auction>,
+
+    #[account(mut)]
+    pub seller: Signer<'info>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init_if_needed,
+        token::mint = mint,
+        payer = seller,
+        token::authority = vault,
+        seeds = [PREFIX.as_bytes(), b"vault", mint.key().as_ref()],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// Who escrowed the NFT in `vault` & for what, so it can be recovered once that's closed.
+    #[account(
+        init_if_needed,
+        payer = seller,
+        space = 8 + VaultDeposit::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"vault_deposit", mint.key().as_ref()],
+        bump
+    )]
+    pub vault_deposit: Account<'info, VaultDeposit>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    /// CHECK: `BlockedMint` PDA of `mint`, must be uninitialized. Validated in `require_not_blocked`.
+    #[account(seeds = [PREFIX.as_bytes(), b"blocked_mint", mint.key().as_ref()], bump)]
+    pub blocked_mint: AccountInfo<'info>,
+
+    /// CHECK: `BannedWallet` PDA of `seller`, must be uninitialized. Validated in `require_not_banned`.
+    #[account(seeds = [PREFIX.as_bytes(), b"banned_wallet", seller.key().as_ref()], bump)]
+    pub seller_ban: AccountInfo<'info>,
+
+    /// Approval of the mint's collection, required in curated mode.
+    pub allowed_collection: Option<Account<'info, AllowedCollection>>,
+
+    /// CHECK: Metaplex metadata of `mint`, required in curated & strict listing modes. Validated in
+    /// `verified_collection` / `strict_collection`.
+    pub metadata: Option<AccountInfo<'info>>,
+
+    /// Registry page of the listing, required for registered listings.
+    #[account(mut)]
+    pub registry_page: Option<AccountLoader<'info, ListingRegistryPage>>,
+
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"global_stats"], bump = global_stats.bump)]
+    pub global_stats: Account<'info, GlobalStats>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct ConvertAuctionToListing<'info> {
+    #[account(mut, has_one = seller, has_one = mint, close = seller)]
+    pub auction: Account<'info, Auction>,
+
+    /// Pays the listing's (& its vault's) rent, returned on delist.
+    #[account(mut)]
+    pub seller: Signer<'info>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"vault", mint.key().as_ref()],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// New listing keypair, signing.
+    #[account(init, payer = seller, space = 8 + Listing::INIT_SPACE)]
+    pub listing: Account<'info, Listing>,
+
+    #[account(
+        init,
+        token::mint = mint,
+        payer = seller,
+        token::authority = listing_vault,
+        seeds = [PREFIX.as_bytes(), b"vault", mint.key().as_ref(), listing.key().as_ref()],
+        bump
+    )]
+    pub listing_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    /// CHECK: `BlockedMint` PDA of `mint`, must be uninitialized. Validated in `require_not_blocked`.
+    #[account(seeds = [PREFIX.as_bytes(), b"blocked_mint", mint.key().as_ref()], bump)]
+    pub blocked_mint: AccountInfo<'info>,
+
+    /// CHECK: `BannedWallet` PDA of `seller`, must be uninitialized. Validated in `require_not_banned`.
+    #[account(seeds = [PREFIX.as_bytes(), b"banned_wallet", seller.key().as_ref()], bump)]
+    pub seller_ban: AccountInfo<'info>,
+
+    /// Approval of the mint's collection, required in curated mode.
+    pub allowed_collection: Option<Account<'info, AllowedCollection>>,
+
+    /// CHECK: Metaplex metadata of `mint`, required in curated & strict listing modes. Validated in
+    /// `verified_collection` / `strict_collection`.
+    pub metadata: Option<AccountInfo<'info>>,
+
+    /// Registry page (with room left) the listing is added to (optional).
+    #[account(mut)]
+    pub registry_page: Option<AccountLoader<'info, ListingRegistryPage>>,
+
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"global_stats"], bump = global_stats.bump)]
+    pub global_stats: Account<'info, GlobalStats>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct SettleAuction<'info> {
+    #[account(mut, has_one = seller, has_one = mint)]
+    pub auction: Account<'info, Auction>,
+
+    /// Anyone can settle, the seller doesn't need to show up.
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+
+    /// CHECK: Receives the proceeds. Validated to match `auction.seller`.
+    #[account(mut)]
+    pub seller: AccountInfo<'info>,
+
+    /// Receives the NFT back when the reserve isn't met.
+    #[account(
+        mut,
+        constraint = seller_nft_account.mint == auction.mint,
+        constraint = seller_nft_account.owner == auction.seller
+    )]
+    pub seller_nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// PDA vault holding NFT
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"vault", mint.key().as_ref()],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Highest bidder, required when there is a bid. Validated to match `auction.highest_bidder`.
+    #[account(mut, address = auction.highest_bidder)]
+    pub bidder: Option<AccountInfo<'info>>,
+
+    /// Highest bid escrow, required when there is a bid.
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"bid", auction.key().as_ref(), auction.highest_bidder.as_ref()],
+        bump
+    )]
+    pub bid: Option<Account<'info, Bid>>,
+
+    /// Winner's NFT account, required when the reserve is met.
+    #[account(
+        mut,
+        constraint = bidder_token_account.mint == auction.mint,
+        constraint = bidder_token_account.owner == auction.highest_bidder
+    )]
+    pub bidder_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(seeds = [PREFIX.as_bytes(), b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(
+        mut,
+        seeds = [PREFIX.as_bytes(), b"provenance", auction.mint.as_ref()],
+        bump = provenance.bump
+    )]
+    pub provenance: Account<'info, Provenance>,
+
+    /// History record of this sale, required when the auction sold.
+    #[account(
+        init,
+        payer = cranker,
+        space = 8 + SaleRecord::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"sale_record", auction.mint.as_ref(), &provenance.sales_count.to_le_bytes()],
+        bump
+    )]
+    pub sale_record: Option<Account<'info, SaleRecord>>,
+
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"global_stats"], bump = global_stats.bump)]
+    pub global_stats: Account<'info, GlobalStats>,
+
+    /// CHECK: Marketplace fee recipient. Validated to match `config.treasury`.
+    #[account(mut, address = config.treasury)]
+    pub treasury: AccountInfo<'info>,
+
+    /// Insurance fund, required while `config.insurance_bps` is set.
+    #[account(mut, seeds = [PREFIX.as_bytes(), b"insurance_fund"], bump = insurance_fund.bump)]
+    pub insurance_fund: Option<Account<'info, InsuranceFund>>,
+
+    /// Seller's withholding, required while `config.withholding_bps` is set.
+    #[account(
+        init_if_needed,
+        payer = cranker,
+        space = 8 + Withholding::INIT_SPACE,
+        seeds = [PREFIX.as_bytes(), b"withholding", seller.key().as_ref()],
+        bump
+    )]
+    pub withholding: Option<Account<'info, Withholding>>,
+
+    /// CHECK: Royalty recipient (optional). Validated to match `provenance.creator`.
+    #[account(mut, address = provenance.creator)]
+    pub creator: Option<AccountInfo<'info>>,
+
+    /// Fixed-price listing of the NFT, required when the reserve isn't met & the auction relists on no
+    /// sale: a new keypair, signing. The cranker pays its (& its vault's) rent, returned on delist.
+    #[account(init, payer = cranker, space = 8 + Listing::INIT_SPACE)]
+    pub relist: Option<Account<'info, Listing>>,
+
+    #[account(
+        init,
+        payer = cranker,
+        token::mint = mint,
+        token::authority = relist_vault,
+        seeds = [PREFIX.as_bytes(), b"vault", mint.key().as_ref(), relist.as_ref().map(|l| l.key()).unwrap_or_default().as_ref()],
+        bump
+    )]
+    pub relist_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Registry page (with room left) the relisting is added to (optional).
+    #[account(mut)]
+    pub registry_page: Option<AccountLoader<'info, ListingRegistryPage>>,
+
+    /// CHECK: `BlockedMint` PDA of `mint`, must be uninitialized to relist. Validated in `require_not_blocked`.
+    #[account(seeds = [PREFIX.as_bytes(), b"blocked_mint", mint.key().as_ref()], bump)]
+    pub blocked_mint: AccountInfo<'info>,
+
+    /// CHECK: `BannedWallet` PDA of `seller`, must be uninitialized to relist. Validated in `require_not_banned`.
+    #[account(seeds = [PREFIX.as_bytes(), b"banned_wallet", seller.key().as_ref()], bump)]
+    pub seller_ban: AccountInfo<'info>,
+
+    /// Approval of the mint's collection, required to relist in curated mode.
+    pub allowed_collection: Option<Account<'info, AllowedCollection>>,
+
+    /// CHECK: Metaplex metadata of `mint`, required to relist in curated & strict listing modes.
+    /// Validated in `verified_collection` / `strict_collection`.
+    pub metadata: Option<AccountInfo<'info>>,
+
+    pub token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetAuctionOnNoSale<'info> {
+    #[account(mut, has_one = seller)]
+    pub auction: Account<'info, Auction>,
+
+    pub seller: Signer<'info>,
+}
+
+// --------------------------------------------------------------------
+// Data & Errors
+// --------------------------------------------------------------------
+#[account]
+#[derive(InitSpace)]
+pub struct Listing {
+    pub seller: Pubkey,
+    pub price: u64,
+    pub mint: Pubkey,
+    pub is_active: bool,
+    /// USD price (6 decimals); when set, `price` is unused & the SOL amount comes from Pyth at purchase.
+    pub price_usd: Option<u64>,
+    // Fields below were added after launch: keep them optional & at the end so listings grown by
+    // `reallocate_listing` (zero-filled) read them as `None`.
+    pub category: Option<ListingCategory>,
+    /// Hash of the off-chain metadata document.
+    pub metadata_hash: Option<[u8; 32]>,
+    pub expires_at: Option<i64>,
+    /// SPL mint the price is denominated in, SOL when `None`.
+    pub payment_mint: Option<Pubkey>,
+    /// Storefront the listing was made through; its fee & treasury apply.
+    pub storefront: Option<Pubkey>,
+    /// Set by the seller to let buyers pay in installments.
+    pub installment_terms: Option<InstallmentTerms>,
+    /// Listing rewards (opt-in): points accrued up to `reward_checkpoint_slot` & not yet claimed.
+    pub reward_points: Option<u64>,
+    pub reward_checkpoint_slot: Option<u64>,
+    /// Recipients of the seller's proceeds (shares sum to 100%), instead of the seller.
+    #[max_len(MAX_PAYEES)]
+    pub payees: Option<Vec<Payee>>,
+    /// Seconds over which the seller's proceeds vest in a `Vesting` PDA, instead of being paid at once.
+    pub vesting_duration: Option<i64>,
+    /// Layout version, `LISTING_VERSION` for new & migrated listings; 0 for listings from before versioning.
+    pub version: u8,
+    /// `ListingRegistryPage` the listing is enumerated in, while active.
+    pub registry_page: Option<u32>,
+    /// Account allowed to delist & claim proceeds for the seller, e.g. a multisig PDA (signing via
+    /// `invoke_signed`) for a program-owned seller that can't sign itself.
+    pub seller_authority: Option<Pubkey>,
+    /// Price decaying from `price` down to a floor, instead of a fixed price.
+    pub dutch_auction: Option<DutchAuction>,
+    /// Unix timestamp before which the listing can't be bought, for staged drops.
+    pub starts_at: Option<i64>,
+    /// Discount applied to the price during a time window.
+    pub flash_sale: Option<FlashSale>,
+    /// Only buyers holding a token of this mint (e.g. a community pass) can buy.
+    pub gate_mint: Option<Pubkey>,
+    /// Root of a merkle tree of the buyers allowed to buy (see `merkle_leaf`).
+    pub allowlist_root: Option<[u8; 32]>,
+    pub purchase_limit: Option<PurchaseLimit>,
+    /// Burn-to-redeem: tokens the buyer burns on purchase, besides paying the price (which may be 0).
+    pub voucher: Option<Voucher>,
+    /// Phygital listings: seconds the buyer has to confirm receipt of the item, the seller's proceeds
+    /// being escrowed in an `EscrowedSale` meanwhile.
+    pub receipt_window: Option<i64>,
+    pub charity_split: Option<CharitySplit>,
+    /// Alternative fixed prices the buyer can pay instead, e.g. 300 USDC besides 2 SOL.
+    pub alt_prices: [Option<PriceLeg>; MAX_PRICE_LEGS],
+    /// Wallet that paid the listing's rent, when not the seller (e.g. a relayer); `None` before.
+    pub rent_payer: Option<Pubkey>,
+    /// Verified Metaplex collection of the mint, when its metadata was passed at listing time.
+    pub collection: Option<Pubkey>,
+    /// Only buyers holding a valid credential of this issuer (e.g. a KYC pass) can buy.
+    pub attestation: Option<AttestationRequirement>,
+    /// Creators sharing the royalty, read from the mint's metadata at listing time; the royalty goes to
+    /// `provenance.creator` when `None`.
+    #[max_len(MAX_ROYALTY_SPLITS)]
+    pub royalty_splits: Option<Vec<Payee>>,
+    /// Token-2022 metadata embedded in the mint at listing time, for mints carrying their own.
+    pub embedded_metadata: Option<EmbeddedMetadata>,
+    /// `LoanOffer` the seller borrowed against the vaulted NFT with; the listing's paused until repaid.
+    pub loan: Option<Pubkey>,
+}
+
+impl Listing {
+    pub fn open(
+        &mut self,
+        seller: Pubkey,
+        mint: Pubkey,
+        price: u64,
+        price_usd: Option<u64>,
+        options: &ListingOptions,
+    ) {
+        self.seller = seller;
+        self.mint = mint;
+        self.is_active = true;
+        self.set_terms(price, price_usd, options);
+        self.storefront = None;
+        self.version = LISTING_VERSION;
+        self.registry_page = None;
+        self.royalty_splits = None;
+        self.embedded_metadata = None;
+        self.loan = None;
+        self.rent_payer = None;
+        self.collection = None;
+        self.installment_terms = None;
+        self.reward_points = None;
+        self.reward_checkpoint_slot = None;
+    }
+
+    /// Sets the price & the fields taken from `options`, on opening or `list_or_update_nft`.
+    pub fn set_terms(&mut self, price: u64, price_usd: Option<u64>, options: &ListingOptions) {
+        self.price = price;
+        self.price_usd = price_usd;
+        self.category = options.category;
+        self.metadata_hash = options.metadata_hash;
+        self.expires_at = options.expires_at;
+        self.payment_mint = options.payment_mint;
+        self.payees = (!options.payees.is_empty()).then(|| options.payees.clone());
+        self.vesting_duration = options.vesting_duration;
+        self.seller_authority = options.seller_authority;
+        self.dutch_auction = options.dutch_auction;
+        self.starts_at = options.starts_at;
+        self.flash_sale = options.flash_sale;
+        self.gate_mint = options.gate_mint;
+        self.allowlist_root = options.allowlist_root;
+        self.purchase_limit = options.purchase_limit;
+        self.voucher = options.voucher;
+        self.receipt_window = options.receipt_window;
+        self.charity_split = options.charity_split;
+        self.alt_prices = options.alt_prices;
+        self.attestation = options.attestation;
+    }
+
+    /// Requires the seller to be able to take the NFT back out of the vault: the listing's active, not
+    /// reserved by an installment plan, sold or borrowed against.
+    pub fn require_delistable(&self) -> Result<()> {
+        require!(self.loan.is_none(), ErrorCode::ListingHasLoan);
+        require!(self.is_active, ErrorCode::InactiveListing);
+        Ok(())
+    }
+
+    /// Adds the slots active since the last checkpoint to the reward points; call before deactivating.
+    pub fn accrue_rewards(&mut self, slot: u64) {
+        if let Some(checkpoint) = self.reward_checkpoint_slot {
+            if self.is_active {
+                let points = self.reward_points.unwrap_or_default();
+                self.reward_points = Some(points.saturating_add(slot.saturating_sub(checkpoint)));
+            }
+            self.reward_checkpoint_slot = Some(slot);
+        }
+    }
+
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|expires_at| now >= expires_at)
+    }
+
+    pub fn has_started(&self, now: i64) -> bool {
+        self.starts_at.is_none_or(|starts_at| now >= starts_at)
+    }
+
+    /// Wallet the listing's (& its vault's) rent goes back to when delisted.
+    pub fn rent_recipient(&self) -> Pubkey {
+        self.rent_payer.unwrap_or(self.seller)
+    }
+
+    fn price_leg(&self, price_leg: u8) -> Option<PriceLeg> {
+        self.alt_prices.get(price_leg as usize).copied().flatten()
+    }
+
+    /// Mint paid in for the price leg the buyer picked, the listing's own when `None`.
+    pub fn leg_payment_mint(&self, price_leg: Option<u8>) -> Option<Pubkey> {
+        match price_leg.and_then(|leg| self.price_leg(leg)) {
+            Some(leg) => leg.mint,
+            None => self.payment_mint,
+        }
+    }
+
+    /// Switches the (about to be sold) listing to the alternative price the buyer picked.
+    pub fn select_price_leg(&mut self, price_leg: Option<u8>) -> Result<()> {
+        let Some(price_leg) = price_leg else {
+            return Ok(());
+        };
+        let leg = self
+            .price_leg(price_leg)
+            .ok_or(ErrorCode::InvalidPriceLeg)?;
+        self.payment_mint = leg.mint;
+        self.price = leg.price;
+        self.price_usd = None;
+        self.dutch_auction = None;
+        Ok(())
+    }
+
+    /// Drop the listing counts towards for purchase limits, default without a limit.
+    pub fn purchase_drop(&self) -> Pubkey {
+        self.purchase_limit
+            .map(|limit| limit.drop)
+            .unwrap_or_default()
+    }
+
+    /// Price due right now, in the payment currency: lamports, or base units of `payment_mint`.
+    /// Fixed SOL prices are stored in SOL, USD prices go through the oracle, Dutch auctions decay.
+    pub fn sale_price(
+        &self,
+        config: &MarketplaceConfig,
+        price_update: Option<&AccountInfo>,
+    ) -> Result<u64> {
+        match self.price_usd {
+            Some(price_usd) => {
+                let price_update = price_update.ok_or(ErrorCode::MissingPriceFeed)?;
+                let sol_usd = pyth::load_sol_usd_price(price_update, config)?;
+                pyth::usd_to_lamports(price_usd, &sol_usd)
+            }
+            None => {
+                let price = self.to_payment_units(self.price)?;
+                match &self.dutch_auction {
+                    Some(dutch_auction) => Ok(dutch_auction.price_at(
+                        price,
+                        self.to_payment_units(dutch_auction.floor_price)?,
+                        Clock::get()?.unix_timestamp,
+                    )),
+                    None => Ok(price),
+                }
+            }
+        }
+        .and_then(|price| self.apply_flash_sale(price))
+    }
+
+    fn apply_flash_sale(&self, price: u64) -> Result<u64> {
+        match &self.flash_sale {
+            Some(flash_sale) if flash_sale.is_live(Clock::get()?.unix_timestamp) => {
+                Ok(price - bps_of(price, flash_sale.discount_bps)?)
+            }
+            _ => Ok(price),
+        }
+    }
+
+    /// `amount` in the unit prices are stored in (SOL, or base units of `payment_mint`) to the payment currency.
+    fn to_payment_units(&self, amount: u64) -> Result<u64> {
+        match self.payment_mint {
+            Some(_) => Ok(amount),
+            None => Ok(amount
+                .checked_mul(LAMPORTS_PER_SOL)
+                .ok_or(ErrorCode::MathOverflow)?),
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum ListingCategory {
+    Apparel,
+    Footwear,
+    Accessories,
+    Jewelry,
+    Bags,
+    Art,
+    Other,
+}
+
+/// Dutch auction pricing of a listing: from `start_ts` the price falls along `curve`, from the listing's
+/// price down to `floor_price` (same unit as the price).
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace)]
+pub struct DutchAuction {
+    pub start_ts: i64,
+    pub floor_price: u64,
+    pub curve: DecayCurve,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum DecayCurve {
+    /// Straight down to the floor over `duration` seconds.
+    Linear { duration: i64 },
+    /// The part above the floor halves every `half_life` seconds (linear within each half-life).
+    Exponential { half_life: i64 },
+    /// Drops `step_bps` of the start-to-floor range every `interval` seconds.
+    Stepped { interval: i64, step_bps: u16 },
+}
+
+impl DutchAuction {
+    pub fn validate(&self, price: u64) -> Result<()> {
+        let curve_ok = match self.curve {
+            DecayCurve::Linear { duration } => duration > 0,
+            DecayCurve::Exponential { half_life } => half_life > 0,
+            DecayCurve::Stepped { interval, step_bps } => {
+                interval > 0 && step_bps > 0 && step_bps as u64 <= BPS_DENOMINATOR
+            }
+        };
+        require!(
+            curve_ok && self.floor_price <= price,
+            ErrorCode::InvalidDutchAuction
+        );
+        Ok(())
+    }
+
+    /// Price at `now` of an auction starting at `start_price` & bottoming out at `floor_price`.
+    pub fn price_at(&self, start_price: u64, floor_price: u64, now: i64) -> u64 {
+        let range = start_price.saturating_sub(floor_price) as u128;
+        let elapsed = now.saturating_sub(self.start_ts).max(0) as u128;
+        let above_floor = match self.curve {
+            DecayCurve::Linear { duration } => {
+                let duration = duration as u128;
+                range - range * elapsed.min(duration) / duration
+            }
+            DecayCurve::Exponential { half_life } => {
+                let half_life = half_life as u128;
+                let halvings = elapsed / half_life;
+                if halvings >= 128 {
+                    0
+                } else {
+                    let above = range >> halvings;
+                    above - (above / 2) * (elapsed % half_life) / half_life
+                }
+            }
+            DecayCurve::Stepped { interval, step_bps } => {
+                let steps = elapsed / interval as u128;
+                let dropped = range * (step_bps as u128) * steps / BPS_DENOMINATOR as u128;
+                range.saturating_sub(dropped)
+            }
+        };
+        floor_price + above_floor as u64
+    }
+}
+
+/// `discount_bps` off a listing's price from `window_start` until `window_end` (unix timestamps).
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace)]
+pub struct FlashSale {
+    pub discount_bps: u16,
+    pub window_start: i64,
+    pub window_end: i64,
+}
+
+impl FlashSale {
+    pub fn validate(&self) -> Result<()> {
+        require!(
+            self.discount_bps > 0
+                && (self.discount_bps as u64) < BPS_DENOMINATOR
+                && self.window_start < self.window_end,
+            ErrorCode::InvalidFlashSale
+        );
+        Ok(())
+    }
+
+    pub fn is_live(&self, now: i64) -> bool {
+        self.window_start <= now && now < self.window_end
+    }
+}
+
+/// Limits each wallet to `max_per_wallet` purchases among the seller's listings sharing `drop`
+/// (any key the seller picks for the drop).
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace)]
+pub struct PurchaseLimit {
+    pub drop: Pubkey,
+    pub max_per_wallet: u16,
+}
+
+/// Digest of a mint's Token-2022 metadata extension, see `embedded_metadata_hash`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace)]
+pub struct EmbeddedMetadata {
+    pub name_symbol_hash: [u8; 32],
+    /// Who can update the metadata, `None` when it's immutable.
+    pub update_authority: Option<Pubkey>,
+}
+
+/// Share (`bps` of the seller's proceeds) of a listing's sale donated to `charity`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace)]
+pub struct CharitySplit {
+    pub charity: Pubkey,
+    pub bps: u16,
+}
+
+/// Fixed `price` in `mint` (SOL when `None`) a listing also accepts.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace)]
+pub struct PriceLeg {
+    pub mint: Option<Pubkey>,
+    pub price: u64,
+}
+
+/// `amount` (base units) of `mint` burned by the buyer of a burn-to-redeem listing.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace)]
+pub struct Voucher {
+    pub mint: Pubkey,
+    pub amount: u64,
+}
+
+/// Credential buyers of an attestation-gated listing must hold.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace)]
+pub struct AttestationRequirement {
+    /// Program issuing the credential accounts, e.g. Civic's gateway program.
+    pub program: Pubkey,
+    /// Issuer the credential must be from (the gatekeeper network, for Civic passes).
+    pub issuer: Pubkey,
+}
+
+/// Credential account layout `verify_attestation` reads, the one of Civic-style gateway tokens.
+#[derive(AnchorDeserialize)]
+struct GatewayToken {
+    _features: u8,
+    _parent_gateway_token: Option<Pubkey>,
+    owner_wallet: Pubkey,
+    _owner_identity: Option<Pubkey>,
+    gatekeeper_network: Pubkey,
+    _issuing_gatekeeper: Pubkey,
+    state: GatewayTokenState,
+    expire_time: Option<i64>,
+}
+
+#[derive(AnchorDeserialize, PartialEq, Eq)]
+enum GatewayTokenState {
+    Active,
+    Frozen,
+    Revoked,
+}
+
+/// Why a listing was taken down by `force_delist` (queued directly or by `admin_delist`).
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum ModerationReason {
+    Stolen,
+    Counterfeit,
+    Fraud,
+    Prohibited,
+    Other,
+}
+
+/// Optional listing fields accepted by `list_nft_v2`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct ListingOptions {
+    pub category: Option<ListingCategory>,
+    pub metadata_hash: Option<[u8; 32]>,
+    pub expires_at: Option<i64>,
+    pub payment_mint: Option<Pubkey>,
+    /// Split of the seller's proceeds, empty to pay the seller.
+    pub payees: Vec<Payee>,
+    /// Vest the seller's proceeds over this many seconds (SOL-priced listings paying the seller only).
+    pub vesting_duration: Option<i64>,
+    /// Account or program acting for the seller on delist & proceeds withdrawal.
+    pub seller_authority: Option<Pubkey>,
+    /// Sell as a Dutch auction starting at the listing's price.
+    pub dutch_auction: Option<DutchAuction>,
+    /// Open for purchase from this unix timestamp on (before `expires_at`).
+    pub starts_at: Option<i64>,
+    pub flash_sale: Option<FlashSale>,
+    /// Restrict buyers to holders of this mint.
+    pub gate_mint: Option<Pubkey>,
+    /// Restrict buyers to the leaves of this merkle tree.
+    pub allowlist_root: Option<[u8; 32]>,
+    /// Cap purchases per wallet across the seller's listings of a drop.
+    pub purchase_limit: Option<PurchaseLimit>,
+    /// Require buyers to burn a voucher / ticket token.
+    pub voucher: Option<Voucher>,
+    /// Escrow the seller's proceeds until receipt (SOL-priced listings paying the seller only).
+    pub receipt_window: Option<i64>,
+    /// Donate a share of the seller's proceeds of every sale.
+    pub charity_split: Option<CharitySplit>,
+    /// Accept other currencies, at fixed prices.
+    pub alt_prices: [Option<PriceLeg>; MAX_PRICE_LEGS],
+    /// Restrict buyers to holders of a credential, for regulated drops.
+    pub attestation: Option<AttestationRequirement>,
+    /// Confirms a SOL price above `config.max_listing_price`, for genuinely high-value items.
+    pub confirm_high_price: bool,
+    /// Pay the royalty to all of the mint's Metaplex creators by their shares (needs `metadata`).
+    pub split_royalties: bool,
+}
+
+impl ListingOptions {
+    pub fn validate(&self, price: u64) -> Result<()> {
+        if !self.payees.is_empty() {
+            let total_bps: u64 = self.payees.iter().map(|payee| payee.share_bps as u64).sum();
+            require!(
+                self.payees.len() <= MAX_PAYEES && total_bps == BPS_DENOMINATOR,
+                ErrorCode::InvalidPayees
+            );
+        }
+        if let Some(vesting_duration) = self.vesting_duration {
+            require!(
+                vesting_duration > 0 && self.payment_mint.is_none() && self.payees.is_empty(),
+                ErrorCode::InvalidListingOptions
+            );
+        }
+        if let Some(expires_at) = self.expires_at {
+            require!(
+                expires_at > Clock::get()?.unix_timestamp,
+                ErrorCode::InvalidListingOptions
+            );
+        }
+        if let (Some(starts_at), Some(expires_at)) = (self.starts_at, self.expires_at) {
+            require!(starts_at < expires_at, ErrorCode::InvalidListingOptions);
+        }
+        if let Some(dutch_auction) = &self.dutch_auction {
+            dutch_auction.validate(price)?;
+        }
+        if let Some(flash_sale) = &self.flash_sale {
+            flash_sale.validate()?;
+        }
+        if let Some(purchase_limit) = &self.purchase_limit {
+            require!(
+                purchase_limit.max_per_wallet > 0,
+                ErrorCode::InvalidListingOptions
+            );
+        }
+        if let Some(voucher) = &self.voucher {
+            require!(voucher.amount > 0, ErrorCode::InvalidListingOptions);
+        }
+        if let Some(charity_split) = &self.charity_split {
+            require!(
+                charity_split.bps > 0 && charity_split.bps as u64 <= BPS_DENOMINATOR,
+                ErrorCode::InvalidListingOptions
+            );
+        }
+        for (i, leg) in self.alt_prices.iter().enumerate() {
+            let Some(leg) = leg else {
+                continue;
+            };
+            let is_duplicate = leg.mint == self.payment_mint
+                || self.alt_prices[..i]
+                    .iter()
+                    .flatten()
+                    .any(|other| other.mint == leg.mint);
+            // Vesting & escrowed sales settle in lamports only.
+            let settles_ok = leg.mint.is_none()
+                || (self.vesting_duration.is_none() && self.receipt_window.is_none());
+            require!(
+                leg.price > 0 && !is_duplicate && settles_ok,
+                ErrorCode::InvalidListingOptions
+            );
+        }
+        if let Some(receipt_window) = self.receipt_window {
+            require!(
+                receipt_window > 0
+                    && self.payment_mint.is_none()
+                    && self.payees.is_empty()
+                    && self.vesting_duration.is_none(),
+                ErrorCode::InvalidListingOptions
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum CpiPolicy {
+    /// Any program can compose `buy_nft`.
+    Open,
+    /// `buy_nft` must be a top-level instruction.
+    Blocked,
+    /// Only `allowed_callers` can compose `buy_nft`, from a top-level instruction.
+    Allowlist,
+}
+
+/// Aggregator program allowed to CPI into `buy_nft`, & its share of the marketplace fee.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace)]
+pub struct AllowedCaller {
+    pub program_id: Pubkey,
+    pub fee_recipient: Pubkey,
+    pub fee_share_bps: u16,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace)]
+pub struct Payee {
+    pub payee: Pubkey,
+    pub share_bps: u16,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace)]
+pub struct InstallmentTerms {
+    /// Share of the price paid upfront, forfeited to the seller if the buyer defaults.
+    pub deposit_bps: u16,
+    /// Number of equal installments the rest is split into.
+    pub installments: u8,
+    /// Seconds allowed for each installment.
+    pub period: i64,
+}
+
+impl InstallmentTerms {
+    pub fn validate(&self) -> Result<()> {
+        require!(
+            self.deposit_bps > 0
+                && (self.deposit_bps as u64) < BPS_DENOMINATOR
+                && self.installments > 0
+                && self.period > 0,
+            ErrorCode::InvalidInstallmentTerms
+        );
+        Ok(())
+    }
+}
+
+/// Fixed-capacity page of active listing keys, so clients can enumerate listings without
+/// `getProgramAccounts`. PDA of its page number; entries aren't kept in listing order.
+#[account(zero_copy)]
+pub struct ListingRegistryPage {
+    pub page: u32,
+    pub count: u32,
+    pub listings: [Pubkey; REGISTRY_PAGE_CAPACITY],
+}
+
+impl ListingRegistryPage {
+    pub fn push(&mut self, listing: Pubkey) -> Result<()> {
+        let count = self.count as usize;
+        require!(count < REGISTRY_PAGE_CAPACITY, ErrorCode::RegistryPageFull);
+        self.listings[count] = listing;
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Swap-removes `listing`, if present.
+    pub fn remove(&mut self, listing: &Pubkey) {
+        let count = self.count as usize;
+        if let Some(index) = self.listings[..count].iter().position(|key| key == listing) {
+            self.listings[index] = self.listings[count - 1];
+            self.listings[count - 1] = Pubkey::default();
+            self.count -= 1;
+        }
+    }
+
+    pub fn active(&self) -> &[Pubkey] {
+        &self.listings[..self.count as usize]
+    }
+}
+
+/// Last deposit into a mint's shared vault: who escrowed the NFT, & the auction, raffle or rental
+/// it was escrowed for. Overwritten by each deposit; closed by `recover_vault`.
+#[account]
+#[derive(InitSpace)]
+pub struct VaultDeposit {
+    pub mint: Pubkey,
+    pub depositor: Pubkey,
+    pub holder: Pubkey,
+    pub bump: u8,
+}
+
+impl VaultDeposit {
+    pub fn record(&mut self, mint: Pubkey, depositor: Pubkey, holder: Pubkey, bump: u8) {
+        self.mint = mint;
+        self.depositor = depositor;
+        self.holder = holder;
+        self.bump = bump;
+    }
+}
+
+/// Auction over a vaulted NFT, prices in lamports.
+#[account]
+#[derive(InitSpace)]
+pub struct Auction {
+    pub seller: Pubkey,
+    pub mint: Pubkey,
+    pub min_bid: u64,
+    /// Below this the NFT goes back to the seller at settlement.
+    pub reserve_price: u64,
+    /// Paying this ends the auction immediately.
+    pub buy_now_price: Option<u64>,
+    pub end_ts: i64,
+    pub highest_bid: u64,
+    pub highest_bidder: Pubkey,
+    pub status: AuctionStatus,
+    pub mode: AuctionMode,
+    /// Sealed auctions: bids are revealed between `end_ts` and this.
+    pub reveal_end_ts: i64,
+    /// Sealed auctions: lamports escrowed with each commitment, forfeited if never revealed.
+    pub bid_deposit: u64,
+    /// What settlement does with the NFT when the reserve isn't met.
+    pub on_no_sale: NoSaleAction,
+    /// Sealed auctions: committed bids not yet revealed or forfeited.
+    pub unrevealed_bids: u32,
+}
+
+impl Auction {
+    /// Starts an english auction of the seller's `mint`, ending `duration` seconds from now.
+    pub fn start_english(
+        &mut self,
+        seller: Pubkey,
+        mint: Pubkey,
+        min_bid: u64,
+        reserve_price: u64,
+        buy_now_price: Option<u64>,
+        duration: i64,
+    ) -> Result<()> {
+        require!(duration > 0, ErrorCode::InvalidAuctionParams);
+        if let Some(buy_now_price) = buy_now_price {
+            require!(
+                buy_now_price >= min_bid && buy_now_price >= reserve_price,
+                ErrorCode::InvalidAuctionParams
+            );
+        }
+
+        self.seller = seller;
+        self.mint = mint;
+        self.min_bid = min_bid;
+        self.reserve_price = reserve_price;
+        self.buy_now_price = buy_now_price;
+        self.end_ts = Clock::get()?
+            .unix_timestamp
+            .checked_add(duration)
+            .ok_or(ErrorCode::MathOverflow)?;
+        self.highest_bid = 0;
+        self.highest_bidder = Pubkey::default();
+        self.status = AuctionStatus::Active;
+        self.mode = AuctionMode::English;
+        self.reveal_end_ts = self.end_ts;
+        self.bid_deposit = 0;
+        self.on_no_sale = NoSaleAction::ReturnToSeller;
+        self.unrevealed_bids = 0;
+        Ok(())
+    }
+
+    /// Earliest settlement time: end of bidding, or of the reveal window for sealed auctions.
+    pub fn settle_ts(&self) -> i64 {
+        match self.mode {
+            AuctionMode::English => self.end_ts,
+            AuctionMode::Sealed => self.reveal_end_ts,
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum AuctionStatus {
+    Active,
+    Settled,
+    Cancelled,
+}
+
+/// What `settle_auction` does with the NFT of an auction whose reserve isn't met.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum NoSaleAction {
+    /// Back to the seller.
+    ReturnToSeller,
+    /// Into a new fixed-price listing of the seller's, at `price` lamports.
+    Relist { price: u64 },
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum AuctionMode {
+    /// Open ascending bids.
+    English,
+    /// Commit-reveal bids.
+    Sealed,
+}
+
+/// A bidder's escrowed bid on an auction; the lamports are held by this account.
+#[account]
+#[derive(InitSpace)]
+pub struct Bid {
+    pub auction: Pubkey,
+    pub bidder: Pubkey,
+    /// Bid amount, 0 until a sealed bid is revealed.
+    pub amount: u64,
+    /// Sealed auctions: `sealed_bid_commitment` of the bid.
+    pub commitment: Option<[u8; 32]>,
+    /// Always true for english auction bids.
+    pub revealed: bool,
+    pub bump: u8,
+}
+
+/// Singleton marketplace settings.
+#[account]
+#[derive(InitSpace)]
+pub struct MarketplaceConfig {
+    pub authority: Pubkey,
+    pub treasury: Pubkey,
+    /// Applied to the first marketplace sale of a mint.
+    pub primary_fees: FeeSchedule,
+    /// Applied to every following (resale) sale.
+    pub secondary_fees: FeeSchedule,
+    /// Reject sales that don't pay the creator royalty.
+    pub enforce_royalties: bool,
+    /// Paid to whoever cranks a settlement, out of the seller proceeds.
+    pub crank_tip_lamports: u64,
+    /// Pyth feed id USD listings are priced against.
+    pub sol_usd_feed_id: [u8; 32],
+    pub max_price_age_secs: u64,
+    /// Max allowed confidence interval, in basis points of the price.
+    pub max_price_conf_bps: u16,
+    /// Volume-based marketplace fee discounts, by ascending volume.
+    #[max_len(MAX_FEE_TIERS)]
+    pub fee_tiers: Vec<FeeTier>,
+    /// Holders of `membership_min_amount` of this mint pay `membership_fee_bps`; disabled when default.
+    pub membership_mint: Pubkey,
+    pub membership_min_amount: u64,
+    pub membership_fee_bps: u16,
+    /// Only mints of collections with an `AllowedCollection` can be listed.
+    pub curated_collections: bool,
+    /// Pay sellers of WSOL-escrowed offers in SOL (unwrapped) rather than WSOL.
+    pub unwrap_wsol_proceeds: bool,
+    /// Token paid for listing reward points (one point per active slot); disabled when default.
+    pub reward_mint: Pubkey,
+    /// Reward token base units per point.
+    pub reward_per_point: u64,
+    /// Share of the marketplace fee rebated to the maker of a filled listing or offer.
+    pub maker_rebate_bps: u16,
+    /// Who may buy through a CPI from another program.
+    pub cpi_policy: CpiPolicy,
+    #[max_len(MAX_ALLOWED_CALLERS)]
+    pub allowed_callers: Vec<AllowedCaller>,
+    /// Resolves disputed escrowed sales; disputes are disabled when default.
+    pub arbiter: Pubkey,
+    /// Share of the seller proceeds of SOL sales (of every kind) parked in the seller's `Withholding`.
+    pub withholding_bps: u16,
+    /// Withholdings can be withdrawn from then on.
+    pub withholding_release_ts: i64,
+    /// New listings, auctions & purchases are refused while set (`pause`).
+    pub paused: bool,
+    /// Seconds between queuing an emergency action & when it can run.
+    pub action_timelock: i64,
+    /// Nominated by the authority to take over with `accept_authority`; default when none.
+    pub pending_authority: Pubkey,
+    /// Listings must come with the mint's metadata, its collection & at least one creator verified.
+    pub strict_listing: bool,
+    /// Share of each SOL sale's marketplace fee paid into the `InsuranceFund`.
+    pub insurance_bps: u16,
+    /// Marketplace token bought back & burned by `execute_buyback`; default when disabled.
+    pub buyback_mint: Pubkey,
+    /// AMM program buyback swaps go through.
+    pub buyback_amm_program: Pubkey,
+    /// Most lamports spent by one buyback.
+    pub max_buyback_lamports: u64,
+    /// Seconds between two buybacks.
+    pub buyback_interval: i64,
+    /// Lowest accepted buyback rate, in token base units per SOL.
+    pub min_buyback_rate: u64,
+    pub last_buyback_ts: i64,
+    /// Trade mining reward base units per SOL of sale volume, to buyer & seller each.
+    pub mining_rate: u64,
+    /// Length of a trade mining epoch, in seconds.
+    pub mining_epoch_secs: i64,
+    /// Most reward tokens emitted per trade mining epoch.
+    pub mining_epoch_cap: u64,
+    /// Loyalty points a buyer earns per SOL spent.
+    pub loyalty_points_per_sol: u64,
+    /// Base units of `reward_mint` a loyalty point redeems for.
+    pub loyalty_point_value: u64,
+    /// Highest SOL listing price, in lamports, unless the seller confirms it; 0 when uncapped.
+    pub max_listing_price: u64,
+    /// Wallet `sweep_fees` pays the fee vault out to.
+    pub fee_sweep_recipient: Pubkey,
+    /// Lamports the fee vault must hold over rent for `sweep_fees` to pay them out.
+    pub fee_sweep_threshold: u64,
+    /// SPL stake pool (& its program) escrows can earn yield in; `Pubkey::default()` disables it.
+    pub yield_stake_pool_program: Pubkey,
+    pub yield_stake_pool: Pubkey,
+    /// Escrow yield goes to the treasury, or to the escrow's owner.
+    pub yield_to_treasury: bool,
+    pub bump: u8,
+}
+
+impl MarketplaceConfig {
+    pub fn fee_schedule(&self, is_primary: bool) -> &FeeSchedule {
+        if is_primary {
+            &self.primary_fees
+        } else {
+            &self.secondary_fees
+        }
+    }
+
+    /// Marketplace fee for a trader with `volume`: the highest tier reached, if lower than `base_bps`.
+    pub fn tiered_fee_bps(&self, base_bps: u16, volume: u64) -> u16 {
+        self.fee_tiers
+            .iter()
+            .rev()
+            .find(|tier| volume >= tier.min_volume_lamports)
+            .map_or(base_bps, |tier| tier.marketplace_fee_bps.min(base_bps))
+    }
+
+    /// Whether `membership_account` holds enough of the membership mint.
+    pub fn is_member(&self, membership_account: Option<&TokenAccount>) -> bool {
+        self.membership_mint != Pubkey::default()
+            && membership_account.is_some_and(|account| {
+                account.mint == self.membership_mint && account.amount >= self.membership_min_amount
+            })
+    }
+}
+
+/// Emergency action queued by the config authority through `queue_action`.
+#[account]
+#[derive(InitSpace)]
+pub struct QueuedAction {
+    pub nonce: u64,
+    pub action: EmergencyAction,
+    /// Unix timestamp the action can run from.
+    pub eta: i64,
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, InitSpace)]
+pub enum EmergencyAction {
+    /// Delists these listings, returning their NFTs to the sellers (`force_delist`).
+    ForceDelist {
+        #[max_len(MAX_FORCED_DELISTS)]
+        listings: Vec<Pubkey>,
+        reason: ModerationReason,
+    },
+    /// Moves `amount` out of the listing rewards vault to the treasury, e.g. to wind rewards down.
+    RewardVaultSweep { amount: u64 },
+    /// Changes `config.action_timelock`.
+    SetTimelock { timelock: i64 },
+}
+
+impl EmergencyAction {
+    pub fn validate(&self) -> Result<()> {
+        let valid = match self {
+            EmergencyAction::ForceDelist { listings, .. } => {
+                !listings.is_empty() && listings.len() <= MAX_FORCED_DELISTS
+            }
+            EmergencyAction::SetTimelock { timelock } => *timelock >= 0,
+            EmergencyAction::RewardVaultSweep { .. } => true,
+        };
+        require!(valid, ErrorCode::InvalidQueuedAction);
+        Ok(())
+    }
+}
+
+/// Marketplace fee applied once a trader's volume reaches `min_volume_lamports`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace)]
+pub struct FeeTier {
+    pub min_volume_lamports: u64,
+    pub marketplace_fee_bps: u16,
+}
+
+/// Proof of a listing purchase for the buyer (loyalty programs, warranty claims).
+#[account]
+#[derive(InitSpace)]
+pub struct SaleReceipt {
+    pub mint: Pubkey,
+    pub listing: Pubkey,
+    pub seller: Pubkey,
+    pub buyer: Pubkey,
+    /// In the payment currency.
+    pub price: u64,
+    /// SOL when `None`.
+    pub payment_mint: Option<Pubkey>,
+    pub is_primary: bool,
+    pub slot: u64,
+    pub bump: u8,
+    /// Fee & royalty charged under the sale's primary or secondary schedule, in the payment currency.
+    pub marketplace_fee: u64,
+    pub royalty: u64,
+}
+
+/// Purchases of a wallet in a seller's drop, against the drop's `PurchaseLimit`.
+#[account]
+#[derive(InitSpace)]
+pub struct WalletPurchases {
+    pub count: u16,
+    pub bump: u8,
+}
+
+/// Per-wallet cumulative purchase volume.
+#[account]
+#[derive(InitSpace)]
+pub struct TraderStats {
+    pub trader: Pubkey,
+    /// Lamports spent on marketplace purchases.
+    pub volume_lamports: u64,
+    pub purchases: u64,
+    pub bump: u8,
+}
+
+impl TraderStats {
+    pub fn record(&mut self, trader: Pubkey, volume: u64, bump: u8) -> Result<()> {
+        self.trader = trader;
+        self.bump = bump;
+        self.volume_lamports = self
+            .volume_lamports
+            .checked_add(volume)
+            .ok_or(ErrorCode::MathOverflow)?;
+        self.purchases = self
+            .purchases
+            .checked_add(1)
+            .ok_or(ErrorCode::MathOverflow)?;
+        Ok(())
+    }
+}
+
+/// Singleton backstop fund collecting `insurance_bps` of sale fees (its lamports above rent), paying
+/// claims through `pay_insurance_claim`.
+#[account]
+#[derive(InitSpace)]
+pub struct InsuranceFund {
+    pub total_deposited: u64,
+    pub total_claimed: u64,
+    pub bump: u8,
+}
+
+/// Per-wallet loyalty points, earned on purchases & spent through `redeem_points`; not transferable.
+#[account]
+#[derive(InitSpace)]
+pub struct LoyaltyAccount {
+    pub wallet: Pubkey,
+    pub points: u64,
+    /// Points ever earned, redeemed ones included.
+    pub lifetime_points: u64,
+    pub bump: u8,
+}
+
+impl LoyaltyAccount {
+    /// Credits the points of a `volume` lamports purchase, at `config.loyalty_points_per_sol`.
+    pub fn earn(
+        &mut self,
+        wallet: Pubkey,
+        volume: u64,
+        config: &MarketplaceConfig,
+        bump: u8,
+    ) -> Result<()> {
+        self.wallet = wallet;
+        self.bump = bump;
+        let points = (volume as u128 * config.loyalty_points_per_sol as u128
+            / LAMPORTS_PER_SOL as u128)
+            .min(u64::MAX as u128) as u64;
+        self.points = self
+            .points
+            .checked_add(points)
+            .ok_or(ErrorCode::MathOverflow)?;
+        self.lifetime_points = self
+            .lifetime_points
+            .checked_add(points)
+            .ok_or(ErrorCode::MathOverflow)?;
+        Ok(())
+    }
+}
+
+/// Singleton trade mining emissions: the current epoch & what it's emitted so far.
+#[account]
+#[derive(InitSpace)]
+pub struct TradeMining {
+    /// Epoch index, `unix_timestamp / config.mining_epoch_secs`.
+    pub epoch: i64,
+    pub emitted: u64,
+    pub total_emitted: u64,
+    pub bump: u8,
+}
+
+impl TradeMining {
+    /// Grants up to `amount` of this epoch's emissions under `config.mining_epoch_cap`, rolling over
+    /// to a new epoch first when due.
+    pub fn grant(&mut self, config: &MarketplaceConfig, now: i64, amount: u64) -> Result<u64> {
+        if config.mining_rate == 0 || config.mining_epoch_secs <= 0 {
+            return Ok(0);
+        }
+        let epoch = now / config.mining_epoch_secs;
+        if epoch != self.epoch {
+            self.epoch = epoch;
+            self.emitted = 0;
+        }
+        let granted = amount.min(config.mining_epoch_cap.saturating_sub(self.emitted));
+        self.emitted += granted;
+        self.total_emitted = self
+            .total_emitted
+            .checked_add(granted)
+            .ok_or(ErrorCode::MathOverflow)?;
+        Ok(granted)
+    }
+}
+
+/// Singleton marketplace-wide counters, for dashboards.
+#[account]
+#[derive(InitSpace)]
+pub struct GlobalStats {
+    /// Fixed-price listings & auctions created.
+    pub total_listings: u64,
+    /// Fixed-price & auction sales.
+    pub total_sales: u64,
+    /// Lamport volume (SPL-priced sales don't count).
+    pub total_volume_lamports: u64,
+    pub bump: u8,
+}
+
+impl GlobalStats {
+    pub fn record_listing(&mut self) -> Result<()> {
+        self.total_listings = self
+            .total_listings
+            .checked_add(1)
+            .ok_or(ErrorCode::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Records a sale, with its lamport price (`None` for SPL-priced sales).
+    pub fn record_sale(&mut self, price_lamports: Option<u64>) -> Result<()> {
+        self.total_sales = self
+            .total_sales
+            .checked_add(1)
+            .ok_or(ErrorCode::MathOverflow)?;
+        if let Some(price) = price_lamports {
+            self.total_volume_lamports = self
+                .total_volume_lamports
+                .checked_add(price)
+                .ok_or(ErrorCode::MathOverflow)?;
+        }
+        Ok(())
+    }
+}
+
+/// A mint's creator shares & verified collection as of `cached_at`, read by `buy_nft` instead of the
+/// mint's metadata.
+#[account]
+#[derive(InitSpace)]
+pub struct RoyaltyCache {
+    pub mint: Pubkey,
+    #[max_len(MAX_ROYALTY_SPLITS)]
+    pub creators: Vec<Payee>,
+    pub collection: Option<Pubkey>,
+    pub cached_at: i64,
+    pub bump: u8,
+}
+
+/// Sales of a verified collection's mints, for floor-price estimation & analytics.
+#[account]
+#[derive(InitSpace)]
+pub struct CollectionStats {
+    pub collection: Pubkey,
+    /// Lamport volume (SPL-priced sales don't count).
+    pub volume_lamports: u64,
+    pub sales_count: u64,
+    /// Lamport price of the latest SOL-settled sale.
+    pub last_sale_price: u64,
+    /// Time of the latest sale, in any currency.
+    pub last_sale_ts: i64,
+    pub bump: u8,
+}
+
+impl CollectionStats {
+    /// Records a sale, with its lamport price (`None` for SPL-priced sales).
+    pub fn record_sale(&mut self, price_lamports: Option<u64>) -> Result<()> {
+        self.sales_count = self
+            .sales_count
+            .checked_add(1)
+            .ok_or(ErrorCode::MathOverflow)?;
+        self.last_sale_ts = Clock::get()?.unix_timestamp;
+        if let Some(price) = price_lamports {
+            self.volume_lamports = self
+                .volume_lamports
+                .checked_add(price)
+                .ok_or(ErrorCode::MathOverflow)?;
+            self.last_sale_price = price;
+        }
+        Ok(())
+    }
+}
+
+/// Opt-in per-wallet trading history, for reputation & loyalty features.
+#[account]
+#[derive(InitSpace)]
+pub struct TraderProfile {
+    pub trader: Pubkey,
+    /// Number of purchases.
+    pub total_bought: u64,
+    /// Number of sales.
+    pub total_sold: u64,
+    /// Lamports traded on both sides (SPL-priced sales don't count).
+    pub volume_lamports: u64,
+    /// Slots of the first & latest trade, 0 before any trade.
+    pub first_trade_slot: u64,
+    pub last_trade_slot: u64,
+    pub bump: u8,
+}
+
+impl TraderProfile {
+    pub fn record_buy(&mut self, volume: u64, slot: u64) -> Result<()> {
+        self.total_bought = self
+            .total_bought
+            .checked_add(1)
+            .ok_or(ErrorCode::MathOverflow)?;
+        self.record_trade(volume, slot)
+    }
+
+    pub fn record_sale(&mut self, volume: u64, slot: u64) -> Result<()> {
+        self.total_sold = self
+            .total_sold
+            .checked_add(1)
+            .ok_or(ErrorCode::MathOverflow)?;
+        self.record_trade(volume, slot)
+    }
+
+    fn record_trade(&mut self, volume: u64, slot: u64) -> Result<()> {
+        self.volume_lamports = self
+            .volume_lamports
+            .checked_add(volume)
+            .ok_or(ErrorCode::MathOverflow)?;
+        if self.first_trade_slot == 0 {
+            self.first_trade_slot = slot;
+        }
+        self.last_trade_slot = slot;
+        Ok(())
+    }
+}
+
+/// Fee & royalty rates, in basis points of the sale price.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct FeeSchedule {
+    pub marketplace_fee_bps: u16,
+    pub royalty_bps: u16,
+}
+
+impl FeeSchedule {
+    pub fn validate(&self) -> Result<()> {
+        let total = self.marketplace_fee_bps as u64 + self.royalty_bps as u64;
+        require!(total <= BPS_DENOMINATOR, ErrorCode::InvalidFeeBps);
+        Ok(())
+    }
+}
+
+/// Admin approval of a verified Metaplex collection, for curated mode.
+#[account]
+#[derive(InitSpace)]
+pub struct AllowedCollection {
+    pub collection: Pubkey,
+    pub bump: u8,
+    /// Lowest price of SOL listings of the collection, in lamports.
+    pub min_price: Option<u64>,
+}
+
+/// Seller's proceeds of a phygital sale, held until the buyer confirms receipt or `release_ts` passes.
+#[account]
+#[derive(InitSpace)]
+pub struct EscrowedSale {
+    pub listing: Pubkey,
+    pub seller: Pubkey,
+    pub buyer: Pubkey,
+    /// In lamports.
+    pub amount: u64,
+    pub release_ts: i64,
+    /// Under dispute: only the arbiter, or a conceding party, can settle it.
+    pub disputed: bool,
+    pub bump: u8,
+}
+
+/// Seller proceeds withheld by the marketplace (see `MarketplaceConfig::withholding_bps`).
+#[account]
+#[derive(InitSpace)]
+pub struct Withholding {
+    pub seller: Pubkey,
+    /// In lamports.
+    pub amount: u64,
+    pub bump: u8,
+}
+
+/// Sale proceeds of a vested listing, released to the seller linearly from `start_ts` over `duration`.
+#[account]
+#[derive(InitSpace)]
+pub struct Vesting {
+    pub seller: Pubkey,
+    /// The listing's `seller_authority`, also allowed to claim.
+    pub seller_authority: Option<Pubkey>,
+    pub listing: Pubkey,
+    /// In lamports.
+    pub total: u64,
+    pub claimed: u64,
+    pub start_ts: i64,
+    pub duration: i64,
+    pub bump: u8,
+}
+
+impl Vesting {
+    /// Lamports vested by `now`, claimed ones included.
+    pub fn vested(&self, now: i64) -> u64 {
+        let elapsed = (now - self.start_ts).clamp(0, self.duration);
+        (self.total as u128 * elapsed as u128 / self.duration as u128) as u64
+    }
+}
+
+/// Open edition primary sale: `supply` units of `mint` in the listing's vault, priced along `curve`.
+#[account]
+#[derive(InitSpace)]
+pub struct EditionListing {
+    pub seller: Pubkey,
+    pub mint: Pubkey,
+    /// Price of the first unit, in lamports.
+    pub base_price: u64,
+    pub curve: BondingCurve,
+    pub supply: u64,
+    pub units_sold: u64,
+    pub bump: u8,
+}
+
+impl EditionListing {
+    /// Price of the next unit.
+    pub fn current_price(&self) -> Result<u64> {
+        self.curve.price_at(self.base_price, self.units_sold)
+    }
+
+    /// Total price of the next `quantity` units.
+    pub fn fill_price(&self, quantity: u64) -> Result<u64> {
+        self.curve
+            .total_price(self.base_price, self.units_sold, quantity)
+    }
+
+    pub fn remaining_units(&self) -> u64 {
+        self.supply - self.units_sold
+    }
+}
+
+/// How an edition's unit price rises with each unit sold.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace)]
+pub enum BondingCurve {
+    /// `step` lamports more per unit sold.
+    Linear { step: u64 },
+    /// `step_bps` more per unit sold, compounding.
+    Exponential { step_bps: u16 },
+}
+
+impl BondingCurve {
+    pub fn is_valid(&self) -> bool {
+        match *self {
+            Self::Linear { .. } => true,
+            Self::Exponential { step_bps } => step_bps > 0 && step_bps as u64 <= BPS_DENOMINATOR,
+        }
+    }
+
+    /// Spot price after a pool sold an NFT.
+    pub fn step_up(&self, price: u64) -> Result<u64> {
+        let price = match *self {
+            Self::Linear { step } => price as u128 + step as u128,
+            Self::Exponential { step_bps } => {
+                price as u128 * (BPS_DENOMINATOR + step_bps as u64) as u128
+                    / BPS_DENOMINATOR as u128
+            }
+        };
+        u64::try_from(price).map_err(|_| error!(ErrorCode::MathOverflow))
+    }
+
+    /// Spot price after a pool bought an NFT.
+    pub fn step_down(&self, price: u64) -> Result<u64> {
+        Ok(match *self {
+            Self::Linear { step } => price.saturating_sub(step),
+            Self::Exponential { step_bps } => {
+                (price as u128 * BPS_DENOMINATOR as u128
+                    / (BPS_DENOMINATOR + step_bps as u64) as u128) as u64
+            }
+        })
+    }
+
+    /// Price after `units_sold` units, from `base_price`.
+    pub fn price_at(&self, base_price: u64, units_sold: u64) -> Result<u64> {
+        let price = match *self {
+            Self::Linear { step } => (base_price as u128)
+                .checked_add(step as u128 * units_sold as u128)
+                .ok_or(ErrorCode::MathOverflow)?,
+            Self::Exponential { step_bps } => {
+                (base_price as u128)
+                    .checked_mul(Self::growth(step_bps, units_sold)?)
+                    .ok_or(ErrorCode::MathOverflow)?
+                    / Self::ONE
+            }
+        };
+        u64::try_from(price).map_err(|_| error!(ErrorCode::MathOverflow))
+    }
+
+    /// Total price of `quantity` units after `units_sold`, from `base_price`, in closed form (no loop
+    /// over the units): an arithmetic series for `Linear`, a geometric one for `Exponential`.
+    pub fn total_price(&self, base_price: u64, units_sold: u64, quantity: u64) -> Result<u64> {
+        let total = match *self {
+            Self::Linear { step } => {
+                // quantity * base_price + step * (units_sold + ... + units_sold + quantity - 1)
+                let (first, quantity) = (units_sold as u128, quantity as u128);
+                let steps = quantity
+                    .checked_mul(first)
+                    .and_then(|steps| steps.checked_add(quantity * quantity.saturating_sub(1) / 2))
+                    .and_then(|steps| steps.checked_mul(step as u128));
+                steps
+                    .and_then(|steps| steps.checked_add(quantity * base_price as u128))
+                    .ok_or(ErrorCode::MathOverflow)?
+            }
+            Self::Exponential { step_bps } => {
+                // base_price * (g^units_sold + ... + g^(units_sold + quantity - 1))
+                //   = base_price * (g^(units_sold + quantity) - g^units_sold) / (g - 1)
+                let end = units_sold
+                    .checked_add(quantity)
+                    .ok_or(ErrorCode::MathOverflow)?;
+                let growth = Self::growth(step_bps, end)? - Self::growth(step_bps, units_sold)?;
+                (base_price as u128)
+                    .checked_mul(growth)
+                    .and_then(|total| total.checked_mul(BPS_DENOMINATOR as u128))
+                    .ok_or(ErrorCode::MathOverflow)?
+                    / (step_bps as u128 * Self::ONE)
+            }
+        };
+        u64::try_from(total).map_err(|_| error!(ErrorCode::MathOverflow))
+    }
+
+    /// `Exponential` fixed point unit.
+    const ONE: u128 = 1_000_000_000_000;
+
+    /// (1 + step_bps / BPS)^exponent in `ONE` fixed point, by squaring.
+    fn growth(step_bps: u16, mut exponent: u64) -> Result<u128> {
+        let mut factor =
+            Self::ONE * (BPS_DENOMINATOR + step_bps as u64) as u128 / BPS_DENOMINATOR as u128;
+        let mut growth = Self::ONE;
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                growth = growth.checked_mul(factor).ok_or(ErrorCode::MathOverflow)? / Self::ONE;
+            }
+            exponent >>= 1;
+            if exponent > 0 {
+                factor = factor.checked_mul(factor).ok_or(ErrorCode::MathOverflow)? / Self::ONE;
+            }
+        }
+        Ok(growth)
+    }
+}
+
+/// Two-sided collection liquidity pool: holds the owner's SOL (`sol_balance`, besides rent) & NFTs
+/// (each in a `[PREFIX, "vault", mint, pool]` vault), trading both ways at `spot_price`.
+#[account]
+#[derive(InitSpace)]
+pub struct Pool {
+    pub owner: Pubkey,
+    /// Verified collection of the NFTs it trades.
+    pub collection: Pubkey,
+    /// In lamports.
+    pub spot_price: u64,
+    pub curve: BondingCurve,
+    /// Owner's fee on each trade, in basis points of the spot price.
+    pub fee_bps: u16,
+    pub sol_balance: u64,
+    pub nft_count: u32,
+    pub bump: u8,
+}
+
+/// Raffle of a vaulted NFT; escrows ticket lamports until the draw.
+#[account]
+#[derive(InitSpace)]
+pub struct Raffle {
+    pub seller: Pubkey,
+    pub mint: Pubkey,
+    /// In lamports.
+    pub ticket_price: u64,
+    pub max_tickets: u64,
+    pub tickets_sold: u64,
+    pub end_ts: i64,
+    /// sha256 of the seller's secret, revealed at the draw.
+    pub commitment: [u8; 32],
+    /// Lamports the seller escrowed, returned at the draw or shared among the entrants without one.
+    pub bond: u64,
+    /// Slot whose hash seeds the draw, set by `lock_raffle`.
+    pub draw_slot: Option<u64>,
+    pub winning_ticket: Option<u64>,
+    pub status: RaffleStatus,
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum RaffleStatus {
+    Open,
+    Drawn,
+    Claimed,
+    Cancelled,
+}
+
+/// Tickets `first_ticket..first_ticket + count` of a raffle, bought together.
+#[account]
+#[derive(InitSpace)]
+pub struct RaffleEntry {
+    pub raffle: Pubkey,
+    pub buyer: Pubkey,
+    pub first_ticket: u64,
+    pub count: u64,
+    pub bump: u8,
+}
+
+/// Fractionalized NFT: the NFT stays in the vault, ownership is split into fungible `share_mint` tokens.
+#[account]
+#[derive(InitSpace)]
+pub struct Fraction {
+    /// Seller who fractionalized the listing.
+    pub curator: Pubkey,
+    pub mint: Pubkey,
+    /// Fractionalized listing, its key seeds the vault still holding the NFT.
+    pub listing: Pubkey,
+    /// SPL Token mint of the shares (0 decimals), mint authority is this PDA.
+    pub share_mint: Pubkey,
+    pub total_shares: u64,
+    /// Minimum buyout bid, in lamports.
+    pub buyout_reserve: u64,
+    /// Seconds a buyout auction runs from its first bid.
+    pub buyout_duration: i64,
+    pub status: FractionStatus,
+    pub buyout_end_ts: i64,
+    pub highest_bid: u64,
+    pub highest_bidder: Option<Pubkey>,
+    /// Winning bid lamports not yet claimed by share holders.
+    pub proceeds_remaining: u64,
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum FractionStatus {
+    /// Shares outstanding, redeemable by whoever holds them all.
+    Shares,
+    /// Buyout auction running.
+    Buyout,
+    Redeemed,
+    BoughtOut,
+}
+
+/// NFT offered for rent; escrows the NFT while available & the renter's collateral while rented.
+#[account]
+#[derive(InitSpace)]
+pub struct Rental {
+    pub owner: Pubkey,
+    pub mint: Pubkey,
+    /// Lamports per rental term.
+    pub fee: u64,
+    /// Lamports the renter escrows, seized by the owner if the NFT isn't returned in time.
+    pub collateral: u64,
+    /// Term length in seconds.
+    pub duration: i64,
+    pub renter: Option<Pubkey>,
+    pub rented_until: i64,
+    pub bump: u8,
+}
+
+/// Installment purchase of a listing; escrows the payments while the NFT stays in the vault.
+#[account]
+#[derive(InitSpace)]
+pub struct InstallmentPlan {
+    pub buyer: Pubkey,
+    pub seller: Pubkey,
+    pub listing: Pubkey,
+    pub mint: Pubkey,
+    /// In lamports.
+    pub price: u64,
+    pub deposit: u64,
+    /// Deposit included.
+    pub paid: u64,
+    pub installment_amount: u64,
+    pub period: i64,
+    pub start_ts: i64,
+    /// Deadline of the current installment; past it the seller can reclaim the NFT.
+    pub next_due_ts: i64,
+    pub bump: u8,
+}
+
+impl InstallmentPlan {
+    pub fn balance(&self) -> u64 {
+        self.price - self.paid
+    }
+
+    /// Each installment covered (paying ahead counts) pushes the deadline one period further.
+    pub fn record_payment(&mut self, amount: u64) -> Result<()> {
+        self.paid = self
+            .paid
+            .checked_add(amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+        if let Some(covered) = (self.paid - self.deposit).checked_div(self.installment_amount) {
+            self.next_due_ts = (covered as i64 + 1)
+                .checked_mul(self.period)
+                .and_then(|elapsed| self.start_ts.checked_add(elapsed))
+                .ok_or(ErrorCode::MathOverflow)?;
+        }
+        Ok(())
+    }
+}
+
+/// Lamports of an escrow (installment plan or auction bid) deposited in the yield stake pool.
+#[account]
+#[derive(InitSpace)]
+pub struct EscrowYield {
+    pub escrow: Pubkey,
+    /// Escrow owner, gets the principal back if the escrow's closed by then.
+    pub beneficiary: Pubkey,
+    /// Lamports taken out of the escrow.
+    pub principal: u64,
+    /// Pool tokens the principal bought, held by the yield vault.
+    pub pool_tokens: u64,
+    pub bump: u8,
+}
+
+/// A lender's loan against a listing's vaulted NFT; escrows the principal until accepted.
+#[account]
+#[derive(InitSpace)]
+pub struct LoanOffer {
+    pub lender: Pubkey,
+    pub listing: Pubkey,
+    pub mint: Pubkey,
+    /// In lamports.
+    pub principal: u64,
+    /// Principal & interest, in lamports.
+    pub repayment: u64,
+    /// Seconds from acceptance the repayment is due within.
+    pub duration: i64,
+    /// Seller who accepted the loan, `None` while it's an open offer.
+    pub borrower: Option<Pubkey>,
+    /// Past this the lender can foreclose; 0 until accepted.
+    pub due_ts: i64,
+    pub bump: u8,
+}
+
+/// Crowdfunded purchase of a listing; escrows contributions, then holds the bought NFT until a
+/// contributor redeems it.
+#[account]
+#[derive(InitSpace)]
+pub struct GroupBuy {
+    pub listing: Pubkey,
+    pub mint: Pubkey,
+    pub organizer: Pubkey,
+    /// Lamports to raise: the listing's price when opened.
+    pub target: u64,
+    /// Contributions held (once redeemed: the shares still to refund).
+    pub raised: u64,
+    /// Unix timestamp after which unexecuted contributions can be refunded.
+    pub deadline: i64,
+    pub status: GroupBuyStatus,
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum GroupBuyStatus {
+    Open,
+    Executed,
+    /// A contributor took the NFT; the others' shares are refundable.
+    Redeemed,
+}
+
+/// One wallet's share of a group buy.
+#[account]
+#[derive(InitSpace)]
+pub struct GroupBuyContribution {
+    pub group_buy: Pubkey,
+    pub contributor: Pubkey,
+    pub amount: u64,
+    pub bump: u8,
+}
+
+/// Standing offer on a mint, escrowing `amount` until accepted by the NFT owner or cancelled.
+#[account]
+#[derive(InitSpace)]
+pub struct Offer {
+    pub bidder: Pubkey,
+    pub mint: Pubkey,
+    /// In lamports (or WSOL base units, the same), or base units of the escrowed SPL token.
+    pub amount: u64,
+    pub escrow: OfferEscrow,
+    pub bump: u8,
+    /// The NFT owner's latest counter, taken with `accept_counter_offer`.
+    pub counter: Option<OfferCounter>,
+    /// Unix timestamp from which the offer can't be filled, & anyone can close it.
+    pub expires_at: Option<i64>,
+    /// Wallet that paid the offer's (& its WSOL escrow's) rent, the bidder unless relayed.
+    pub rent_payer: Pubkey,
+}
+
+impl Offer {
+    pub fn open(
+        &mut self,
+        bidder: Pubkey,
+        mint: Pubkey,
+        amount: u64,
+        escrow: OfferEscrow,
+        expires_at: Option<i64>,
+        bump: u8,
+    ) {
+        self.bidder = bidder;
+        self.mint = mint;
+        self.amount = amount;
+        self.escrow = escrow;
+        self.bump = bump;
+        self.counter = None;
+        self.expires_at = expires_at;
+        self.rent_payer = bidder;
+    }
+
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|expires_at| now >= expires_at)
+    }
+
+    /// SPL mint the offer's escrowed in, `None` for SOL (lamport / WSOL) offers.
+    pub fn payment_mint(&self) -> Option<Pubkey> {
+        match self.escrow {
+            OfferEscrow::Token { mint, .. } => Some(mint),
+            _ => None,
+        }
+    }
+
+    /// Token program of `payment_mint`.
+    pub fn payment_token_program(&self) -> Option<Pubkey> {
+        match self.escrow {
+            OfferEscrow::Token { token_program, .. } => Some(token_program),
+            _ => None,
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace)]
+pub struct OfferCounter {
+    pub seller: Pubkey,
+    /// In lamports, above the offer's amount.
+    pub amount: u64,
+}
+
+/// Highest live offer on a mint, so sellers & UIs find the top bid without scanning every offer.
+/// Cleared when that offer is filled, cancelled or closed, until `refresh_best_offer` or a new offer.
+#[account]
+#[derive(InitSpace)]
+pub struct BestOffer {
+    pub mint: Pubkey,
+    /// Default when there's no tracked offer.
+    pub offer: Pubkey,
+    pub bidder: Pubkey,
+    pub amount: u64,
+    pub expires_at: Option<i64>,
+    pub bump: u8,
+}
+
+impl BestOffer {
+    /// Tracks `offer` if it's a live SOL offer & beats the best one (or that one has expired).
+    pub fn consider(&mut self, key: Pubkey, offer: &Offer, now: i64) {
+        let best_is_live = self.offer != Pubkey::default()
+            && self.expires_at.is_none_or(|expires_at| now < expires_at);
+        if offer.payment_mint().is_some()
+            || offer.is_expired(now)
+            || (best_is_live && offer.amount <= self.amount)
+        {
+            return;
+        }
+        self.offer = key;
+        self.bidder = offer.bidder;
+        self.amount = offer.amount;
+        self.expires_at = offer.expires_at;
+    }
+
+    /// Stops tracking `offer`, if it's the best one.
+    pub fn remove(&mut self, offer: Pubkey) {
+        if self.offer == offer {
+            self.offer = Pubkey::default();
+            self.bidder = Pubkey::default();
+            self.amount = 0;
+            self.expires_at = None;
+        }
+    }
+}
+
+/// Top `OFFER_BOOK_DEPTH` offers on a mint, highest first (earliest first at equal amounts), so
+/// sellers & UIs show bid depth without scanning every offer. Lower offers fall off when it's full.
+#[account(zero_copy)]
+pub struct OfferBook {
+    pub mint: Pubkey,
+    pub count: u32,
+    pub bump: u8,
+    pub _padding: [u8; 3],
+    pub bids: [BookBid; OFFER_BOOK_DEPTH],
+}
+
+#[zero_copy]
+#[derive(Default)]
+pub struct BookBid {
+    pub offer: Pubkey,
+    pub bidder: Pubkey,
+    pub amount: u64,
+    /// `i64::MAX` for offers that don't expire.
+    pub expires_at: i64,
+}
+
+impl OfferBook {
+    /// Books `offer` (again, if it already is) by amount, dropping expired bids to make room. SPL
+    /// offers aren't booked, their amounts not being comparable with the SOL ones.
+    pub fn insert(&mut self, key: Pubkey, offer: &Offer, now: i64) {
+        self.remove(&key);
+        if offer.payment_mint().is_some() {
+            return;
+        }
+        self.prune_expired(now);
+        let count = self.count as usize;
+        let index = self.bids[..count]
+            .iter()
+            .position(|bid| bid.amount < offer.amount)
+            .unwrap_or(count);
+        if index == OFFER_BOOK_DEPTH {
+            return;
+        }
+        let end = count.min(OFFER_BOOK_DEPTH - 1);
+        self.bids.copy_within(index..end, index + 1);
+        self.bids[index] = BookBid {
+            offer: key,
+            bidder: offer.bidder,
+            amount: offer.amount,
+            expires_at: offer.expires_at.unwrap_or(i64::MAX),
+        };
+        self.count = (end + 1) as u32;
+    }
+
+    /// Drops `offer`, if booked.
+    pub fn remove(&mut self, offer: &Pubkey) {
+        let count = self.count as usize;
+        if let Some(index) = self.bids[..count]
+            .iter()
+            .position(|bid| &bid.offer == offer)
+        {
+            self.bids.copy_within(index + 1..count, index);
+            self.bids[count - 1] = BookBid::default();
+            self.count -= 1;
+        }
+    }
+
+    fn prune_expired(&mut self, now: i64) {
+        let count = self.count as usize;
+        let mut kept = 0;
+        for index in 0..count {
+            if now < self.bids[index].expires_at {
+                self.bids[kept] = self.bids[index];
+                kept += 1;
+            }
+        }
+        self.bids[kept..count].fill(BookBid::default());
+        self.count = kept as u32;
+    }
+
+    pub fn bids(&self) -> &[BookBid] {
+        &self.bids[..self.count as usize]
+    }
+}
+
+/// Where an offer's funds are held.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum OfferEscrow {
+    /// Raw lamports on the offer PDA.
+    Lamports,
+    /// WSOL in a token account owned by the offer PDA.
+    Wsol,
+    /// Tokens of an SPL `mint` (e.g. USDC) in a token account owned by the offer PDA.
+    Token { mint: Pubkey, token_program: Pubkey },
+}
+
+/// Standing lamport offer on any one NFT of a verified collection, escrowing `amount` until accepted or cancelled.
+#[account]
+#[derive(InitSpace)]
+pub struct CollectionOffer {
+    pub bidder: Pubkey,
+    pub collection: Pubkey,
+    pub amount: u64,
+    /// Root of a merkle tree of eligible mints (see `merkle_leaf`), any mint of the collection when `None`.
+    pub mint_root: Option<[u8; 32]>,
+    /// Unix timestamp from which the offer can't be filled.
+    pub expires_at: Option<i64>,
+    pub bump: u8,
+}
+
+impl CollectionOffer {
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|expires_at| now >= expires_at)
+    }
+}
+
+/// Off-chain order, signed by the seller & filled by the buyer with `fill_signed_order`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct SignedOrder {
+    pub seller: Pubkey,
+    pub mint: Pubkey,
+    /// In lamports.
+    pub price: u64,
+    /// Unix timestamp the order can't be filled from.
+    pub expires_at: i64,
+    /// Seller-chosen, unique per seller.
+    pub nonce: u64,
+}
+
+/// Used nonce of a filled or cancelled signed order, from before `OrderNonceBitmap`.
+#[account]
+#[derive(InitSpace)]
+pub struct OrderNonce {
+    pub seller: Pubkey,
+    pub nonce: u64,
+    pub bump: u8,
+}
+
+/// Page `page` of a seller's signed-order nonces: bit `nonce % ORDER_NONCES_PER_PAGE` is set once
+/// the order with that nonce is filled or cancelled.
+#[account]
+#[derive(InitSpace)]
+pub struct OrderNonceBitmap {
+    pub seller: Pubkey,
+    pub page: u64,
+    pub used: [u8; (ORDER_NONCES_PER_PAGE / 8) as usize],
+    pub bump: u8,
+}
+
+impl OrderNonceBitmap {
+    pub fn init_if_new(&mut self, seller: Pubkey, nonce: u64, bump: u8) {
+        if self.seller == Pubkey::default() {
+            self.seller = seller;
+            self.page = nonce / ORDER_NONCES_PER_PAGE;
+            self.bump = bump;
+        }
+    }
+
+    fn bit(nonce: u64) -> (usize, u8) {
+        let bit = nonce % ORDER_NONCES_PER_PAGE;
+        ((bit / 8) as usize, 1 << (bit % 8))
+    }
+
+    pub fn is_used(&self, nonce: u64) -> bool {
+        let (byte, mask) = Self::bit(nonce);
+        self.used[byte] & mask != 0
+    }
+
+    pub fn mark_used(&mut self, nonce: u64) -> Result<()> {
+        require!(!self.is_used(nonce), ErrorCode::OrderNonceUsed);
+        let (byte, mask) = Self::bit(nonce);
+        self.used[byte] |= mask;
+        Ok(())
+    }
+}
+
+/// Admin block of a mint (known fake, frozen asset): can't be listed or bought.
+#[account]
+#[derive(InitSpace)]
+pub struct BlockedMint {
+    pub mint: Pubkey,
+    pub bump: u8,
+}
+
+/// Admin ban of a wallet (sanctioned, exploit-linked): can't list, buy, or make or accept offers.
+#[account]
+#[derive(InitSpace)]
+pub struct BannedWallet {
+    pub wallet: Pubkey,
+    pub bump: u8,
+}
+
+/// Cancellable account `cancel` unwinds, with the instruction it runs.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum CancelKind {
+    /// `remove_listed_nft`.
+    Listing,
+    /// `cancel_offer`.
+    Offer,
+    /// `cancel_collection_offer`.
+    CollectionOffer,
+    /// `cancel_loan_offer`.
+    LoanOffer,
+    /// `cancel_bid`.
+    Bid,
+}
+
+impl CancelKind {
+    pub fn discriminator(&self) -> [u8; 8] {
+        match self {
+            CancelKind::Listing => Listing::DISCRIMINATOR,
+            CancelKind::Offer => Offer::DISCRIMINATOR,
+            CancelKind::CollectionOffer => CollectionOffer::DISCRIMINATOR,
+            CancelKind::LoanOffer => LoanOffer::DISCRIMINATOR,
+            CancelKind::Bid => Bid::DISCRIMINATOR,
+        }
+    }
+}
+
+/// Trading action a `BannedWallet` was refused.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum TradeAction {
+    List,
+    Buy,
+    /// Selling through `buy_nft` or a signed order.
+    Sell,
+    MakeOffer,
+    AcceptOffer,
+    CounterOffer,
+}
+
+/// Branded storefront sharing the marketplace rails with its own fee, treasury & curation.
+#[account]
+#[derive(InitSpace)]
+pub struct Storefront {
+    pub authority: Pubkey,
+    /// Receives the storefront's fee.
+    pub treasury: Pubkey,
+    /// Replaces the marketplace fee on storefront sales.
+    pub fee_bps: u16,
+    /// Verified collections that can be listed; empty means any.
+    #[max_len(MAX_STOREFRONT_COLLECTIONS)]
+    pub allowed_collections: Vec<Pubkey>,
+}
+
+/// Per-mint marketplace history.
+#[account]
+#[derive(InitSpace)]
+pub struct Provenance {
+    pub mint: Pubkey,
+    /// Receives royalties: the mint's first verified Metaplex creator when its metadata was passed at
+    /// the first listing (or on `sync_royalty_creator`), else the seller of that listing.
+    pub creator: Pubkey,
+    /// Completed marketplace sales of this mint.
+    pub sales_count: u64,
+    pub bump: u8,
+}
+
+impl Provenance {
+    /// Records the royalty recipient the first time the mint goes through the marketplace.
+    pub fn init_if_new(&mut self, mint: Pubkey, creator: Pubkey, bump: u8) {
+        if self.mint == Pubkey::default() {
+            self.mint = mint;
+            self.creator = creator;
+            self.sales_count = 0;
+            self.bump = bump;
+        }
+    }
+
+    pub fn is_primary(&self) -> bool {
+        self.sales_count == 0
+    }
+
+    /// Writes the `SaleRecord` of the mint's current sale, then counts the sale so following sales
+    /// of the mint are secondary.
+    pub fn record_sale(
+        &mut self,
+        sale_record: &mut SaleRecord,
+        seller: Pubkey,
+        buyer: Pubkey,
+        price: u64,
+        payment_mint: Option<Pubkey>,
+        bump: u8,
+    ) -> Result<()> {
+        sale_record.mint = self.mint;
+        sale_record.sale_number = self.sales_count;
+        sale_record.seller = seller;
+        sale_record.buyer = buyer;
+        sale_record.price = price;
+        sale_record.payment_mint = payment_mint;
+        sale_record.timestamp = Clock::get()?.unix_timestamp;
+        sale_record.bump = bump;
+        self.sales_count = self
+            .sales_count
+            .checked_add(1)
+            .ok_or(ErrorCode::MathOverflow)?;
+        Ok(())
+    }
+}
+
+/// Immutable record of a mint's marketplace sale number `sale_number`, so its provenance can be
+/// rebuilt on-chain.
+#[account]
+#[derive(InitSpace)]
+pub struct SaleRecord {
+    pub mint: Pubkey,
+    pub sale_number: u64,
+    pub seller: Pubkey,
+    pub buyer: Pubkey,
+    /// In the payment currency.
+    pub price: u64,
+    /// SOL when `None`.
+    pub payment_mint: Option<Pubkey>,
+    pub timestamp: i64,
+    pub bump: u8,
+}
+
+/// Price breakdown of a sale, all amounts in the payment currency (lamports unless SPL-priced).
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct SaleQuote {
+    pub is_primary: bool,
+    pub price: u64,
+    pub marketplace_fee: u64,
+    pub royalty: u64,
+    pub seller_proceeds: u64,
+    /// Part of the marketplace fee paid back to the maker (seller of a listing, bidder of an offer).
+    pub maker_rebate: u64,
+    /// Part of the marketplace fee shared with the allowlisted program that called in via CPI.
+    pub caller_fee: u64,
+    /// Part of the seller proceeds donated to the listing's charity.
+    pub charity: u64,
+    /// Part of the seller proceeds parked in the seller's withholding.
+    pub withheld: u64,
+    /// Part of the marketplace fee paid into the insurance fund.
+    pub insurance: u64,
+}
+
+impl SaleQuote {
+    pub fn new(price: u64, is_primary: bool, schedule: &FeeSchedule) -> Result<Self> {
+        let marketplace_fee = bps_of(price, schedule.marketplace_fee_bps)?;
+        let royalty = bps_of(price, schedule.royalty_bps)?;
+        let seller_proceeds = price
+            .checked_sub(marketplace_fee)
+            .and_then(|rest| rest.checked_sub(royalty))
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        Ok(Self {
+            is_primary,
+            price,
+            marketplace_fee,
+            royalty,
+            seller_proceeds,
+            maker_rebate: 0,
+            caller_fee: 0,
+            charity: 0,
+            withheld: 0,
+            insurance: 0,
+        })
+    }
+
+    /// Moves `fee_share_bps` of the (remaining) marketplace fee to the CPI caller.
+    pub fn apply_caller_fee_share(&mut self, fee_share_bps: u16) -> Result<()> {
+        self.caller_fee = bps_of(self.marketplace_fee, fee_share_bps)?;
+        self.marketplace_fee -= self.caller_fee;
+        Ok(())
+    }
+
+    /// Moves `config.maker_rebate_bps` of the marketplace fee to the maker.
+    pub fn apply_maker_rebate(&mut self, config: &MarketplaceConfig) -> Result<()> {
+        self.maker_rebate = bps_of(self.marketplace_fee, config.maker_rebate_bps)?;
+        self.marketplace_fee -= self.maker_rebate;
+        Ok(())
+    }
+
+    /// Moves `config.insurance_bps` of the (remaining) marketplace fee to the insurance fund.
+    pub fn apply_insurance(&mut self, config: &MarketplaceConfig) -> Result<()> {
+        self.insurance = bps_of(self.marketplace_fee, config.insurance_bps)?;
+        self.marketplace_fee -= self.insurance;
+        Ok(())
+    }
+
+    /// Carves the charity's share out of the seller proceeds.
+    pub fn apply_charity_split(&mut self, charity_split: Option<CharitySplit>) -> Result<()> {
+        if let Some(charity_split) = charity_split {
+            self.charity = bps_of(self.seller_proceeds, charity_split.bps)?;
+            self.seller_proceeds -= self.charity;
+        }
+        Ok(())
+    }
+
+    /// Withholds the config's share of the seller proceeds.
+    pub fn apply_withholding(&mut self, config: &MarketplaceConfig) -> Result<()> {
+        self.withheld = bps_of(self.seller_proceeds, config.withholding_bps)?;
+        self.seller_proceeds -= self.withheld;
+        Ok(())
+    }
+
+    /// Royalty is only paid out when the creator account is supplied; otherwise it stays with the seller,
+    /// unless the marketplace enforces royalties.
+    pub fn route_royalty(&mut self, config: &MarketplaceConfig, has_creator: bool) -> Result<()> {
+        if has_creator {
+            return Ok(());
+        }
+        require!(
+            !config.enforce_royalties || self.royalty == 0,
+            ErrorCode::RoyaltyRecipientMissing
+        );
+        self.seller_proceeds = self
+            .seller_proceeds
+            .checked_add(self.royalty)
+            .ok_or(ErrorCode::MathOverflow)?;
+        self.royalty = 0;
+        Ok(())
+    }
+}
+
+#[event]
+pub struct NftSold {
+    pub listing: Pubkey,
+    pub mint: Pubkey,
+    pub seller: Pubkey,
+    pub buyer: Pubkey,
+    pub price: u64,
+    pub marketplace_fee: u64,
+    /// Effective marketplace fee rate, after storefront / volume tier.
+    pub marketplace_fee_bps: u16,
+    pub royalty: u64,
+    pub seller_proceeds: u64,
+    /// Paid to the seller, on top of `seller_proceeds`.
+    pub maker_rebate: u64,
+    pub is_primary: bool,
+    /// SPL mint the amounts are in, SOL when `None`.
+    pub payment_mint: Option<Pubkey>,
+    /// Recipient of `charity_amount`, out of the seller proceeds.
+    pub charity: Option<Pubkey>,
+    pub charity_amount: u64,
+    /// Seller proceeds parked in the seller's withholding.
+    pub withheld: u64,
+}
+
+#[event]
+pub struct ActionQueued {
+    pub queued_action: Pubkey,
+    pub action: EmergencyAction,
+    pub eta: i64,
+}
+
+#[event]
+pub struct QueuedActionCancelled {
+    pub queued_action: Pubkey,
+}
+
+#[event]
+pub struct QueuedActionExecuted {
+    pub queued_action: Pubkey,
+    pub action: EmergencyAction,
+}
+
+#[event]
+pub struct ConfigAuthorityNominated {
+    pub authority: Pubkey,
+    pub pending_authority: Pubkey,
+}
+
+#[event]
+pub struct ConfigAuthorityChanged {
+    pub old_authority: Pubkey,
+    pub new_authority: Pubkey,
+}
+
+#[event]
+pub struct FeesUpdated {
+    pub primary_fees: FeeSchedule,
+    pub secondary_fees: FeeSchedule,
+}
+
+#[event]
+pub struct EscrowedSaleReleased {
+    pub escrowed_sale: Pubkey,
+    pub listing: Pubkey,
+    pub seller: Pubkey,
+    pub buyer: Pubkey,
+    pub amount: u64,
+    /// Confirmed by the buyer, rather than released after the receipt window.
+    pub confirmed: bool,
+}
+
+#[event]
+pub struct EscrowedSaleRefunded {
+    pub escrowed_sale: Pubkey,
+    pub listing: Pubkey,
+    pub seller: Pubkey,
+    pub buyer: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct DisputeOpened {
+    pub escrowed_sale: Pubkey,
+    pub listing: Pubkey,
+    pub opened_by: Pubkey,
+}
+
+#[event]
+pub struct DisputeResolved {
+    pub escrowed_sale: Pubkey,
+    pub listing: Pubkey,
+    pub arbiter: Pubkey,
+    pub release_to: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct WithholdingWithdrawn {
+    pub withholding: Pubkey,
+    pub seller: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct VestedClaimed {
+    pub vesting: Pubkey,
+    pub seller: Pubkey,
+    pub amount: u64,
+    pub claimed: u64,
+    pub total: u64,
+}
+
+#[event]
+pub struct ListingRewardsClaimed {
+    pub listing: Pubkey,
+    pub seller: Pubkey,
+    pub points: u64,
+    pub amount: u64,
+}
+
+#[event]
+pub struct EditionListed {
+    pub edition_listing: Pubkey,
+    pub mint: Pubkey,
+    pub seller: Pubkey,
+    pub base_price: u64,
+    pub supply: u64,
+}
+
+#[event]
+pub struct EditionSold {
+    pub edition_listing: Pubkey,
+    pub mint: Pubkey,
+    pub seller: Pubkey,
+    pub buyer: Pubkey,
+    pub price: u64,
+    pub marketplace_fee: u64,
+    /// Paid to the seller, with the maker rebate.
+    pub seller_proceeds: u64,
+    /// Units bought in this fill.
+    pub quantity: u64,
+    /// Cumulative over the listing's fills, this one included.
+    pub units_sold: u64,
+    /// Left for sale after this fill.
+    pub units_remaining: u64,
+}
+
+#[event]
+pub struct EditionClosed {
+    pub edition_listing: Pubkey,
+    pub mint: Pubkey,
+    pub seller: Pubkey,
+    pub units_sold: u64,
+}
+
+#[event]
+pub struct PoolCreated {
+    pub pool: Pubkey,
+    pub owner: Pubkey,
+    pub collection: Pubkey,
+    pub spot_price: u64,
+    pub fee_bps: u16,
+}
+
+#[event]
+pub struct PoolTrade {
+    pub pool: Pubkey,
+    pub mint: Pubkey,
+    pub trader: Pubkey,
+    /// Trader bought from the pool, rather than sold into it.
+    pub is_buy: bool,
+    pub price: u64,
+    pub pool_fee: u64,
+    pub marketplace_fee: u64,
+    pub royalty: u64,
+    /// Spot price after the trade.
+    pub spot_price: u64,
+}
+
+#[event]
+pub struct PoolClosed {
+    pub pool: Pubkey,
+    pub owner: Pubkey,
+    pub sol_balance: u64,
+}
+
+#[event]
+pub struct RaffleCreated {
+    pub raffle: Pubkey,
+    pub mint: Pubkey,
+    pub seller: Pubkey,
+    pub ticket_price: u64,
+    pub max_tickets: u64,
+    pub end_ts: i64,
+}
+
+#[event]
+pub struct RaffleTicketsBought {
+    pub raffle: Pubkey,
+    pub entry: Pubkey,
+    pub buyer: Pubkey,
+    pub first_ticket: u64,
+    pub count: u64,
+}
+
+#[event]
+pub struct RaffleLocked {
+    pub raffle: Pubkey,
+    pub mint: Pubkey,
+    pub draw_slot: u64,
+}
+
+#[event]
+pub struct RaffleDrawn {
+    pub raffle: Pubkey,
+    pub mint: Pubkey,
+    pub winning_ticket: u64,
+    pub proceeds: u64,
+}
+
+#[event]
+pub struct RafflePrizeClaimed {
+    pub raffle: Pubkey,
+    pub mint: Pubkey,
+    pub winner: Pubkey,
+}
+
+#[event]
+pub struct RaffleEntryRefunded {
+    pub raffle: Pubkey,
+    pub entry: Pubkey,
+    pub buyer: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct ListingFractionalized {
+    pub fraction: Pubkey,
+    pub listing: Pubkey,
+    pub mint: Pubkey,
+    pub share_mint: Pubkey,
+    pub total_shares: u64,
+}
+
+#[event]
+pub struct FractionRedeemed {
+    pub fraction: Pubkey,
+    pub mint: Pubkey,
+    pub holder: Pubkey,
+}
+
+#[event]
+pub struct BuyoutBidPlaced {
+    pub fraction: Pubkey,
+    pub bidder: Pubkey,
+    pub amount: u64,
+    pub buyout_end_ts: i64,
+}
+
+#[event]
+pub struct BuyoutSettled {
+    pub fraction: Pubkey,
+    pub mint: Pubkey,
+    pub winner: Pubkey,
+    pub price: u64,
+}
+
+#[event]
+pub struct BuyoutProceedsClaimed {
+    pub fraction: Pubkey,
+    pub holder: Pubkey,
+    pub shares: u64,
+    pub amount: u64,
+}
+
+#[event]
+pub struct NftRented {
+    pub rental: Pubkey,
+    pub mint: Pubkey,
+    pub owner: Pubkey,
+    pub renter: Pubkey,
+    pub fee: u64,
+    pub collateral: u64,
+    pub rented_until: i64,
+}
+
+#[event]
+pub struct NftReturned {
+    pub rental: Pubkey,
+    pub mint: Pubkey,
+    pub renter: Pubkey,
+}
+
+#[event]
+pub struct RentalCollateralSeized {
+    pub rental: Pubkey,
+    pub mint: Pubkey,
+    pub owner: Pubkey,
+    pub renter: Pubkey,
+    pub collateral: u64,
+}
+
+#[event]
+pub struct InstallmentPlanStarted {
+    pub plan: Pubkey,
+    pub listing: Pubkey,
+    pub buyer: Pubkey,
+    pub price: u64,
+    pub deposit: u64,
+    pub installment_amount: u64,
+    pub next_due_ts: i64,
+}
+
+#[event]
+pub struct InstallmentPaid {
+    pub plan: Pubkey,
+    pub buyer: Pubkey,
+    pub amount: u64,
+    pub balance: u64,
+    pub next_due_ts: i64,
+}
+
+#[event]
+pub struct InstallmentPlanDefaulted {
+    pub plan: Pubkey,
+    pub listing: Pubkey,
+    pub buyer: Pubkey,
+    pub forfeited: u64,
+    pub refunded: u64,
+}
+
+#[event]
+pub struct GroupBuyOpened {
+    pub group_buy: Pubkey,
+    pub listing: Pubkey,
+    pub organizer: Pubkey,
+    pub target: u64,
+    pub deadline: i64,
+}
+
+#[event]
+pub struct GroupBuyContributed {
+    pub group_buy: Pubkey,
+    pub contributor: Pubkey,
+    pub amount: u64,
+    pub raised: u64,
+    pub target: u64,
+}
+
+#[event]
+pub struct GroupNftRedeemed {
+    pub group_buy: Pubkey,
+    pub redeemer: Pubkey,
+    /// Lamports paid in for the other contributors' shares.
+    pub paid: u64,
+}
+
+#[event]
+pub struct GroupBuyRefunded {
+    pub group_buy: Pubkey,
+    pub contributor: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct OfferMade {
+    pub offer: Pubkey,
+    pub mint: Pubkey,
+    pub bidder: Pubkey,
+    pub amount: u64,
+    pub escrow: OfferEscrow,
+    pub expires_at: Option<i64>,
+}
+
+#[event]
+pub struct VaultRecovered {
+    pub vault: Pubkey,
+    pub mint: Pubkey,
+    pub depositor: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct StateSwept {
+    pub account: Pubkey,
+    pub seller: Pubkey,
+    pub cranker: Pubkey,
+    pub crank_tip: u64,
+}
+
+#[event]
+pub struct OfferExpired {
+    pub offer: Pubkey,
+    pub mint: Pubkey,
+    pub bidder: Pubkey,
+    pub cranker: Pubkey,
+    pub crank_tip: u64,
+}
+
+#[event]
+pub struct OfferCancelled {
+    pub offer: Pubkey,
+    pub mint: Pubkey,
+    pub bidder: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct OfferAccepted {
+    pub offer: Pubkey,
+    pub mint: Pubkey,
+    pub seller: Pubkey,
+    pub bidder: Pubkey,
+    pub price: u64,
+    pub marketplace_fee: u64,
+    pub royalty: u64,
+    pub seller_proceeds: u64,
+    /// Paid to the maker: the bidder, or the seller for an accepted counter offer.
+    pub maker_rebate: u64,
+    pub is_primary: bool,
+    pub escrow: OfferEscrow,
+}
+
+#[event]
+pub struct ListingAuthorityTransferred {
+    pub listing: Pubkey,
+    pub mint: Pubkey,
+    pub old_seller: Pubkey,
+    pub new_seller: Pubkey,
+}
+
+#[event]
+pub struct ListingUpdated {
+    pub listing: Pubkey,
+    pub mint: Pubkey,
+    pub seller: Pubkey,
+    pub price: u64,
+    pub payment_mint: Option<Pubkey>,
+}
+
+#[event]
+pub struct PaymentTermsUpdated {
+    pub listing: Pubkey,
+    pub mint: Pubkey,
+    pub payment_mint: Option<Pubkey>,
+    pub price: u64,
+}
+
+#[event]
+pub struct NftGifted {
+    pub mint: Pubkey,
+    pub giver: Pubkey,
+    pub recipient: Pubkey,
+}
+
+#[event]
+pub struct OfferCountered {
+    pub offer: Pubkey,
+    pub mint: Pubkey,
+    pub bidder: Pubkey,
+    pub seller: Pubkey,
+    /// The offer's amount when countered.
+    pub bid: u64,
+    pub amount: u64,
+}
+
+#[event]
+pub struct CollectionOfferMade {
+    pub offer: Pubkey,
+    pub collection: Pubkey,
+    pub bidder: Pubkey,
+    pub amount: u64,
+    pub mint_root: Option<[u8; 32]>,
+    pub expires_at: Option<i64>,
+}
+
+#[event]
+pub struct CollectionOfferCancelled {
+    pub offer: Pubkey,
+    pub collection: Pubkey,
+    pub bidder: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct CollectionOfferAccepted {
+    pub offer: Pubkey,
+    pub collection: Pubkey,
+    pub mint: Pubkey,
+    pub seller: Pubkey,
+    pub bidder: Pubkey,
+    pub price: u64,
+    pub marketplace_fee: u64,
+    pub royalty: u64,
+    pub seller_proceeds: u64,
+    pub maker_rebate: u64,
+    pub is_primary: bool,
+}
+
+/// A signed order found dead at fill time, its NFT moved out of the seller's account or un-delegated.
+#[event]
+pub struct ListingInvalidated {
+    /// `OrderNonceBitmap` page the order's nonce was marked used in.
+    pub order_nonces: Pubkey,
+    pub mint: Pubkey,
+    pub seller: Pubkey,
+    pub nonce: u64,
+    pub nft_balance: u64,
+    pub delegated_amount: u64,
+}
+
+#[event]
+pub struct SignedOrderFilled {
+    /// `OrderNonceBitmap` page the order's nonce was marked used in.
+    pub order_nonces: Pubkey,
+    pub mint: Pubkey,
+    pub seller: Pubkey,
+    pub buyer: Pubkey,
+    pub nonce: u64,
+    pub price: u64,
+    pub marketplace_fee: u64,
+    pub royalty: u64,
+    pub seller_proceeds: u64,
+    pub is_primary: bool,
+}
+
+#[event]
+pub struct ListingModerated {
+    pub listing: Pubkey,
+    pub mint: Pubkey,
+    pub seller: Pubkey,
+    pub authority: Pubkey,
+    pub reason: ModerationReason,
+}
+
+#[event]
+pub struct ListingExpired {
+    pub listing: Pubkey,
+    pub mint: Pubkey,
+    pub seller: Pubkey,
+    pub cranker: Pubkey,
+    pub crank_tip: u64,
+}
+
+#[event]
+pub struct BidPlaced {
+    pub auction: Pubkey,
+    pub bidder: Pubkey,
+    pub amount: u64,
+    /// The bid hit the buy-now price and ended the auction.
+    pub buy_now: bool,
+}
+
+#[event]
+pub struct BidCommitted {
+    pub auction: Pubkey,
+    pub bidder: Pubkey,
+}
+
+#[event]
+pub struct BidRevealed {
+    pub auction: Pubkey,
+    pub bidder: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct DepositForfeited {
+    pub auction: Pubkey,
+    pub bidder: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct BidCancelled {
+    pub auction: Pubkey,
+    pub bidder: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct ListingConvertedToAuction {
+    pub listing: Pubkey,
+    pub auction: Pubkey,
+    pub mint: Pubkey,
+    pub seller: Pubkey,
+}
+
+#[event]
+pub struct AuctionConvertedToListing {
+    pub auction: Pubkey,
+    pub listing: Pubkey,
+    pub mint: Pubkey,
+    pub seller: Pubkey,
+    pub price: u64,
+}
+
+#[event]
+pub struct AuctionRelisted {
+    pub auction: Pubkey,
+    pub listing: Pubkey,
+    pub mint: Pubkey,
+    pub seller: Pubkey,
+    pub price: u64,
+}
+
+#[event]
+pub struct AuctionSettled {
+    pub auction: Pubkey,
+    pub mint: Pubkey,
+    pub seller: Pubkey,
+    /// `None` when the reserve wasn't met and the NFT went back to the seller.
+    pub winner: Option<Pubkey>,
+    pub price: u64,
+    pub marketplace_fee: u64,
+    pub royalty: u64,
+    pub seller_proceeds: u64,
+    pub is_primary: bool,
+    pub cranker: Pubkey,
+    pub crank_tip: u64,
+}
+
+#[event]
+pub struct InsuranceClaimPaid {
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub claim_id: u64,
+    /// Fund balance left over rent.
+    pub balance: u64,
+}
+
+#[event]
+pub struct LoanOffered {
+    pub loan_offer: Pubkey,
+    pub listing: Pubkey,
+    pub lender: Pubkey,
+    pub principal: u64,
+    pub repayment: u64,
+    pub duration: i64,
+}
+
+#[event]
+pub struct LoanAccepted {
+    pub loan_offer: Pubkey,
+    pub listing: Pubkey,
+    pub borrower: Pubkey,
+    pub lender: Pubkey,
+    pub principal: u64,
+    pub due_ts: i64,
+}
+
+#[event]
+pub struct LoanRepaid {
+    pub loan_offer: Pubkey,
+    pub listing: Pubkey,
+    pub borrower: Pubkey,
+    pub lender: Pubkey,
+    pub repayment: u64,
+}
+
+#[event]
+pub struct LoanForeclosed {
+    pub loan_offer: Pubkey,
+    pub listing: Pubkey,
+    pub borrower: Pubkey,
+    pub lender: Pubkey,
+    pub mint: Pubkey,
+}
+
+#[event]
+pub struct EscrowStaked {
+    pub escrow: Pubkey,
+    pub beneficiary: Pubkey,
+    pub lamports: u64,
+    pub pool_tokens: u64,
+}
+
+#[event]
+pub struct EscrowUnstaked {
+    pub escrow: Pubkey,
+    /// Lamports of the principal returned (less any pool loss).
+    pub principal: u64,
+    /// Pool loss taken out of the escrow owner's balance (the plan's paid amount, the bid's amount).
+    pub shortfall: u64,
+    pub yield_lamports: u64,
+    pub yield_recipient: Pubkey,
+}
+
+#[event]
+pub struct FeesSwept {
+    pub recipient: Pubkey,
+    pub lamports: u64,
+    pub payment_mint: Option<Pubkey>,
+    /// Base units of `payment_mint` swept.
+    pub tokens: u64,
+    pub cranker: Pubkey,
+}
+
+#[event]
+pub struct BuybackExecuted {
+    pub mint: Pubkey,
+    pub sol_spent: u64,
+    pub tokens_burned: u64,
+    pub cranker: Pubkey,
+}
+
+#[event]
+pub struct LoyaltyPointsRedeemed {
+    pub wallet: Pubkey,
+    pub points: u64,
+    pub amount: u64,
+    pub remaining_points: u64,
+}
+
+#[event]
+pub struct VaultMigrated {
+    pub listing: Pubkey,
+    pub mint: Pubkey,
+    pub legacy_vault: Pubkey,
+    pub vault: Pubkey,
+}
+
+#[event]
+pub struct RoyaltyCreatorSynced {
+    pub mint: Pubkey,
+    pub creator: Pubkey,
+}
+
+#[event]
+pub struct RoyaltiesCached {
+    pub mint: Pubkey,
+    pub creators: Vec<Payee>,
+    pub collection: Option<Pubkey>,
+}
+
+/// One creator's part of a royalty paid through the listing's royalty splits.
+#[event]
+pub struct RoyaltyPaid {
+    pub listing: Pubkey,
+    pub mint: Pubkey,
+    pub creator: Pubkey,
+    /// In lamports, or base units of `payment_mint`.
+    pub amount: u64,
+    pub payment_mint: Option<Pubkey>,
+}
+
+#[event]
+pub struct TradeRewardsMined {
+    pub listing: Pubkey,
+    pub buyer: Pubkey,
+    pub seller: Pubkey,
+    pub buyer_reward: u64,
+    pub seller_reward: u64,
+    pub epoch: i64,
+}
+
+#[event]
+pub struct BannedWalletRejected {
+    pub wallet: Pubkey,
+    pub action: TradeAction,
+}
+
+#[error_code]
+pub enum ErrorCode {
+    #[msg("Listing is not active")]
+    InactiveListing,
+    #[msg("Fee basis points exceed 10000")]
+    InvalidFeeBps,
+    #[msg("Arithmetic overflow")]
+    MathOverflow,
+    #[msg("Mint charges a transfer fee on the NFT unit")]
+    TransferFeeNotSupported,
+    #[msg("Mint is non-transferable")]
+    NonTransferableMint,
+    #[msg("Mint has a permanent delegate")]
+    PermanentDelegatePresent,
+    #[msg("Royalties are enforced but the creator account is missing")]
+    RoyaltyRecipientMissing,
+    #[msg("Invalid auction parameters")]
+    InvalidAuctionParams,
+    #[msg("Auction is not active")]
+    AuctionNotActive,
+    #[msg("Auction has ended")]
+    AuctionEnded,
+    #[msg("Auction has not ended yet")]
+    AuctionNotEnded,
+    #[msg("Bid is too low")]
+    BidTooLow,
+    #[msg("Highest bidder accounts are missing")]
+    MissingBidderAccounts,
+    #[msg("Instruction doesn't apply to this auction mode")]
+    WrongAuctionMode,
+    #[msg("Reveal window has not opened yet")]
+    RevealNotOpen,
+    #[msg("Reveal window has closed")]
+    RevealClosed,
+    #[msg("Reveal window has not closed yet")]
+    RevealNotClosed,
+    #[msg("Bid was already revealed")]
+    BidAlreadyRevealed,
+    #[msg("Revealed bid doesn't match the commitment")]
+    CommitmentMismatch,
+    #[msg("The highest bid can't be cancelled")]
+    BidIsHighest,
+    #[msg("Unrevealed bids can't be cancelled")]
+    BidNotRevealed,
+    #[msg("Auction already has bids")]
+    AuctionHasBids,
+    #[msg("USD listings need the Pyth price update account")]
+    MissingPriceFeed,
+    #[msg("Invalid Pyth price update account")]
+    InvalidPriceFeed,
+    #[msg("Pyth price is too old")]
+    StalePrice,
+    #[msg("Pyth price confidence interval is too wide")]
+    PriceConfidenceTooWide,
+    #[msg("Not a listing account")]
+    InvalidListingAccount,
+    #[msg("Signer is not allowed to do this")]
+    Unauthorized,
+    #[msg("Invalid listing options")]
+    InvalidListingOptions,
+    #[msg("Listing has expired")]
+    ListingHasExpired,
+    #[msg("Listing has not expired")]
+    ListingNotExpired,
+    #[msg("SPL-priced listing needs the payment mint, token program & token accounts")]
+    MissingPaymentAccounts,
+    #[msg("Storefront listing needs the storefront account")]
+    MissingStorefront,
+    #[msg("Fee recipient doesn't match the treasury")]
+    InvalidTreasury,
+    #[msg("Storefront collection list is full")]
+    StorefrontCollectionsFull,
+    #[msg("Missing or invalid Metaplex metadata account")]
+    InvalidMetadata,
+    #[msg("Mint's collection is not allowed")]
+    CollectionNotAllowed,
+    #[msg("Too many fee tiers, or not sorted by ascending volume")]
+    InvalidFeeTiers,
+    #[msg("Mint is blocked")]
+    MintBlocked,
+    #[msg("Mint isn't in the stats' collection")]
+    CollectionMismatch,
+    #[msg("Signed order has expired")]
+    OrderExpired,
+    #[msg("Expected an Ed25519 signature verification right before this instruction")]
+    MissingSignatureVerification,
+    #[msg("Ed25519 verification doesn't match the signer or order")]
+    InvalidSignatureVerification,
+    #[msg("Creator doesn't match the mint's provenance")]
+    InvalidCreator,
+    #[msg("Offer amount must be positive")]
+    InvalidOffer,
+    #[msg("WSOL offer needs the escrow, WSOL mint, token program & token accounts")]
+    MissingWsolAccounts,
+    #[msg("Group buys only support SOL-priced listings paying the seller")]
+    GroupBuyNotSupported,
+    #[msg("Invalid group buy deadline or contribution")]
+    InvalidGroupBuyParams,
+    #[msg("Group buy is not open")]
+    GroupBuyNotOpen,
+    #[msg("Group buy deadline has passed")]
+    GroupBuyDeadlinePassed,
+    #[msg("Group buy deadline has not passed yet")]
+    GroupBuyDeadlineNotPassed,
+    #[msg("Group buy is not funded at the listing's price")]
+    GroupBuyNotFunded,
+    #[msg("Installments are not available for this listing")]
+    InstallmentsNotSupported,
+    #[msg("Invalid installment terms")]
+    InvalidInstallmentTerms,
+    #[msg("Invalid installment amount")]
+    InvalidInstallmentAmount,
+    #[msg("Installment is overdue")]
+    InstallmentOverdue,
+    #[msg("Installment is not overdue yet")]
+    InstallmentNotOverdue,
+    #[msg("Installment plan is not fully paid")]
+    InstallmentPlanNotPaid,
+    #[msg("Installment plan is already fully paid")]
+    InstallmentPlanPaid,
+    #[msg("Invalid rental terms")]
+    InvalidRentalTerms,
+    #[msg("NFT is already rented")]
+    NftAlreadyRented,
+    #[msg("NFT is not rented")]
+    NftNotRented,
+    #[msg("Signer is not the renter")]
+    NotRenter,
+    #[msg("Rental is not overdue yet")]
+    RentalNotOverdue,
+    #[msg("Invalid fractionalization parameters")]
+    InvalidFractionParams,
+    #[msg("Invalid status of the fractionalized NFT for this action")]
+    InvalidFractionStatus,
+    #[msg("Not enough shares")]
+    InsufficientShares,
+    #[msg("Previous highest bidder account is missing")]
+    MissingPreviousBidder,
+    #[msg("NFT account is not owned by the buyout winner")]
+    NotBuyoutWinner,
+    #[msg("Invalid raffle parameters")]
+    InvalidRaffleParams,
+    #[msg("Invalid raffle status for this action")]
+    InvalidRaffleStatus,
+    #[msg("Raffle has ended")]
+    RaffleEnded,
+    #[msg("Raffle has not ended yet")]
+    RaffleNotEnded,
+    #[msg("Not enough raffle tickets left")]
+    RaffleSoldOut,
+    #[msg("Raffle has no tickets")]
+    RaffleHasNoTickets,
+    #[msg("Raffle draw window has closed")]
+    RaffleDrawWindowClosed,
+    #[msg("Secret does not match the raffle commitment")]
+    InvalidRaffleSecret,
+    #[msg("Entry does not hold the winning ticket")]
+    NotRaffleWinner,
+    #[msg("SlotHashes sysvar no longer holds the draw slot")]
+    InvalidSlotHashes,
+    #[msg("Listing rewards are disabled")]
+    ListingRewardsDisabled,
+    #[msg("Listing is already enrolled in rewards")]
+    AlreadyEnrolledInRewards,
+    #[msg("Listing is not enrolled in rewards")]
+    NotEnrolledInRewards,
+    #[msg("Rewards vault can't cover the claim")]
+    RewardVaultUnderfunded,
+    #[msg("Payee shares must sum to 10000 bps, with at most 8 payees")]
+    InvalidPayees,
+    #[msg("Payee accounts missing or not matching the listing's payees")]
+    InvalidPayee,
+    #[msg("Vesting account is required for vested listings only")]
+    MissingVestingAccount,
+    #[msg("Too many allowed CPI callers")]
+    TooManyAllowedCallers,
+    #[msg("Instructions sysvar is required by the CPI policy")]
+    MissingInstructionsSysvar,
+    #[msg("Calling program is not allowed to CPI into the marketplace")]
+    CpiCallerNotAllowed,
+    #[msg("Missing or invalid CPI caller fee recipient")]
+    InvalidCallerFeeRecipient,
+    #[msg("Registry page is full")]
+    RegistryPageFull,
+    #[msg("Missing or wrong registry page for the listing")]
+    InvalidRegistryPage,
+    #[msg("Not the seller or its seller authority")]
+    UnauthorizedSeller,
+    #[msg("Invalid config authority")]
+    InvalidConfigAuthority,
+    #[msg("Counter offers are only supported for lamport offers")]
+    CounterOfferNotSupported,
+    #[msg("Invalid counter offer")]
+    InvalidCounterOffer,
+    #[msg("Offer has no counter offer")]
+    NoCounterOffer,
+    #[msg("Offer has expired")]
+    ExpiredOffer,
+    #[msg("Offer has not expired")]
+    OfferNotExpired,
+    #[msg("Invalid new seller")]
+    InvalidNewSeller,
+    #[msg("Payment terms not supported by this listing")]
+    InvalidPaymentTerms,
+    #[msg("Mint not eligible for this collection offer")]
+    IneligibleMint,
+    #[msg("Invalid Dutch auction")]
+    InvalidDutchAuction,
+    #[msg("Listing has not started yet")]
+    ListingNotStarted,
+    #[msg("Invalid flash sale")]
+    InvalidFlashSale,
+    #[msg("Buyer must hold the listing's gate token")]
+    GateTokenRequired,
+    #[msg("Buyer not in the listing's allowlist")]
+    BuyerNotAllowlisted,
+    #[msg("Missing wallet purchases account")]
+    MissingWalletPurchases,
+    #[msg("Wallet purchase limit reached")]
+    PurchaseLimitReached,
+    #[msg("Missing voucher accounts")]
+    MissingVoucherAccounts,
+    #[msg("Missing escrowed sale account")]
+    MissingEscrowedSale,
+    #[msg("Escrowed sale not releasable yet")]
+    EscrowNotReleasable,
+    #[msg("Dispute not allowed")]
+    DisputeNotAllowed,
+    #[msg("Escrowed sale isn't disputed")]
+    NoDispute,
+    #[msg("Disputed funds go to the buyer or seller")]
+    InvalidDisputeOutcome,
+    #[msg("Missing charity account")]
+    MissingCharityAccount,
+    #[msg("Missing withholding account")]
+    MissingWithholdingAccount,
+    #[msg("Withholding is locked until the release date")]
+    WithholdingLocked,
+    #[msg("Token account frozen by the mint's default account state")]
+    FrozenByDefaultState,
+    #[msg("Recipient requires a memo: pass the memo program")]
+    MissingMemoProgram,
+    #[msg("Invalid price leg")]
+    InvalidPriceLeg,
+    #[msg("Invalid edition listing parameters")]
+    InvalidEditionParams,
+    #[msg("Edition sold out")]
+    EditionSoldOut,
+    #[msg("Edition price above the buyer's maximum")]
+    EditionPriceAboveMax,
+    #[msg("Invalid pool parameters")]
+    InvalidPoolParams,
+    #[msg("Pool spot price out of the trader's bounds")]
+    PoolPriceOutOfBounds,
+    #[msg("Insufficient pool funds")]
+    InsufficientPoolFunds,
+    #[msg("Pool still holds NFTs")]
+    PoolNotEmpty,
+    #[msg("Missing sale record account")]
+    MissingSaleRecord,
+    #[msg("Account is not in a sweepable state")]
+    NotSweepable,
+    #[msg("Vault is still held for a live auction, raffle or rental")]
+    VaultNotOrphaned,
+    #[msg("Invalid queued action")]
+    InvalidQueuedAction,
+    #[msg("Queued action's timelock isn't over")]
+    ActionTimelocked,
+    #[msg("Marketplace is paused")]
+    MarketplacePaused,
+    #[msg("Missing listing rewards accounts")]
+    MissingRewardAccounts,
+    #[msg("Signed order nonce already used")]
+    OrderNonceUsed,
+    #[msg("Mint's metadata has no verified collection")]
+    UnverifiedCollection,
+    #[msg("Mint's metadata has no verified creator")]
+    UnverifiedCreator,
+    #[msg("Wallet is banned from trading")]
+    WalletBanned,
+    #[msg("Listing requires the buyer's attestation credential")]
+    AttestationRequired,
+    #[msg("Attestation credential doesn't match the listing's program, issuer or buyer, or isn't active")]
+    InvalidAttestation,
+    #[msg("Attestation credential has expired")]
+    AttestationExpired,
+    #[msg("Insurance fund is required while insurance_bps is set")]
+    MissingInsuranceFund,
+    #[msg("Insurance fund balance is too low for the claim")]
+    InsufficientInsuranceFunds,
+    #[msg("Buybacks are disabled")]
+    BuybackDisabled,
+    #[msg("Buyback interval hasn't passed yet")]
+    BuybackTooSoon,
+    #[msg("Buyback swap moved funds outside its bounds")]
+    InvalidBuyback,
+    #[msg("Buyback swap rate is below the configured minimum")]
+    BuybackSlippage,
+    #[msg("Trade mining needs a positive epoch length")]
+    InvalidTradeMiningParams,
+    #[msg("Not enough loyalty points")]
+    InsufficientLoyaltyPoints,
+    #[msg("Only a token size of 1 can be sold")]
+    InvalidTokenSize,
+    #[msg("Listing or offer can't be settled through execute_sale")]
+    ExecuteSaleNotSupported,
+    #[msg("Buyer price doesn't match the offer, or is below the listing's price")]
+    ExecuteSalePriceMismatch,
+    #[msg("Listing's settled price is above the buyer's max price")]
+    SalePriceAboveMax,
+    #[msg("Edition quantity must be between 1 and the units remaining")]
+    InvalidEditionQuantity,
+    #[msg("Price is below the collection's minimum")]
+    PriceBelowCollectionMin,
+    #[msg("Price is above the marketplace's listing cap; confirm it to list anyway")]
+    PriceAboveListingMax,
+    #[msg("Delist batch accounts must come in (listing, vault, NFT account, mint, rent payer, registry page) sets")]
+    InvalidDelistBatch,
+    #[msg("Royalty splits need the mint's creators, at most 5, with shares summing to 100%")]
+    InvalidRoyaltySplits,
+    #[msg("Fee vault holds less than the sweep threshold")]
+    FeeSweepBelowThreshold,
+    #[msg("Mint's embedded metadata is invalid or repointable by someone other than its update authority")]
+    InvalidTokenMetadata,
+    #[msg("Escrow yield is disabled")]
+    EscrowYieldDisabled,
+    #[msg("Invalid escrow, amount or stake pool for escrow yield")]
+    InvalidEscrowYield,
+    #[msg("Invalid loan offer, or not in the state this needs")]
+    InvalidLoanOffer,
+    #[msg("Listing's NFT is collateral for an outstanding loan")]
+    ListingHasLoan,
+    #[msg("Loan isn't overdue yet")]
+    LoanNotDue,
+    #[msg("Cancel target isn't an account of the given kind")]
+    InvalidCancelTarget,
+    #[msg("Listing account already exists; list_or_update_nft updates it")]
+    ListingAlreadyExists,
+    #[msg("Group buy has not executed, or its NFT was already redeemed")]
+    GroupBuyNotExecuted,
+    #[msg("Raffle hasn't been locked for a draw")]
+    RaffleNotLocked,
+    #[msg("Raffle's draw slot hasn't been produced yet")]
+    RaffleDrawSlotPending,
+    #[msg("Calling program can't be identified below the top-level instruction")]
+    CpiCallerUnknown,
+    #[msg("Escrow is staked: unstake it first")]
+    EscrowStaked,
+    #[msg("Payment mint charges a transfer fee")]
+    PaymentMintTransferFee,
+    #[msg("Relisting accounts are required for auctions relisting on no sale")]
+    MissingRelistAccounts,
+    #[msg("Escrow is owed to the seller and can't be staked")]
+    EscrowOwedToSeller,
+}
+
+// --------------------------------------------------------------------
+// Oracle
+// --------------------------------------------------------------------
+/// Minimal reader for Pyth pull-oracle `PriceUpdateV2` accounts. The Pyth SDK crates pull in
+/// solana-program 2.x / a newer anchor, so the (small, stable) account layout is decoded here.
+pub mod pyth {
+    use super::*;
+
+    /// Pyth Solana receiver program, owner of `PriceUpdateV2` accounts.
+    pub const RECEIVER_PROGRAM_ID: Pubkey = pubkey!("rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ");
+
+    /// Anchor discriminator of `PriceUpdateV2` (sha256("account:PriceUpdateV2")[..8]).
+    pub const PRICE_UPDATE_V2_DISCRIMINATOR: [u8; 8] = [34, 241, 35, 99, 157, 126, 244, 205];
+
+    #[derive(AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+    pub enum VerificationLevel {
+        Partial { num_signatures: u8 },
+        Full,
+    }
+
+    #[derive(AnchorDeserialize, Clone, Copy)]
+    pub struct PriceFeedMessage {
+        pub feed_id: [u8; 32],
+        pub price: i64,
+        pub conf: u64,
+        pub exponent: i32,
+        pub publish_time: i64,
+        pub prev_publish_time: i64,
+        pub ema_price: i64,
+        pub ema_conf: u64,
+    }
+
+    #[derive(AnchorDeserialize)]
+    pub struct PriceUpdateV2 {
+        pub write_authority: Pubkey,
+        pub verification_level: VerificationLevel,
+        pub price_message: PriceFeedMessage,
+        pub posted_slot: u64,
+    }
+
+    /// Loads the configured SOL/USD price, validating owner, feed, verification, staleness & confidence.
+    pub fn load_sol_usd_price(
+        price_update: &AccountInfo,
+        config: &MarketplaceConfig,
+    ) -> Result<PriceFeedMessage> {
+        require_keys_eq!(
+            *price_update.owner,
+            RECEIVER_PROGRAM_ID,
+            ErrorCode::InvalidPriceFeed
+        );
+
+        let data = price_update.try_borrow_data()?;
+        require!(
+            data.len() > 8 && data[..8] == PRICE_UPDATE_V2_DISCRIMINATOR,
+            ErrorCode::InvalidPriceFeed
+        );
+        let update = PriceUpdateV2::deserialize(&mut &data[8..])
+            .map_err(|_| error!(ErrorCode::InvalidPriceFeed))?;
+        let message = update.price_message;
+
+        require!(
+            update.verification_level == VerificationLevel::Full
+                && message.feed_id == config.sol_usd_feed_id
+                && message.price > 0,
+            ErrorCode::InvalidPriceFeed
+        );
+
+        let age = Clock::get()?
+            .unix_timestamp
+            .saturating_sub(message.publish_time);
+        require!(
+            age <= config.max_price_age_secs as i64,
+            ErrorCode::StalePrice
+        );
+
+        // conf / price <= max_conf_bps / 10_000
+        require!(
+            (message.conf as u128) * (BPS_DENOMINATOR as u128)
+                <= (message.price as u128) * (config.max_price_conf_bps as u128),
+            ErrorCode::PriceConfidenceTooWide
+        );
+
+        Ok(message)
+    }
+
+    /// `price_usd` (6 decimals) in lamports at the given SOL/USD price, rounded up in the seller's favour.
+    pub fn usd_to_lamports(price_usd: u64, sol_usd: &PriceFeedMessage) -> Result<u64> {
+        // lamports = usd * 10^-6 * 10^9 / (price * 10^exponent)
+        let scale = |exponent: u32| {
+            10u128
+                .checked_pow(exponent)
+                .ok_or(error!(ErrorCode::MathOverflow))
+        };
+        let mut numerator = (price_usd as u128) * 1_000;
+        let mut denominator = sol_usd.price as u128;
+        if sol_usd.exponent < 0 {
+            numerator = numerator
+                .checked_mul(scale(sol_usd.exponent.unsigned_abs())?)
+                .ok_or(ErrorCode::MathOverflow)?;
+        } else {
+            denominator = denominator
+                .checked_mul(scale(sol_usd.exponent as u32)?)
+                .ok_or(ErrorCode::MathOverflow)?;
+        }
+        let lamports = numerator.div_ceil(denominator);
+        u64::try_from(lamports).map_err(|_| error!(ErrorCode::MathOverflow))
+    }
+}
+
+// --------------------------------------------------------------------
+// Constants
+// --------------------------------------------------------------------
+pub mod constant {
+    pub const PREFIX: &str = "MARKETPLACE";
+
+    // Listing prices are in SOL; if prices move to lamports, drop the multiplication.
+    pub const LAMPORTS_PER_SOL: u64 = 1_000_000_000;
+
+    pub const BPS_DENOMINATOR: u64 = 10_000;
+
+    pub const MAX_STOREFRONT_COLLECTIONS: usize = 16;
+
+    pub const MAX_FEE_TIERS: usize = 8;
+
+    pub const MAX_PAYEES: usize = 8;
+
+    /// SPL stake pool instruction tags of `DepositSol` & `WithdrawSol`.
+    pub const STAKE_POOL_DEPOSIT_SOL: u8 = 14;
+    pub const STAKE_POOL_WITHDRAW_SOL: u8 = 16;
+
+    /// Metaplex caps a mint's creators at 5.
+    pub const MAX_ROYALTY_SPLITS: usize = 5;
+
+    pub const MAX_PRICE_LEGS: usize = 3;
+
+    pub const MAX_ALLOWED_CALLERS: usize = 8;
+
+    /// Listings per queued `ForceDelist` action.
+    pub const MAX_FORCED_DELISTS: usize = 10;
+
+    /// Initial `action_timelock`, in seconds.
+    pub const DEFAULT_ACTION_TIMELOCK: i64 = 2 * 86_400;
+
+    /// Current `Listing` layout; bump when appending fields, `migrate_listing` upgrades older accounts.
+    pub const LISTING_VERSION: u8 = 19;
+
+    /// Listings per `ListingRegistryPage`, keeping pages under the 10KiB init limit.
+    pub const REGISTRY_PAGE_CAPACITY: usize = 256;
+
+    /// Bids kept per `OfferBook`.
+    pub const OFFER_BOOK_DEPTH: usize = 32;
+
+    /// Signed-order nonces tracked per `OrderNonceBitmap` page, one bit each.
+    pub const ORDER_NONCES_PER_PAGE: u64 = 1024;
+
+    /// Seconds after a raffle ends for the seller to draw; past it, entrants can get refunds.
+    pub const RAFFLE_DRAW_WINDOW: i64 = 86_400;
+
+    /// Slots between `lock_raffle` & the slot whose hash seeds the draw.
+    pub const RAFFLE_DRAW_DELAY_SLOTS: u64 = 32;
+
+    /// Seller's raffle bond, in basis points of the full ticket takings.
+    pub const RAFFLE_BOND_BPS: u16 = 1_000;
+
+    /// Seconds after an auction's settlement time before `sweep_closed_state` can close it, so
+    /// outbid & unrevealed bids are refunded or forfeited against the auction first.
+    pub const SWEEP_GRACE_PERIOD: i64 = 7 * 86_400;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open_listing() -> Listing {
+        let data = vec![0u8; 8 + Listing::INIT_SPACE];
+        let mut listing = Listing::try_deserialize_unchecked(&mut &data[..]).unwrap();
+        listing.open(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            1_000_000_000,
+            None,
+            &ListingOptions::default(),
+        );
+        listing
+    }
+
+    fn edition_units_price(
+        curve: BondingCurve,
+        base_price: u64,
+        units_sold: u64,
+        quantity: u64,
+    ) -> u64 {
+        (units_sold..units_sold + quantity)
+            .map(|units_sold| curve.price_at(base_price, units_sold).unwrap())
+            .sum()
+    }
+
+    #[test]
+    fn linear_total_price_matches_unit_prices() {
+        let curve = BondingCurve::Linear { step: 7_000 };
+        for (units_sold, quantity) in [(0, 1), (0, 10), (13, 50), (1_000, 3)] {
+            assert_eq!(
+                curve.total_price(1_000_000, units_sold, quantity).unwrap(),
+                edition_units_price(curve, 1_000_000, units_sold, quantity)
+            );
+        }
+    }
+
+    #[test]
+    fn exponential_total_price_approximates_unit_prices() {
+        let curve = BondingCurve::Exponential { step_bps: 250 };
+        for (units_sold, quantity) in [(0, 1), (0, 10), (13, 50), (100, 3)] {
+            let total = curve
+                .total_price(1_000_000_000, units_sold, quantity)
+                .unwrap();
+            let by_unit = edition_units_price(curve, 1_000_000_000, units_sold, quantity);
+            // Each unit price rounds down on its own, so the closed form can only be a few lamports over.
+            assert!(
+                total >= by_unit && total - by_unit <= quantity,
+                "{total} vs {by_unit}"
+            );
+        }
+    }
+
+    #[test]
+    fn active_listing_is_delistable() {
+        assert!(open_listing().require_delistable().is_ok());
+    }
+
+    #[test]
+    fn delist_during_installment_plan_is_rejected() {
+        let mut listing = open_listing();
+        // As `start_installment_plan` leaves it, the NFT still in the vault for the buyer.
+        listing.is_active = false;
+        assert_eq!(
+            listing.require_delistable().unwrap_err(),
+            error!(ErrorCode::InactiveListing)
+        );
+    }
+
+    #[test]
+    fn delist_with_loan_is_rejected() {
+        let mut listing = open_listing();
+        listing.is_active = false;
+        listing.loan = Some(Pubkey::new_unique());
+        assert_eq!(
+            listing.require_delistable().unwrap_err(),
+            error!(ErrorCode::ListingHasLoan)
+        );
+    }
+}